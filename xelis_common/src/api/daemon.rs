@@ -15,7 +15,7 @@ use crate::{
 };
 use super::RPCTransaction;
 
-#[derive(Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BlockType {
     Sync,
     Side,
@@ -105,6 +105,54 @@ pub struct GetBlockTemplateResult {
     pub difficulty: Difficulty // difficulty required for valid block
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct DryRunBlockTemplateParams<'a> {
+    // strategy to use instead of the one configured on the node
+    pub strategy: Option<Cow<'a, str>>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SkippedTransactionReason {
+    // Not enough space left in the block
+    BlockFull,
+    // A previous TX of the same source was skipped
+    PreviousSkipped,
+    // TX is not valid against the current chain state
+    Invalid
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BlockTemplateTransaction<'a> {
+    pub hash: Cow<'a, Hash>,
+    pub source: Address,
+    pub nonce: u64,
+    pub fee: u64,
+    pub size: usize
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SkippedBlockTemplateTransaction<'a> {
+    #[serde(flatten)]
+    pub transaction: BlockTemplateTransaction<'a>,
+    pub reason: SkippedTransactionReason,
+    // verification error if the TX is invalid
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub error: Option<String>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DryRunBlockTemplateResult<'a> {
+    pub strategy: Cow<'a, str>,
+    // TXs selected in the template, in block order
+    pub selected: Vec<BlockTemplateTransaction<'a>>,
+    pub skipped: Vec<SkippedBlockTemplateTransaction<'a>>,
+    // size of the selected TXs
+    pub total_size: usize,
+    pub total_fees: u64
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SubmitBlockParams {
     pub block_template: String, // hex: represent the BlockHeader (Block)
@@ -204,6 +252,88 @@ pub struct SubmitTransactionParams {
     pub data: String // should be in hex format
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct SimulateTransactionParams {
+    pub data: String // should be in hex format
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionSimulationFailure {
+    // TX is bigger than the maximum size allowed
+    TooBig,
+    AlreadyInMempool,
+    AlreadyInBlockchain,
+    InvalidNonce,
+    // Fee is lower than the required fee
+    InvalidFee,
+    // Fee is too low to replace the pending TX with the same nonce
+    ReplacementUnderpriced,
+    InvalidSignature,
+    InvalidProof,
+    // Any other error returned by the chain state (balance, reference, version...)
+    InvalidState
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionProofKind {
+    // A proof or ciphertext is ill-formed
+    Format,
+    CommitmentEq,
+    CiphertextValidity,
+    // Sigma proofs are verified in batch, the failing one is unknown
+    Sigma,
+    Range,
+    Transcript
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TransactionSimulationError {
+    pub kind: TransactionSimulationFailure,
+    // proof that failed if kind is InvalidProof
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub proof: Option<TransactionProofKind>,
+    pub message: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SimulatedTransfer<'a> {
+    pub destination: Address,
+    pub asset: Cow<'a, Hash>,
+    // destination is not registered yet, extra fees are required
+    pub new_account: bool
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SimulateTransactionResult<'a> {
+    pub hash: Cow<'a, Hash>,
+    // TX would be accepted in the mempool
+    pub valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub error: Option<TransactionSimulationError>,
+    pub size: usize,
+    pub fee: u64,
+    // fee required based on the size and the new accounts
+    pub required_fee: u64,
+    pub nonce: u64,
+    // next nonce available for the source, including its TXs in mempool
+    pub expected_nonce: u64,
+    // pending TX having the same nonce, that would be replaced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub replaced_tx: Option<Cow<'a, Hash>>,
+    // minimum fee to replace the pending TX
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub replacement_fee: Option<u64>,
+    pub transfers: Vec<SimulatedTransfer<'a>>,
+    pub in_mempool: bool,
+    pub executed_in_block: bool
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetTransactionParams<'a> {
     pub hash: Cow<'a, Hash>
@@ -267,7 +397,26 @@ pub struct PeerEntry<'a> {
     pub pruned_topoheight: Option<u64>,
     pub peers: Cow<'a, HashMap<SocketAddr, Direction>>,
     pub cumulative_difficulty: Cow<'a, CumulativeDifficulty>,
-    pub connected_on: TimestampSeconds
+    pub connected_on: TimestampSeconds,
+    // Persistent identity key of the peer in hex
+    // None for peers without the handshake extension
+    pub identity: Option<Cow<'a, String>>,
+    // Reputation of the peer across its connections
+    pub score: PeerScoreEntry
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PeerScoreEntry {
+    // Final score, higher is better
+    pub value: i64,
+    // Average round trip time of the requests in milliseconds
+    pub latency: Option<u64>,
+    pub objects_served: u64,
+    // Invalid blocks or transactions relayed
+    pub invalid_objects: u64,
+    pub sync_timeouts: u64,
+    // Seconds connected
+    pub uptime: u64
 }
 
 #[derive(Serialize, Deserialize)]
@@ -278,7 +427,9 @@ pub struct P2pStatusResult<'a> {
     pub our_topoheight: u64,
     pub best_topoheight: u64,
     pub median_topoheight: u64,
-    pub peer_id: u64
+    pub peer_id: u64,
+    // Our persistent identity key in hex
+    pub identity: Cow<'a, String>
 }
 
 #[derive(Serialize, Deserialize)]
@@ -346,6 +497,33 @@ pub struct AccountHistoryEntry {
     pub block_timestamp: TimestampMillis
 }
 
+// Role of an account in a transaction
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountTransactionRole {
+    // Account is the sender of the transaction
+    Source,
+    // Account is a destination of a transfer
+    Destination
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetAccountTransactionsParams<'a> {
+    pub address: Cow<'a, Address>,
+    pub skip: Option<usize>,
+    pub maximum: Option<usize>,
+    pub minimum_topoheight: Option<u64>,
+    pub maximum_topoheight: Option<u64>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AccountTransactionEntry<'a> {
+    // topoheight of the block in which the transaction was executed
+    pub topoheight: u64,
+    pub hash: Cow<'a, Hash>,
+    pub role: AccountTransactionRole
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetAccountAssetsParams<'a> {
     pub address: Cow<'a, Address>
@@ -423,6 +601,24 @@ pub struct GetMempoolCacheResult {
     balances: HashMap<Hash, CiphertextCache>
 }
 
+impl GetMempoolCacheResult {
+    pub fn get_min(&self) -> u64 {
+        self.min
+    }
+
+    pub fn get_max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn get_txs(&self) -> &Vec<Hash> {
+        &self.txs
+    }
+
+    pub fn get_balances(&self) -> &HashMap<Hash, CiphertextCache> {
+        &self.balances
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum NotifyEvent {
@@ -447,6 +643,12 @@ pub enum NotifyEvent {
     // When a transaction has been included in a valid block & executed on chain
     // it contains TransactionExecutedEvent struct as value
     TransactionExecuted,
+    // When a transaction in mempool is replaced by another one with the same nonce and a higher fee
+    // it contains TransactionReplacedEvent struct as value
+    TransactionReplaced,
+    // When a transaction is removed from mempool because it is full, expired or not valid anymore after a replacement
+    // it contains TransactionEvictedEvent struct as value
+    TransactionEvicted,
     // When a registered TX SC Call hash has been executed by chain
     // TODO: Smart Contracts
     TransactionSCResult,
@@ -510,6 +712,49 @@ pub struct TransactionExecutedEvent<'a> {
     pub block_hash: Cow<'a, Hash>,
     pub tx_hash: Cow<'a, Hash>,
     pub topoheight: u64,
+    pub source: Address,
+    // empty if the TX has no transfers
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default)]
+    pub transfers: Vec<ExecutedTransfer<'a>>
+}
+
+// Transfer of an executed TX, without its encrypted parts
+#[derive(Serialize, Deserialize)]
+pub struct ExecutedTransfer<'a> {
+    pub asset: Cow<'a, Hash>,
+    pub destination: Address
+}
+
+// Value of NotifyEvent::TransactionReplaced
+#[derive(Serialize, Deserialize)]
+pub struct TransactionReplacedEvent<'a> {
+    // TX removed from mempool
+    pub old_tx_hash: Cow<'a, Hash>,
+    pub old_fee: u64,
+    // TX added in mempool
+    pub new_tx_hash: Cow<'a, Hash>,
+    pub new_fee: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionEvictionReason {
+    // Mempool reached its size limit and the TX had the lowest fee per byte
+    MempoolFull,
+    // TX stayed in mempool for too long
+    Expired,
+    // TX was depending on a replaced TX and is not valid anymore
+    Invalid
+}
+
+// Value of NotifyEvent::TransactionEvicted
+#[derive(Serialize, Deserialize)]
+pub struct TransactionEvictedEvent<'a> {
+    pub tx_hash: Cow<'a, Hash>,
+    pub reason: TransactionEvictionReason,
+    // timestamp in seconds when the TX was added in mempool
+    pub first_seen: TimestampSeconds
 }
 
 // Value of NotifyEvent::PeerConnected
@@ -3,7 +3,7 @@ pub mod wallet;
 pub mod daemon;
 pub mod query;
 
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashSet};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use bulletproofs::RangeProof;
@@ -13,6 +13,7 @@ use crate::{
         proofs::CiphertextValidityProof,
         Address,
         Hash,
+        PublicKey,
         Signature
     },
     transaction::{
@@ -26,15 +27,135 @@ use crate::{
     }
 };
 pub use data::*;
+use daemon::BlockType;
 
 #[derive(Serialize, Deserialize)]
 pub struct SubscribeParams<'a, E: Clone> {
-    pub notify: Cow<'a, E>
+    pub notify: Cow<'a, E>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub filter: Option<Cow<'a, EventFilter>>,
+    // sequence of the last event received, the events after it are replayed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub since: Option<u64>
+}
+
+// Filter applied by the server on the events of a subscription
+// An event is sent only if it matches every field set
+// An event without the information required by a field never matches it
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct EventFilter {
+    // TX source, TX destination or block miner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub accounts: Option<Vec<Address>>,
+    // Asset transferred or burned
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub assets: Option<Vec<Hash>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub miners: Option<Vec<Address>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub block_types: Option<Vec<BlockType>>
+}
+
+// Fields of an event that matched the filter
+#[derive(Default)]
+struct EventMatches {
+    account: bool,
+    asset: bool,
+    miner: bool,
+    block_type: bool
+}
+
+// EventFilter indexed to match quickly the events of subscriptions with a lot of accounts
+// Events are matched on their JSON value, using the fields shared by the API types:
+// "source", "destination", "miner", "asset" and "block_type"
+pub struct EventMatcher {
+    accounts: Option<HashSet<PublicKey>>,
+    assets: Option<HashSet<Hash>>,
+    miners: Option<HashSet<PublicKey>>,
+    block_types: Option<Vec<BlockType>>
+}
+
+impl EventMatcher {
+    pub fn matches(&self, value: &Value) -> bool {
+        let mut matches = EventMatches::default();
+        self.visit(value, &mut matches);
+
+        (self.accounts.is_none() || matches.account)
+            && (self.assets.is_none() || matches.asset)
+            && (self.miners.is_none() || matches.miner)
+            && (self.block_types.is_none() || matches.block_type)
+    }
+
+    fn visit(&self, value: &Value, matches: &mut EventMatches) {
+        match value {
+            Value::Object(map) => for (key, value) in map {
+                match key.as_str() {
+                    "source" | "destination" => if !matches.account {
+                        matches.account = Self::contains_address(&self.accounts, value);
+                    },
+                    "miner" => {
+                        if !matches.account {
+                            matches.account = Self::contains_address(&self.accounts, value);
+                        }
+                        if !matches.miner {
+                            matches.miner = Self::contains_address(&self.miners, value);
+                        }
+                    },
+                    "asset" => if !matches.asset {
+                        matches.asset = match (&self.assets, Hash::deserialize(value)) {
+                            (Some(assets), Ok(asset)) => assets.contains(&asset),
+                            _ => false
+                        };
+                    },
+                    "block_type" => if !matches.block_type {
+                        matches.block_type = match (&self.block_types, BlockType::deserialize(value)) {
+                            (Some(block_types), Ok(block_type)) => block_types.contains(&block_type),
+                            _ => false
+                        };
+                    },
+                    _ => self.visit(value, matches)
+                }
+            },
+            Value::Array(values) => for value in values {
+                self.visit(value, matches);
+            },
+            _ => {}
+        }
+    }
+
+    fn contains_address(keys: &Option<HashSet<PublicKey>>, value: &Value) -> bool {
+        match (keys, Address::deserialize(value)) {
+            (Some(keys), Ok(address)) => keys.contains(address.get_public_key()),
+            _ => false
+        }
+    }
+}
+
+impl From<EventFilter> for EventMatcher {
+    fn from(filter: EventFilter) -> Self {
+        let into_keys = |addresses: Vec<Address>| addresses.into_iter().map(Address::to_public_key).collect();
+        Self {
+            accounts: filter.accounts.map(into_keys),
+            assets: filter.assets.map(HashSet::from_iter),
+            miners: filter.miners.map(into_keys),
+            block_types: filter.block_types
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct EventResult<'a, E: Clone> {
     pub event: Cow<'a, E>,
+    // increasing number of the event, used as cursor to replay the missed events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub sequence: Option<u64>,
     #[serde(flatten)]
     pub value: Value
 }
@@ -174,4 +295,51 @@ impl<'a> From<RPCTransaction<'a>> for Transaction {
 
 // We create a type above it so for deserialize we can use this type directly
 // and not have to specify the lifetime
-pub type TransactionResponse = RPCTransaction<'static>;
\ No newline at end of file
+pub type TransactionResponse = RPCTransaction<'static>;
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::crypto::KeyPair;
+    use super::*;
+
+    #[test]
+    fn test_event_matcher() {
+        let source = KeyPair::new().get_public_key().to_address(false);
+        let destination = KeyPair::new().get_public_key().to_address(false);
+        let asset = Hash::zero();
+        let event = json!({
+            "source": source,
+            "data": {
+                "transfers": [{ "asset": asset, "destination": destination }]
+            }
+        });
+
+        let matcher = EventMatcher::from(EventFilter {
+            accounts: Some(vec![destination.clone()]),
+            ..Default::default()
+        });
+        assert!(matcher.matches(&event));
+
+        let matcher = EventMatcher::from(EventFilter {
+            accounts: Some(vec![source.clone()]),
+            assets: Some(vec![Hash::max()]),
+            ..Default::default()
+        });
+        assert!(!matcher.matches(&event));
+
+        // the event has no miner
+        let matcher = EventMatcher::from(EventFilter {
+            miners: Some(vec![source]),
+            ..Default::default()
+        });
+        assert!(!matcher.matches(&event));
+
+        let matcher = EventMatcher::from(EventFilter {
+            block_types: Some(vec![BlockType::Normal]),
+            ..Default::default()
+        });
+        assert!(matcher.matches(&json!({ "block_type": "Normal", "miner": destination })));
+        assert!(!matcher.matches(&json!({ "block_type": "Side" })));
+    }
+}
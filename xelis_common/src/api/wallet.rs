@@ -1,9 +1,19 @@
 use std::borrow::Cow;
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use crate::{
-    crypto::{Address, Hash},
+    config::XELIS_ASSET,
+    crypto::{
+        elgamal::{CompressedCiphertext, CompressedPublicKey},
+        Address,
+        AddressType,
+        Hash
+    },
+    network::Network,
+    serializer::{Reader, ReaderError, Serializer, Writer},
     transaction::{
         builder::{FeeBuilder, TransactionTypeBuilder},
+        Reference,
         Transaction
     }
 };
@@ -21,6 +31,97 @@ pub struct BuildTransactionParams {
     pub tx_as_hex: bool
 }
 
+// Transaction to be built and signed by an offline wallet
+// It contains everything the wallet would read from its synced storage
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UnsignedTransaction {
+    // Wallet that must sign it
+    pub source: Address,
+    pub network: Network,
+    pub tx_type: TransactionTypeBuilder,
+    pub fee: FeeBuilder,
+    pub reference: Reference,
+    pub nonce: u64,
+    // Latest balance of each asset used, the offline wallet decrypts them itself
+    pub balances: IndexMap<Hash, CompressedCiphertext>,
+    // Destinations known as registered on chain, used to compute the fees
+    pub registered_keys: Vec<Address>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BuildUnsignedTransactionParams {
+    // Address of the offline wallet that will sign it
+    pub source: Address,
+    #[serde(flatten)]
+    pub tx_type: TransactionTypeBuilder,
+    pub fee: Option<FeeBuilder>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SubmitTransactionParams {
+    // Signed TX in HEX format
+    pub tx_as_hex: String
+}
+
+// Recipient of a batch payout
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PayoutEntry {
+    pub destination: Address,
+    #[serde(default = "default_xelis_asset")]
+    pub asset: Hash,
+    // Amount in atomic units
+    pub amount: u64,
+    #[serde(default)]
+    pub extra_data: Option<DataElement>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutStatus {
+    // Not sent yet
+    Pending,
+    // TX built but the daemon may not have received it
+    Built {
+        tx_hash: Hash,
+        nonce: u64
+    },
+    // TX accepted by the daemon
+    Submitted {
+        tx_hash: Hash,
+        nonce: u64
+    },
+    // Recipient can't be paid, it is never retried
+    Failed {
+        error: String
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PayoutRecipientReport {
+    #[serde(flatten)]
+    pub entry: PayoutEntry,
+    pub status: PayoutStatus
+}
+
+// Status of each recipient of a batch payout, in the same order as the entries
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct PayoutReport {
+    // Hash of the entries, the same entries resume the same payout
+    pub id: Hash,
+    pub recipients: Vec<PayoutRecipientReport>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct BatchPayoutParams {
+    pub entries: Vec<PayoutEntry>,
+    pub fee: Option<FeeBuilder>
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetPayoutReportParams {
+    pub id: Hash
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct EstimateFeesParams {
     #[serde(flatten)]
@@ -36,6 +137,10 @@ fn default_false_value() -> bool {
     false
 }
 
+fn default_xelis_asset() -> Hash {
+    XELIS_ASSET
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct ListTransactionsParams {
     pub min_topoheight: Option<u64>,
@@ -98,6 +203,45 @@ pub struct SetOnlineModeParams {
     pub daemon_address: String
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct CreateAccountParams {
+    #[serde(default)]
+    pub label: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SetAccountLabelParams {
+    pub account: u32,
+    pub label: String
+}
+
+// Account derived from the wallet seed
+// Account 0 is the one of the seed itself
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AccountEntry {
+    pub index: u32,
+    pub label: String,
+    pub address: Address
+}
+
+// Named address of the wallet address book
+// It can be an integrated address to always send the same data
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ContactEntry {
+    pub name: String,
+    pub address: Address
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GetContactParams {
+    pub name: String
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct RemoveContactParams {
+    pub name: String
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct GetBalanceParams {
     pub asset: Option<Hash>
@@ -233,4 +377,107 @@ pub struct TransactionEntry {
     pub topoheight: u64,
     #[serde(flatten)]
     pub entry: EntryType,
-}
\ No newline at end of file
+}
+impl Serializer for PayoutEntry {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_bool(self.destination.is_mainnet());
+        self.destination.get_type().write(writer);
+        self.destination.get_public_key().write(writer);
+        writer.write_hash(&self.asset);
+        writer.write_u64(&self.amount);
+        self.extra_data.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let mainnet = reader.read_bool()?;
+        let addr_type = AddressType::read(reader)?;
+        let key = CompressedPublicKey::read(reader)?;
+        Ok(Self {
+            destination: Address::new(mainnet, addr_type, key),
+            asset: reader.read_hash()?,
+            amount: reader.read_u64()?,
+            extra_data: Option::read(reader)?
+        })
+    }
+}
+
+impl Serializer for PayoutStatus {
+    fn write(&self, writer: &mut Writer) {
+        match self {
+            Self::Pending => writer.write_u8(0),
+            Self::Built { tx_hash, nonce } => {
+                writer.write_u8(1);
+                writer.write_hash(tx_hash);
+                writer.write_u64(nonce);
+            },
+            Self::Submitted { tx_hash, nonce } => {
+                writer.write_u8(2);
+                writer.write_hash(tx_hash);
+                writer.write_u64(nonce);
+            },
+            Self::Failed { error } => {
+                writer.write_u8(3);
+                writer.write_string(error);
+            }
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(match reader.read_u8()? {
+            0 => Self::Pending,
+            1 => Self::Built { tx_hash: reader.read_hash()?, nonce: reader.read_u64()? },
+            2 => Self::Submitted { tx_hash: reader.read_hash()?, nonce: reader.read_u64()? },
+            3 => Self::Failed { error: reader.read_string()? },
+            _ => return Err(ReaderError::InvalidValue)
+        })
+    }
+}
+
+impl Serializer for PayoutReport {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_hash(&self.id);
+        // A payout can have more recipients than the default Vec limit
+        writer.write_u32(&(self.recipients.len() as u32));
+        for recipient in &self.recipients {
+            recipient.entry.write(writer);
+            recipient.status.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let id = reader.read_hash()?;
+        let count = reader.read_u32()?;
+        let mut recipients = Vec::new();
+        for _ in 0..count {
+            recipients.push(PayoutRecipientReport {
+                entry: PayoutEntry::read(reader)?,
+                status: PayoutStatus::read(reader)?
+            });
+        }
+
+        Ok(Self {
+            id,
+            recipients
+        })
+    }
+}
+
+impl Serializer for ContactEntry {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_string(&self.name);
+        writer.write_bool(self.address.is_mainnet());
+        self.address.get_type().write(writer);
+        self.address.get_public_key().write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let name = reader.read_string()?;
+        let mainnet = reader.read_bool()?;
+        let addr_type = AddressType::read(reader)?;
+        let key = CompressedPublicKey::read(reader)?;
+        Ok(Self {
+            name,
+            address: Address::new(mainnet, addr_type, key)
+        })
+    }
+}
@@ -4,6 +4,7 @@ use curve25519_dalek::{
     Scalar
 };
 use rand::rngs::OsRng;
+use sha3::{Digest, Sha3_512};
 use zeroize::Zeroize;
 use crate::{
     api::DataElement,
@@ -25,6 +26,9 @@ use super::{
     H
 };
 
+// Domain of the hash used to derive the accounts of a wallet
+const ACCOUNT_DERIVATION_DOMAIN: &[u8] = b"xelis-wallet-account";
+
 #[derive(Clone)]
 pub struct PublicKey(RistrettoPoint);
 
@@ -109,6 +113,16 @@ impl PrivateKey {
         &self.0
     }
 
+    // Derive the private key of an account from this key
+    // The same key and index always give the same account key
+    pub fn derive_account(&self, index: u32) -> PrivateKey {
+        let mut hasher = Sha3_512::new();
+        hasher.update(ACCOUNT_DERIVATION_DOMAIN);
+        hasher.update(self.0.as_bytes());
+        hasher.update(index.to_be_bytes());
+        Self::from_scalar(Scalar::from_hash(hasher))
+    }
+
     // Decrypt a Ciphertext to a point
     pub fn decrypt_to_point(&self, ciphertext: &Ciphertext) -> RistrettoPoint {
         let commitment = ciphertext.commitment().as_point();
@@ -218,6 +232,17 @@ mod tests {
         assert!(signature.verify(message, public_key));
     }
 
+    #[test]
+    fn test_derive_account() {
+        let keypair = KeyPair::new();
+        let private_key = keypair.get_private_key();
+
+        let account = private_key.derive_account(1);
+        assert_eq!(account.as_scalar(), private_key.derive_account(1).as_scalar());
+        assert_ne!(account.as_scalar(), private_key.derive_account(2).as_scalar());
+        assert_ne!(account.as_scalar(), private_key.as_scalar());
+    }
+
     #[test]
     fn test_encrypt_decrypt() {
         let keypair = KeyPair::new();
@@ -67,6 +67,8 @@ pub enum JsonRPCError {
     ConnectionError(String),
     #[error("Event not registered")]
     EventNotRegistered,
+    #[error("Events notified after sequence {} were missed and can't be replayed", _0)]
+    EventsMissed(u64),
     #[error(transparent)]
     SocketError(#[from] TungsteniteError),
     #[error(transparent)]
@@ -14,21 +14,32 @@ use futures_util::{StreamExt, stream::{SplitSink, SplitStream}, SinkExt};
 use serde::{de::DeserializeOwned, Serialize};
 use serde_json::{Value, json};
 use tokio::{net::TcpStream, sync::{broadcast, oneshot, Mutex}, task::JoinHandle, time::sleep};
-use tokio_tungstenite::{WebSocketStream, MaybeTlsStream, connect_async, tungstenite::Message};
+use tokio_tungstenite::{WebSocketStream, MaybeTlsStream, Connector, connect_async_tls_with_config, tungstenite::Message};
 use log::{debug, error, trace, warn};
 
-use crate::api::SubscribeParams;
+use crate::{api::{EventFilter, SubscribeParams}, tls::TlsClientConfig};
 
 use super::{JSON_RPC_VERSION, JsonRPCError, JsonRPCResponse, JsonRPCResult};
 
+// Code of the error returned by the server when the events to replay are not available anymore
+const EVENT_GAP_TOO_LARGE_CODE: i16 = -32006;
+
+// Message sent to the receivers of a subscription
+#[derive(Clone)]
+enum EventMessage {
+    Event(Value),
+    // Events notified after this sequence were missed and can't be replayed
+    Gap(u64)
+}
+
 // EventReceiver allows to get the event value parsed directly
 pub struct EventReceiver<T: DeserializeOwned> {
-    inner: broadcast::Receiver<Value>,
+    inner: broadcast::Receiver<EventMessage>,
     _phantom: PhantomData<T>
 }
 
 impl<T: DeserializeOwned> EventReceiver<T> {
-    pub fn new(inner: broadcast::Receiver<Value>) -> Self {
+    fn new(inner: broadcast::Receiver<EventMessage>) -> Self {
         Self {
             inner,
             _phantom: PhantomData
@@ -39,6 +50,8 @@ impl<T: DeserializeOwned> EventReceiver<T> {
     // if we lagged behind, we will catch up
     // If you don't want to miss any event, you should create a queue to store them
     // or an unbounded channel
+    // Returns a `JsonRPCError::EventsMissed` error if events were missed during a reconnection
+    // and can't be replayed, the state built from the events should be synced again
     pub async fn next(&mut self) -> Result<T, Error> {
         let mut res = self.inner.recv().await;
         // If we lagged behind, we need to catch up
@@ -52,11 +65,21 @@ impl<T: DeserializeOwned> EventReceiver<T> {
             };
         }
  
-        let value = res?;
-        Ok(serde_json::from_value(value)?)
+        match res? {
+            EventMessage::Event(value) => Ok(serde_json::from_value(value)?),
+            EventMessage::Gap(since) => Err(JsonRPCError::EventsMissed(since).into())
+        }
     }
 }
 
+// Parameters of an event subscription, kept to subscribe again after a reconnection
+#[derive(Clone, Default)]
+struct Subscription {
+    filter: Option<EventFilter>,
+    // sequence of the last event received, used to replay the missed events
+    last_sequence: Option<u64>
+}
+
 // It is around a Arc to be shareable easily
 // it has a tokio task running in background to handle all incoming messages
 pub type WebSocketJsonRPCClient<E> = Arc<WebSocketJsonRPCClientImpl<E>>;
@@ -69,12 +92,16 @@ pub struct WebSocketJsonRPCClientImpl<E: Serialize + Hash + Eq + Send + Sync + C
     requests: Mutex<HashMap<usize, oneshot::Sender<JsonRPCResponse>>>,
     // This contains all id sent to register to a event on daemon
     // It stores the sender channel to propagate the event to apps 
-    handler_by_id: Mutex<HashMap<usize, broadcast::Sender<Value>>>,
+    handler_by_id: Mutex<HashMap<usize, broadcast::Sender<EventMessage>>>,
     // This contains all events registered by the app with its usize
     // This allows us to subscribe to same channel if its already subscribed
     events_to_id: Mutex<HashMap<E, usize>>,
+    // Subscription using this id
+    subscription_by_id: Mutex<HashMap<usize, Subscription>>,
     // websocket server address
     target: String,
+    // custom TLS configuration, used to trust a self-signed server
+    tls_config: Option<TlsClientConfig>,
     // auto reconnect duration
     auto_reconnect: Mutex<Option<Duration>>,
     // is the client online
@@ -88,10 +115,14 @@ pub struct WebSocketJsonRPCClientImpl<E: Serialize + Hash + Eq + Send + Sync + C
 }
 
 pub const DEFAULT_AUTO_RECONNECT: Duration = Duration::from_secs(5);
+// Events buffered for the receivers of a subscription
+// Replayed events are received in burst, they must not be dropped
+const EVENT_CHANNEL_SIZE: usize = 1024;
 
 impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCClientImpl<E> {
-    async fn connect_to(target: &String) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, JsonRPCError> {
-        let (ws, response) = connect_async(target).await?;
+    async fn connect_to(target: &String, tls_config: &Option<TlsClientConfig>) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, JsonRPCError> {
+        let connector = tls_config.as_ref().map(|config| Connector::Rustls(Arc::clone(config)));
+        let (ws, response) = connect_async_tls_with_config(target, None, false, connector).await?;
         let status = response.status();
         if status.is_server_error() || status.is_client_error() {
             return Err(JsonRPCError::ConnectionError(status.to_string()));
@@ -100,7 +131,12 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
         Ok(ws)
     }
 
-    pub async fn new(mut target: String) -> Result<WebSocketJsonRPCClient<E>, JsonRPCError> {
+    pub async fn new(target: String) -> Result<WebSocketJsonRPCClient<E>, JsonRPCError> {
+        Self::new_with_tls(target, None).await
+    }
+
+    // Create a client using a custom TLS configuration for wss:// connections
+    pub async fn new_with_tls(mut target: String, tls_config: Option<TlsClientConfig>) -> Result<WebSocketJsonRPCClient<E>, JsonRPCError> {
         if target.starts_with("https://") {
             target.replace_range(..8, "wss://");
         }
@@ -111,7 +147,7 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
             target.insert_str(0, "ws://");
         }
 
-        let ws = Self::connect_to(&target).await?;
+        let ws = Self::connect_to(&target, &tls_config).await?;
         
         let (write, read) = ws.split();
         let client = Arc::new(WebSocketJsonRPCClientImpl {
@@ -120,7 +156,9 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
             requests: Mutex::new(HashMap::new()),
             handler_by_id: Mutex::new(HashMap::new()),
             events_to_id: Mutex::new(HashMap::new()),
+            subscription_by_id: Mutex::new(HashMap::new()),
             target,
+            tls_config,
             auto_reconnect: Mutex::new(Some(DEFAULT_AUTO_RECONNECT)),
             online: AtomicBool::new(true),
             offline_channel: Mutex::new(None),
@@ -210,11 +248,42 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
             let events = self.events_to_id.lock().await;
             events.clone()
         };
+        let subscriptions = {
+            let subscriptions = self.subscription_by_id.lock().await;
+            subscriptions.clone()
+        };
         for (event, id) in events {
-            // Send it to the server
-            if !self.send::<_, bool>("subscribe", Some(id), &SubscribeParams {
+            let subscription = subscriptions.get(&id).cloned().unwrap_or_default();
+            let mut params = SubscribeParams {
                 notify: Cow::Borrowed(&event),
-            }).await? {
+                filter: subscription.filter.as_ref().map(Cow::Borrowed),
+                // Ask the events missed while we were disconnected
+                since: subscription.last_sequence
+            };
+
+            // Send it to the server
+            let accepted = match (self.send::<_, bool>("subscribe", Some(id), &params).await, params.since) {
+                (Err(JsonRPCError::ServerError { code: EVENT_GAP_TOO_LARGE_CODE, .. }), Some(since)) => {
+                    warn!("Missed events with id {} since {} can't be replayed, subscribing again without them", id, since);
+                    // Let the receivers know that they missed events
+                    {
+                        let handlers = self.handler_by_id.lock().await;
+                        if let Some(sender) = handlers.get(&id) {
+                            if sender.receiver_count() > 0 {
+                                if let Err(e) = sender.send(EventMessage::Gap(since)) {
+                                    error!("Error sending missed events to the request: {:?}", e);
+                                }
+                            }
+                        }
+                    }
+
+                    params.since = None;
+                    self.send::<_, bool>("subscribe", Some(id), &params).await?
+                },
+                (res, _) => res?
+            };
+
+            if !accepted {
                 error!("Error while resubscribing to event with id {}", id);
             }
         }
@@ -262,7 +331,7 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
         }
 
         {
-            let ws = Self::connect_to(&self.target).await?;
+            let ws = Self::connect_to(&self.target, &self.tls_config).await?;
             let (write, read) = ws.split();
             {
                 let mut lock = self.ws.lock().await;
@@ -302,7 +371,7 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
             sleep(*duration).await;
             debug!("Trying to reconnect to the server...");
 
-            let ws = match Self::connect_to(&self.target).await {
+            let ws = match Self::connect_to(&self.target, &self.tls_config).await {
                 Ok(ws) => ws,
                 Err(e) => {
                     debug!("Error while reconnecting to the server: {:?}", e);
@@ -360,6 +429,10 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
             let mut handlers = self.handler_by_id.lock().await;
             handlers.clear();
         }
+        {
+            let mut subscriptions = self.subscription_by_id.lock().await;
+            subscriptions.clear();
+        }
     }
 
     // Task running in background to handle every messages from the WebSocket server
@@ -402,9 +475,19 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
                         {
                             let mut handlers = self.handler_by_id.lock().await;
                             if let Some(sender) = handlers.get_mut(&id) {
+                                let sequence = response.result.as_ref()
+                                    .and_then(|result| result.get("sequence"))
+                                    .and_then(Value::as_u64);
+                                if let Some(sequence) = sequence {
+                                    let mut subscriptions = self.subscription_by_id.lock().await;
+                                    if let Some(subscription) = subscriptions.get_mut(&id) {
+                                        subscription.last_sequence = Some(sequence);
+                                    }
+                                }
+
                                 // Check that we still have someone who listen it
                                 if sender.receiver_count() > 0 {
-                                    if let Err(e) = sender.send(response.result.unwrap_or_default()) {
+                                    if let Err(e) = sender.send(EventMessage::Event(response.result.unwrap_or_default())) {
                                         error!("Error sending event to the request: {:?}", e);
                                     }
                                 }
@@ -440,6 +523,18 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
 
     // Subscribe to an event
     pub async fn subscribe_event<T: DeserializeOwned>(&self, event: E) -> JsonRPCResult<EventReceiver<T>> {
+        self.subscribe_event_with_filter(event, None).await
+    }
+
+    // Subscribe to an event, only the events matching the filter are sent by the server
+    // If already subscribed to this event, the existing subscription is used and its filter is kept
+    pub async fn subscribe_event_with_filter<T: DeserializeOwned>(&self, event: E, filter: Option<EventFilter>) -> JsonRPCResult<EventReceiver<T>> {
+        self.subscribe_event_since(event, filter, None).await
+    }
+
+    // Subscribe to an event and receive again the events notified after the `since` sequence
+    // The server returns an error if some of them are not available anymore
+    pub async fn subscribe_event_since<T: DeserializeOwned>(&self, event: E, filter: Option<EventFilter>, since: Option<u64>) -> JsonRPCResult<EventReceiver<T>> {
         // Returns a Receiver for this event if already registered
         {
             let ids = self.events_to_id.lock().await;
@@ -454,10 +549,36 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
         // Generate the ID for this request
         let id = self.next_id();
 
+        // Create a channel to receive the event
+        // It is registered before subscribing to not miss the events replayed right after the response
+        let (sender, receiver) = broadcast::channel(EVENT_CHANNEL_SIZE);
+        {
+            let mut handlers = self.handler_by_id.lock().await;
+            handlers.insert(id, sender);
+        }
+        {
+            let mut subscriptions = self.subscription_by_id.lock().await;
+            subscriptions.insert(id, Subscription {
+                filter: filter.clone(),
+                last_sequence: since
+            });
+        }
+
         // Send it to the server
-        self.send::<_, bool>("subscribe", Some(id), &SubscribeParams {
-            notify: Cow::Borrowed(&event)
-        }).await?;
+        let res = self.send::<_, bool>("subscribe", Some(id), &SubscribeParams {
+            notify: Cow::Borrowed(&event),
+            filter: filter.as_ref().map(Cow::Borrowed),
+            since
+        }).await;
+
+        if let Err(e) = res {
+            self.handler_by_id.lock().await.remove(&id);
+            self.subscription_by_id.lock().await.remove(&id);
+            return Err(match (e, since) {
+                (JsonRPCError::ServerError { code: EVENT_GAP_TOO_LARGE_CODE, .. }, Some(since)) => JsonRPCError::EventsMissed(since),
+                (e, _) => e
+            })
+        }
 
         // Create a mapping from the event to the ID used for the request
         {
@@ -465,16 +586,21 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
             ids.insert(event, id);
         }
 
-        // Create a channel to receive the event
-        let (sender, receiver) = broadcast::channel(1);
-        {
-            let mut handlers = self.handler_by_id.lock().await;
-            handlers.insert(id, sender);
-        }
-
         Ok(EventReceiver::new(receiver))
     }
 
+    // Get the sequence of the last event received for this subscription
+    // It can be saved to replay the events missed with `subscribe_event_since`
+    pub async fn get_last_event_sequence(&self, event: &E) -> Option<u64> {
+        let id = {
+            let ids = self.events_to_id.lock().await;
+            *ids.get(event)?
+        };
+
+        let subscriptions = self.subscription_by_id.lock().await;
+        subscriptions.get(&id).and_then(|subscription| subscription.last_sequence)
+    }
+
     // Unsubscribe from an event
     pub async fn unsubscribe_event(&self, event: &E) -> JsonRPCResult<()> {        
         // Retrieve the id for this event
@@ -491,6 +617,10 @@ impl<E: Serialize + Hash + Eq + Send + Sync + Clone + 'static> WebSocketJsonRPCC
             let mut handlers = self.handler_by_id.lock().await;
             handlers.remove(&id);
         }
+        {
+            let mut subscriptions = self.subscription_by_id.lock().await;
+            subscriptions.remove(&id);
+        }
 
         Ok(())
     }
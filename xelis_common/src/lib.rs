@@ -15,6 +15,7 @@ pub mod context;
 pub mod queue;
 pub mod varuint;
 pub mod time;
+pub mod tls;
 
 #[cfg(feature = "json_rpc")]
 pub mod json_rpc;
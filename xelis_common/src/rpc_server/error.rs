@@ -21,8 +21,16 @@ pub enum InternalRpcError {
     UnexpectedParams,
     #[error("Expected json_rpc set to '2.0'")]
     InvalidVersion,
+    #[error("Batch contains {} requests, maximum allowed is {}", _0, _1)]
+    BatchTooLarge(usize, usize),
     #[error("Method '{}' in request was not found", _0)]
     MethodNotFound(String),
+    #[error("Missing or invalid credentials")]
+    Unauthorized,
+    #[error("Method '{}' is not allowed for these credentials", _0)]
+    MethodNotAllowed(String),
+    #[error("Too many requests, please retry later")]
+    RateLimited,
     #[error(transparent)]
     DeserializerError(#[from] ReaderError),
     #[error(transparent)]
@@ -33,6 +41,8 @@ pub enum InternalRpcError {
     EventNotSubscribed,
     #[error("Event is already subscribed")]
     EventAlreadySubscribed,
+    #[error("Events since sequence {} are not available anymore", _0)]
+    EventGapTooLarge(u64),
     #[error("{}", _0)]
     Custom(String),
     #[error("{}", _0)]
@@ -43,9 +53,13 @@ impl InternalRpcError {
     pub fn get_code(&self) -> i16 {
         match self {
             Self::ParseBodyError => -32700,
-            Self::InvalidRequest | InternalRpcError::InvalidVersion => -32600,
+            Self::InvalidRequest | InternalRpcError::InvalidVersion | InternalRpcError::BatchTooLarge(_, _) => -32600,
             Self::MethodNotFound(_) => -32601,
             Self::InvalidParams(_) | InternalRpcError::UnexpectedParams => -32602,
+            Self::Unauthorized => -32001,
+            Self::MethodNotAllowed(_) => -32002,
+            Self::RateLimited => -32005,
+            Self::EventGapTooLarge(_) => -32006,
             _ => -32603
         }
     }
@@ -1,20 +1,23 @@
 pub mod websocket;
+pub mod tls;
 mod error;
 mod rpc_handler;
 
 use std::borrow::Cow;
 
 pub use error::{RpcResponseError, InternalRpcError};
-pub use rpc_handler::{RPCHandler, Handler};
+pub use rpc_handler::{RPCHandler, Handler, MethodObserver, MethodAuthorizer};
 pub use rpc_handler::parse_params;
 
 use actix_web::{HttpResponse, web::{self, Data, Payload}, Responder, HttpRequest};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use self::websocket::{WebSocketServerShared, WebSocketHandler};
+use self::websocket::{WebSocketServerShared, WebSocketHandler, HttpRequest as RpcHttpRequest};
 
 pub const JSON_RPC_VERSION: &str = "2.0";
+// Default maximum number of requests in a single batch
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RpcRequest {
@@ -24,6 +27,13 @@ pub struct RpcRequest {
     pub params: Option<Value>
 }
 
+// Body received: a single request or a batch of requests
+// Each request is kept as a JSON value so an invalid one doesn't fail the whole batch
+pub enum RpcRequestBody {
+    Single(Value),
+    Batch(Vec<Value>)
+}
+
 #[derive(Serialize)]
 pub struct RpcResponse<'a> {
     pub jsonrpc: &'a str,
@@ -47,13 +57,17 @@ pub trait RPCServerHandler<T: Send + Clone> {
 }
 
 // JSON RPC handler endpoint
-pub async fn json_rpc<T, H>(server: Data<H>, body: web::Bytes) -> Result<impl Responder, RpcResponseError>
+pub async fn json_rpc<T, H>(server: Data<H>, request: HttpRequest, body: web::Bytes) -> Result<impl Responder, RpcResponseError>
 where
     T: Send + Sync + Clone + 'static,
     H: RPCServerHandler<T>
 {
-    let result = server.get_rpc_handler().handle_request(&body).await?;
-    Ok(HttpResponse::Ok().json(result))
+    let request = RpcHttpRequest::from(request);
+    match server.get_rpc_handler().handle_request(&request, &body).await? {
+        Some(result) => Ok(HttpResponse::Ok().json(result)),
+        // Only notifications were received
+        None => Ok(HttpResponse::NoContent().finish())
+    }
 }
 
 // trait to retrieve easily a websocket handler for registered route
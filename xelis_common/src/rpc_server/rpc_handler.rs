@@ -1,16 +1,28 @@
-use std::{collections::HashMap, pin::Pin, future::Future};
+use std::{collections::HashMap, pin::Pin, future::Future, time::{Duration, Instant}};
 use serde::de::DeserializeOwned;
 use serde_json::{Value, json};
 use crate::context::Context;
 
-use super::{InternalRpcError, RpcResponseError, RpcRequest, JSON_RPC_VERSION};
-use log::{error, trace};
+use super::{websocket::HttpRequest, InternalRpcError, RpcResponseError, RpcRequest, RpcRequestBody, DEFAULT_MAX_BATCH_SIZE, JSON_RPC_VERSION};
+use log::{debug, error, trace};
 
 pub type Handler = fn(Context, Value) -> Pin<Box<dyn Future<Output = Result<Value, InternalRpcError>> + Send>>;
 
+// Called after each executed method with its name, its execution time and if it was successful
+pub type MethodObserver = Box<dyn Fn(&str, Duration, bool) + Send + Sync>;
+
+// Called before each method execution to check if the client is allowed to call it
+pub type MethodAuthorizer = Box<dyn Fn(&Context, &str) -> Result<(), InternalRpcError> + Send + Sync>;
+
 pub struct RPCHandler<T: Send + Clone + 'static> {
     methods: HashMap<String, Handler>, // all RPC methods registered
-    data: T
+    data: T,
+    // maximum number of requests allowed in a batch
+    max_batch_size: usize,
+    // optional observer of all executed methods
+    observer: Option<MethodObserver>,
+    // optional access control of all methods
+    authorizer: Option<MethodAuthorizer>
 }
 
 impl<T> RPCHandler<T>
@@ -20,12 +32,67 @@ where
     pub fn new(data: T) -> Self {
         Self {
             methods: HashMap::new(),
-            data
+            data,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            observer: None,
+            authorizer: None
+        }
+    }
+
+    // Handle a single request or a batch of requests received over HTTP
+    // Returns None if there is nothing to respond (only notifications)
+    pub async fn handle_request(&self, request: &HttpRequest, body: &[u8]) -> Result<Option<Value>, RpcResponseError> {
+        self.handle_body(body, move |value| self.handle_request_value(Self::create_context(request), value)).await
+    }
+
+    // Parse the body and execute each request it contains with `execute`
+    // A batch is executed in order, the errors are returned in its responses
+    // Returns None if there is nothing to respond (only notifications)
+    pub async fn handle_body<F, Fut>(&self, body: &[u8], execute: F) -> Result<Option<Value>, RpcResponseError>
+    where
+        F: Fn(Value) -> Fut,
+        Fut: Future<Output = Result<Option<Value>, RpcResponseError>>
+    {
+        match self.parse_body(body)? {
+            RpcRequestBody::Single(value) => execute(value).await,
+            RpcRequestBody::Batch(values) => {
+                let mut responses = Vec::with_capacity(values.len());
+                for value in values {
+                    match execute(value).await {
+                        Ok(Some(response)) => responses.push(response),
+                        Ok(None) => {},
+                        Err(e) => responses.push(e.to_json())
+                    }
+                }
+
+                if responses.is_empty() {
+                    Ok(None)
+                } else {
+                    Ok(Some(Value::Array(responses)))
+                }
+            }
         }
     }
 
-    pub async fn handle_request(&self, body: &[u8]) -> Result<Value, RpcResponseError> {
-        self.handle_request_with_context(Context::default(), body).await
+    // Create the context of a request, the HTTP request is stored to be used by the authorizer
+    fn create_context(request: &HttpRequest) -> Context {
+        let mut context = Context::default();
+        context.store(request.clone());
+        context
+    }
+
+    // Execute a single request from its JSON value
+    // A notification (request without id) never returns a response, even on error
+    pub async fn handle_request_value(&self, context: Context, value: Value) -> Result<Option<Value>, RpcResponseError> {
+        let request = self.parse_request_from_value(value)?;
+        if request.id.is_none() {
+            if let Err(e) = self.execute_method(context, request).await {
+                debug!("Error while executing notification: {}", e);
+            }
+            return Ok(None)
+        }
+
+        self.execute_method(context, request).await.map(Some)
     }
 
     pub async fn handle_request_with_context(&self, context: Context, body: &[u8]) -> Result<Value, RpcResponseError> {
@@ -41,11 +108,49 @@ where
         Ok(request)
     }
 
+    // Parse a single request that was already deserialized, for example from a batch
+    pub fn parse_request_from_value(&self, value: Value) -> Result<RpcRequest, RpcResponseError> {
+        let request: RpcRequest = serde_json::from_value(value).map_err(|_| RpcResponseError::new(None, InternalRpcError::InvalidRequest))?;
+        if request.jsonrpc != JSON_RPC_VERSION {
+            return Err(RpcResponseError::new(request.id, InternalRpcError::InvalidVersion));
+        }
+        Ok(request)
+    }
+
+    // Parse the body as a single request or as a batch of requests
+    // An empty batch or a batch above the configured limit is rejected
+    pub fn parse_body(&self, body: &[u8]) -> Result<RpcRequestBody, RpcResponseError> {
+        let value: Value = serde_json::from_slice(&body).map_err(|_| RpcResponseError::new(None, InternalRpcError::ParseBodyError))?;
+        match value {
+            Value::Array(values) => {
+                if values.is_empty() {
+                    return Err(RpcResponseError::new(None, InternalRpcError::InvalidRequest));
+                }
+
+                if values.len() > self.max_batch_size {
+                    return Err(RpcResponseError::new(None, InternalRpcError::BatchTooLarge(values.len(), self.max_batch_size)));
+                }
+
+                Ok(RpcRequestBody::Batch(values))
+            },
+            value => Ok(RpcRequestBody::Single(value))
+        }
+    }
+
     pub fn has_method(&self, method_name: &String) -> bool {
         self.methods.contains_key(method_name)
     }
 
+    // Verify using the authorizer if the method can be called in this context
+    pub fn authorize(&self, context: &Context, method: &str) -> Result<(), InternalRpcError> {
+        match &self.authorizer {
+            Some(authorizer) => authorizer(context, method),
+            None => Ok(())
+        }
+    }
+
     pub async fn execute_method(&self, mut context: Context, mut request: RpcRequest) -> Result<Value, RpcResponseError> {
+        self.authorize(&context, &request.method).map_err(|err| RpcResponseError::new(request.id, err))?;
         let handler = match self.methods.get(&request.method) {
             Some(handler) => handler,
             None => return Err(RpcResponseError::new(request.id, InternalRpcError::MethodNotFound(request.method)))
@@ -54,7 +159,12 @@ where
         let params = request.params.take().unwrap_or(Value::Null);
         // Add the data
         context.store(self.get_data().clone());
-        let result = handler(context, params).await.map_err(|err| RpcResponseError::new(request.id, err))?;
+        let start = Instant::now();
+        let result = handler(context, params).await;
+        if let Some(observer) = &self.observer {
+            observer(&request.method, start.elapsed(), result.is_ok());
+        }
+        let result = result.map_err(|err| RpcResponseError::new(request.id, err))?;
         Ok(json!({
             "jsonrpc": JSON_RPC_VERSION,
             "id": request.id,
@@ -72,8 +182,98 @@ where
     pub fn get_data(&self) -> &T {
         &self.data
     }
+
+    // set the maximum number of requests allowed in a batch
+    pub fn set_max_batch_size(&mut self, max_batch_size: usize) {
+        self.max_batch_size = max_batch_size;
+    }
+
+    pub fn get_max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    // set the observer called after each executed method
+    pub fn set_method_observer(&mut self, observer: MethodObserver) {
+        self.observer = Some(observer);
+    }
+
+    // set the authorizer called before each method execution
+    pub fn set_method_authorizer(&mut self, authorizer: MethodAuthorizer) {
+        self.authorizer = Some(authorizer);
+    }
 }
 
 pub fn parse_params<P: DeserializeOwned>(value: Value) -> Result<P, InternalRpcError> {
     serde_json::from_value(value).map_err(|e| InternalRpcError::InvalidParams(e))
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::async_handler;
+    use super::*;
+
+    async fn echo(_: Context, params: Value) -> Result<Value, InternalRpcError> {
+        Ok(params)
+    }
+
+    fn create_handler(max_batch_size: usize) -> RPCHandler<()> {
+        let mut handler = RPCHandler::new(());
+        handler.register_method("echo", async_handler!(echo));
+        handler.set_max_batch_size(max_batch_size);
+        handler
+    }
+
+    async fn handle(handler: &RPCHandler<()>, body: Value) -> Result<Option<Value>, RpcResponseError> {
+        let body = serde_json::to_vec(&body).unwrap();
+        handler.handle_body(&body, |value| handler.handle_request_value(Context::default(), value)).await
+    }
+
+    fn request(id: Option<usize>, method: &str) -> Value {
+        match id {
+            Some(id) => json!({ "jsonrpc": JSON_RPC_VERSION, "id": id, "method": method, "params": id }),
+            None => json!({ "jsonrpc": JSON_RPC_VERSION, "method": method })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_size_limit() {
+        let handler = create_handler(2);
+        let batch = json!([request(Some(1), "echo"), request(Some(2), "echo")]);
+        let responses = handle(&handler, batch).await.unwrap().unwrap();
+        assert_eq!(responses.as_array().unwrap().len(), 2);
+
+        let batch = json!([request(Some(1), "echo"), request(Some(2), "echo"), request(Some(3), "echo")]);
+        let err = handle(&handler, batch).await.unwrap_err().to_json();
+        assert_eq!(err["error"]["code"], -32600);
+        assert_eq!(err["error"]["message"], InternalRpcError::BatchTooLarge(3, 2).to_string());
+    }
+
+    #[tokio::test]
+    async fn test_empty_batch() {
+        let handler = create_handler(DEFAULT_MAX_BATCH_SIZE);
+        let err = handle(&handler, json!([])).await.unwrap_err().to_json();
+        assert_eq!(err["error"]["message"], InternalRpcError::InvalidRequest.to_string());
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_notifications() {
+        let handler = create_handler(DEFAULT_MAX_BATCH_SIZE);
+        // Notifications get no response, even when they fail
+        let batch = json!([request(None, "echo"), request(Some(1), "echo"), request(None, "unknown"), request(Some(2), "unknown"), 42]);
+        let responses = handle(&handler, batch).await.unwrap().unwrap();
+        let responses = responses.as_array().unwrap();
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"], 1);
+        assert_eq!(responses[1]["id"], 2);
+        assert_eq!(responses[1]["error"]["code"], -32601);
+        // Invalid request in the batch
+        assert_eq!(responses[2]["error"]["code"], -32600);
+
+        // Nothing to respond to a batch of notifications
+        let batch = json!([request(None, "echo"), request(None, "echo")]);
+        assert!(handle(&handler, batch).await.unwrap().is_none());
+
+        // A single notification
+        assert!(handle(&handler, request(None, "echo")).await.unwrap().is_none());
+    }
+}
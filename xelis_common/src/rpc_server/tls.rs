@@ -0,0 +1,80 @@
+use std::sync::{Arc, RwLock};
+use anyhow::{Context, Result};
+use rustls::{
+    crypto::ring::sign::any_supported_type,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig
+};
+use crate::tls::{read_certificates, read_private_key};
+
+// Certificate and private key of a server, loaded from PEM files
+// They can be reloaded without restarting the server
+#[derive(Debug)]
+pub struct ReloadableCertResolver {
+    cert_path: String,
+    key_path: String,
+    key: RwLock<Arc<CertifiedKey>>
+}
+
+impl ReloadableCertResolver {
+    pub fn new(cert_path: String, key_path: String) -> Result<Self> {
+        let key = Self::load(&cert_path, &key_path)?;
+        Ok(Self {
+            cert_path,
+            key_path,
+            key: RwLock::new(Arc::new(key))
+        })
+    }
+
+    fn load(cert_path: &str, key_path: &str) -> Result<CertifiedKey> {
+        let certs = read_certificates(cert_path)?;
+        let key = read_private_key(key_path)?;
+        let signing_key = any_supported_type(&key).context("Unsupported private key type")?;
+        Ok(CertifiedKey::new(certs, signing_key))
+    }
+
+    // Read again the certificate and private key files
+    // In case of error, the current ones are kept
+    pub fn reload(&self) -> Result<()> {
+        let key = Self::load(&self.cert_path, &self.key_path)?;
+        let mut lock = self.key.write().map_err(|_| anyhow::anyhow!("TLS certificate lock is poisoned"))?;
+        *lock = Arc::new(key);
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertResolver {
+    fn resolve(&self, _: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.key.read().ok().map(|key| Arc::clone(&key))
+    }
+}
+
+// Create the TLS configuration of a server using the PEM certificate and private key files
+// On unix, the files are reloaded when the process receives a SIGHUP
+pub fn load_server_config(cert_path: String, key_path: String) -> Result<ServerConfig> {
+    let resolver = Arc::new(ReloadableCertResolver::new(cert_path, key_path)?);
+
+    #[cfg(unix)]
+    {
+        use log::{error, info};
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut stream = signal(SignalKind::hangup()).context("Error while listening for SIGHUP")?;
+        let resolver = Arc::clone(&resolver);
+        tokio::spawn(async move {
+            while stream.recv().await.is_some() {
+                match resolver.reload() {
+                    Ok(_) => info!("TLS certificate '{}' has been reloaded", resolver.cert_path),
+                    Err(e) => error!("Error while reloading TLS certificate, keeping the current one: {:#}", e)
+                }
+            }
+        });
+    }
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+
+    Ok(config)
+}
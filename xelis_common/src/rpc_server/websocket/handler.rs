@@ -1,16 +1,131 @@
-use std::{collections::{HashMap, HashSet}, hash::Hash, borrow::Cow};
+use std::{collections::{HashMap, HashSet, VecDeque}, hash::Hash, borrow::Cow};
 use actix_web::web::Bytes;
 use async_trait::async_trait;
 use log::debug;
 use serde_json::{Value, json};
 use serde::{de::DeserializeOwned, Serialize};
-use tokio::sync::Mutex;
-use crate::{rpc_server::{RPCHandler, RpcResponseError, InternalRpcError, RpcRequest, RpcResponse}, api::{SubscribeParams, EventResult}, context::Context};
+use tokio::sync::{mpsc, Mutex};
+use crate::{rpc_server::{RPCHandler, RpcResponseError, InternalRpcError, RpcRequest, RpcResponse}, api::{SubscribeParams, EventResult, EventMatcher}, context::Context, time::get_current_time_in_millis};
 use super::{WebSocketSessionShared, WebSocketHandler};
 
+// Subscription of a session to an event
+struct Subscription {
+    // id of the subscribe request, used as id of the notifications
+    id: Option<usize>,
+    // only the events matching it are sent
+    filter: Option<EventMatcher>,
+    // events waiting for the subscribe response to be sent, replayed ones included
+    pending: Option<Vec<(u64, Value)>>
+}
+
+// Subscriptions of a session
+struct SessionSubscriptions<E> {
+    subscriptions: HashMap<E, Subscription>,
+    // events are sent in order through it by a task of the session
+    sender: mpsc::UnboundedSender<String>
+}
+
+impl<E> SessionSubscriptions<E> {
+    fn new<H: WebSocketHandler + 'static>(session: WebSocketSessionShared<H>) -> Self {
+        let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            while let Some(value) = receiver.recv().await {
+                if let Err(e) = session.send_text(value).await {
+                    debug!("Error occured while notifying a new event: {}", e);
+                    break;
+                }
+            }
+        });
+
+        Self {
+            subscriptions: HashMap::new(),
+            sender
+        }
+    }
+
+    fn send(&self, value: &Value) {
+        if self.sender.send(value.to_string()).is_err() {
+            debug!("Error occured while notifying a new event: session is closed");
+        }
+    }
+}
+
+// Last events notified, replayed to the sessions subscribing with a cursor
+struct EventHistory<E> {
+    // maximum count of events kept, 0 to disable the replay
+    capacity: usize,
+    // sequence of the next event
+    next_sequence: u64,
+    // lowest sequence that can still be replayed
+    oldest_sequence: u64,
+    // events with their sequence and their final value
+    events: VecDeque<(u64, E, Value)>,
+    // events still recorded after their last subscriber left, with the sequence at which it left
+    // a session can resume them until the history rotates past this sequence
+    tracked: HashMap<E, u64>
+}
+
+impl<E: Eq + Hash + Clone> EventHistory<E> {
+    fn new(capacity: usize) -> Self {
+        // Start from the current time so the sequences are still increasing after a restart
+        // and the cursors of a previous run are too old to be replayed
+        let sequence = get_current_time_in_millis() * 1000;
+        Self {
+            capacity,
+            next_sequence: sequence,
+            oldest_sequence: sequence,
+            events: VecDeque::with_capacity(capacity),
+            tracked: HashMap::new()
+        }
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        sequence
+    }
+
+    fn record(&mut self, sequence: u64, event: &E, value: &Value) {
+        if self.capacity == 0 {
+            self.oldest_sequence = sequence + 1;
+            return;
+        }
+
+        self.events.push_back((sequence, event.clone(), value.clone()));
+        while self.events.len() > self.capacity {
+            if let Some((sequence, _, _)) = self.events.pop_front() {
+                self.oldest_sequence = sequence + 1;
+            }
+        }
+
+        // no cursor of the sessions that left can be resumed anymore
+        let oldest_sequence = self.oldest_sequence;
+        self.tracked.retain(|_, left| *left >= oldest_sequence);
+    }
+
+    // Keep recording an event after its last subscriber left
+    fn track(&mut self, event: E) {
+        if self.capacity > 0 {
+            self.tracked.insert(event, self.next_sequence);
+        }
+    }
+
+    // Get the events notified after this sequence
+    // Returns None if some of them are not available anymore
+    fn get_events_since(&self, since: u64) -> Option<impl Iterator<Item = &(u64, E, Value)>> {
+        if since.saturating_add(1) < self.oldest_sequence || since >= self.next_sequence {
+            return None
+        }
+
+        Some(self.events.iter().filter(move |(sequence, _, _)| *sequence > since))
+    }
+}
+
 // generic websocket handler supporting event subscriptions 
 pub struct EventWebSocketHandler<T: Sync + Send + Clone + 'static, E: Serialize + DeserializeOwned + Send + Eq + Hash + Clone + 'static> {
-    sessions: Mutex<HashMap<WebSocketSessionShared<Self>, HashMap<E, Option<usize>>>>,
+    sessions: Mutex<HashMap<WebSocketSessionShared<Self>, SessionSubscriptions<E>>>,
+    // it must be locked before the sessions
+    history: Mutex<EventHistory<E>>,
     handler: RPCHandler<T>
 }
 
@@ -20,89 +135,171 @@ where
     E: Serialize + DeserializeOwned + Send + Eq + Hash + Clone + 'static
 {
     pub fn new(handler: RPCHandler<T>) -> Self {
+        Self::with_history(handler, 0)
+    }
+
+    // Keep the last `history_size` events to replay them to the sessions subscribing with a cursor
+    pub fn with_history(handler: RPCHandler<T>, history_size: usize) -> Self {
         Self {
             sessions: Mutex::new(HashMap::new()),
+            history: Mutex::new(EventHistory::new(history_size)),
             handler
         }
     }
 
     pub async fn get_tracked_events(&self) -> HashSet<E> {
+        let history = self.history.lock().await;
         let sessions = self.sessions.lock().await;
-        HashSet::from_iter(sessions.values().map(|e| e.keys().cloned()).flatten())
+        let mut events = HashSet::from_iter(sessions.values().map(|e| e.subscriptions.keys().cloned()).flatten());
+        events.extend(history.tracked.keys().cloned());
+        events
     }
 
     pub async fn is_event_tracked(&self, event: &E) -> bool {
+        let history = self.history.lock().await;
+        if history.tracked.contains_key(event) {
+            return true
+        }
+
         let sessions = self.sessions.lock().await;
-        sessions
-            .values()
-            .find(|e| e.keys().into_iter().find(|x| *x == event).is_some())
-            .is_some()
+        Self::is_subscribed(&sessions, event)
+    }
+
+    fn is_subscribed(sessions: &HashMap<WebSocketSessionShared<Self>, SessionSubscriptions<E>>, event: &E) -> bool {
+        sessions.values().any(|e| e.subscriptions.contains_key(event))
     }
 
     pub async fn notify(&self, event: &E, value: Value) {
-        let value = json!(EventResult { event: Cow::Borrowed(event), value });
-        let sessions = self.sessions.lock().await;
-        for (session, subscriptions) in sessions.iter() {
-            if let Some(id) = subscriptions.get(event) {
-                let response = json!(RpcResponse::new(Cow::Borrowed(&id), Cow::Borrowed(&value)));
-                let session = session.clone();
-                tokio::spawn(async move {
-                    if let Err(e) = session.send_text(response.to_string()).await {
-                        debug!("Error occured while notifying a new event: {}", e);
-                    };
-                });
+        let (sequence, value) = {
+            let mut history = self.history.lock().await;
+            let sequence = history.next_sequence();
+            let value = json!(EventResult { event: Cow::Borrowed(event), sequence: Some(sequence), value });
+            history.record(sequence, event, &value);
+            (sequence, value)
+        };
+
+        // events are queued under the lock so each session receives them in order
+        let mut sessions = self.sessions.lock().await;
+        for session in sessions.values_mut() {
+            let Some(subscription) = session.subscriptions.get_mut(event) else {
+                continue;
+            };
+
+            if let Some(filter) = &subscription.filter {
+                if !filter.matches(&value) {
+                    continue;
+                }
+            }
+
+            let response = json!(RpcResponse::new(Cow::Borrowed(&subscription.id), Cow::Borrowed(&value)));
+            match subscription.pending.as_mut() {
+                // the subscribe response is not sent yet
+                Some(pending) => pending.push((sequence, response)),
+                None => session.send(&response)
             }
         }
     }
 
-    async fn subscribe_session_to_event(&self, session: &WebSocketSessionShared<Self>, event: E, id: Option<usize>) -> Result<(), RpcResponseError> {
+    // The replayed and new events are kept pending until `flush_pending_events` is called
+    async fn subscribe_session_to_event(&self, session: &WebSocketSessionShared<Self>, event: E, filter: Option<EventMatcher>, since: Option<u64>, id: Option<usize>) -> Result<(), RpcResponseError> {
+        // history stays locked until the subscription is added
+        // so no event can be notified between the replay and the subscription
+        let mut history = self.history.lock().await;
         let mut sessions = self.sessions.lock().await;
-        let events = sessions.entry(session.clone()).or_insert_with(HashMap::new);
-        if events.contains_key(&event) {
+        let events = sessions.entry(session.clone()).or_insert_with(|| SessionSubscriptions::new(session.clone()));
+        if events.subscriptions.contains_key(&event) {
             return Err(RpcResponseError::new(id, InternalRpcError::EventAlreadySubscribed));
         }
 
-        events.insert(event, id);
+        let replay = match since {
+            Some(since) => history.get_events_since(since)
+                .ok_or_else(|| RpcResponseError::new(id, InternalRpcError::EventGapTooLarge(since)))?
+                .filter(|(_, e, value)| *e == event && filter.as_ref().map_or(true, |filter| filter.matches(value)))
+                .map(|(sequence, _, value)| (*sequence, json!(RpcResponse::new(Cow::Borrowed(&id), Cow::Borrowed(value)))))
+                .collect(),
+            None => Vec::new()
+        };
+
+        history.tracked.remove(&event);
+        events.subscriptions.insert(event, Subscription { id, filter, pending: Some(replay) });
         Ok(())
     }
 
+    // Send the events kept pending by the new subscriptions of the session
+    async fn flush_pending_events(&self, session: &WebSocketSessionShared<Self>) {
+        let mut sessions = self.sessions.lock().await;
+        let Some(events) = sessions.get_mut(session) else {
+            return;
+        };
+
+        let mut pending: Vec<(u64, Value)> = events.subscriptions.values_mut()
+            .filter_map(|subscription| subscription.pending.take())
+            .flatten()
+            .collect();
+        pending.sort_by_key(|(sequence, _)| *sequence);
+
+        for (_, value) in pending {
+            events.send(&value);
+        }
+    }
+
     async fn unsubscribe_session_from_event(&self, session: &WebSocketSessionShared<Self>, event: E, id: Option<usize>) -> Result<(), RpcResponseError> {
+        let mut history = self.history.lock().await;
         let mut sessions = self.sessions.lock().await;
-        let events = sessions.entry(session.clone()).or_insert_with(HashMap::new);
-        if !events.contains_key(&event) {
+        let removed = sessions.get_mut(session)
+            .and_then(|events| events.subscriptions.remove(&event))
+            .is_some();
+        if !removed {
             return Err(RpcResponseError::new(id, InternalRpcError::EventNotSubscribed));
         }
 
-        events.remove(&event);
+        if !Self::is_subscribed(&sessions, &event) {
+            history.track(event);
+        }
         Ok(())
     }
 
-    fn parse_event(&self, request: &mut RpcRequest) -> Result<E, RpcResponseError> {
+    fn parse_event(&self, request: &mut RpcRequest) -> Result<SubscribeParams<'static, E>, RpcResponseError> {
         let value = request.params.take().ok_or_else(|| RpcResponseError::new(request.id, InternalRpcError::ExpectedParams))?;
-        let params: SubscribeParams<E> = serde_json::from_value(value).map_err(|e| RpcResponseError::new(request.id, InternalRpcError::InvalidParams(e)))?;
-        Ok(params.notify.into_owned())
+        serde_json::from_value(value).map_err(|e| RpcResponseError::new(request.id, InternalRpcError::InvalidParams(e)))
+    }
+
+    async fn on_message_internal(&self, session: &WebSocketSessionShared<Self>, message: Bytes) -> Result<Option<Value>, RpcResponseError> {
+        self.handler.handle_body(&message, move |value| self.on_request(session, value)).await
     }
 
-    async fn on_message_internal(&self, session: &WebSocketSessionShared<Self>, message: Bytes) -> Result<Value, RpcResponseError> {
-        let mut request: RpcRequest = self.handler.parse_request(&message)?;
+    // Handle a single request, notifications (requests without id) don't get any response
+    async fn on_request(&self, session: &WebSocketSessionShared<Self>, value: Value) -> Result<Option<Value>, RpcResponseError> {
+        let request = self.handler.parse_request_from_value(value)?;
+        let is_notification = request.id.is_none();
+        match self.execute_request(session, request).await {
+            Ok(response) if !is_notification => Ok(Some(response)),
+            Err(e) if !is_notification => Err(e),
+            _ => Ok(None)
+        }
+    }
+
+    async fn execute_request(&self, session: &WebSocketSessionShared<Self>, mut request: RpcRequest) -> Result<Value, RpcResponseError> {
+        let mut context = Context::default();
+        context.store(session.get_request().clone());
+
         let response: Value = match request.method.as_str() {
             "subscribe" => {
-                let event = self.parse_event(&mut request)?;
-                self.subscribe_session_to_event(&session, event, request.id).await?;
+                self.handler.authorize(&context, &request.method).map_err(|e| RpcResponseError::new(request.id, e))?;
+                let params = self.parse_event(&mut request)?;
+                let filter = params.filter.map(|filter| EventMatcher::from(filter.into_owned()));
+                self.subscribe_session_to_event(&session, params.notify.into_owned(), filter, params.since, request.id).await?;
                 json!(RpcResponse::new(Cow::Borrowed(&request.id), Cow::Owned(json!(true))))
             },
             "unsubscribe" => {
-                let event = self.parse_event(&mut request)?;
-                self.unsubscribe_session_from_event(&session, event, request.id).await?;
+                self.handler.authorize(&context, &request.method).map_err(|e| RpcResponseError::new(request.id, e))?;
+                let params = self.parse_event(&mut request)?;
+                self.unsubscribe_session_from_event(&session, params.notify.into_owned(), request.id).await?;
                 json!(RpcResponse::new(Cow::Borrowed(&request.id), Cow::Owned(json!(true))))
             },
             _ => {
-                let mut context = Context::default();
                 context.store(session.clone());
-                match self.handler.execute_method(context, request).await {
-                    Ok(result) => result,
-                    Err(e) => e.to_json(),
-                }
+                self.handler.execute_method(context, request).await?
             }
         };
         Ok(response)
@@ -121,18 +318,85 @@ where
 {
     async fn on_close(&self, session: &WebSocketSessionShared<Self>) -> Result<(), anyhow::Error> {
         debug!("closing websocket connection");
+        let mut history = self.history.lock().await;
         let mut sessions = self.sessions.lock().await;
-        sessions.remove(session);
+        if let Some(events) = sessions.remove(session) {
+            // keep recording the events so the client can resume them once reconnected
+            for event in events.subscriptions.into_keys() {
+                if !Self::is_subscribed(&sessions, &event) {
+                    history.track(event);
+                }
+            }
+        }
         Ok(())
     }
 
     async fn on_message(&self, session: WebSocketSessionShared<Self>, message: Bytes) -> Result<(), anyhow::Error> {
         debug!("new message received on websocket");
-        let response: Value = match self.on_message_internal(&session, message).await {
+        let response = match self.on_message_internal(&session, message).await {
             Ok(result) => result,
-            Err(e) => e.to_json(),
+            Err(e) => Some(e.to_json()),
         };
-        session.send_text(response.to_string()).await?;
+
+        let res = match response {
+            Some(response) => session.send_text(response.to_string()).await,
+            None => Ok(())
+        };
+
+        // replayed and new events are sent once the client knows its subscription is accepted
+        self.flush_pending_events(&session).await;
+        res?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_history() {
+        let mut history = EventHistory::new(2);
+        let start = history.next_sequence;
+        for i in 0..3 {
+            let sequence = history.next_sequence();
+            history.record(sequence, &i, &json!(i));
+        }
+
+        // first event was dropped
+        assert!(history.get_events_since(start - 1).is_none());
+        let events: Vec<u64> = history.get_events_since(start).unwrap().map(|(sequence, _, _)| *sequence).collect();
+        assert_eq!(events, vec![start + 1, start + 2]);
+        assert_eq!(history.get_events_since(start + 2).unwrap().count(), 0);
+        // unknown cursor
+        assert!(history.get_events_since(start + 3).is_none());
+
+        let mut history = EventHistory::new(0);
+        let sequence = history.next_sequence();
+        history.record(sequence, &0, &json!(0));
+        assert!(history.get_events_since(sequence).is_some());
+        assert!(history.get_events_since(sequence - 1).is_none());
+        // nothing to resume without history
+        history.track(0);
+        assert!(history.tracked.is_empty());
+    }
+
+    #[test]
+    fn test_tracked_events_pruned() {
+        let mut history = EventHistory::new(2);
+        let sequence = history.next_sequence();
+        history.record(sequence, &0, &json!(0));
+        history.track(0);
+
+        // the cursor of the session that left can still be resumed
+        for i in 1..3 {
+            let sequence = history.next_sequence();
+            history.record(sequence, &i, &json!(i));
+            assert!(history.tracked.contains_key(&0));
+        }
+
+        // events notified before it left are not available anymore
+        let sequence = history.next_sequence();
+        history.record(sequence, &3, &json!(3));
+        assert!(history.tracked.is_empty());
+    }
+}
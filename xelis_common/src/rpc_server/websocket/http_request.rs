@@ -1,9 +1,11 @@
+use std::net::SocketAddr;
 use actix_web::{dev::RequestHead, http::{Uri, header::HeaderMap}};
 use reqwest::{Method, Version};
 use actix_web::HttpRequest as ActixHttpRequest;
 
 // Copy of actix_web::HttpRequest
 // Its done to Copy it & save it in WebSocketSession
+#[derive(Clone)]
 pub struct HttpRequest {
     head: RequestHead
 }
@@ -37,6 +39,12 @@ impl HttpRequest {
     pub fn headers(&self) -> &HeaderMap {
         &self.head().headers
     }
+
+    /// Peer socket address.
+    #[inline]
+    pub fn peer_addr(&self) -> Option<SocketAddr> {
+        self.head().peer_addr
+    }
 }
 
 impl From<ActixHttpRequest> for HttpRequest {
@@ -0,0 +1,51 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::Arc
+};
+use anyhow::{Context, Result};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    ClientConfig,
+    RootCertStore
+};
+
+// TLS configuration used by the clients to connect to a server
+pub type TlsClientConfig = Arc<ClientConfig>;
+
+// Read all the certificates from a PEM file
+pub fn read_certificates(path: &str) -> Result<Vec<CertificateDer<'static>>> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("Error while opening certificates file '{}'", path))?);
+    let certs = rustls_pemfile::certs(&mut reader)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Error while reading certificates from '{}'", path))?;
+
+    if certs.is_empty() {
+        anyhow::bail!("No certificate found in '{}'", path);
+    }
+
+    Ok(certs)
+}
+
+// Read the first private key from a PEM file
+pub fn read_private_key(path: &str) -> Result<PrivateKeyDer<'static>> {
+    let mut reader = BufReader::new(File::open(path).with_context(|| format!("Error while opening private key file '{}'", path))?);
+    rustls_pemfile::private_key(&mut reader)
+        .with_context(|| format!("Error while reading private key from '{}'", path))?
+        .with_context(|| format!("No private key found in '{}'", path))
+}
+
+// Create a client configuration trusting only the certificates from the CA bundle
+// This allows to connect to a node using a self-signed certificate
+pub fn load_client_config(ca_bundle: &str) -> Result<TlsClientConfig> {
+    let mut roots = RootCertStore::empty();
+    for cert in read_certificates(ca_bundle)? {
+        roots.add(cert).context("Invalid CA certificate")?;
+    }
+
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+
+    Ok(Arc::new(config))
+}
@@ -133,6 +133,19 @@ impl Transaction {
         Ok(output)
     }
 
+    /// Get the amount spent by the sender for an asset, encrypted for the sender
+    // This is used by a wallet to update its balance with a TX it didn't build itself
+    pub fn get_sender_output_ciphertext(&self, asset: &Hash) -> Result<Ciphertext, DecompressionError> {
+        let transfers = match &self.data {
+            TransactionType::Transfers(transfers) => transfers.iter()
+                .map(DecompressedTransferCt::decompress)
+                .collect::<Result<Vec<_>, DecompressionError>>()?,
+            TransactionType::Burn(_) => Vec::new()
+        };
+
+        self.get_sender_output_ct(asset, &transfers)
+    }
+
     pub(crate) fn prepare_transcript(
         version: u8,
         source_pubkey: &CompressedPublicKey,
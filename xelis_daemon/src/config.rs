@@ -19,9 +19,24 @@ pub const NETWORK_ID: [u8; NETWORK_ID_SIZE] = [0x73, 0x6c, 0x69, 0x78, 0x65, 0x5
 pub const DEFAULT_P2P_BIND_ADDRESS: &str = "0.0.0.0:2125";
 pub const DEFAULT_RPC_BIND_ADDRESS: &str = "0.0.0.0:8080";
 
+// Default count of events kept by the RPC server to replay them to the WebSocket clients
+pub const DEFAULT_RPC_EVENT_HISTORY_SIZE: usize = 1000;
+
 // Default cache size for storage DB
 pub const DEFAULT_CACHE_SIZE: usize = 1024;
 
+// Mempool limits
+// Default maximum size in bytes of all the TXs in mempool
+pub const DEFAULT_MEMPOOL_MAX_SIZE: usize = 128 * 1024 * 1024;
+// Default maximum TXs count in mempool
+pub const DEFAULT_MEMPOOL_MAX_TXS: usize = 50_000;
+// Default time in seconds before a TX is removed from mempool (3 hours)
+pub const DEFAULT_MEMPOOL_TX_EXPIRATION: u64 = 3 * 60 * 60;
+// Default minimum fee increase in percent to replace a TX in mempool
+pub const DEFAULT_MEMPOOL_REPLACE_FEE_INCREASE: u64 = 10;
+// Interval in seconds between two removals of the expired TXs in mempool
+pub const MEMPOOL_EXPIRATION_CHECK_INTERVAL: u64 = 60;
+
 // Block rules
 // Millis per second, it is used to prevent having random 1000 values anywhere
 pub const MILLIS_PER_SECOND: u64 = 1000;
@@ -48,6 +63,9 @@ pub const PRUNE_SAFETY_LIMIT: u64 = STABLE_LIMIT * 10;
 // BlockDAG rules
 pub const STABLE_LIMIT: u64 = 8; // in how many height we consider the block stable
 
+// Blocks replayed before releasing the storage lock when verifying the chain
+pub const REPLAY_CHUNK_SIZE: u64 = 100;
+
 // Emission rules
 // 15%, 10%, 5% per block going to dev address
 pub const DEV_FEES: [DevFeeThreshold; 3] = [
@@ -104,8 +122,20 @@ pub const CHAIN_SYNC_DEFAULT_RESPONSE_BLOCKS: usize = 4096;
 pub const CHAIN_SYNC_RESPONSE_MAX_BLOCKS: usize = 16384;
 // send last 10 heights
 pub const CHAIN_SYNC_TOP_BLOCKS: usize = 10;
+// Headers-first sync: blocks headers requested in a single window to a peer
+pub const CHAIN_SYNC_HEADERS_WINDOW: usize = 64;
+// Headers-first sync: blocks whose TXs are requested in a single window to a peer
+pub const CHAIN_SYNC_BODIES_WINDOW: usize = 16;
+// Headers-first sync: maximum peers downloading windows in parallel
+pub const CHAIN_SYNC_MAX_PEERS: usize = 8;
 
 // P2p rules
+// P2P protocol version, negotiated during the key exchange
+// 0 is the legacy key exchange sending the encryption keys, without the handshake extension
+// 1 is the ephemeral key exchange signed by the node identity in the handshake extension
+pub const P2P_PROTOCOL_VERSION: u8 = 1;
+// time in seconds before trying again the ephemeral key exchange with a legacy peer
+pub const P2P_LEGACY_PEER_RETRY_DELAY: u64 = 60 * 60;
 // time between each ping
 pub const P2P_PING_DELAY: u64 = 10;
 // time in seconds between each update of peerlist
@@ -118,6 +148,16 @@ pub const P2P_DEFAULT_MAX_PEERS: usize = 32;
 pub const P2P_EXTEND_PEERLIST_DELAY: u64 = 60;
 // Peer wait on error accept new p2p connections in seconds
 pub const P2P_PEER_WAIT_ON_ERROR: u64 = 15;
+// Dandelion: duration in seconds of an epoch, the stem peer is selected again at each new epoch
+pub const P2P_DANDELION_EPOCH: u64 = 10 * 60;
+// Dandelion: default chance in percent to fluff a TX received through the stem
+pub const DEFAULT_P2P_DANDELION_FLUFF_PROBABILITY: u8 = 10;
+// Dandelion: default delay in seconds before fluffing ourself a TX stemmed
+pub const DEFAULT_P2P_DANDELION_EMBARGO: u64 = 30;
+// Dandelion: maximum TXs in the stem at the same time, next ones are fluffed directly
+pub const P2P_DANDELION_MAX_STEM_TXS: usize = 1024;
+// Dandelion: time in milliseconds between each check of the embargo timers
+pub const P2P_DANDELION_CHECK_DELAY: u64 = 1000;
 
 // Peer rules
 // number of seconds to reset the counter
@@ -5,16 +5,28 @@ use serde_json::{Value, json};
 use xelis_common::{
     api::{
         daemon::{
+            AccountTransactionRole,
             BlockOrderedEvent,
             BlockOrphanedEvent,
             BlockType,
+            ExecutedTransfer,
             NotifyEvent,
+            SimulateTransactionResult,
+            SimulatedTransfer,
+            SkippedTransactionReason,
             StableHeightChangedEvent,
+            TransactionEvictedEvent,
+            TransactionEvictionReason,
             TransactionExecutedEvent,
-            TransactionResponse
+            TransactionProofKind,
+            TransactionReplacedEvent,
+            TransactionResponse,
+            TransactionSimulationError,
+            TransactionSimulationFailure
         },
         RPCTransaction
     },
+    rpc_server::DEFAULT_MAX_BATCH_SIZE,
     asset::AssetData,
     block::{
         Block,
@@ -29,6 +41,7 @@ use xelis_common::{
         XELIS_ASSET
     },
     crypto::{
+        proofs::ProofVerificationError,
         Hash,
         Hashable,
         PublicKey,
@@ -51,28 +64,32 @@ use crate::{
     config::{
         get_genesis_block_hash, get_hex_genesis_block, get_minimum_difficulty,
         BLOCK_TIME_MILLIS, CHAIN_SYNC_RESPONSE_MAX_BLOCKS, CHAIN_SYNC_RESPONSE_MIN_BLOCKS,
-        DEFAULT_CACHE_SIZE, DEFAULT_P2P_BIND_ADDRESS, DEFAULT_RPC_BIND_ADDRESS, DEV_FEES,
+        DEFAULT_CACHE_SIZE, DEFAULT_MEMPOOL_MAX_SIZE, DEFAULT_RPC_EVENT_HISTORY_SIZE, DEFAULT_MEMPOOL_MAX_TXS, DEFAULT_MEMPOOL_REPLACE_FEE_INCREASE,
+        DEFAULT_MEMPOOL_TX_EXPIRATION, DEFAULT_P2P_BIND_ADDRESS, DEFAULT_P2P_DANDELION_EMBARGO, DEFAULT_P2P_DANDELION_FLUFF_PROBABILITY, DEFAULT_RPC_BIND_ADDRESS, DEV_FEES,
         DEV_PUBLIC_KEY, EMISSION_SPEED_FACTOR, GENESIS_BLOCK_DIFFICULTY, MAX_BLOCK_SIZE,
-        MILLIS_PER_SECOND, P2P_DEFAULT_MAX_PEERS, SIDE_BLOCK_REWARD_MAX_BLOCKS, PRUNE_SAFETY_LIMIT,
+        MEMPOOL_EXPIRATION_CHECK_INTERVAL, MILLIS_PER_SECOND, P2P_DEFAULT_MAX_PEERS, SIDE_BLOCK_REWARD_MAX_BLOCKS, PRUNE_SAFETY_LIMIT, REPLAY_CHUNK_SIZE,
         SIDE_BLOCK_REWARD_PERCENT, SIDE_BLOCK_REWARD_MIN_PERCENT, STABLE_LIMIT, TIMESTAMP_IN_FUTURE_LIMIT
     },
     core::{
         blockdag,
         difficulty,
         error::BlockchainError,
-        mempool::Mempool,
+        mempool::{Mempool, MempoolConfig, MempoolUpdate},
         nonce_checker::NonceChecker,
         simulator::Simulator,
-        storage::{DagOrderProvider, DifficultyProvider, Storage},
-        tx_selector::{TxSelector, TxSelectorEntry},
+        snapshot,
+        storage::{upgrade_schema, DagOrderProvider, DifficultyProvider, FsyncPolicy, Storage, StorageBackend},
+        tx_selector::{TxSelection, TxSelectionStrategy, TxSelectionStrategyType, TxSelectorEntry},
         state::{ChainState, ApplicableChainState},
     },
-    p2p::P2pServer,
+    metrics,
+    p2p::{Dandelion, P2pServer},
     rpc::{
         rpc::{
             get_block_type_for_block,
             get_block_response
         },
+        auth::{RpcAuth, RpcToken, RpcUser},
         DaemonRpcServer,
         SharedDaemonRpcServer
     }
@@ -91,9 +108,9 @@ use std::{
         atomic::{AtomicU64, Ordering},
         Arc
     },
-    time::Instant
+    time::{Duration, Instant}
 };
-use tokio::sync::{Mutex, RwLock};
+use tokio::{sync::{Mutex, RwLock}, time::interval};
 use log::{info, error, debug, warn, trace};
 use rand::Rng;
 
@@ -118,6 +135,39 @@ pub struct Config {
     /// Rpc bind address to listen for HTTP requests
     #[clap(long, default_value_t = String::from(DEFAULT_RPC_BIND_ADDRESS))]
     pub rpc_bind_address: String,
+    /// Maximum number of requests allowed in a JSON-RPC batch
+    #[clap(long, default_value_t = DEFAULT_MAX_BATCH_SIZE)]
+    pub rpc_max_batch_size: usize,
+    /// Count of events kept to be replayed to the WebSocket clients subscribing with a cursor (0 = disabled)
+    #[clap(long, default_value_t = DEFAULT_RPC_EVENT_HISTORY_SIZE)]
+    pub rpc_event_history_size: usize,
+    /// PEM certificate file to serve the RPC server over HTTPS/WSS.
+    /// 
+    /// It is reloaded with the private key when the process receives a SIGHUP.
+    #[clap(long)]
+    pub rpc_tls_cert: Option<String>,
+    /// PEM private key file of the RPC server TLS certificate.
+    #[clap(long)]
+    pub rpc_tls_key: Option<String>,
+    /// Bearer token accepted by the RPC server, as `<access>:<token>`.
+    /// 
+    /// Access is `admin` for all methods, `public` for the read-only methods or a list of methods separated by commas.
+    #[clap(long)]
+    pub rpc_auth_token: Vec<RpcToken>,
+    /// Basic auth credentials accepted by the RPC server, as `<access>:<username>:<password>`.
+    #[clap(long)]
+    pub rpc_auth_user: Vec<RpcUser>,
+    /// Reject RPC requests without credentials.
+    /// 
+    /// Otherwise, they can only call the public methods when credentials are set.
+    #[clap(long)]
+    pub rpc_disable_public_access: bool,
+    /// Maximum RPC requests per second per IP (0 = disabled).
+    #[clap(long, default_value_t = 0)]
+    pub rpc_rate_limit: u32,
+    /// Expose the Prometheus metrics on the `/metrics` route of the RPC server.
+    #[clap(long)]
+    pub enable_metrics: bool,
     /// Add a priority node to connect when P2p is started.
     /// A priority node is connected only one time.
     #[clap(long)]
@@ -134,6 +184,49 @@ pub struct Config {
     /// Set LRUCache size (0 = disabled).
     #[clap(long, default_value_t = DEFAULT_CACHE_SIZE)]
     pub cache_size: usize,
+    /// Storage backend to use for the chain database.
+    /// 
+    /// RocksDB is only available if the daemon was compiled with the `rocksdb` feature.
+    #[clap(long, default_value_t = StorageBackend::Sled)]
+    pub storage_backend: StorageBackend,
+    /// When the storage changes are flushed to disk.
+    /// 
+    /// `block` flush after each block applied, `background` let the storage backend flush periodically.
+    /// Each block is applied atomically in both cases.
+    #[clap(long, default_value_t = FsyncPolicy::Background)]
+    pub fsync_policy: FsyncPolicy,
+    /// Import a chain snapshot file at startup.
+    /// 
+    /// The storage must be empty. A snapshot can be created using the `export_snapshot` command.
+    #[clap(long)]
+    pub import_snapshot: Option<String>,
+    /// Index the transactions of each account.
+    /// 
+    /// Required by the `get_account_transactions` RPC method. Only blocks added while it is enabled are indexed.
+    #[clap(long)]
+    pub index_account_transactions: bool,
+    /// Maximum size in bytes of all the transactions in mempool.
+    /// 
+    /// When reached, transactions with the lowest fee per byte are evicted first.
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_MAX_SIZE)]
+    pub mempool_max_size: usize,
+    /// Maximum count of transactions in mempool.
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_MAX_TXS)]
+    pub mempool_max_txs: usize,
+    /// Time in seconds after which a transaction is removed from mempool.
+    /// 
+    /// Set it to 0 to keep transactions until they are included in a block.
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_TX_EXPIRATION)]
+    pub mempool_tx_expiration: u64,
+    /// Minimum fee increase in percent to replace a transaction in mempool with the same source and nonce.
+    #[clap(long, default_value_t = DEFAULT_MEMPOOL_REPLACE_FEE_INCREASE)]
+    pub mempool_replace_fee_increase: u64,
+    /// Strategy used to select the mempool transactions for a block template.
+    /// 
+    /// `fee_per_byte` highest fee per byte first, `fifo` oldest transactions first,
+    /// `knapsack` set of transactions with the highest total fees fitting in the block.
+    #[clap(long, default_value_t = TxSelectionStrategyType::FeePerByte)]
+    pub tx_selection_strategy: TxSelectionStrategyType,
     /// Disable GetWork Server (WebSocket for miners).
     #[clap(long)]
     pub disable_getwork_server: bool,
@@ -182,7 +275,41 @@ pub struct Config {
     /// 
     /// This is useful for seed nodes under heavy load or for nodes that don't want to connect to others.
     #[clap(long, default_value = "false")]
-    pub disable_outgoing_connections: bool
+    pub disable_outgoing_connections: bool,
+    /// Relay the transactions using Dandelion++.
+    /// 
+    /// A transaction is first forwarded from peer to peer through a random stem,
+    /// and is broadcasted only later, so it is harder to link it to the IP of the node that sent it first.
+    #[clap(long)]
+    pub p2p_enable_dandelion: bool,
+    /// Chance in percent to broadcast a transaction received through the Dandelion stem instead of forwarding it.
+    #[clap(long, default_value_t = DEFAULT_P2P_DANDELION_FLUFF_PROBABILITY)]
+    pub p2p_dandelion_fluff_probability: u8,
+    /// Minimum delay in seconds before broadcasting ourself a transaction forwarded through the Dandelion stem.
+    /// 
+    /// This is the fallback in case a node of the stem never broadcasts it.
+    #[clap(long, default_value_t = DEFAULT_P2P_DANDELION_EMBARGO)]
+    pub p2p_dandelion_embargo: u64,
+    /// Disable the compact blocks propagation.
+    /// 
+    /// By default, the blocks are sent to the peers supporting it with short IDs of their transactions
+    /// that are rebuilt from the mempool, instead of the full transactions hashes.
+    #[clap(long)]
+    pub p2p_disable_compact_blocks: bool,
+    /// Disable the headers-first chain sync.
+    /// 
+    /// By default, the headers of the blocks to sync are downloaded and verified first,
+    /// then their transactions are downloaded in parallel from several peers.
+    /// If disabled, blocks are requested one by one to the peer we sync from (or in parallel with boost sync).
+    #[clap(long)]
+    pub disable_headers_first_sync: bool,
+    /// Require an identity from all the P2p peers.
+    /// 
+    /// By default, peers running an older version without identity are accepted
+    /// using the legacy protocol, unless they already connected with an identity.
+    /// If enabled, the legacy protocol is never used.
+    #[clap(long)]
+    pub p2p_require_identity: bool
 }
 
 pub struct Blockchain<S: Storage> {
@@ -218,11 +345,17 @@ pub struct Blockchain<S: Storage> {
     // using base hash, current tip hash and base height, this cache is used to store the DAG order
     full_order_cache: Mutex<LruCache<(Hash, Hash, u64), IndexSet<Hash>>>,
     // auto prune mode if enabled, will delete all blocks every N and keep only N top blocks (topoheight based)
-    auto_prune_keep_n_blocks: Option<u64>
+    auto_prune_keep_n_blocks: Option<u64>,
+    // flush the storage after each block applied or not
+    fsync_policy: FsyncPolicy,
+    // link each executed transaction to its source and destinations
+    index_account_transactions: bool,
+    // strategy used to select the transactions for a block template
+    tx_selection_strategy: Box<dyn TxSelectionStrategy>
 }
 
 impl<S: Storage> Blockchain<S> {
-    pub async fn new(config: Config, network: Network, storage: S) -> Result<Arc<Self>, Error> {
+    pub async fn new(config: Config, network: Network, mut storage: S) -> Result<Arc<Self>, Error> {
         // Do some checks on config params
         {
             if config.simulator.is_some() && network != Network::Dev {
@@ -248,8 +381,28 @@ impl<S: Storage> Blockchain<S> {
                 error!("Boost sync and fast sync can't be enabled at the same time!");
                 return Err(BlockchainError::ConfigSyncMode.into())
             }
+
+            if config.rpc_tls_cert.is_some() != config.rpc_tls_key.is_some() {
+                error!("RPC TLS certificate and private key must be set together!");
+                return Err(BlockchainError::ConfigTls.into())
+            }
+
+            if config.p2p_dandelion_fluff_probability > 100 {
+                error!("Dandelion fluff probability must be a percentage!");
+                return Err(BlockchainError::ConfigDandelionFluffProbability.into())
+            }
+        }
+
+        // Verify the storage layout and apply the migration steps if needed
+        upgrade_schema(&mut storage).await?;
+
+        if let Some(path) = config.import_snapshot.as_ref() {
+            snapshot::import_snapshot(&mut storage, &network, path).await?;
         }
 
+        // Rewind the block that was being applied if the node was stopped in the middle of it
+        Self::repair_half_applied_block(&mut storage).await?;
+
         let on_disk = storage.has_blocks().await;
         let (height, topoheight) = if on_disk {
             info!("Reading last metadata available...");
@@ -265,7 +418,12 @@ impl<S: Storage> Blockchain<S> {
             topoheight: AtomicU64::new(topoheight),
             stable_height: AtomicU64::new(0),
             stable_topoheight: AtomicU64::new(0),
-            mempool: RwLock::new(Mempool::new(network)),
+            mempool: RwLock::new(Mempool::new(network, MempoolConfig {
+                max_size: config.mempool_max_size,
+                max_txs: config.mempool_max_txs,
+                tx_expiration: config.mempool_tx_expiration,
+                replace_fee_increase: config.mempool_replace_fee_increase
+            })),
             storage: RwLock::new(storage),
             p2p: RwLock::new(None),
             rpc: RwLock::new(None),
@@ -275,7 +433,10 @@ impl<S: Storage> Blockchain<S> {
             tip_base_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
             tip_work_score_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
             full_order_cache: Mutex::new(LruCache::new(NonZeroUsize::new(1024).unwrap())),
-            auto_prune_keep_n_blocks: config.auto_prune_keep_n_blocks
+            auto_prune_keep_n_blocks: config.auto_prune_keep_n_blocks,
+            fsync_policy: config.fsync_policy,
+            index_account_transactions: config.index_account_transactions,
+            tx_selection_strategy: config.tx_selection_strategy.build()
         };
 
         // include genesis block
@@ -318,7 +479,13 @@ impl<S: Storage> Blockchain<S> {
                 exclusive_nodes.push(addr);
             }
 
-            match P2pServer::new(config.dir_path, config.tag, config.max_peers, config.p2p_bind_address, Arc::clone(&arc), exclusive_nodes.is_empty(), exclusive_nodes, config.allow_fast_sync, config.allow_boost_sync, config.max_chain_response_size, !config.disable_ip_sharing, config.disable_outgoing_connections) {
+            let dandelion = if config.p2p_enable_dandelion {
+                Some(Dandelion::new(config.p2p_dandelion_fluff_probability, config.p2p_dandelion_embargo))
+            } else {
+                None
+            };
+
+            match P2pServer::new(config.dir_path, config.tag, config.max_peers, config.p2p_bind_address, Arc::clone(&arc), exclusive_nodes.is_empty(), exclusive_nodes, config.allow_fast_sync, config.allow_boost_sync, config.max_chain_response_size, !config.disable_ip_sharing, config.disable_outgoing_connections, dandelion, !config.p2p_disable_compact_blocks, !config.disable_headers_first_sync, config.p2p_require_identity) {
                 Ok(p2p) => {
                     // connect to priority nodes
                     for addr in config.priority_nodes {
@@ -341,7 +508,9 @@ impl<S: Storage> Blockchain<S> {
         // create RPC Server
         {
             info!("RPC Server will listen on: {}", config.rpc_bind_address);
-            match DaemonRpcServer::new(config.rpc_bind_address, Arc::clone(&arc), config.disable_getwork_server).await {
+            let tls = config.rpc_tls_cert.zip(config.rpc_tls_key);
+            let auth = RpcAuth::new(config.rpc_auth_token, config.rpc_auth_user, config.rpc_disable_public_access, config.rpc_rate_limit);
+            match DaemonRpcServer::new(config.rpc_bind_address, Arc::clone(&arc), config.disable_getwork_server, config.rpc_max_batch_size, config.rpc_event_history_size, config.enable_metrics, auth, tls).await {
                 Ok(server) => *arc.rpc.write().await = Some(server),
                 Err(e) => error!("Error while starting RPC server: {}", e)
             };
@@ -356,6 +525,21 @@ impl<S: Storage> Blockchain<S> {
             });
         }
 
+        // Expired TXs are removed even if no block is added
+        {
+            let blockchain = Arc::downgrade(&arc);
+            tokio::spawn(async move {
+                let mut interval = interval(Duration::from_secs(MEMPOOL_EXPIRATION_CHECK_INTERVAL));
+                loop {
+                    interval.tick().await;
+                    let Some(blockchain) = blockchain.upgrade() else {
+                        break;
+                    };
+                    blockchain.remove_expired_txs_from_mempool().await;
+                }
+            });
+        }
+
         Ok(arc)
     }
 
@@ -397,7 +581,9 @@ impl<S: Storage> Blockchain<S> {
     // Clear the mempool also in case of not being up-to-date
     pub async fn reload_from_disk(&self) -> Result<(), BlockchainError> {
         trace!("Reloading chain from disk");
-        let storage = self.storage.read().await;
+        let mut storage = self.storage.write().await;
+        Self::repair_half_applied_block(&mut storage).await?;
+
         let topoheight = storage.get_top_topoheight()?;
         let height = storage.get_top_height()?;
         self.topoheight.store(topoheight, Ordering::SeqCst);
@@ -425,6 +611,61 @@ impl<S: Storage> Blockchain<S> {
         Ok(())
     }
 
+    // Verify that the top topoheight, the tips and the DAG order agree on the last block applied
+    // Otherwise, rewind the chain to the last complete block
+    // A block is written in a single batch, but a storage written by a previous version
+    // may have been stopped between two of its writes
+    async fn repair_half_applied_block(storage: &mut S) -> Result<(), BlockchainError> {
+        if !storage.has_blocks().await {
+            return Ok(())
+        }
+
+        let topoheight = storage.get_top_topoheight()?;
+        let height = storage.get_top_height()?;
+        let tips = storage.get_tips().await?;
+
+        // The DAG order is written before the top topoheight
+        let mut last_topoheight = topoheight;
+        while storage.has_hash_at_topoheight(last_topoheight + 1).await? {
+            last_topoheight += 1;
+        }
+
+        // The block at the top topoheight is the best tip, written after it
+        let top_hash = storage.get_hash_at_topo_height(topoheight).await?;
+        if last_topoheight == topoheight && tips.contains(&top_hash) {
+            // Only the top height may be missing, it is the last one written
+            let mut tips_height = height;
+            for tip in tips.iter() {
+                tips_height = tips_height.max(storage.get_height_for_block_hash(tip).await?);
+            }
+
+            if tips_height > height {
+                warn!("Top height {} is behind the tips, setting it to {}", height, tips_height);
+                storage.set_top_height(tips_height)?;
+            }
+
+            return Ok(())
+        }
+
+        warn!("Last block was not fully applied (topoheight {}, ordered until {}), rewinding to the last complete block", topoheight, last_topoheight);
+        // The pointers must include every block ordered so they are all rewinded
+        let mut new_height = height;
+        for topoheight in topoheight..=last_topoheight {
+            let hash = storage.get_hash_at_topo_height(topoheight).await?;
+            new_height = new_height.max(storage.get_height_for_block_hash(&hash).await?);
+        }
+        storage.set_top_topoheight(last_topoheight)?;
+        storage.set_top_height(new_height)?;
+
+        // The block at the top topoheight may be the one that was being applied, except the genesis
+        let count = last_topoheight - topoheight + if topoheight > 0 { 1 } else { 0 };
+        let (new_height, new_topoheight, _) = storage.pop_blocks(new_height, last_topoheight, count, 0).await?;
+        storage.delete_account_transactions_above_topoheight(new_topoheight).await?;
+        info!("Chain rewinded to topoheight {} and height {}", new_topoheight, new_height);
+
+        Ok(())
+    }
+
     // function to include the genesis block and register the public dev key.
     async fn create_genesis_block(&self) -> Result<(), BlockchainError> {
         let mut storage = self.storage.write().await;
@@ -604,6 +845,16 @@ impl<S: Storage> Blockchain<S> {
         &self.network
     }
 
+    // Are the transactions indexed by account
+    pub fn is_account_transactions_indexed(&self) -> bool {
+        self.index_account_transactions
+    }
+
+    // Get the strategy used to select the transactions for a block template
+    pub fn get_tx_selection_strategy(&self) -> &dyn TxSelectionStrategy {
+        self.tx_selection_strategy.as_ref()
+    }
+
     // Get the current emitted supply of XELIS at current topoheight
     pub async fn get_supply(&self) -> Result<u64, BlockchainError> {
         self.storage.read().await.get_supply_at_topo_height(self.get_topo_height()).await
@@ -1217,6 +1468,98 @@ impl<S: Storage> Blockchain<S> {
         &self.mempool
     }
 
+    // Verify a TX against the current chain state and mempool without adding it
+    // A TX rejected by the verification is reported in the result and not as an error
+    pub async fn simulate_transaction<'a>(&self, tx: &'a Transaction) -> Result<SimulateTransactionResult<'a>, BlockchainError> {
+        let hash = tx.hash();
+        let size = tx.size();
+        let storage = self.storage.read().await;
+        let mempool = self.mempool.read().await;
+        let topoheight = self.get_topo_height();
+        let mainnet = self.network.is_mainnet();
+
+        let mut transfers = Vec::new();
+        if let TransactionType::Transfers(txs) = tx.get_data() {
+            for transfer in txs {
+                let new_account = !storage.is_account_registered_below_topoheight(transfer.get_destination(), topoheight).await?;
+                transfers.push(SimulatedTransfer {
+                    destination: transfer.get_destination().as_address(mainnet),
+                    asset: Cow::Borrowed(transfer.get_asset()),
+                    new_account
+                });
+            }
+        }
+
+        let required_fee = estimate_required_tx_fees(&*storage, topoheight, tx).await?;
+        let (expected_nonce, replaced_tx) = match mempool.get_cache_for(tx.get_source()) {
+            Some(cache) => (cache.get_next_nonce(), cache.has_tx_with_same_nonce(tx.get_nonce()).map(|hash| hash.as_ref().clone())),
+            None => {
+                let nonce = storage.get_nonce_at_maximum_topoheight(tx.get_source(), topoheight).await?
+                    .map(|(_, v)| v.get_nonce()).unwrap_or(0);
+                (nonce, None)
+            }
+        };
+
+        let replacement_fee = match replaced_tx.as_ref() {
+            Some(replaced) => Some(mempool.get_replacement_fee(mempool.get_sorted_tx(replaced)?.get_fee())),
+            None => None
+        };
+
+        let in_mempool = mempool.contains_tx(&hash);
+        let executed_in_block = storage.is_tx_executed_in_a_block(&hash)?;
+        let res = if size > MAX_TRANSACTION_SIZE {
+            Err(BlockchainError::TxTooBig(size, MAX_TRANSACTION_SIZE))
+        } else if in_mempool {
+            Err(BlockchainError::TxAlreadyInMempool(hash.clone()))
+        } else if executed_in_block {
+            Err(BlockchainError::TxAlreadyInBlockchain(hash.clone()))
+        } else {
+            mempool.simulate_tx(&*storage, topoheight, &hash, tx).await
+        };
+
+        let error = res.err().map(|e| {
+            debug!("Simulated TX {} is invalid: {}", hash, e);
+            get_simulation_error(e)
+        });
+
+        Ok(SimulateTransactionResult {
+            hash: Cow::Owned(hash),
+            valid: error.is_none(),
+            error,
+            size,
+            fee: tx.get_fee(),
+            required_fee,
+            nonce: tx.get_nonce(),
+            expected_nonce,
+            replaced_tx: replaced_tx.map(Cow::Owned),
+            replacement_fee,
+            transfers,
+            in_mempool,
+            executed_in_block
+        })
+    }
+
+    // Verify a TX relayed through the Dandelion stem, it is not added to the mempool
+    // Previous are the TXs of the same source still in the stem, in nonce order
+    pub async fn verify_stem_tx(&self, hash: &Hash, tx: &Transaction, previous: &[Arc<Transaction>]) -> Result<(), BlockchainError> {
+        let tx_size = tx.size();
+        if tx_size > MAX_TRANSACTION_SIZE {
+            return Err(BlockchainError::TxTooBig(tx_size, MAX_TRANSACTION_SIZE))
+        }
+
+        let storage = self.storage.read().await;
+        let mempool = self.mempool.read().await;
+        if mempool.contains_tx(hash) {
+            return Err(BlockchainError::TxAlreadyInMempool(hash.clone()))
+        }
+
+        if storage.is_tx_executed_in_a_block(hash)? {
+            return Err(BlockchainError::TxAlreadyInBlockchain(hash.clone()))
+        }
+
+        mempool.verify_stem_tx(&*storage, self.get_topo_height(), previous, tx).await
+    }
+
     // Add a tx to the mempool, its hash will be computed
     pub async fn add_tx_to_mempool(&self, tx: Transaction, broadcast: bool) -> Result<(), BlockchainError> {
         let hash = tx.hash();
@@ -1232,41 +1575,26 @@ impl<S: Storage> Blockchain<S> {
     // Add a tx to the mempool with the given hash, it will verify the TX and check that it is not already in mempool or in blockchain
     // and its validity (nonce, balance, etc...)
     pub async fn add_tx_to_mempool_with_storage_and_hash<'a>(&'a self, storage: &S, tx: Arc<Transaction>, hash: Hash, broadcast: bool) -> Result<(), BlockchainError> {
-        let tx_size = tx.size();
-        if tx_size > MAX_TRANSACTION_SIZE {
-            return Err(BlockchainError::TxTooBig(tx_size, MAX_TRANSACTION_SIZE))
-        }
+        let update = self.add_tx_to_mempool_internal(storage, tx.clone(), hash.clone()).await?;
 
-        {
-            let mut mempool = self.mempool.write().await;
-    
-            if mempool.contains_tx(&hash) {
-                return Err(BlockchainError::TxAlreadyInMempool(hash))
-            }
-    
-            // check that the TX is not already in blockchain
-            if storage.is_tx_executed_in_a_block(&hash)? {
-                return Err(BlockchainError::TxAlreadyInBlockchain(hash))
-            }
-
-            let current_topoheight = self.get_topo_height();
-            // get the highest nonce available
-            // if presents, it means we have at least one tx from this owner in mempool
-            if let Some(cache) = mempool.get_cache_for(tx.get_source()) {
-                // we accept to delete a tx from mempool if the new one has a higher fee
-                if let Some(hash) = cache.has_tx_with_same_nonce(tx.get_nonce()) {
-                    // A TX with the same nonce is already in mempool
-                    return Err(BlockchainError::TxNonceAlreadyUsed(tx.get_nonce(), hash.as_ref().clone()))
-                }
-
-                // check that the nonce is in the range
-                if !(tx.get_nonce() <= cache.get_max() + 1 && tx.get_nonce() >= cache.get_min()) {
-                    debug!("TX {} nonce is not in the range of the pending TXs for this owner, received: {}, expected between {} and {}", hash, tx.get_nonce(), cache.get_min(), cache.get_max());
-                    return Err(BlockchainError::InvalidTxNonceMempoolCache(tx.get_nonce(), cache.get_min(), cache.get_max()))
+        // Notify the replaced and evicted TXs
+        if update.replaced.is_some() || !update.evicted.is_empty() {
+            if let Some(rpc) = self.rpc.read().await.as_ref() {
+                let tracked_events = rpc.get_tracked_events().await;
+                let mut events = HashMap::new();
+                self.track_mempool_update_events(&hash, &tx, &update, &tracked_events, &mut events);
+                for (event, values) in events {
+                    for value in values {
+                        if let Err(e) = rpc.notify_clients(&event, value).await {
+                            debug!("Error while broadcasting event {:?} to websocket: {}", event, e);
+                        }
+                    }
                 }
             }
+        }
 
-            mempool.add_tx(storage, current_topoheight, hash.clone(), tx.clone(), tx_size).await?;
+        if update.is_evicted(&hash) {
+            return Err(BlockchainError::MempoolFull(hash))
         }
 
         if broadcast {
@@ -1304,6 +1632,90 @@ impl<S: Storage> Blockchain<S> {
         Ok(())
     }
 
+    // Verify and add a TX to the mempool without notifying anyone
+    // Returns the TXs replaced or evicted from the mempool
+    async fn add_tx_to_mempool_internal(&self, storage: &S, tx: Arc<Transaction>, hash: Hash) -> Result<MempoolUpdate, BlockchainError> {
+        let tx_size = tx.size();
+        if tx_size > MAX_TRANSACTION_SIZE {
+            return Err(BlockchainError::TxTooBig(tx_size, MAX_TRANSACTION_SIZE))
+        }
+
+        let mut mempool = self.mempool.write().await;
+
+        if mempool.contains_tx(&hash) {
+            return Err(BlockchainError::TxAlreadyInMempool(hash))
+        }
+
+        // check that the TX is not already in blockchain
+        if storage.is_tx_executed_in_a_block(&hash)? {
+            return Err(BlockchainError::TxAlreadyInBlockchain(hash))
+        }
+
+        let current_topoheight = self.get_topo_height();
+        // get the highest nonce available
+        // if presents, it means we have at least one tx from this owner in mempool
+        if let Some(cache) = mempool.get_cache_for(tx.get_source()) {
+            // check that the nonce is in the range
+            // A TX with an already used nonce will replace the previous one if its fee is high enough
+            if !(tx.get_nonce() <= cache.get_max() + 1 && tx.get_nonce() >= cache.get_min()) {
+                debug!("TX {} nonce is not in the range of the pending TXs for this owner, received: {}, expected between {} and {}", hash, tx.get_nonce(), cache.get_min(), cache.get_max());
+                return Err(BlockchainError::InvalidTxNonceMempoolCache(tx.get_nonce(), cache.get_min(), cache.get_max()))
+            }
+        }
+
+        mempool.add_tx(storage, current_topoheight, hash, tx, tx_size).await
+    }
+
+    // Remove the TXs that stayed for too long in mempool and notify them as evicted
+    pub async fn remove_expired_txs_from_mempool(&self) {
+        let expired = self.mempool.write().await.remove_expired_txs();
+        if expired.is_empty() {
+            return;
+        }
+
+        debug!("{} expired TXs removed from mempool", expired.len());
+        if let Some(rpc) = self.rpc.read().await.as_ref() {
+            if rpc.is_event_tracked(&NotifyEvent::TransactionEvicted).await {
+                for (tx_hash, sorted_tx) in expired {
+                    let value = json!(TransactionEvictedEvent {
+                        tx_hash: Cow::Borrowed(tx_hash.as_ref()),
+                        reason: TransactionEvictionReason::Expired,
+                        first_seen: sorted_tx.get_first_seen()
+                    });
+                    if let Err(e) = rpc.notify_clients(&NotifyEvent::TransactionEvicted, value).await {
+                        debug!("Error while broadcasting event TransactionEvicted to websocket: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    // Build the events for the TXs replaced or evicted while adding a TX to the mempool
+    fn track_mempool_update_events(&self, hash: &Hash, tx: &Transaction, update: &MempoolUpdate, tracked_events: &HashSet<NotifyEvent>, events: &mut HashMap<NotifyEvent, Vec<Value>>) {
+        if let Some((replaced_hash, sorted_tx)) = update.replaced.as_ref() {
+            if tracked_events.contains(&NotifyEvent::TransactionReplaced) {
+                let value = json!(TransactionReplacedEvent {
+                    old_tx_hash: Cow::Borrowed(replaced_hash.as_ref()),
+                    old_fee: sorted_tx.get_fee(),
+                    new_tx_hash: Cow::Borrowed(hash),
+                    new_fee: tx.get_fee()
+                });
+                events.entry(NotifyEvent::TransactionReplaced).or_insert_with(Vec::new).push(value);
+            }
+        }
+
+        if tracked_events.contains(&NotifyEvent::TransactionEvicted) {
+            for (tx_hash, sorted_tx, reason) in update.evicted.iter() {
+                let value = json!(TransactionEvictedEvent {
+                    tx_hash: Cow::Borrowed(tx_hash.as_ref()),
+                    reason: *reason,
+                    first_seen: sorted_tx.get_first_seen()
+                });
+                events.entry(NotifyEvent::TransactionEvicted).or_insert_with(Vec::new).push(value);
+            }
+        }
+    }
+
     // this will be used in future for hard fork versions
     pub fn get_version_at_height(&self, _height: u64) -> u8 {
         0
@@ -1419,6 +1831,18 @@ impl<S: Storage> Blockchain<S> {
         let mempool = self.mempool.read().await;
         trace!("Mempool locked for building block template");
 
+        let selection = self.select_block_template_txs(storage, &mempool, block.size(), self.get_tx_selection_strategy()).await?;
+        for entry in selection.selected {
+            // TODO no clone
+            block.txs_hashes.insert(entry.hash.as_ref().clone());
+        }
+
+        Ok(block)
+    }
+
+    // Select the TXs from mempool to include in a block template using the strategy
+    // All the TXs are returned, selected or skipped with the reason
+    pub async fn select_block_template_txs<'a>(&self, storage: &S, mempool: &'a Mempool, header_size: usize, strategy: &dyn TxSelectionStrategy) -> Result<TxSelection<'a>, BlockchainError> {
         // use the mempool cache to get all availables txs grouped by account
         let caches = mempool.get_caches();
         let mut entries: Vec<Vec<TxSelectorEntry>> = Vec::with_capacity(caches.len());
@@ -1428,16 +1852,17 @@ impl<S: Storage> Blockchain<S> {
             // Map every tx hash to a TxSelectorEntry
             for tx_hash in cache_txs.iter() {
                 let sorted_tx = mempool.get_sorted_tx(tx_hash)?;
-                txs.push(TxSelectorEntry { size: sorted_tx.get_size(), hash: tx_hash, tx: sorted_tx.get_tx() });
+                txs.push(TxSelectorEntry { size: sorted_tx.get_size(), hash: tx_hash, tx: sorted_tx.get_tx(), first_seen: sorted_tx.get_first_seen() });
             }
             entries.push(txs);
         }
 
-        // Build the tx selector using the mempool
-        let mut tx_selector = TxSelector::grouped(entries.into_iter());
+        // Order the TXs using the strategy
+        trace!("Selecting TXs using {} strategy", strategy.name());
+        let entries = strategy.select(entries, MAX_BLOCK_SIZE.saturating_sub(header_size));
 
         // size of block
-        let mut block_size = block.size();
+        let mut block_size = header_size;
         let mut total_txs_size = 0;
 
         // data used to verify txs
@@ -1445,33 +1870,43 @@ impl<S: Storage> Blockchain<S> {
         trace!("build chain state for block template");
         let mut chain_state = ChainState::new(storage, topoheight);
 
-        let mut failed_sources = HashSet::new();
-        while let Some(TxSelectorEntry { size, hash, tx }) = tx_selector.next() {
-            if block_size + total_txs_size + size >= MAX_BLOCK_SIZE {
-                break;
+        let mut selection = TxSelection {
+            selected: Vec::new(),
+            skipped: Vec::new()
+        };
+        // sources having a TX skipped, next TXs can't be included as the nonce would not follow
+        let mut skipped_sources = HashSet::new();
+        for entry in entries {
+            let (size, hash, tx) = (entry.size, entry.hash, entry.tx);
+            let source = tx.get_source();
+            if skipped_sources.contains(&source) {
+                debug!("Skipping TX {} because its source has been skipped before", hash);
+                selection.skipped.push((entry, SkippedTransactionReason::PreviousSkipped, None));
+                continue;
             }
 
-            // Check if the TX is valid for this potential block
-            trace!("Checking TX {} with nonce {}, {}", hash, tx.get_nonce(), tx.get_source().as_address(self.network.is_mainnet()));
-            let source = tx.get_source();
-            if failed_sources.contains(&source) {
-                debug!("Skipping TX {} because its source has failed before", hash);
+            if block_size + total_txs_size + size >= MAX_BLOCK_SIZE {
+                trace!("Skipping TX {} because the block is full", hash);
+                skipped_sources.insert(source);
+                selection.skipped.push((entry, SkippedTransactionReason::BlockFull, None));
                 continue;
             }
 
+            // Check if the TX is valid for this potential block
+            trace!("Checking TX {} with nonce {}, {}", hash, tx.get_nonce(), source.as_address(self.network.is_mainnet()));
             if let Err(e) = tx.verify(&mut chain_state).await {
                 warn!("TX {} ({}) is not valid for mining: {}", hash, source.as_address(self.network.is_mainnet()), e);
-                failed_sources.insert(source);
+                skipped_sources.insert(source);
+                selection.skipped.push((entry, SkippedTransactionReason::Invalid, Some(e.to_string())));
             } else {
                 trace!("Selected {} (nonce: {}, fees: {}) for mining", hash, tx.get_nonce(), format_xelis(tx.get_fee()));
-                // TODO no clone
-                block.txs_hashes.insert(hash.as_ref().clone());
                 block_size += HASH_SIZE; // add the hash size
                 total_txs_size += size;
+                selection.selected.push(entry);
             }
         }
 
-        Ok(block)
+        Ok(selection)
     }
 
     // Build a block using the header and search for TXs in mempool and storage
@@ -1504,7 +1939,23 @@ impl<S: Storage> Blockchain<S> {
     }
 
     // Add a new block in chain using the requested storage
+    // If anything fails while applying it, none of its changes are written
     pub async fn add_new_block_for_storage(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
+        let res = self.add_new_block_internal(storage, block, broadcast, mining).await;
+        if res.is_err() {
+            if storage.has_commit_point()? {
+                warn!("Error while applying block, discarding its changes");
+                storage.end_commit_point(false).await?;
+            }
+            // Pointers may have been updated before the error
+            self.topoheight.store(storage.get_top_topoheight().unwrap_or(0), Ordering::SeqCst);
+            self.height.store(storage.get_top_height().unwrap_or(0), Ordering::SeqCst);
+        }
+
+        res
+    }
+
+    async fn add_new_block_internal(&self, storage: &mut S, block: Block, broadcast: bool, mining: bool) -> Result<(), BlockchainError> {
         let start = Instant::now();
 
         // Verify that the block is on the correct version
@@ -1707,6 +2158,8 @@ impl<S: Storage> Blockchain<S> {
         let (block, txs) = block.split();
         let block = block.to_arc();
         debug!("Saving block {} on disk", block_hash);
+        // All changes made from here must be applied atomically
+        storage.start_commit_point().await?;
         // Add block to chain
         storage.save_block(block.clone(), &txs, difficulty, p, block_hash.clone()).await?;
 
@@ -1789,6 +2242,9 @@ impl<S: Storage> Blockchain<S> {
 
                     // Block may be orphaned if its not in the new full order set
                     let is_orphaned = !full_order.contains(&hash_at_topo);
+                    if is_orphaned {
+                        metrics::BLOCKS_ORPHANED.inc();
+                    }
                     // Notify if necessary that we have a block orphaned
                     if is_orphaned && should_track_events.contains(&NotifyEvent::BlockOrphaned) {
                         let value = json!(BlockOrphanedEvent {
@@ -1814,6 +2270,7 @@ impl<S: Storage> Blockchain<S> {
                     storage.delete_versioned_balances_at_topoheight(topoheight).await?;
                     storage.delete_versioned_nonces_at_topoheight(topoheight).await?;
                     storage.delete_registrations_at_topoheight(topoheight).await?;
+                    storage.delete_account_transactions_at_topoheight(topoheight).await?;
 
                     topoheight += 1;
                 }
@@ -1921,6 +2378,17 @@ impl<S: Storage> Blockchain<S> {
                         // mark tx as executed
                         chain_state.get_mut_storage().set_tx_executed_in_block(tx_hash, &hash)?;
 
+                        // link the tx to all the accounts involved
+                        if self.index_account_transactions {
+                            let storage = chain_state.get_mut_storage();
+                            storage.add_account_transaction(tx.get_source(), highest_topo, tx_hash, AccountTransactionRole::Source).await?;
+                            if let TransactionType::Transfers(transfers) = tx.get_data() {
+                                for transfer in transfers {
+                                    storage.add_account_transaction(transfer.get_destination(), highest_topo, tx_hash, AccountTransactionRole::Destination).await?;
+                                }
+                            }
+                        }
+
                         // Delete the transaction from  the list if it was marked as orphaned
                         if orphaned_transactions.remove(&tx_hash) {
                             trace!("Transaction {} was marked as orphaned, but got executed again", tx_hash);
@@ -1928,10 +2396,20 @@ impl<S: Storage> Blockchain<S> {
 
                         // if the rpc_server is enable, track events
                         if should_track_events.contains(&NotifyEvent::TransactionExecuted) {
+                            let mainnet = self.network.is_mainnet();
+                            let transfers = match tx.get_data() {
+                                TransactionType::Transfers(transfers) => transfers.iter().map(|transfer| ExecutedTransfer {
+                                    asset: Cow::Borrowed(transfer.get_asset()),
+                                    destination: transfer.get_destination().as_address(mainnet)
+                                }).collect(),
+                                _ => Vec::new()
+                            };
                             let value = json!(TransactionExecutedEvent {
                                 tx_hash: Cow::Borrowed(&tx_hash),
                                 block_hash: Cow::Borrowed(&hash),
                                 topoheight: highest_topo,
+                                source: tx.get_source().as_address(mainnet),
+                                transfers
                             });
                             events.entry(NotifyEvent::TransactionExecuted).or_insert_with(Vec::new).push(value);
                         }
@@ -2014,6 +2492,23 @@ impl<S: Storage> Blockchain<S> {
             }
         }
 
+        // Store the new tips available
+        storage.store_tips(&tips)?;
+
+        let mut current_height = current_height;
+        if current_height == 0 || block.get_height() > current_height {
+            debug!("storing new top height {}", block.get_height());
+            storage.set_top_height(block.get_height())?;
+            self.height.store(block.get_height(), Ordering::Release);
+            current_height = block.get_height();
+        }
+
+        // Block is now fully applied
+        storage.end_commit_point(true).await?;
+        if self.fsync_policy == FsyncPolicy::Block {
+            storage.flush().await?;
+        }
+
         // auto prune mode
         if extended {
             if let Some(keep_only) = self.auto_prune_keep_n_blocks {
@@ -2028,17 +2523,6 @@ impl<S: Storage> Blockchain<S> {
             }
         }
 
-        // Store the new tips available
-        storage.store_tips(&tips)?;
-
-        let mut current_height = current_height;
-        if current_height == 0 || block.get_height() > current_height {
-            debug!("storing new top height {}", block.get_height());
-            storage.set_top_height(block.get_height())?;
-            self.height.store(block.get_height(), Ordering::Release);
-            current_height = block.get_height();
-        }
-
         // update stable height and difficulty in cache
         {
             let (stable_hash, stable_height) = self.find_common_base::<S, _>(&storage, &tips).await?;
@@ -2068,14 +2552,26 @@ impl<S: Storage> Blockchain<S> {
         // Check if the event is tracked
         let orphan_event_tracked = should_track_events.contains(&NotifyEvent::TransactionOrphaned);
 
-        // Clean mempool from old txs
-        let mempool_deleted_txs = {
+        // Clean mempool from old and expired txs
+        let (mempool_deleted_txs, mempool_expired_txs) = {
             debug!("Locking mempool write mode");
             let mut mempool = self.mempool.write().await;
             debug!("mempool write mode ok");
-            mempool.clean_up(&*storage, highest_topo).await
+            let deleted = mempool.clean_up(&*storage, highest_topo).await;
+            (deleted, mempool.remove_expired_txs())
         };
 
+        if should_track_events.contains(&NotifyEvent::TransactionEvicted) {
+            for (tx_hash, sorted_tx) in mempool_expired_txs {
+                let value = json!(TransactionEvictedEvent {
+                    tx_hash: Cow::Borrowed(tx_hash.as_ref()),
+                    reason: TransactionEvictionReason::Expired,
+                    first_seen: sorted_tx.get_first_seen()
+                });
+                events.entry(NotifyEvent::TransactionEvicted).or_insert_with(Vec::new).push(value);
+            }
+        }
+
         if orphan_event_tracked {
             for (tx_hash, sorted_tx) in mempool_deleted_txs {
                 // Delete it from our orphaned transactions list
@@ -2115,14 +2611,22 @@ impl<S: Storage> Blockchain<S> {
                     }
                 };
 
-                // Clone only if its necessary
-                if !orphan_event_tracked {
-                    if let Err(e) = self.add_tx_to_mempool_with_storage_and_hash(&storage, tx, tx_hash, false).await {
-                        debug!("Error while adding back orphaned tx: {}", e);
-                    }
-                } else {
-                    if let Err(e) = self.add_tx_to_mempool_with_storage_and_hash(&storage, tx.clone(), tx_hash.clone(), false).await {
-                        debug!("Error while adding back orphaned tx: {}, broadcasting event", e);
+                // RPC server is already locked, so we track the mempool events ourself
+                let res = match self.add_tx_to_mempool_internal(&storage, tx.clone(), tx_hash.clone()).await {
+                    Ok(update) => {
+                        self.track_mempool_update_events(&tx_hash, &tx, &update, &should_track_events, &mut events);
+                        if update.is_evicted(&tx_hash) {
+                            Err(BlockchainError::MempoolFull(tx_hash.clone()))
+                        } else {
+                            Ok(())
+                        }
+                    },
+                    Err(e) => Err(e)
+                };
+
+                if let Err(e) = res {
+                    debug!("Error while adding back orphaned tx: {}", e);
+                    if orphan_event_tracked {
                         // We couldn't add it back to mempool, let's notify this event
                         let data = RPCTransaction::from_tx(&tx, &tx_hash, storage.is_mainnet());
                         let data = TransactionResponse {
@@ -2139,6 +2643,7 @@ impl<S: Storage> Blockchain<S> {
         }
 
         info!("Processed block {} at height {} in {:?} with {} txs (DAG: {})", block_hash, block.get_height(), start.elapsed(), block.get_txs_count(), block_is_ordered);
+        metrics::BLOCKS_ACCEPTED.inc();
 
         // Broadcast to p2p nodes
         if broadcast {
@@ -2356,6 +2861,7 @@ impl<S: Storage> Blockchain<S> {
         };
         let (new_height, new_topoheight, txs) = storage.pop_blocks(current_height, current_topoheight, count, until).await?;
         debug!("New topoheight: {} (diff: {})", new_topoheight, current_topoheight - new_topoheight);
+        storage.delete_account_transactions_above_topoheight(new_topoheight).await?;
 
         // Try to add all txs back to mempool if possible
         // We try to prevent lost/to be orphaned
@@ -2402,6 +2908,306 @@ impl<S: Storage> Blockchain<S> {
         Ok(new_topoheight)
     }
 
+    // Replay the stored chain from genesis (or the pruned topoheight) until the requested topoheight
+    // Each block goes through the same checks as when it was added: PoW difficulty, DAG order,
+    // rewards, transactions proofs and the resulting versioned balances and nonces
+    // Returns the count of blocks replayed, or the first divergence found
+    pub async fn verify_chain_replay(&self, until_topoheight: u64) -> Result<u64, BlockchainError> {
+        let (start, mut past_supply) = {
+            let storage = self.storage.read().await;
+            let start = match storage.get_pruned_topoheight().await? {
+                Some(pruned_topoheight) => pruned_topoheight + 1,
+                None => 0
+            };
+
+            let past_supply = if start > 0 {
+                storage.get_supply_at_topo_height(start - 1).await?
+            } else {
+                0
+            };
+            (start, past_supply)
+        };
+
+        info!("Replaying chain from topoheight {} to {}", start, until_topoheight);
+        // Storage is locked by chunks of blocks so new blocks can still be added during the replay
+        // Last block replayed, it must still be at its topoheight when the next chunk starts
+        let mut last_replayed: Option<Hash> = None;
+        let mut chunk_start = start;
+        while chunk_start <= until_topoheight {
+            let mut storage = self.storage.write().await;
+            if let Some(hash) = last_replayed.as_ref() {
+                if storage.get_hash_at_topo_height(chunk_start - 1).await? != *hash {
+                    return Err(BlockchainError::ChainReorgDuringReplay(chunk_start - 1))
+                }
+            }
+
+            let chunk_end = until_topoheight.min(chunk_start + REPLAY_CHUNK_SIZE - 1);
+            for topoheight in chunk_start..=chunk_end {
+                let hash = storage.get_hash_at_topo_height(topoheight).await?;
+                // We are too near from the pruned topoheight, previous blocks may be missing
+                // Only the transactions and the balances can be verified
+                let full_checks = start == 0 || topoheight - start > STABLE_LIMIT;
+                if full_checks {
+                    self.verify_stored_block_dag(&storage, &hash, topoheight).await?;
+                }
+
+                past_supply = self.replay_stored_block(&mut storage, &hash, topoheight, past_supply, full_checks).await?;
+                if topoheight % 1000 == 0 {
+                    info!("Replayed chain until topoheight {}", topoheight);
+                }
+                last_replayed = Some(hash);
+            }
+            chunk_start = chunk_end + 1;
+        }
+
+        let storage = self.storage.read().await;
+        if let Some(hash) = last_replayed.as_ref() {
+            if storage.get_hash_at_topo_height(until_topoheight).await? != *hash {
+                return Err(BlockchainError::ChainReorgDuringReplay(until_topoheight))
+            }
+        }
+
+        // The stored order above the stable point must be the same as the one generated from current tips
+        if until_topoheight == self.get_topo_height() {
+            let tips = storage.get_tips().await?;
+            let (base_hash, base_height) = self.find_common_base::<S, _>(&storage, &tips).await?;
+            let best_tip = self.find_best_tip(&storage, &tips, &base_hash, base_height).await?;
+            let base_topo_height = storage.get_topo_height_for_hash(&base_hash).await?;
+            let full_order = self.generate_full_order(&*storage, best_tip, &base_hash, base_height, base_topo_height).await?;
+            for (i, hash) in full_order.iter().enumerate() {
+                let topoheight = base_topo_height + i as u64;
+                let hash_at_topo = storage.get_hash_at_topo_height(topoheight).await?;
+                if hash_at_topo != *hash {
+                    return Err(BlockchainError::ChainDivergence(topoheight, hash_at_topo, format!("DAG order expects block {} at this topoheight", hash)))
+                }
+            }
+        }
+
+        Ok((until_topoheight + 1).saturating_sub(start))
+    }
+
+    // Recompute the height, PoW difficulty and cumulative difficulty of a stored block from its tips
+    async fn verify_stored_block_dag(&self, storage: &S, hash: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
+        let header = storage.get_block_header_by_hash(hash).await?;
+        let tips = header.get_tips();
+
+        // All tips must be ordered before the block
+        for tip in tips {
+            if !storage.is_block_topological_ordered(tip).await || storage.get_topo_height_for_hash(tip).await? >= topoheight {
+                return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("tip {} is not ordered before the block", tip)))
+            }
+        }
+
+        let height = blockdag::calculate_height_at_tips(storage, tips.iter()).await?;
+        if height != header.get_height() {
+            return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("height is {} but expected {} from its tips", header.get_height(), height)))
+        }
+
+        let pow_hash = header.get_pow_hash()?;
+        let (difficulty, p) = match self.verify_proof_of_work(storage, &pow_hash, tips.iter()).await {
+            Ok(v) => v,
+            Err(BlockchainError::InvalidDifficulty) => return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), "invalid proof of work".to_owned())),
+            Err(e) => return Err(e)
+        };
+
+        let stored_difficulty = storage.get_difficulty_for_block_hash(hash).await?;
+        if difficulty != stored_difficulty {
+            return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("difficulty is {} but expected {}", stored_difficulty, difficulty)))
+        }
+
+        let stored_p = storage.get_estimated_covariance_for_block_hash(hash).await?;
+        if p != stored_p {
+            return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("estimated covariance is {} but expected {}", stored_p, p)))
+        }
+
+        let cumulative_difficulty: CumulativeDifficulty = if tips.is_empty() {
+            GENESIS_BLOCK_DIFFICULTY.into()
+        } else {
+            let (base, base_height) = self.find_common_base(storage, tips).await?;
+            let (_, cumulative_difficulty) = self.find_tip_work_score(storage, hash, &base, base_height).await?;
+            cumulative_difficulty
+        };
+
+        let stored_cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(hash).await?;
+        if cumulative_difficulty != stored_cumulative_difficulty {
+            return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("cumulative difficulty is {} but expected {}", stored_cumulative_difficulty, cumulative_difficulty)))
+        }
+
+        Ok(())
+    }
+
+    // Execute again a stored block at its topoheight and compare the result with what is stored
+    // Everything is done inside a commit point that is always reverted, so storage is left untouched
+    // Returns the supply at this topoheight
+    async fn replay_stored_block(&self, storage: &mut S, hash: &Hash, topoheight: u64, past_supply: u64, verify_reward: bool) -> Result<u64, BlockchainError> {
+        let block = storage.get_block_by_hash(hash).await?;
+
+        // Collect all accounts that may be updated by this block
+        let mut keys: HashSet<&PublicKey> = HashSet::new();
+        keys.insert(block.get_miner());
+        keys.insert(&DEV_PUBLIC_KEY);
+        let mut executed_txs = HashSet::new();
+        for (tx, tx_hash) in block.get_transactions().iter().zip(block.get_txs_hashes()) {
+            keys.insert(tx.get_source());
+            if let TransactionType::Transfers(transfers) = tx.get_data() {
+                for transfer in transfers {
+                    keys.insert(transfer.get_destination());
+                }
+            }
+
+            if storage.is_tx_executed_in_block(tx_hash, hash)? {
+                executed_txs.insert(tx_hash);
+            }
+        }
+
+        let mut accounts = HashMap::new();
+        for key in keys {
+            let assets = storage.get_assets_for(key).await?;
+            accounts.insert(key, assets);
+        }
+        let expected = Self::get_versions_at_topoheight(storage, &accounts, topoheight).await?;
+
+        storage.start_commit_point().await?;
+        let res = self.replay_stored_block_internal(storage, &block, hash, topoheight, past_supply, verify_reward, &executed_txs).await;
+        let res = match res {
+            Ok(supply) => Self::get_versions_at_topoheight(storage, &accounts, topoheight).await.map(|versions| (supply, versions)),
+            Err(e) => Err(e)
+        };
+        storage.end_commit_point(false).await?;
+        let (supply, versions) = res?;
+
+        for ((key, asset), expected_version) in expected.iter() {
+            let name = match asset {
+                Some(asset) => format!("balance of {} for asset {}", key.as_address(self.network.is_mainnet()), asset),
+                None => format!("nonce of {}", key.as_address(self.network.is_mainnet()))
+            };
+            match versions.get(&(key.clone(), asset.clone())) {
+                Some(version) if version == expected_version => {},
+                Some(_) => return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("stored {} is different from the replayed one", name))),
+                None => return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("stored {} is not produced by the replay", name)))
+            }
+        }
+
+        if let Some((key, asset)) = versions.keys().find(|k| !expected.contains_key(k)) {
+            let name = match asset {
+                Some(asset) => format!("balance of {} for asset {}", key.as_address(self.network.is_mainnet()), asset),
+                None => format!("nonce of {}", key.as_address(self.network.is_mainnet()))
+            };
+            return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("replayed {} is missing in storage", name)))
+        }
+
+        Ok(supply)
+    }
+
+    // Serialized versioned balances and nonces stored exactly at topoheight for the requested accounts
+    // Nonces are stored without asset
+    async fn get_versions_at_topoheight(storage: &S, accounts: &HashMap<&PublicKey, Vec<Hash>>, topoheight: u64) -> Result<HashMap<(PublicKey, Option<Hash>), Vec<u8>>, BlockchainError> {
+        let mut versions = HashMap::new();
+        for (key, assets) in accounts {
+            for asset in assets {
+                if storage.has_balance_at_exact_topoheight(key, asset, topoheight).await? {
+                    let version = storage.get_balance_at_exact_topoheight(key, asset, topoheight).await?;
+                    versions.insert(((*key).clone(), Some(asset.clone())), version.to_bytes());
+                }
+            }
+
+            if storage.has_nonce_at_exact_topoheight(key, topoheight).await? {
+                let version = storage.get_nonce_at_exact_topoheight(key, topoheight).await?;
+                versions.insert(((*key).clone(), None), version.to_bytes());
+            }
+        }
+
+        Ok(versions)
+    }
+
+    // Undo the changes made by the block at topoheight, then execute it again the same way as in add_new_block
+    // Must be called inside a commit point
+    async fn replay_stored_block_internal(&self, storage: &mut S, block: &Block, hash: &Hash, topoheight: u64, past_supply: u64, verify_reward: bool, executed_txs: &HashSet<&Hash>) -> Result<u64, BlockchainError> {
+        // Same cleaning as a DAG reorg
+        for tx_hash in executed_txs {
+            storage.remove_tx_executed(tx_hash)?;
+        }
+        storage.delete_versioned_balances_at_topoheight(topoheight).await?;
+        storage.delete_versioned_nonces_at_topoheight(topoheight).await?;
+        storage.delete_registrations_at_topoheight(topoheight).await?;
+
+        let mut block_reward = if verify_reward {
+            let is_side_block = self.is_side_block_internal(&*storage, hash, topoheight).await?;
+            let mut side_blocks_count = 0;
+            for block_at_height in storage.get_blocks_at_height(block.get_height()).await? {
+                if block_at_height != *hash && self.is_side_block_internal(&*storage, &block_at_height, topoheight).await? {
+                    side_blocks_count += 1;
+                }
+            }
+
+            let block_reward = self.internal_get_block_reward(past_supply, is_side_block, side_blocks_count).await?;
+            let stored_block_reward = storage.get_block_reward_at_topo_height(topoheight)?;
+            if block_reward != stored_block_reward {
+                return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("block reward is {} but expected {}", format_xelis(stored_block_reward), format_xelis(block_reward))))
+            }
+            block_reward
+        } else {
+            // Trust the stored reward as we don't know previous blocks
+            storage.get_block_reward_at_topo_height(topoheight)?
+        };
+
+        let supply = past_supply + block_reward;
+        let stored_supply = storage.get_supply_at_topo_height(topoheight).await?;
+        if supply != stored_supply {
+            return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("supply is {} but expected {}", format_xelis(stored_supply), format_xelis(supply))))
+        }
+
+        // Verify the proofs of all executed transactions against the state before this block
+        {
+            let mut chain_state = ChainState::new(&*storage, topoheight);
+            for (tx, tx_hash) in block.get_transactions().iter().zip(block.get_txs_hashes()) {
+                if executed_txs.contains(tx_hash) {
+                    if let Err(e) = tx.verify(&mut chain_state).await {
+                        return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("transaction {} is invalid: {}", tx_hash, e)))
+                    }
+                }
+            }
+        }
+
+        let mut nonce_checker = NonceChecker::new();
+        let mut total_fees = 0;
+        let mut chain_state = ApplicableChainState::new(storage, topoheight);
+        for (tx, tx_hash) in block.get_transactions().iter().zip(block.get_txs_hashes()) {
+            // Executed in another block
+            if chain_state.get_storage().is_tx_executed_in_a_block(tx_hash)? {
+                continue;
+            }
+
+            let executed = if !nonce_checker.use_nonce(chain_state.get_storage(), tx.get_source(), tx.get_nonce(), topoheight).await? {
+                false
+            } else if let Err(e) = tx.apply_with_partial_verify(chain_state.as_mut()).await {
+                debug!("Error while replaying TX {}: {}", tx_hash, e);
+                false
+            } else {
+                let next_nonce = nonce_checker.get_new_nonce(tx.get_source(), self.network.is_mainnet())?;
+                chain_state.as_mut().update_account_nonce(tx.get_source(), next_nonce).await?;
+                chain_state.get_mut_storage().set_tx_executed_in_block(tx_hash, hash)?;
+                total_fees += tx.get_fee();
+                true
+            };
+
+            if executed != executed_txs.contains(tx_hash) {
+                return Err(BlockchainError::ChainDivergence(topoheight, hash.clone(), format!("transaction {} execution is {} but expected {}", tx_hash, executed, executed_txs.contains(tx_hash))))
+            }
+        }
+
+        let dev_fee_percentage = get_block_dev_fee(block.get_height());
+        if dev_fee_percentage != 0 {
+            let dev_fee_part = block_reward * dev_fee_percentage / 100;
+            chain_state.reward_miner(&DEV_PUBLIC_KEY, dev_fee_part).await?;
+            block_reward -= dev_fee_part;
+        }
+        chain_state.reward_miner(block.get_miner(), block_reward + total_fees).await?;
+        chain_state.apply_changes().await?;
+
+        Ok(supply)
+    }
+
     // Calculate the average block time on the last 50 blocks
     // It will return the target block time if we don't have enough blocks
     // We calculate it by taking the timestamp of the block at topoheight - 50 and the timestamp of the block at topoheight
@@ -2460,6 +3266,37 @@ pub async fn estimate_required_tx_fees<P: AccountProvider>(provider: &P, current
     Ok(calculate_tx_fee(tx.size(), output_count, new_addresses))
 }
 
+// Classify the error returned while verifying a simulated TX
+fn get_simulation_error(err: BlockchainError) -> TransactionSimulationError {
+    let (kind, proof) = match &err {
+        BlockchainError::TxTooBig(_, _) => (TransactionSimulationFailure::TooBig, None),
+        BlockchainError::TxAlreadyInMempool(_) => (TransactionSimulationFailure::AlreadyInMempool, None),
+        BlockchainError::TxAlreadyInBlockchain(_) => (TransactionSimulationFailure::AlreadyInBlockchain, None),
+        BlockchainError::InvalidNonce => (TransactionSimulationFailure::InvalidNonce, None),
+        BlockchainError::InvalidTxFee(_, _) => (TransactionSimulationFailure::InvalidFee, None),
+        BlockchainError::TxReplacementUnderpriced(_, _, _, _) => (TransactionSimulationFailure::ReplacementUnderpriced, None),
+        BlockchainError::InvalidTransactionSignature => (TransactionSimulationFailure::InvalidSignature, None),
+        BlockchainError::TransactionProof(e) => {
+            let proof = match e {
+                ProofVerificationError::Decompression(_) | ProofVerificationError::Format => TransactionProofKind::Format,
+                ProofVerificationError::CommitmentEqProof => TransactionProofKind::CommitmentEq,
+                ProofVerificationError::CiphertextValidityProof => TransactionProofKind::CiphertextValidity,
+                ProofVerificationError::GenericProof => TransactionProofKind::Sigma,
+                ProofVerificationError::RangeProof(_) => TransactionProofKind::Range,
+                ProofVerificationError::Transcript(_) => TransactionProofKind::Transcript
+            };
+            (TransactionSimulationFailure::InvalidProof, Some(proof))
+        },
+        _ => (TransactionSimulationFailure::InvalidState, None)
+    };
+
+    TransactionSimulationError {
+        kind,
+        proof,
+        message: err.to_string()
+    }
+}
+
 // Get the block reward for a side block based on how many side blocks exists at same height
 pub fn side_block_reward_percentage(side_blocks: u64) -> u64 {
     let mut side_block_percent = SIDE_BLOCK_REWARD_PERCENT;
@@ -2514,9 +3351,201 @@ pub fn get_block_dev_fee(height: u64) -> u64 {
 // }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use clap::Parser;
+    use rand::{rngs::OsRng, RngCore};
+    use xelis_common::{
+        account::{CiphertextCache, VersionedBalance},
+        config::COIN_VALUE,
+        crypto::{elgamal::Ciphertext, KeyPair},
+        transaction::{
+            builder::{AccountState, FeeBuilder, FeeHelper, TransactionBuilder, TransactionTypeBuilder},
+            BurnPayload,
+            Reference
+        }
+    };
+    use crate::core::storage::{BalanceProvider, DagOrderProvider, SledStorage};
+    #[cfg(feature = "rocksdb")]
+    use crate::core::storage::RocksStorage;
     use super::*;
 
+    #[derive(Parser)]
+    struct TestConfig {
+        #[clap(flatten)]
+        config: Config
+    }
+
+    // Storage backend a test node can be started with
+    pub(crate) trait TestStorage: Storage + Sized {
+        fn open(dir_path: &str) -> Self;
+    }
+
+    impl TestStorage for SledStorage {
+        fn open(dir_path: &str) -> Self {
+            SledStorage::new(dir_path.to_owned(), None, Network::Dev).unwrap()
+        }
+    }
+
+    #[cfg(feature = "rocksdb")]
+    impl TestStorage for RocksStorage {
+        fn open(dir_path: &str) -> Self {
+            RocksStorage::new(dir_path.to_owned(), None, Network::Dev).unwrap()
+        }
+    }
+
+    // Unique directory in the temp dir for the storage of a test
+    pub(crate) fn temp_dir_path(name: &str) -> String {
+        format!("{}/", std::env::temp_dir().join(format!("xelis-{}-{}", name, OsRng.next_u64())).display())
+    }
+
+    // Delete a directory created by `temp_dir_path`
+    pub(crate) fn remove_temp_dir(dir_path: &str) {
+        let _ = std::fs::remove_dir_all(dir_path);
+    }
+
+    // Stop a node started by `start_node` and delete its storage
+    // All the locks on its storage must be released before
+    pub(crate) async fn stop_node<S: Storage>(blockchain: &Arc<Blockchain<S>>, dir_path: &str) {
+        blockchain.stop().await;
+        remove_temp_dir(dir_path);
+    }
+
+    // Start a node on the dev network with the simulator, so blocks are mined without PoW
+    pub(crate) async fn start_node<S: TestStorage>(dir_path: &str) -> Arc<Blockchain<S>> {
+        start_node_with(dir_path, &[]).await
+    }
+
+    // Same as `start_node` with additional arguments
+    pub(crate) async fn start_node_with<S: TestStorage>(dir_path: &str, extra_args: &[&str]) -> Arc<Blockchain<S>> {
+        let mut args = vec![
+            "xelis_daemon".to_owned(),
+            "--dir-path".to_owned(), dir_path.to_owned(),
+            "--simulator".to_owned(), "blockchain".to_owned(),
+            "--rpc-bind-address".to_owned(), "127.0.0.1:0".to_owned(),
+            "--disable-getwork-server".to_owned(),
+            "--disable-p2p-server".to_owned()
+        ];
+        args.extend(extra_args.iter().map(|arg| arg.to_string()));
+
+        let config = TestConfig::parse_from(args).config;
+        Blockchain::new(config, Network::Dev, S::open(dir_path)).await.unwrap()
+    }
+
+    // Give a balance to a new account directly in the storage of each node
+    pub(crate) async fn create_account<S: Storage>(nodes: &[&Arc<Blockchain<S>>], balance: u64) -> TestAccount {
+        let keypair = KeyPair::new();
+        let key = keypair.get_public_key().compress();
+        let ciphertext = keypair.get_public_key().encrypt(balance);
+        for node in nodes {
+            let mut storage = node.get_storage().write().await;
+            let version = VersionedBalance::new(CiphertextCache::Decompressed(ciphertext.clone()), None);
+            storage.set_last_balance_to(&key, &XELIS_ASSET, 0, &version).await.unwrap();
+        }
+
+        let storage = nodes[0].get_storage().read().await;
+        let reference = Reference {
+            hash: storage.get_hash_at_topo_height(0).await.unwrap(),
+            topoheight: 0
+        };
+
+        TestAccount {
+            keypair,
+            balance,
+            ciphertext: CiphertextCache::Decompressed(ciphertext),
+            nonce: 0,
+            reference
+        }
+    }
+
+    // Account state used to build the TXs of the tests
+    #[derive(Clone)]
+    pub(crate) struct TestAccount {
+        pub(crate) keypair: KeyPair,
+        balance: u64,
+        ciphertext: CiphertextCache,
+        nonce: u64,
+        reference: Reference
+    }
+
+    impl FeeHelper for TestAccount {
+        type Error = ();
+
+        fn account_exists(&self, _: &PublicKey) -> Result<bool, Self::Error> {
+            Ok(true)
+        }
+    }
+
+    impl AccountState for TestAccount {
+        fn is_mainnet(&self) -> bool {
+            false
+        }
+
+        fn get_account_balance(&self, _: &Hash) -> Result<u64, Self::Error> {
+            Ok(self.balance)
+        }
+
+        fn get_reference(&self) -> Reference {
+            self.reference.clone()
+        }
+
+        fn get_account_ciphertext(&self, _: &Hash) -> Result<CiphertextCache, Self::Error> {
+            Ok(self.ciphertext.clone())
+        }
+
+        fn update_account_balance(&mut self, _: &Hash, balance: u64, ciphertext: Ciphertext) -> Result<(), Self::Error> {
+            self.balance = balance;
+            self.ciphertext = CiphertextCache::Decompressed(ciphertext);
+            Ok(())
+        }
+
+        fn get_nonce(&self) -> Result<u64, Self::Error> {
+            Ok(self.nonce)
+        }
+
+        fn update_nonce(&mut self, nonce: u64) -> Result<(), Self::Error> {
+            self.nonce = nonce;
+            Ok(())
+        }
+    }
+
+    impl TestAccount {
+        // Account unknown to the nodes, for the tests where its TXs are not verified
+        pub(crate) fn detached(balance: u64) -> Self {
+            let keypair = KeyPair::new();
+            let ciphertext = keypair.get_public_key().encrypt(balance);
+            TestAccount {
+                keypair,
+                balance,
+                ciphertext: CiphertextCache::Decompressed(ciphertext),
+                nonce: 0,
+                reference: Reference {
+                    hash: Hash::zero(),
+                    topoheight: 0
+                }
+            }
+        }
+
+        pub(crate) fn build_burn_tx(&mut self) -> Transaction {
+            self.build_burn_tx_with_fee(FeeBuilder::default())
+        }
+
+        pub(crate) fn build_burn_tx_with_fee(&mut self, fee: FeeBuilder) -> Transaction {
+            let keypair = self.keypair.clone();
+            let data = TransactionTypeBuilder::Burn(BurnPayload { asset: XELIS_ASSET, amount: 1 });
+            TransactionBuilder::new(0, keypair.get_public_key().compress(), data, fee)
+                .build(self, &keypair)
+                .unwrap()
+        }
+    }
+
+    pub(crate) async fn mine_blocks<S: Storage>(blockchain: &Arc<Blockchain<S>>, count: usize) {
+        let key = KeyPair::new().get_public_key().compress();
+        for _ in 0..count {
+            let block = blockchain.mine_block(&key).await.unwrap();
+            blockchain.add_new_block(block, false, false).await.unwrap();
+        }
+    }
+
     #[test]
     fn test_reward_side_block_percentage() {
         assert_eq!(side_block_reward_percentage(0), SIDE_BLOCK_REWARD_PERCENT);
@@ -2524,4 +3553,200 @@ mod tests {
         assert_eq!(side_block_reward_percentage(2), SIDE_BLOCK_REWARD_PERCENT / 4);
         assert_eq!(side_block_reward_percentage(3), SIDE_BLOCK_REWARD_MIN_PERCENT);
     }
+
+    async fn test_verify_chain_replay<S: TestStorage>() {
+        let dir_path = temp_dir_path("replay");
+        let blockchain = start_node::<S>(&dir_path).await;
+        mine_blocks(&blockchain, 5).await;
+
+        let topoheight = blockchain.get_topo_height();
+        assert_eq!(blockchain.verify_chain_replay(topoheight).await.unwrap(), topoheight + 1);
+
+        // Replaying must not change anything stored
+        assert_eq!(blockchain.verify_chain_replay(topoheight).await.unwrap(), topoheight + 1);
+
+        stop_node(&blockchain, &dir_path).await;
+    }
+
+    async fn test_verify_chain_replay_divergence<S: TestStorage>() {
+        let dir_path = temp_dir_path("replay");
+        let blockchain = start_node::<S>(&dir_path).await;
+        mine_blocks(&blockchain, 5).await;
+
+        // Corrupt the reward stored for a block
+        let hash = {
+            let mut storage = blockchain.get_storage().write().await;
+            let reward = storage.get_block_reward_at_topo_height(2).unwrap();
+            storage.set_block_reward_at_topo_height(2, reward + 1).unwrap();
+            storage.get_hash_at_topo_height(2).await.unwrap()
+        };
+
+        match blockchain.verify_chain_replay(blockchain.get_topo_height()).await {
+            Err(BlockchainError::ChainDivergence(topoheight, block, _)) => {
+                assert_eq!(topoheight, 2);
+                assert_eq!(block, hash);
+            },
+            res => panic!("Expected a chain divergence, got {:?}", res)
+        }
+
+        stop_node(&blockchain, &dir_path).await;
+    }
+
+    async fn test_simulate_transaction<S: TestStorage>() {
+        let dir_path = temp_dir_path("simulate");
+        let blockchain = start_node::<S>(&dir_path).await;
+        let mut account = create_account(&[&blockchain], COIN_VALUE).await;
+        let mut replacing = account.clone();
+        let first = account.build_burn_tx_with_fee(FeeBuilder::Value(100_000));
+        let second = account.build_burn_tx_with_fee(FeeBuilder::Value(100_000));
+
+        // Nonce doesn't follow the one stored
+        let result = blockchain.simulate_transaction(&second).await.unwrap();
+        assert!(!result.valid);
+        assert_eq!(result.error.map(|e| e.kind), Some(TransactionSimulationFailure::InvalidNonce));
+        assert_eq!((result.nonce, result.expected_nonce), (1, 0));
+
+        let result = blockchain.simulate_transaction(&first).await.unwrap();
+        assert!(result.valid && result.error.is_none());
+        assert_eq!(result.fee, 100_000);
+        assert!(result.required_fee > 0 && result.required_fee <= result.fee);
+        assert!(result.replaced_tx.is_none() && result.replacement_fee.is_none());
+        // Nothing is added to the mempool
+        assert_eq!(blockchain.get_mempool().read().await.size(), 0);
+
+        blockchain.add_tx_to_mempool(first.clone(), false).await.unwrap();
+        let result = blockchain.simulate_transaction(&first).await.unwrap();
+        assert!(result.in_mempool);
+        assert_eq!(result.error.map(|e| e.kind), Some(TransactionSimulationFailure::AlreadyInMempool));
+
+        // The next nonce is now expected after the one in mempool
+        let result = blockchain.simulate_transaction(&second).await.unwrap();
+        assert!(result.valid);
+        assert_eq!(result.expected_nonce, 1);
+
+        // Same nonce as the pending TX: the fee must be high enough to replace it
+        let replacement_fee = blockchain.get_mempool().read().await.get_replacement_fee(100_000);
+        let underpriced = replacing.clone().build_burn_tx_with_fee(FeeBuilder::Value(replacement_fee - 1));
+        let result = blockchain.simulate_transaction(&underpriced).await.unwrap();
+        assert_eq!(result.error.map(|e| e.kind), Some(TransactionSimulationFailure::ReplacementUnderpriced));
+        assert_eq!(result.replaced_tx.map(|hash| hash.into_owned()), Some(first.hash()));
+        assert_eq!(result.replacement_fee, Some(replacement_fee));
+
+        let replacement = replacing.build_burn_tx_with_fee(FeeBuilder::Value(replacement_fee));
+        let result = blockchain.simulate_transaction(&replacement).await.unwrap();
+        assert!(result.valid);
+        assert_eq!(result.replaced_tx.map(|hash| hash.into_owned()), Some(first.hash()));
+
+        // The balance stored is lower than the one used to build the TX
+        let mut account = create_account(&[&blockchain], COIN_VALUE).await;
+        let tx = account.build_burn_tx();
+        {
+            let mut storage = blockchain.get_storage().write().await;
+            let ciphertext = account.keypair.get_public_key().encrypt(0u64);
+            let version = VersionedBalance::new(CiphertextCache::Decompressed(ciphertext), None);
+            storage.set_last_balance_to(&account.keypair.get_public_key().compress(), &XELIS_ASSET, 0, &version).await.unwrap();
+        }
+        let result = blockchain.simulate_transaction(&tx).await.unwrap();
+        assert!(!result.valid);
+        assert_eq!(result.error.map(|e| e.kind), Some(TransactionSimulationFailure::InvalidProof));
+
+        // Only the TX added explicitly is in the mempool
+        let mempool = blockchain.get_mempool().read().await;
+        assert_eq!(mempool.size(), 1);
+        assert!(mempool.contains_tx(&first.hash()));
+        drop(mempool);
+
+        stop_node(&blockchain, &dir_path).await;
+    }
+
+    async fn test_repair_half_applied_block<S: TestStorage>() {
+        let dir_path = temp_dir_path("half-applied");
+        let blockchain = start_node::<S>(&dir_path).await;
+        mine_blocks(&blockchain, 5).await;
+
+        // Stopped after the tips of a block were written, but not the top height
+        let height = blockchain.get_height();
+        mine_blocks(&blockchain, 1).await;
+        blockchain.get_storage().write().await.set_top_height(height).unwrap();
+        blockchain.reload_from_disk().await.unwrap();
+        assert_eq!(blockchain.get_height(), height + 1);
+
+        // Stopped after a block was ordered, but before the pointers were updated
+        let topoheight = blockchain.get_topo_height();
+        let (height, tips) = {
+            let storage = blockchain.get_storage().read().await;
+            (storage.get_top_height().unwrap(), storage.get_tips().await.unwrap())
+        };
+        mine_blocks(&blockchain, 1).await;
+        let hash = {
+            let mut storage = blockchain.get_storage().write().await;
+            storage.set_top_topoheight(topoheight).unwrap();
+            storage.store_tips(&tips).unwrap();
+            storage.set_top_height(height).unwrap();
+            storage.get_hash_at_topo_height(topoheight + 1).await.unwrap()
+        };
+
+        // Both blocks are rewinded as the last one may be incomplete
+        blockchain.reload_from_disk().await.unwrap();
+        assert_eq!(blockchain.get_topo_height(), topoheight - 1);
+        {
+            let storage = blockchain.get_storage().read().await;
+            assert!(!storage.has_block_with_hash(&hash).await.unwrap());
+            assert!(!storage.has_hash_at_topoheight(topoheight).await.unwrap());
+            let top_hash = storage.get_hash_at_topo_height(topoheight - 1).await.unwrap();
+            assert!(storage.get_tips().await.unwrap().contains(&top_hash));
+        }
+
+        // The chain can be extended again and replays without divergence
+        mine_blocks(&blockchain, 2).await;
+        let topoheight = blockchain.get_topo_height();
+        assert_eq!(blockchain.verify_chain_replay(topoheight).await.unwrap(), topoheight + 1);
+
+        stop_node(&blockchain, &dir_path).await;
+    }
+
+    // Run the blockchain tests against each storage backend
+    #[tokio::test]
+    async fn test_verify_chain_replay_sled() {
+        test_verify_chain_replay::<SledStorage>().await;
+    }
+
+    #[tokio::test]
+    async fn test_verify_chain_replay_divergence_sled() {
+        test_verify_chain_replay_divergence::<SledStorage>().await;
+    }
+
+    #[tokio::test]
+    async fn test_simulate_transaction_sled() {
+        test_simulate_transaction::<SledStorage>().await;
+    }
+
+    #[tokio::test]
+    async fn test_repair_half_applied_block_sled() {
+        test_repair_half_applied_block::<SledStorage>().await;
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[tokio::test]
+    async fn test_verify_chain_replay_rocksdb() {
+        test_verify_chain_replay::<RocksStorage>().await;
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[tokio::test]
+    async fn test_verify_chain_replay_divergence_rocksdb() {
+        test_verify_chain_replay_divergence::<RocksStorage>().await;
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[tokio::test]
+    async fn test_simulate_transaction_rocksdb() {
+        test_simulate_transaction::<RocksStorage>().await;
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[tokio::test]
+    async fn test_repair_half_applied_block_rocksdb() {
+        test_repair_half_applied_block::<RocksStorage>().await;
+    }
 }
\ No newline at end of file
@@ -74,6 +74,10 @@ pub enum BlockchainError {
     TxNotFoundInSortedList(Hash),
     #[error("Tx {} already in mempool", _0)]
     TxAlreadyInMempool(Hash),
+    #[error("Tx {} cannot replace Tx {}: fee {} is lower than required {}", _0, _1, _2, _3)]
+    TxReplacementUnderpriced(Hash, Hash, u64, u64),
+    #[error("Mempool is full and Tx {} fee per byte is too low", _0)]
+    MempoolFull(Hash),
     #[error("Normal Tx {} is empty", _0)]
     TxEmpty(Hash),
     #[error("Transaction has an invalid reference: block hash not found")]
@@ -164,14 +168,40 @@ pub enum BlockchainError {
     UnexpectedTransactionVariant,
     #[error("Unexpected error on database: {}", _0)]
     DatabaseError(#[from] sled::Error),
+    #[cfg(feature = "rocksdb")]
+    #[error("Unexpected error on RocksDB: {}", _0)]
+    RocksDBError(#[from] rocksdb::Error),
+    #[cfg(feature = "rocksdb")]
+    #[error("Column family {} not found", _0)]
+    ColumnFamilyNotFound(&'static str),
     #[error("Unsupported operation")]
     UnsupportedOperation,
+    #[error("Storage schema version {} is not supported, latest supported is {}", _0, _1)]
+    UnsupportedSchemaVersion(u64, u64),
+    #[error("Destination storage is not empty")]
+    StorageNotEmpty,
+    #[error("A commit point is already started")]
+    CommitPointAlreadyStarted,
+    #[error("Invalid snapshot file")]
+    InvalidSnapshot,
+    #[error("Invalid snapshot checksum")]
+    InvalidSnapshotChecksum,
+    #[error("Snapshot version {} is not supported", _0)]
+    UnsupportedSnapshotVersion(u8),
+    #[error("Chain diverges at topoheight {} (block {}): {}", _0, _1, _2)]
+    ChainDivergence(u64, Hash, String),
+    #[error("Chain was reorganized during the replay at topoheight {}", _0)]
+    ChainReorgDuringReplay(u64),
     #[error("Data not found on disk: {}", _0)]
     NotFoundOnDisk(DiskContext),
     #[error("Invalid paramater: max chain response size isn't in range")]
     ConfigMaxChainResponseSize,
     #[error("Invalid config sync mode")]
     ConfigSyncMode,
+    #[error("Invalid config: TLS certificate and private key must be set together")]
+    ConfigTls,
+    #[error("Invalid config: Dandelion fluff probability must be in range [0-100]")]
+    ConfigDandelionFluffProbability,
     #[error("Expected at least one tips")]
     ExpectedTips,
     #[error("Block {0} has invalid tips count: {1}")]
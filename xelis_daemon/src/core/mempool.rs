@@ -4,7 +4,8 @@ use super::{
     storage::Storage
 };
 use std::{
-    collections::{HashMap, HashSet},
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap, HashSet},
     sync::Arc,
     mem,
 };
@@ -12,6 +13,7 @@ use serde::{Serialize, Deserialize};
 use indexmap::IndexSet;
 use log::{trace, debug, warn};
 use xelis_common::{
+    api::daemon::TransactionEvictionReason,
     time::{TimestampSeconds, get_current_time_in_seconds},
     crypto::elgamal::Ciphertext,
     network::Network,
@@ -46,7 +48,62 @@ pub struct AccountCache {
     txs: IndexSet<Arc<Hash>>,
     // Expected balances after all txs in this cache
     // This is also used to verify the validity of the TX spendings
-    balances: HashMap<Hash, Ciphertext>
+    balances: HashMap<Hash, Ciphertext>,
+    // fees and size of all txs in this cache, as indexed in the mempool
+    #[serde(skip)]
+    fee_rate: (u128, u128)
+}
+
+// Fee per byte of all the TXs of an owner, used to find the owner to evict
+// Owners with the same fee per byte are ordered by their key
+struct OwnerFeeRate {
+    fees: u128,
+    size: u128,
+    key: PublicKey
+}
+
+impl Ord for OwnerFeeRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // compare fees / size without losing precision
+        (self.fees * other.size).cmp(&(other.fees * self.size))
+            .then_with(|| self.key.as_bytes().cmp(other.key.as_bytes()))
+    }
+}
+
+impl PartialOrd for OwnerFeeRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for OwnerFeeRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OwnerFeeRate {}
+
+// Limits and policies applied to the mempool
+pub struct MempoolConfig {
+    // maximum size in bytes of all txs
+    pub max_size: usize,
+    // maximum count of txs
+    pub max_txs: usize,
+    // time in seconds before a tx is removed, 0 to disable it
+    pub tx_expiration: u64,
+    // minimum fee increase in percent to replace a tx with the same nonce
+    pub replace_fee_increase: u64
+}
+
+// Changes made to the mempool while adding a TX
+#[derive(Default)]
+pub struct MempoolUpdate {
+    // TX replaced by the new one
+    pub replaced: Option<(Arc<Hash>, SortedTx)>,
+    // TXs removed from the mempool with the reason
+    // It may contains the new TX itself if its fee per byte was too low
+    pub evicted: Vec<(Arc<Hash>, SortedTx, TransactionEvictionReason)>
 }
 
 pub struct Mempool {
@@ -55,21 +112,53 @@ pub struct Mempool {
     // store all txs waiting to be included in a block
     txs: HashMap<Arc<Hash>, SortedTx>,
     // store all sender's nonce for faster finding
-    caches: HashMap<PublicKey, AccountCache>
+    caches: HashMap<PublicKey, AccountCache>,
+    // total size in bytes of all txs
+    total_size: usize,
+    // owners ordered by the fee per byte of their txs, lowest first
+    fee_rates: BTreeSet<OwnerFeeRate>,
+    // limits and policies
+    config: MempoolConfig
 }
 
 impl Mempool {
     // Create a new empty mempool
-    pub fn new(network: Network) -> Self {
+    pub fn new(network: Network, config: MempoolConfig) -> Self {
         Mempool {
             mainnet: network.is_mainnet(),
             txs: HashMap::new(),
-            caches: HashMap::new()
+            caches: HashMap::new(),
+            total_size: 0,
+            fee_rates: BTreeSet::new(),
+            config
         }
     }
 
     // All checks are made in Blockchain before calling this function
-    pub async fn add_tx<S: Storage>(&mut self, storage: &S, topoheight: u64, hash: Hash, tx: Arc<Transaction>, size: usize) -> Result<(), BlockchainError> {
+    // If a TX with the same nonce is already present for this sender, it is replaced if the fee is high enough
+    // TXs with the lowest fee per byte are evicted if the mempool limits are reached
+    pub async fn add_tx<S: Storage>(&mut self, storage: &S, topoheight: u64, hash: Hash, tx: Arc<Transaction>, size: usize) -> Result<MempoolUpdate, BlockchainError> {
+        let hash = Arc::new(hash);
+        let mut update = MempoolUpdate::default();
+        let replaced = self.caches.get(tx.get_source())
+            .and_then(|cache| cache.has_tx_with_same_nonce(tx.get_nonce()))
+            .cloned();
+
+        if let Some(replaced) = replaced {
+            self.replace_tx(storage, topoheight, hash.clone(), tx, size, replaced, &mut update).await?;
+        } else {
+            self.add_new_tx(storage, topoheight, hash.clone(), tx, size).await?;
+        }
+
+        for (hash, sorted_tx) in self.evict_lowest_fee_txs() {
+            update.evicted.push((hash, sorted_tx, TransactionEvictionReason::MempoolFull));
+        }
+
+        Ok(update)
+    }
+
+    // Add a TX using the next nonce of its sender
+    async fn add_new_tx<S: Storage>(&mut self, storage: &S, topoheight: u64, hash: Arc<Hash>, tx: Arc<Transaction>, size: usize) -> Result<(), BlockchainError> {
         let mut state = MempoolState::new(&self, storage, topoheight);
         tx.verify(&mut state).await?;
 
@@ -77,38 +166,11 @@ impl Mempool {
             .ok_or_else(|| BlockchainError::AccountNotFound(tx.get_source().as_address(storage.is_mainnet())))?
             .iter().map(|(asset, ciphertext)| (Hash::clone(*asset), ciphertext.clone())).collect();
 
-        let hash = Arc::new(hash);
         let nonce = tx.get_nonce();
         // update the cache for this owner
-        let mut must_update = true;
         if let Some(cache) = self.caches.get_mut(tx.get_source()) {
-            // delete the TX if its in the range of already tracked nonces
             trace!("Cache found for owner {} with nonce range {}-{}, nonce = {}", tx.get_source().as_address(self.mainnet), cache.get_min(), cache.get_max(), nonce);
-
-            // Support the case where the nonce is already used in cache
-            // If a user want to cancel its TX, he can just resend a TX with same nonce and higher fee
-            // NOTE: This is not possible anymore, disabled in blockchain function
-            if nonce >= cache.get_min() && nonce <= cache.get_max() {
-                trace!("nonce {} is in range {}-{}", nonce, cache.get_min(), cache.get_max());
-                // because it's based on order and we may have the same order
-                let index = ((nonce - cache.get_min()) % (cache.get_max() + 1 - cache.get_min())) as usize;
-                cache.txs.insert(hash.clone());
-                must_update = false;
-
-                if let Some(tx_hash) = cache.txs.swap_remove_index(index) {
-                    trace!("TX {} with same nonce found in cache, removing it from sorted txs", tx_hash);
-                    // remove the tx hash from sorted txs
-                    if self.txs.remove(&tx_hash).is_none() {
-                        warn!("TX {} not found in mempool while deleting collision with {}", tx_hash, hash);
-                    }
-                } else {
-                    warn!("No TX found in cache for nonce {} while adding {}", nonce, hash);
-                }
-            }
-
-            if must_update {
-                cache.update(nonce, hash.clone());
-            }
+            cache.update(nonce, hash.clone());
             // Update re-computed balances
             cache.set_balances(balances);
         } else {
@@ -120,11 +182,13 @@ impl Mempool {
                 max: nonce,
                 min: nonce,
                 txs,
-                balances
+                balances,
+                fee_rate: (0, 0)
             };
             self.caches.insert(tx.get_source().clone(), cache);
         }
 
+        let key = tx.get_source().clone();
         let sorted_tx = SortedTx {
             size,
             first_seen: get_current_time_in_seconds(),
@@ -132,15 +196,264 @@ impl Mempool {
         };
 
         // insert in map
-        self.txs.insert(hash, sorted_tx);
+        self.insert_sorted_tx(hash, sorted_tx);
+        self.update_fee_rate(&key);
+
+        Ok(())
+    }
+
+    // Replace the TX having the same nonce as the new one
+    // All the TXs with a higher nonce depend on the replaced one and are verified again
+    // The first one that is not valid anymore is evicted with all its next TXs
+    async fn replace_tx<S: Storage>(&mut self, storage: &S, topoheight: u64, hash: Arc<Hash>, tx: Arc<Transaction>, size: usize, replaced: Arc<Hash>, update: &mut MempoolUpdate) -> Result<(), BlockchainError> {
+        let required_fee = self.get_replacement_fee(self.get_sorted_tx(&replaced)?.get_fee());
+        if tx.get_fee() < required_fee {
+            return Err(BlockchainError::TxReplacementUnderpriced(hash.as_ref().clone(), replaced.as_ref().clone(), tx.get_fee(), required_fee))
+        }
+
+        let key = tx.get_source().clone();
+        let nonce = tx.get_nonce();
+        debug!("Replacing TX {} by {} for owner {} at nonce {}", replaced, hash, key.as_address(self.mainnet), nonce);
+
+        // Split the TXs of this owner around the replaced one
+        let mut previous = Vec::new();
+        let mut dependents = Vec::new();
+        if let Some(cache) = self.caches.get(&key) {
+            for tx_hash in cache.txs.iter() {
+                let sorted_tx = self.get_sorted_tx(tx_hash)?;
+                let tx_nonce = sorted_tx.get_tx().get_nonce();
+                if tx_nonce < nonce {
+                    previous.push((Arc::clone(tx_hash), Arc::clone(sorted_tx.get_tx())));
+                } else if tx_nonce > nonce {
+                    dependents.push((Arc::clone(tx_hash), Arc::clone(sorted_tx.get_tx())));
+                }
+            }
+        }
+
+        // Remove the cache, so the state is built from the storage only
+        let mut cache = self.caches.remove(&key).ok_or_else(|| BlockchainError::TxNotFound(replaced.as_ref().clone()))?;
+        let (res, balances) = {
+            let mut state = MempoolState::new(&self, storage, topoheight);
+            let res = Self::verify_replacement(&mut state, &previous, &tx, &dependents).await;
+            let balances: HashMap<Hash, Ciphertext> = state.get_sender_balances(&key)
+                .map(|balances| balances.into_iter().map(|(asset, ciphertext)| (asset.clone(), ciphertext)).collect())
+                .unwrap_or_default();
+            (res, balances)
+        };
+
+        let valid = match res {
+            Ok(valid) => valid,
+            Err(e) => {
+                // Restore the previous state
+                self.caches.insert(key, cache);
+                return Err(e)
+            }
+        };
+
+        // Rebuild the cache with the new TX and its valid dependents
+        let mut txs = IndexSet::with_capacity(previous.len() + 1 + valid);
+        txs.extend(previous.into_iter().map(|(hash, _)| hash));
+        txs.insert(hash.clone());
+        let invalid = dependents.split_off(valid);
+        txs.extend(dependents.into_iter().map(|(hash, _)| hash));
+
+        cache.max = nonce + valid as u64;
+        cache.txs = txs;
+        cache.set_balances(balances);
+        self.caches.insert(key.clone(), cache);
+
+        if let Some(sorted_tx) = self.remove_sorted_tx(&replaced) {
+            update.replaced = Some((replaced, sorted_tx));
+        }
+
+        for (tx_hash, _) in invalid {
+            debug!("TX {} is not valid anymore after replacement, evicting it", tx_hash);
+            if let Some(sorted_tx) = self.remove_sorted_tx(&tx_hash) {
+                update.evicted.push((tx_hash, sorted_tx, TransactionEvictionReason::Invalid));
+            }
+        }
+
+        let sorted_tx = SortedTx {
+            size,
+            first_seen: get_current_time_in_seconds(),
+            tx
+        };
+        self.insert_sorted_tx(hash, sorted_tx);
+        self.update_fee_rate(&key);
+
+        Ok(())
+    }
+
+    // Minimum fee required for a TX to replace a pending one
+    // The new fee must always be strictly higher
+    pub fn get_replacement_fee(&self, old_fee: u64) -> u64 {
+        old_fee.saturating_add((old_fee.saturating_mul(self.config.replace_fee_increase) / 100).max(1))
+    }
+
+    // Verify a TX as it would be when added, without modifying the mempool
+    // If a pending TX has the same nonce, the TX is verified as its replacement
+    pub async fn simulate_tx<'a, S: Storage>(&'a self, storage: &'a S, topoheight: u64, hash: &Hash, tx: &'a Transaction) -> Result<(), BlockchainError> {
+        let key = tx.get_source();
+        let nonce = tx.get_nonce();
+        let cache = self.caches.get(key);
+        match cache.and_then(|cache| cache.has_tx_with_same_nonce(nonce)) {
+            Some(replaced) => {
+                let required_fee = self.get_replacement_fee(self.get_sorted_tx(replaced)?.get_fee());
+                if tx.get_fee() < required_fee {
+                    return Err(BlockchainError::TxReplacementUnderpriced(hash.clone(), replaced.as_ref().clone(), tx.get_fee(), required_fee))
+                }
+
+                // previous TXs were already verified, but they are needed to build the state
+                let mut state = MempoolState::new_without_cache_for(self, storage, topoheight, key);
+                for tx_hash in cache.iter().flat_map(|cache| cache.txs.iter()) {
+                    let previous_tx = self.get_sorted_tx(tx_hash)?.get_tx();
+                    if previous_tx.get_nonce() < nonce {
+                        previous_tx.verify(&mut state).await?;
+                    }
+                }
+                tx.verify(&mut state).await?;
+            },
+            None => {
+                let mut state = MempoolState::new(self, storage, topoheight);
+                tx.verify(&mut state).await?;
+            }
+        }
 
         Ok(())
     }
 
+    // Verify a TX relayed through the Dandelion stem, without adding it to the mempool
+    // The previous TXs of its source still in the stem are applied first, in nonce order
+    pub async fn verify_stem_tx<'a, S: Storage>(&'a self, storage: &'a S, topoheight: u64, previous: &'a [Arc<Transaction>], tx: &'a Transaction) -> Result<(), BlockchainError> {
+        let mut state = MempoolState::new(self, storage, topoheight);
+        for previous_tx in previous.iter().filter(|previous_tx| previous_tx.get_nonce() < tx.get_nonce()) {
+            previous_tx.verify(&mut state).await?;
+        }
+
+        tx.verify(&mut state).await
+    }
+
+    // Verify the TXs of an owner with the replacing TX
+    // Returns how many dependents TXs are still valid, in nonce order
+    async fn verify_replacement<'a, S: Storage>(state: &mut MempoolState<'a, S>, previous: &'a [(Arc<Hash>, Arc<Transaction>)], tx: &'a Arc<Transaction>, dependents: &'a [(Arc<Hash>, Arc<Transaction>)]) -> Result<usize, BlockchainError> {
+        // previous TXs were already verified, but they are needed to build the state
+        for (_, previous_tx) in previous {
+            previous_tx.verify(state).await?;
+        }
+
+        tx.verify(state).await?;
+
+        let mut valid = 0;
+        for (tx_hash, dependent_tx) in dependents {
+            if let Err(e) = dependent_tx.verify(state).await {
+                debug!("Dependent TX {} is not valid anymore: {}", tx_hash, e);
+                break;
+            }
+            valid += 1;
+        }
+
+        Ok(valid)
+    }
+
+    // Evict the TXs of the owners having the lowest fee per byte until the mempool limits are respected
+    // All the TXs of an owner are evicted together as they depend on each other
+    fn evict_lowest_fee_txs(&mut self) -> Vec<(Arc<Hash>, SortedTx)> {
+        let mut evicted = Vec::new();
+        while self.total_size > self.config.max_size || self.txs.len() > self.config.max_txs {
+            let Some(OwnerFeeRate { key, .. }) = self.fee_rates.pop_first() else {
+                break;
+            };
+
+            debug!("Mempool is full, evicting all TXs from owner {}", key.as_address(self.mainnet));
+            if let Some(cache) = self.caches.remove(&key) {
+                for hash in cache.txs {
+                    if let Some(sorted_tx) = self.remove_sorted_tx(&hash) {
+                        evicted.push((hash, sorted_tx));
+                    }
+                }
+            }
+        }
+
+        evicted
+    }
+
+    // Remove all TXs that stayed for too long in mempool
+    // All the TXs of an owner are removed together as they depend on each other
+    pub fn remove_expired_txs(&mut self) -> Vec<(Arc<Hash>, SortedTx)> {
+        let mut expired = Vec::new();
+        if self.config.tx_expiration == 0 {
+            return expired
+        }
+
+        let now = get_current_time_in_seconds();
+        let keys: Vec<PublicKey> = self.caches.iter()
+            .filter(|(_, cache)| cache.txs.iter().any(|hash| {
+                self.txs.get(hash).map_or(false, |tx| tx.get_first_seen() + self.config.tx_expiration <= now)
+            }))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in keys {
+            debug!("Removing expired TXs from owner {}", key.as_address(self.mainnet));
+            if let Some(cache) = self.remove_cache(&key) {
+                for hash in cache.txs {
+                    if let Some(sorted_tx) = self.remove_sorted_tx(&hash) {
+                        expired.push((hash, sorted_tx));
+                    }
+                }
+            }
+        }
+
+        expired
+    }
+
+    // Compute again the fee per byte of an owner after its TXs changed
+    fn update_fee_rate(&mut self, key: &PublicKey) {
+        let Some(cache) = self.caches.get_mut(key) else {
+            return;
+        };
+
+        let (fees, size) = cache.fee_rate;
+        if size != 0 {
+            self.fee_rates.remove(&OwnerFeeRate { fees, size, key: key.clone() });
+        }
+
+        let (fees, size) = cache.txs.iter()
+            .filter_map(|hash| self.txs.get(hash))
+            .fold((0u128, 0u128), |(fees, size), tx| (fees + tx.get_fee() as u128, size + tx.get_size() as u128));
+        cache.fee_rate = (fees, size);
+        if size != 0 {
+            self.fee_rates.insert(OwnerFeeRate { fees, size, key: key.clone() });
+        }
+    }
+
+    // Remove the cache of an owner from the fee index
+    fn remove_cache(&mut self, key: &PublicKey) -> Option<AccountCache> {
+        let cache = self.caches.remove(key)?;
+        let (fees, size) = cache.fee_rate;
+        self.fee_rates.remove(&OwnerFeeRate { fees, size, key: key.clone() });
+        Some(cache)
+    }
+
+    // Insert a TX and track its size
+    fn insert_sorted_tx(&mut self, hash: Arc<Hash>, sorted_tx: SortedTx) {
+        self.total_size += sorted_tx.get_size();
+        if let Some(previous) = self.txs.insert(hash, sorted_tx) {
+            self.total_size -= previous.get_size();
+        }
+    }
+
+    // Remove a TX and untrack its size
+    fn remove_sorted_tx(&mut self, hash: &Hash) -> Option<SortedTx> {
+        let sorted_tx = self.txs.remove(hash)?;
+        self.total_size -= sorted_tx.get_size();
+        Some(sorted_tx)
+    }
+
     // Remove a TX using its hash from mempool
     // This will recalculate the cache bounds
     pub fn remove_tx(&mut self, hash: &Hash) -> Result<(), BlockchainError> {
-        let tx = self.txs.remove(hash).ok_or_else(|| BlockchainError::TxNotFound(hash.clone()))?;
+        let tx = self.remove_sorted_tx(hash).ok_or_else(|| BlockchainError::TxNotFound(hash.clone()))?;
         // remove the tx hash from sorted txs
         let key = tx.get_tx().get_source();
         let mut delete = false;
@@ -192,7 +505,9 @@ impl Mempool {
 
         if delete {
             trace!("Removing empty nonce cache for owner {}", key.as_address(self.mainnet));
-            self.caches.remove(key);
+            self.remove_cache(key);
+        } else {
+            self.update_fee_rate(key);
         }
 
         Ok(())
@@ -256,10 +571,17 @@ impl Mempool {
         self.txs.len()
     }
 
+    // Returns the size in bytes of all txs in mempool
+    pub fn get_total_size(&self) -> usize {
+        self.total_size
+    }
+
     // Clear all txs and caches in mempool
     pub fn clear(&mut self) {
         self.txs.clear();
         self.caches.clear();
+        self.fee_rates.clear();
+        self.total_size = 0;
     }
 
     // delete all old txs not compatible anymore with current state of chain
@@ -301,7 +623,7 @@ impl Mempool {
                 // We can delete all these TXs as they got automatically orphaned
                 // Because of the suite being broked
                 for hash in cache.txs.iter() {
-                    if self.txs.remove(hash).is_none() {
+                    if self.remove_sorted_tx(hash).is_none() {
                         warn!("TX {} not found in mempool while deleting", hash);
                     }
                 }
@@ -405,7 +727,7 @@ impl Mempool {
                 // now delete all necessary txs
                 for hash in hashes {
                     debug!("Deleting TX {} for owner {}", hash, key.as_address(self.mainnet));
-                    if let Some(sorted_tx) = self.txs.remove(&hash) {
+                    if let Some(sorted_tx) = self.remove_sorted_tx(&hash) {
                         deleted_transactions.push((hash, sorted_tx));
                     } else {
                         // This should never happen, but better to put a warning here
@@ -421,10 +743,24 @@ impl Mempool {
             }
         }
 
+        // Index again the fee per byte of the owners left
+        self.fee_rates.clear();
+        let keys: Vec<PublicKey> = self.caches.keys().cloned().collect();
+        for key in keys {
+            self.update_fee_rate(&key);
+        }
+
         deleted_transactions
     }
 }
 
+impl MempoolUpdate {
+    // Check if a TX was evicted during this update
+    pub fn is_evicted(&self, hash: &Hash) -> bool {
+        self.evicted.iter().any(|(evicted, _, _)| evicted.as_ref() == hash)
+    }
+}
+
 impl SortedTx {
     pub fn get_tx(&self) -> &Arc<Transaction> {
         &self.tx
@@ -508,4 +844,137 @@ impl AccountCache {
         let index = ((nonce - self.min) % (self.max + 1 - self.min)) as usize;
         self.txs.get_index(index)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use tokio::time::sleep;
+    use xelis_common::{
+        config::COIN_VALUE,
+        crypto::Hashable,
+        serializer::Serializer,
+        transaction::builder::FeeBuilder
+    };
+    use crate::core::{
+        blockchain::tests::{create_account, start_node, stop_node, temp_dir_path},
+        storage::SledStorage
+    };
+    use super::*;
+
+    fn create_mempool(max_size: usize, tx_expiration: u64) -> Mempool {
+        Mempool::new(Network::Dev, MempoolConfig {
+            max_size,
+            max_txs: usize::MAX,
+            tx_expiration,
+            replace_fee_increase: 10
+        })
+    }
+
+    async fn add_tx(mempool: &mut Mempool, storage: &SledStorage, tx: &Transaction) -> Result<MempoolUpdate, BlockchainError> {
+        mempool.add_tx(storage, 0, tx.hash(), Arc::new(tx.clone()), tx.size()).await
+    }
+
+    #[tokio::test]
+    async fn test_replace_by_fee() {
+        let dir_path = temp_dir_path("mempool-rbf");
+        let blockchain = start_node::<SledStorage>(&dir_path).await;
+        let mut account = create_account(&[&blockchain], COIN_VALUE).await;
+        let mut replacing = account.clone();
+        let first = account.build_burn_tx_with_fee(FeeBuilder::Value(100_000));
+        let dependent = account.build_burn_tx_with_fee(FeeBuilder::Value(100_000));
+
+        let storage = blockchain.get_storage().read().await;
+        let mut mempool = create_mempool(usize::MAX, 0);
+        add_tx(&mut mempool, &storage, &first).await.unwrap();
+        add_tx(&mut mempool, &storage, &dependent).await.unwrap();
+
+        // The fee must be increased by at least 10%
+        assert_eq!(mempool.get_replacement_fee(100_000), 110_000);
+        let underpriced = replacing.clone().build_burn_tx_with_fee(FeeBuilder::Value(109_999));
+        assert!(matches!(add_tx(&mut mempool, &storage, &underpriced).await, Err(BlockchainError::TxReplacementUnderpriced(..))));
+        assert!(mempool.contains_tx(&first.hash()));
+
+        let replacement = replacing.build_burn_tx_with_fee(FeeBuilder::Value(110_000));
+        let update = add_tx(&mut mempool, &storage, &replacement).await.unwrap();
+        assert_eq!(update.replaced.as_ref().map(|(hash, _)| hash.as_ref().clone()), Some(first.hash()));
+        // The next TX was built on the balance left by the replaced one
+        assert!(update.is_evicted(&dependent.hash()));
+        assert!(mempool.contains_tx(&replacement.hash()));
+        assert_eq!(mempool.size(), 1);
+        assert_eq!(mempool.get_total_size(), replacement.size());
+
+        drop(storage);
+        stop_node(&blockchain, &dir_path).await;
+    }
+
+    #[test]
+    fn test_replacement_fee() {
+        let mempool = create_mempool(usize::MAX, 0);
+        assert_eq!(mempool.get_replacement_fee(1000), 1100);
+        // Always strictly higher, even when the increase rounds to zero
+        assert_eq!(mempool.get_replacement_fee(0), 1);
+        assert_eq!(mempool.get_replacement_fee(5), 6);
+        assert_eq!(mempool.get_replacement_fee(u64::MAX), u64::MAX);
+    }
+
+    #[tokio::test]
+    async fn test_mempool_full_eviction() {
+        let dir_path = temp_dir_path("mempool-eviction");
+        let blockchain = start_node::<SledStorage>(&dir_path).await;
+        let mut txs = Vec::new();
+        for fee in [200_000, 300_000, 100_000, 400_000] {
+            let mut account = create_account(&[&blockchain], COIN_VALUE).await;
+            txs.push(account.build_burn_tx_with_fee(FeeBuilder::Value(fee)));
+        }
+
+        // Only two TXs fit in it
+        let max_size = txs.iter().map(|tx| tx.size()).max().unwrap() * 2;
+        let storage = blockchain.get_storage().read().await;
+        let mut mempool = create_mempool(max_size, 0);
+        for tx in &txs[..2] {
+            let update = add_tx(&mut mempool, &storage, tx).await.unwrap();
+            assert!(update.evicted.is_empty());
+        }
+
+        // A TX paying less than the others is rejected
+        let update = add_tx(&mut mempool, &storage, &txs[2]).await.unwrap();
+        assert_eq!(update.evicted.len(), 1);
+        assert!(update.is_evicted(&txs[2].hash()));
+
+        // A TX paying more evicts the one paying the least
+        let update = add_tx(&mut mempool, &storage, &txs[3]).await.unwrap();
+        assert_eq!(update.evicted.len(), 1);
+        assert!(update.is_evicted(&txs[0].hash()));
+        assert!(mempool.contains_tx(&txs[1].hash()) && mempool.contains_tx(&txs[3].hash()));
+        assert!(mempool.get_total_size() <= max_size);
+
+        drop(storage);
+        stop_node(&blockchain, &dir_path).await;
+    }
+
+    #[tokio::test]
+    async fn test_mempool_expiration() {
+        let dir_path = temp_dir_path("mempool-expiration");
+        let blockchain = start_node::<SledStorage>(&dir_path).await;
+        let mut account = create_account(&[&blockchain], COIN_VALUE).await;
+        let first = account.build_burn_tx();
+        let second = account.build_burn_tx();
+
+        let storage = blockchain.get_storage().read().await;
+        let mut mempool = create_mempool(usize::MAX, 1);
+        add_tx(&mut mempool, &storage, &first).await.unwrap();
+        add_tx(&mut mempool, &storage, &second).await.unwrap();
+
+        // All the TXs of the owner are removed together
+        sleep(Duration::from_secs(2)).await;
+        let expired = mempool.remove_expired_txs();
+        assert_eq!(expired.len(), 2);
+        assert_eq!(mempool.size(), 0);
+        assert_eq!(mempool.get_total_size(), 0);
+        assert!(mempool.get_cache_for(first.get_source()).is_none());
+
+        drop(storage);
+        stop_node(&blockchain, &dir_path).await;
+    }
+}
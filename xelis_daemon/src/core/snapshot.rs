@@ -0,0 +1,441 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    sync::Arc
+};
+use log::{debug, info, trace};
+use xelis_common::{
+    account::{BalanceType, CiphertextCache, VersionedNonce},
+    asset::AssetWithData,
+    block::BlockHeader,
+    crypto::{hash, Hash, Hashable, PublicKey, HASH_SIZE},
+    immutable::Immutable,
+    network::Network,
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    transaction::Transaction
+};
+use crate::{
+    config::{MAX_BLOCK_SIZE, PRUNE_SAFETY_LIMIT},
+    core::{error::BlockchainError, storage::Storage},
+    p2p::packet::bootstrap_chain::{BlockMetadata, MAX_ITEMS_PER_PAGE}
+};
+
+// A snapshot contains the same chain state as the fast sync steps (see p2p/packet/bootstrap_chain.rs)
+// but in a single file, so a node can be bootstrapped without any trusted peer
+//
+// Layout:
+// magic | version | header (network, stable topoheight, stable height, stable hash)
+// | assets | accounts (key and nonce, then its balances) | blocks (metadata, header, txs)
+// | checksum
+//
+// Each element is written in its own frame (u32 size | bytes) and each list ends with an empty frame,
+// so a snapshot is written and read without having it fully in memory
+// The checksum is chained on each frame: hash(previous checksum | frame), starting from the hash of the magic and version
+pub const SNAPSHOT_MAGIC: &[u8; 8] = b"XELISNAP";
+// Increase it each time the layout is changed
+pub const SNAPSHOT_VERSION: u8 = 2;
+
+// The biggest frame is a block with its transactions and its metadata
+const MAX_FRAME_SIZE: usize = MAX_BLOCK_SIZE + 1024;
+
+fn chain_checksum(checksum: &Hash, frame: &[u8]) -> Hash {
+    let mut bytes = Vec::with_capacity(HASH_SIZE + frame.len());
+    bytes.extend_from_slice(checksum.as_bytes());
+    bytes.extend_from_slice(frame);
+    hash(&bytes)
+}
+
+// Write the frames of a snapshot while computing its checksum
+struct SnapshotWriter<W: Write> {
+    inner: W,
+    checksum: Hash
+}
+
+impl<W: Write> SnapshotWriter<W> {
+    fn new(mut inner: W) -> Result<Self, BlockchainError> {
+        let mut prefix = SNAPSHOT_MAGIC.to_vec();
+        prefix.push(SNAPSHOT_VERSION);
+        inner.write_all(&prefix)?;
+
+        Ok(Self {
+            inner,
+            checksum: hash(&prefix)
+        })
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<(), BlockchainError> {
+        self.inner.write_all(&(frame.len() as u32).to_be_bytes())?;
+        self.inner.write_all(frame)?;
+        self.checksum = chain_checksum(&self.checksum, frame);
+        Ok(())
+    }
+
+    fn write<T: Serializer>(&mut self, value: &T) -> Result<(), BlockchainError> {
+        self.write_frame(&value.to_bytes())
+    }
+
+    // Mark the end of a list
+    fn end_list(&mut self) -> Result<(), BlockchainError> {
+        self.write_frame(&[])
+    }
+
+    // Write the checksum, returns it
+    fn finish(mut self) -> Result<Hash, BlockchainError> {
+        self.inner.write_all(self.checksum.as_bytes())?;
+        self.inner.flush()?;
+        Ok(self.checksum)
+    }
+}
+
+// Read the frames of a snapshot while computing its checksum
+struct SnapshotReader<R: Read> {
+    inner: R,
+    checksum: Hash,
+    buffer: Vec<u8>
+}
+
+impl<R: Read> SnapshotReader<R> {
+    fn new(mut inner: R) -> Result<Self, BlockchainError> {
+        let mut prefix = [0; SNAPSHOT_MAGIC.len() + 1];
+        read_exact(&mut inner, &mut prefix)?;
+        if prefix[..SNAPSHOT_MAGIC.len()] != *SNAPSHOT_MAGIC {
+            return Err(BlockchainError::InvalidSnapshot)
+        }
+
+        let version = prefix[SNAPSHOT_MAGIC.len()];
+        if version != SNAPSHOT_VERSION {
+            return Err(BlockchainError::UnsupportedSnapshotVersion(version))
+        }
+
+        Ok(Self {
+            inner,
+            checksum: hash(&prefix),
+            buffer: Vec::new()
+        })
+    }
+
+    // Read the next frame and parse it entirely
+    // Returns None at the end of a list
+    fn read_frame<T, F: FnOnce(&mut Reader) -> Result<T, ReaderError>>(&mut self, parse: F) -> Result<Option<T>, BlockchainError> {
+        let mut size = [0; 4];
+        read_exact(&mut self.inner, &mut size)?;
+        let size = u32::from_be_bytes(size) as usize;
+        if size > MAX_FRAME_SIZE {
+            return Err(BlockchainError::InvalidSnapshot)
+        }
+
+        self.buffer.resize(size, 0);
+        read_exact(&mut self.inner, &mut self.buffer)?;
+        self.checksum = chain_checksum(&self.checksum, &self.buffer);
+        if size == 0 {
+            return Ok(None)
+        }
+
+        let mut reader = Reader::new(&self.buffer);
+        let value = parse(&mut reader)?;
+        if reader.size() != 0 {
+            return Err(BlockchainError::InvalidSnapshot)
+        }
+
+        Ok(Some(value))
+    }
+
+    fn read<T: Serializer>(&mut self) -> Result<Option<T>, BlockchainError> {
+        self.read_frame(T::read)
+    }
+
+    // Verify the checksum at the end of the snapshot
+    fn finish(mut self) -> Result<(), BlockchainError> {
+        let mut checksum = [0; HASH_SIZE];
+        read_exact(&mut self.inner, &mut checksum)?;
+        if checksum != *self.checksum.as_bytes() {
+            return Err(BlockchainError::InvalidSnapshotChecksum)
+        }
+
+        // Nothing is expected after it
+        if self.inner.read(&mut [0; 1])? != 0 {
+            return Err(BlockchainError::InvalidSnapshot)
+        }
+
+        Ok(())
+    }
+}
+
+// A truncated snapshot is invalid
+fn read_exact<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<(), BlockchainError> {
+    reader.read_exact(buffer).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => BlockchainError::InvalidSnapshot,
+        _ => e.into()
+    })
+}
+
+// Write the chain state at the requested stable topoheight into a snapshot file
+pub async fn export_snapshot<S: Storage>(storage: &S, network: &Network, topoheight: u64, height: u64, path: &str) -> Result<(), BlockchainError> {
+    let stable_hash = storage.get_hash_at_topo_height(topoheight).await?;
+    info!("Exporting snapshot at topoheight {} ({})", topoheight, stable_hash);
+
+    let mut writer = SnapshotWriter::new(BufWriter::new(File::create(path)?))?;
+    let mut header = Writer::new();
+    network.write(&mut header);
+    header.write_u64(&topoheight);
+    header.write_u64(&height);
+    header.write_hash(&stable_hash);
+    writer.write_frame(header.as_bytes())?;
+
+    let mut count = 0;
+    loop {
+        let assets = storage.get_partial_assets(MAX_ITEMS_PER_PAGE, count, 0, topoheight).await?;
+        for asset in assets.iter() {
+            writer.write(asset)?;
+        }
+
+        count += assets.len();
+        if assets.len() < MAX_ITEMS_PER_PAGE {
+            break;
+        }
+    }
+    writer.end_list()?;
+    debug!("Exported {} assets", count);
+
+    let mut count = 0;
+    loop {
+        let keys = storage.get_partial_keys(MAX_ITEMS_PER_PAGE, count, 0, topoheight).await?;
+        for key in keys.iter() {
+            let nonce = storage.get_nonce_at_maximum_topoheight(key, topoheight).await?.map(|(_, v)| v.get_nonce()).unwrap_or(0);
+            let mut account = Writer::new();
+            key.write(&mut account);
+            account.write_u64(&nonce);
+            writer.write_frame(account.as_bytes())?;
+
+            for asset in storage.get_assets_for(key).await? {
+                if let Some((_, version)) = storage.get_balance_at_maximum_topoheight(key, &asset, topoheight).await? {
+                    let (balance, output_balance, balance_type, _) = version.consume();
+                    let mut entry = Writer::new();
+                    entry.write_hash(&asset);
+                    balance.write(&mut entry);
+                    output_balance.write(&mut entry);
+                    balance_type.write(&mut entry);
+                    writer.write_frame(entry.as_bytes())?;
+                }
+            }
+            writer.end_list()?;
+        }
+
+        count += keys.len();
+        if keys.len() < MAX_ITEMS_PER_PAGE {
+            break;
+        }
+    }
+    writer.end_list()?;
+    debug!("Exported {} accounts", count);
+
+    // Same range as the fast sync: last N blocks until the stable topoheight
+    let lower = match storage.get_pruned_topoheight().await? {
+        Some(pruned_topoheight) if topoheight.saturating_sub(PRUNE_SAFETY_LIMIT) <= pruned_topoheight => pruned_topoheight + 1,
+        _ => topoheight.saturating_sub(PRUNE_SAFETY_LIMIT)
+    };
+    debug!("Exporting blocks from topoheight {} to {}", lower, topoheight);
+    for topoheight in (lower..=topoheight).rev() {
+        let hash = storage.get_hash_at_topo_height(topoheight).await?;
+        trace!("Exporting block {} at topoheight {}", hash, topoheight);
+        let metadata = BlockMetadata {
+            supply: storage.get_supply_at_topo_height(topoheight).await?,
+            reward: storage.get_block_reward_at_topo_height(topoheight)?,
+            difficulty: storage.get_difficulty_for_block_hash(&hash).await?,
+            cumulative_difficulty: storage.get_cumulative_difficulty_for_block_hash(&hash).await?,
+            p: storage.get_estimated_covariance_for_block_hash(&hash).await?,
+            hash
+        };
+
+        let mut block = Writer::new();
+        metadata.write(&mut block);
+        let header = storage.get_block_header_by_hash(&metadata.hash).await?;
+        header.write(&mut block);
+        for tx_hash in header.get_txs_hashes() {
+            storage.get_transaction(tx_hash).await?.write(&mut block);
+        }
+        writer.write_frame(block.as_bytes())?;
+    }
+    writer.end_list()?;
+
+    let checksum = writer.finish()?;
+    info!("Snapshot exported to {} with checksum {}", path, checksum);
+
+    Ok(())
+}
+
+// Load a snapshot file into an empty storage
+// All the changes are reverted if the snapshot can't be fully imported
+pub async fn import_snapshot<S: Storage>(storage: &mut S, network: &Network, path: &str) -> Result<(), BlockchainError> {
+    if storage.has_blocks().await {
+        return Err(BlockchainError::StorageNotEmpty)
+    }
+
+    info!("Importing snapshot from {}", path);
+    let mut reader = SnapshotReader::new(BufReader::new(File::open(path)?))?;
+    let (snapshot_network, stable_topoheight, stable_height, stable_hash) = reader.read_frame(|reader| {
+        Ok((Network::read(reader)?, reader.read_u64()?, reader.read_u64()?, reader.read_hash()?))
+    })?.ok_or(BlockchainError::InvalidSnapshot)?;
+
+    if snapshot_network != *network {
+        return Err(BlockchainError::InvalidNetwork)
+    }
+    info!("Snapshot is at topoheight {} and height {} ({})", stable_topoheight, stable_height, stable_hash);
+
+    // Nothing is written on disk until the checksum is verified
+    storage.start_commit_point().await?;
+    let res = import_snapshot_content(storage, reader, stable_topoheight, stable_height, stable_hash).await;
+    storage.end_commit_point(res.is_ok()).await?;
+    res?;
+
+    info!("Snapshot imported");
+    Ok(())
+}
+
+// Write all the snapshot content in storage, same as the fast sync does
+async fn import_snapshot_content<S: Storage, R: Read>(storage: &mut S, mut reader: SnapshotReader<R>, stable_topoheight: u64, stable_height: u64, stable_hash: Hash) -> Result<(), BlockchainError> {
+    let mut count = 0;
+    while let Some(asset) = reader.read::<AssetWithData>()? {
+        let (asset, data) = asset.consume();
+        storage.add_asset(&asset, data).await?;
+        count += 1;
+    }
+    debug!("Imported {} assets", count);
+
+    let mut count = 0;
+    while let Some((key, nonce)) = reader.read_frame(|reader| Ok((PublicKey::read(reader)?, reader.read_u64()?)))? {
+        storage.set_last_nonce_to(&key, stable_topoheight, &VersionedNonce::new(nonce, None)).await?;
+        storage.set_account_registration_topoheight(&key, stable_topoheight).await?;
+
+        while let Some((asset, balance, output_balance, balance_type)) = reader.read_frame(|reader| {
+            Ok((reader.read_hash()?, CiphertextCache::read(reader)?, Option::<CiphertextCache>::read(reader)?, BalanceType::read(reader)?))
+        })? {
+            let mut versioned_balance = storage.get_new_versioned_balance(&key, &asset, stable_topoheight).await?;
+            versioned_balance.set_balance(balance);
+            versioned_balance.set_output_balance(output_balance);
+            versioned_balance.set_balance_type(balance_type);
+            versioned_balance.set_previous_topoheight(None);
+            storage.set_last_balance_to(&key, &asset, stable_topoheight, &versioned_balance).await?;
+        }
+        count += 1;
+    }
+    debug!("Imported {} accounts", count);
+
+    let mut topoheight = stable_topoheight;
+    let mut count = 0;
+    while let Some((metadata, header, txs)) = reader.read_frame(|reader| {
+        let metadata = BlockMetadata::read(reader)?;
+        let header = BlockHeader::read(reader)?;
+        let txs = header.get_txs_hashes()
+            .iter()
+            .map(|_| Transaction::read(reader))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((metadata, header, txs))
+    })? {
+        // Blocks are written from the stable topoheight down to the genesis at most
+        if count > stable_topoheight {
+            return Err(BlockchainError::InvalidSnapshot)
+        }
+        topoheight = stable_topoheight - count;
+
+        if header.hash() != metadata.hash {
+            return Err(BlockchainError::InvalidSnapshot)
+        }
+
+        let mut block_txs = Vec::with_capacity(txs.len());
+        for (tx, tx_hash) in txs.into_iter().zip(header.get_txs_hashes()) {
+            if tx.hash() != *tx_hash {
+                return Err(BlockchainError::InvalidSnapshot)
+            }
+            storage.add_block_for_tx(tx_hash, &metadata.hash)?;
+            block_txs.push(Immutable::Owned(tx));
+        }
+
+        trace!("Importing block {} at topoheight {}", metadata.hash, topoheight);
+        storage.set_supply_at_topo_height(topoheight, metadata.supply)?;
+        storage.set_block_reward_at_topo_height(topoheight, metadata.reward)?;
+        storage.set_topo_height_for_block(&metadata.hash, topoheight).await?;
+        storage.set_cumulative_difficulty_for_block_hash(&metadata.hash, metadata.cumulative_difficulty).await?;
+        storage.save_block(Arc::new(header), &block_txs, metadata.difficulty, metadata.p, metadata.hash).await?;
+        count += 1;
+    }
+
+    if count == 0 {
+        return Err(BlockchainError::InvalidSnapshot)
+    }
+    debug!("Imported {} blocks", count);
+
+    reader.finish()?;
+
+    // Everything below the lowest block imported is not available
+    if topoheight > 0 {
+        storage.set_pruned_topoheight(topoheight).await?;
+    }
+    storage.set_top_topoheight(stable_topoheight)?;
+    storage.set_top_height(stable_height)?;
+    storage.store_tips(&HashSet::from([stable_hash]))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{account::VersionedBalance, config::XELIS_ASSET};
+    use crate::core::{
+        blockchain::tests::{mine_blocks, remove_temp_dir, start_node, stop_node, temp_dir_path},
+        storage::{BalanceProvider, BlockProvider, DagOrderProvider, NonceProvider, SledStorage}
+    };
+    use super::*;
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let source_path = temp_dir_path("snapshot-source");
+        let blockchain = start_node::<SledStorage>(&source_path).await;
+        mine_blocks(&blockchain, 5).await;
+
+        let topoheight = blockchain.get_topo_height();
+        let snapshot_path = format!("{}snapshot.bin", source_path);
+        let source = blockchain.get_storage().read().await;
+        export_snapshot(&*source, &Network::Dev, topoheight, blockchain.get_height(), &snapshot_path).await.unwrap();
+
+        let target_path = temp_dir_path("snapshot-target");
+        let mut storage = SledStorage::new(target_path.clone(), None, Network::Dev).unwrap();
+        import_snapshot(&mut storage, &Network::Dev, &snapshot_path).await.unwrap();
+
+        assert_eq!(storage.get_top_topoheight().unwrap(), topoheight);
+        assert_eq!(storage.get_tips().await.unwrap(), source.get_tips().await.unwrap());
+        assert_eq!(storage.get_hash_at_topo_height(topoheight).await.unwrap(), source.get_hash_at_topo_height(topoheight).await.unwrap());
+
+        let keys = source.get_partial_keys(usize::MAX, 0, 0, topoheight).await.unwrap();
+        assert!(!keys.is_empty());
+        assert_eq!(storage.get_partial_keys(usize::MAX, 0, 0, topoheight).await.unwrap(), keys);
+        for key in keys.iter() {
+            let balance = |res: Option<(u64, VersionedBalance)>| res.map(|(_, v)| v.get_balance().to_bytes());
+            assert_eq!(
+                balance(storage.get_balance_at_maximum_topoheight(key, &XELIS_ASSET, topoheight).await.unwrap()),
+                balance(source.get_balance_at_maximum_topoheight(key, &XELIS_ASSET, topoheight).await.unwrap())
+            );
+        }
+
+        // Only an empty storage can import a snapshot
+        assert!(matches!(import_snapshot(&mut storage, &Network::Dev, &snapshot_path).await, Err(BlockchainError::StorageNotEmpty)));
+
+        // Nothing is written from a corrupted snapshot
+        let mut bytes = std::fs::read(&snapshot_path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let corrupted_path = format!("{}corrupted.bin", source_path);
+        std::fs::write(&corrupted_path, bytes).unwrap();
+
+        let corrupted_target_path = temp_dir_path("snapshot-corrupted");
+        let mut corrupted = SledStorage::new(corrupted_target_path.clone(), None, Network::Dev).unwrap();
+        assert!(matches!(import_snapshot(&mut corrupted, &Network::Dev, &corrupted_path).await, Err(BlockchainError::InvalidSnapshotChecksum)));
+        assert!(!corrupted.has_blocks().await);
+
+        drop(source);
+        stop_node(&blockchain, &source_path).await;
+        remove_temp_dir(&target_path);
+        remove_temp_dir(&corrupted_target_path);
+    }
+}
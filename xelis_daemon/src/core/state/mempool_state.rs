@@ -43,6 +43,8 @@ pub struct MempoolState<'a, S: Storage> {
     accounts: HashMap<&'a PublicKey, Account<'a>>,
     // The current topoheight of the chain
     topoheight: u64,
+    // Account for which the mempool cache is not used
+    ignored_cache: Option<&'a PublicKey>,
 }
 
 impl<'a, S: Storage> MempoolState<'a, S> {
@@ -53,6 +55,16 @@ impl<'a, S: Storage> MempoolState<'a, S> {
             receiver_balances: HashMap::new(),
             accounts: HashMap::new(),
             topoheight,
+            ignored_cache: None,
+        }
+    }
+
+    // Same as new, but the state of this account is built from the storage only
+    // This is used to verify a TX replacing one of its pending TXs
+    pub fn new_without_cache_for(mempool: &'a Mempool, storage: &'a S, topoheight: u64, key: &'a PublicKey) -> Self {
+        Self {
+            ignored_cache: Some(key),
+            ..Self::new(mempool, storage, topoheight)
         }
     }
 
@@ -93,7 +105,7 @@ impl<'a, S: Storage> MempoolState<'a, S> {
                 let account = o.into_mut();
                 match account.assets.entry(asset) {
                     Entry::Occupied(entry) => Ok(entry.into_mut()),
-                    Entry::Vacant(entry) => match self.mempool.get_cache_for(key) {
+                    Entry::Vacant(entry) => match self.mempool.get_cache_for(key).filter(|_| self.ignored_cache != Some(key)) {
                         Some(cache) => {
                             if let Some(version) = cache.get_balances().get(asset) {
                                 Ok(entry.insert(version.clone()))
@@ -134,7 +146,7 @@ impl<'a, S: Storage> MempoolState<'a, S> {
     async fn internal_get_account_nonce(&mut self, key: &'a PublicKey) -> Result<u64, BlockchainError> {
         match self.accounts.entry(key) {
             Entry::Occupied(o) => Ok(o.get().nonce),
-            Entry::Vacant(e) => match self.mempool.get_cache_for(key) {
+            Entry::Vacant(e) => match self.mempool.get_cache_for(key).filter(|_| self.ignored_cache != Some(key)) {
                 Some(cache) => Ok(cache.get_next_nonce()),
                 None => {
                     let nonce = self.storage.get_nonce_at_maximum_topoheight(key, self.topoheight).await?
@@ -0,0 +1,146 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    iter::Peekable,
+    marker::PhantomData,
+    vec::IntoIter
+};
+
+// Changes made while a commit point is started, kept in memory until it is ended
+// They are written in a single atomic batch, so a block is either fully applied or not at all
+// All reads must go through them to see the values not written yet
+pub struct PendingChanges<T: Eq + Hash> {
+    // Changes of each tree (or column family), a None value is a deleted key
+    trees: HashMap<T, BTreeMap<Vec<u8>, Option<Vec<u8>>>>
+}
+
+impl<T: Eq + Hash> PendingChanges<T> {
+    pub fn new() -> Self {
+        Self {
+            trees: HashMap::new()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.trees.is_empty()
+    }
+
+    pub fn insert(&mut self, tree: T, key: &[u8], value: Vec<u8>) {
+        self.trees.entry(tree).or_default().insert(key.to_vec(), Some(value));
+    }
+
+    pub fn remove(&mut self, tree: T, key: &[u8]) {
+        self.trees.entry(tree).or_default().insert(key.to_vec(), None);
+    }
+
+    // Returns None if the key was not changed, Some(None) if it was deleted
+    pub fn get(&self, tree: &T, key: &[u8]) -> Option<Option<&[u8]>> {
+        self.trees.get(tree)
+            .and_then(|changes| changes.get(key))
+            .map(|value| value.as_deref())
+    }
+
+    // Copy the changes of a tree with a key starting with the prefix and greater or equal to start
+    // They are copied so the tree can be changed while iterating
+    pub fn scan(&self, tree: &T, start: &[u8], prefix: &[u8]) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+        match self.trees.get(tree) {
+            Some(changes) => changes.range(start.to_vec()..)
+                .take_while(|(key, _)| key.starts_with(prefix))
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            None => Vec::new()
+        }
+    }
+
+    pub fn into_trees(self) -> impl Iterator<Item = (T, BTreeMap<Vec<u8>, Option<Vec<u8>>>)> {
+        self.trees.into_iter()
+    }
+}
+
+// Merge the entries of a tree with its pending changes, both sorted by key
+// A pending change replaces the entry with the same key, deleted keys are skipped
+pub struct MergedIter<I: Iterator, K, V> {
+    inner: Peekable<I>,
+    changes: Peekable<IntoIter<(Vec<u8>, Option<Vec<u8>>)>>,
+    _phantom: PhantomData<(K, V)>
+}
+
+impl<I: Iterator, K, V> MergedIter<I, K, V> {
+    pub fn new(inner: I, changes: Vec<(Vec<u8>, Option<Vec<u8>>)>) -> Self {
+        Self {
+            inner: inner.peekable(),
+            changes: changes.into_iter().peekable(),
+            _phantom: PhantomData
+        }
+    }
+}
+
+impl<I, K, V, E> Iterator for MergedIter<I, K, V>
+where
+    I: Iterator<Item = Result<(K, V), E>>,
+    K: AsRef<[u8]> + From<Vec<u8>>,
+    V: From<Vec<u8>>
+{
+    type Item = Result<(K, V), E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let ordering = match (self.inner.peek(), self.changes.peek()) {
+                (None, None) => return None,
+                (Some(_), None) | (Some(Err(_)), _) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(Ok((key, _))), Some((changed, _))) => key.as_ref().cmp(changed.as_slice())
+            };
+
+            match ordering {
+                Ordering::Less => return self.inner.next(),
+                // Replaced by the pending change
+                Ordering::Equal => {
+                    self.inner.next();
+                },
+                Ordering::Greater => {}
+            }
+
+            if let Some((key, Some(value))) = self.changes.next() {
+                return Some(Ok((key.into(), value.into())))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merged_iter() {
+        let mut changes = PendingChanges::new();
+        changes.insert(0, b"b", b"2".to_vec());
+        changes.insert(0, b"c", b"new".to_vec());
+        changes.remove(0, b"d");
+        changes.insert(0, b"e", b"5".to_vec());
+        changes.insert(1, b"a", b"other".to_vec());
+
+        let entries: Vec<Result<(Vec<u8>, Vec<u8>), ()>> = vec![
+            Ok((b"a".to_vec(), b"1".to_vec())),
+            Ok((b"c".to_vec(), b"3".to_vec())),
+            Ok((b"d".to_vec(), b"4".to_vec()))
+        ];
+        let merged: Vec<(Vec<u8>, Vec<u8>)> = MergedIter::new(entries.into_iter(), changes.scan(&0, b"", b""))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(merged, vec![
+            (b"a".to_vec(), b"1".to_vec()),
+            (b"b".to_vec(), b"2".to_vec()),
+            (b"c".to_vec(), b"new".to_vec()),
+            (b"e".to_vec(), b"5".to_vec())
+        ]);
+
+        assert_eq!(changes.get(&0, b"d"), Some(None));
+        assert_eq!(changes.get(&0, b"a"), None);
+        assert_eq!(changes.scan(&0, b"c", b"").len(), 3);
+        assert_eq!(changes.scan(&0, b"", b"e").len(), 0);
+    }
+}
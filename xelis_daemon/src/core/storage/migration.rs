@@ -0,0 +1,306 @@
+use log::{debug, info, trace, warn};
+use xelis_common::{
+    crypto::{Hash, PublicKey},
+    immutable::Immutable
+};
+use crate::core::error::BlockchainError;
+use super::Storage;
+
+// Current version of the storage layout
+// Increase it and add a new step in `apply_migration_step`
+// each time the layout of the storage is changed
+pub const CURRENT_SCHEMA_VERSION: u64 = 1;
+
+// Show a progress message every N heights copied
+const PROGRESS_INTERVAL: u64 = 1000;
+
+// Verify the schema version of the storage and apply all the migration steps needed
+// A storage without any block is considered as up-to-date
+pub async fn upgrade_schema<S: Storage>(storage: &mut S) -> Result<(), BlockchainError> {
+    if !storage.has_blocks().await {
+        debug!("Empty storage, setting schema version to {}", CURRENT_SCHEMA_VERSION);
+        storage.set_schema_version(CURRENT_SCHEMA_VERSION)?;
+        return Ok(())
+    }
+
+    // No version means that it was created before the schema version was introduced
+    let mut version = storage.get_schema_version()?.unwrap_or(0);
+    if version > CURRENT_SCHEMA_VERSION {
+        return Err(BlockchainError::UnsupportedSchemaVersion(version, CURRENT_SCHEMA_VERSION))
+    }
+
+    while version < CURRENT_SCHEMA_VERSION {
+        info!("Migrating storage from schema version {} to {}", version, version + 1);
+        apply_migration_step(storage, version).await?;
+        version += 1;
+        storage.set_schema_version(version)?;
+    }
+
+    Ok(())
+}
+
+// Apply the migration step to go from `version` to `version + 1`
+async fn apply_migration_step<S: Storage>(_: &mut S, version: u64) -> Result<(), BlockchainError> {
+    match version {
+        // Version 0 has the same layout as version 1 but without the schema version key
+        0 => Ok(()),
+        _ => Err(BlockchainError::UnsupportedSchemaVersion(version, CURRENT_SCHEMA_VERSION))
+    }
+}
+
+// Copy the whole chain from the source storage into a fresh destination storage
+// This goes through every provider, so it can be used between different backends
+// Destination will be marked with the current schema version
+pub async fn migrate_storage<S: Storage, D: Storage>(source: &S, destination: &mut D) -> Result<(), BlockchainError> {
+    if destination.has_blocks().await {
+        return Err(BlockchainError::StorageNotEmpty)
+    }
+
+    if !source.has_blocks().await {
+        return Err(BlockchainError::NotEnoughBlocks)
+    }
+
+    let top_height = source.get_top_height()?;
+    let top_topoheight = source.get_top_topoheight()?;
+    let pruned_topoheight = source.get_pruned_topoheight().await?;
+    info!("Migrating chain with height {} and topoheight {} (pruned topoheight: {:?})", top_height, top_topoheight, pruned_topoheight);
+
+    info!("Copying assets");
+    for asset in source.get_partial_assets(usize::MAX, 0, 0, top_topoheight).await? {
+        let (hash, data) = asset.consume();
+        trace!("Copying asset {}", hash);
+        destination.add_asset(&hash, data).await?;
+    }
+
+    info!("Copying blocks");
+    for height in 0..=top_height {
+        if !source.has_blocks_at_height(height).await? {
+            trace!("No blocks at height {}", height);
+            continue;
+        }
+
+        for hash in source.get_blocks_at_height(height).await? {
+            copy_block(source, destination, &hash).await?;
+        }
+
+        if height % PROGRESS_INTERVAL == 0 {
+            info!("Blocks copied until height {}/{}", height, top_height);
+        }
+    }
+
+    info!("Copying accounts");
+    let keys = source.get_partial_keys(usize::MAX, 0, 0, top_topoheight).await?;
+    for key in keys.iter() {
+        copy_account(source, destination, key).await?;
+    }
+    info!("{} accounts copied", keys.len());
+
+    info!("Copying chain pointers");
+    destination.store_tips(&source.get_tips().await?)?;
+    destination.set_top_topoheight(top_topoheight)?;
+    destination.set_top_height(top_height)?;
+    if let Some(pruned_topoheight) = pruned_topoheight {
+        destination.set_pruned_topoheight(pruned_topoheight).await?;
+    }
+
+    destination.set_schema_version(CURRENT_SCHEMA_VERSION)?;
+    info!("Migration done");
+
+    Ok(())
+}
+
+// Copy a block with its transactions, its difficulty and its DAG order if ordered
+async fn copy_block<S: Storage, D: Storage>(source: &S, destination: &mut D, hash: &Hash) -> Result<(), BlockchainError> {
+    trace!("Copying block {}", hash);
+    let header = source.get_block_header_by_hash(hash).await?;
+    let mut txs = Vec::with_capacity(header.get_transactions().len());
+    for tx_hash in header.get_transactions() {
+        txs.push(Immutable::Arc(source.get_transaction(tx_hash).await?));
+    }
+
+    let difficulty = source.get_difficulty_for_block_hash(hash).await?;
+    let p = source.get_estimated_covariance_for_block_hash(hash).await?;
+    destination.save_block(header.clone(), &txs, difficulty, p, hash.clone()).await?;
+
+    let cumulative_difficulty = source.get_cumulative_difficulty_for_block_hash(hash).await?;
+    destination.set_cumulative_difficulty_for_block_hash(hash, cumulative_difficulty).await?;
+
+    // Copy the client protocol data of each transaction
+    for tx_hash in header.get_transactions() {
+        if source.has_tx_blocks(tx_hash)? {
+            destination.set_blocks_for_tx(tx_hash, &source.get_blocks_for_tx(tx_hash)?)?;
+        }
+
+        if source.is_tx_executed_in_a_block(tx_hash)? {
+            destination.set_tx_executed_in_block(tx_hash, &source.get_block_executor_for_tx(tx_hash)?)?;
+        }
+    }
+
+    // Side blocks that are not ordered don't have any topoheight data
+    if source.is_block_topological_ordered(hash).await {
+        let topoheight = source.get_topo_height_for_hash(hash).await?;
+        destination.set_topo_height_for_block(hash, topoheight).await?;
+        destination.set_block_reward_at_topo_height(topoheight, source.get_block_reward_at_topo_height(topoheight)?)?;
+        destination.set_supply_at_topo_height(topoheight, source.get_supply_at_topo_height(topoheight).await?)?;
+
+        if let Ok(merkle_hash) = source.get_balances_merkle_hash_at_topoheight(topoheight).await {
+            destination.set_balances_merkle_hash_at_topoheight(topoheight, &merkle_hash).await?;
+        }
+    }
+
+    Ok(())
+}
+
+// Copy all the versions of nonces and balances of an account, its registration and its indexed transactions
+async fn copy_account<S: Storage, D: Storage>(source: &S, destination: &mut D, key: &PublicKey) -> Result<(), BlockchainError> {
+    trace!("Copying account {}", key.as_address(source.is_mainnet()));
+    let (last_topoheight, mut version) = source.get_last_nonce(key).await?;
+    let mut topoheight = last_topoheight;
+    loop {
+        destination.set_nonce_at_topoheight(key, topoheight, &version).await?;
+        match version.get_previous_topoheight() {
+            Some(previous) if source.has_nonce_at_exact_topoheight(key, previous).await? => {
+                version = source.get_nonce_at_exact_topoheight(key, previous).await?;
+                topoheight = previous;
+            },
+            Some(previous) => {
+                warn!("Nonce version at topoheight {} for {} is missing, stopping there", previous, key.as_address(source.is_mainnet()));
+                break;
+            },
+            None => break
+        }
+    }
+    destination.set_last_topoheight_for_nonce(key, last_topoheight).await?;
+
+    for asset in source.get_assets_for(key).await? {
+        let (last_topoheight, mut version) = source.get_last_balance(key, &asset).await?;
+        let mut topoheight = last_topoheight;
+        loop {
+            destination.set_balance_at_topoheight(&asset, topoheight, key, &version).await?;
+            match version.get_previous_topoheight() {
+                Some(previous) if source.has_balance_at_exact_topoheight(key, &asset, previous).await? => {
+                    version = source.get_balance_at_exact_topoheight(key, &asset, previous).await?;
+                    topoheight = previous;
+                },
+                Some(previous) => {
+                    warn!("Balance version at topoheight {} for {} is missing, stopping there", previous, key.as_address(source.is_mainnet()));
+                    break;
+                },
+                None => break
+            }
+        }
+        destination.set_last_topoheight_for_balance(key, &asset, last_topoheight)?;
+    }
+
+    if source.is_account_registered(key).await? {
+        let topoheight = source.get_account_registration_topoheight(key).await?;
+        destination.set_account_registration_topoheight(key, topoheight).await?;
+    }
+
+    // Copy the account transactions index if any
+    for (topoheight, tx, role) in source.get_account_transactions(key, 0, u64::MAX, 0, usize::MAX).await? {
+        destination.add_account_transaction(key, topoheight, &tx, role).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        config::{COIN_VALUE, XELIS_ASSET},
+        crypto::Hashable,
+        network::Network,
+        serializer::Serializer
+    };
+    use crate::core::{
+        blockchain::tests::{create_account, mine_blocks, remove_temp_dir, start_node_with, stop_node, temp_dir_path},
+        storage::SledStorage
+    };
+    use super::*;
+
+    // Verify that the destination has the same chain and accounts as the source
+    async fn assert_same_chain<S: Storage, D: Storage>(source: &S, destination: &D) {
+        let topoheight = source.get_top_topoheight().unwrap();
+        assert_eq!(destination.get_top_topoheight().unwrap(), topoheight);
+        assert_eq!(destination.get_top_height().unwrap(), source.get_top_height().unwrap());
+        assert_eq!(destination.get_tips().await.unwrap(), source.get_tips().await.unwrap());
+
+        for topoheight in 0..=topoheight {
+            let hash = source.get_hash_at_topo_height(topoheight).await.unwrap();
+            assert_eq!(destination.get_hash_at_topo_height(topoheight).await.unwrap(), hash);
+            assert_eq!(destination.get_block_reward_at_topo_height(topoheight).unwrap(), source.get_block_reward_at_topo_height(topoheight).unwrap());
+            assert_eq!(destination.get_supply_at_topo_height(topoheight).await.unwrap(), source.get_supply_at_topo_height(topoheight).await.unwrap());
+            assert_eq!(destination.get_cumulative_difficulty_for_block_hash(&hash).await.unwrap(), source.get_cumulative_difficulty_for_block_hash(&hash).await.unwrap());
+
+            let header = source.get_block_header_by_hash(&hash).await.unwrap();
+            assert_eq!(destination.get_block_header_by_hash(&hash).await.unwrap().hash(), header.hash());
+            for tx in header.get_transactions() {
+                assert!(destination.has_transaction(tx).await.unwrap());
+                assert_eq!(destination.is_tx_executed_in_a_block(tx).unwrap(), source.is_tx_executed_in_a_block(tx).unwrap());
+            }
+        }
+
+        let keys = source.get_partial_keys(usize::MAX, 0, 0, topoheight).await.unwrap();
+        assert_eq!(destination.get_partial_keys(usize::MAX, 0, 0, topoheight).await.unwrap(), keys);
+        for key in keys.iter() {
+            let (nonce_topoheight, nonce) = source.get_last_nonce(key).await.unwrap();
+            let (migrated_topoheight, migrated) = destination.get_last_nonce(key).await.unwrap();
+            assert_eq!((migrated_topoheight, migrated.get_nonce()), (nonce_topoheight, nonce.get_nonce()));
+
+            let (balance_topoheight, balance) = source.get_last_balance(key, &XELIS_ASSET).await.unwrap();
+            let (migrated_topoheight, migrated) = destination.get_last_balance(key, &XELIS_ASSET).await.unwrap();
+            assert_eq!(migrated_topoheight, balance_topoheight);
+            assert_eq!(migrated.get_balance().to_bytes(), balance.get_balance().to_bytes());
+
+            assert_eq!(
+                destination.get_account_transactions(key, 0, u64::MAX, 0, usize::MAX).await.unwrap(),
+                source.get_account_transactions(key, 0, u64::MAX, 0, usize::MAX).await.unwrap()
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_migrate_storage() {
+        let source_path = temp_dir_path("migration-source");
+        let blockchain = start_node_with::<SledStorage>(&source_path, &["--index-account-transactions"]).await;
+        mine_blocks(&blockchain, 3).await;
+
+        // Execute a TX so the chain has transactions and several versions for an account
+        let mut account = create_account(&[&blockchain], COIN_VALUE).await;
+        let tx = account.build_burn_tx();
+        let tx_hash = tx.hash();
+        blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+        mine_blocks(&blockchain, 2).await;
+
+        let source = blockchain.get_storage().read().await;
+        assert!(source.is_tx_executed_in_a_block(&tx_hash).unwrap());
+
+        let destination_path = temp_dir_path("migration-destination");
+        let mut destination = SledStorage::new(destination_path.clone(), None, Network::Dev).unwrap();
+        migrate_storage(&*source, &mut destination).await.unwrap();
+
+        assert_eq!(destination.get_schema_version().unwrap(), Some(CURRENT_SCHEMA_VERSION));
+        assert!(destination.has_transaction(&tx_hash).await.unwrap());
+        let key = account.keypair.get_public_key().compress();
+        assert_eq!(destination.get_last_nonce(&key).await.unwrap().1.get_nonce(), 1);
+        assert!(!destination.get_account_transactions(&key, 0, u64::MAX, 0, usize::MAX).await.unwrap().is_empty());
+        assert_same_chain(&*source, &destination).await;
+
+        // Only an empty storage can be the destination
+        assert!(matches!(migrate_storage(&*source, &mut destination).await, Err(BlockchainError::StorageNotEmpty)));
+
+        // Nothing to migrate from an empty storage
+        let empty_path = temp_dir_path("migration-empty");
+        let empty = SledStorage::new(empty_path.clone(), None, Network::Dev).unwrap();
+        let other_path = temp_dir_path("migration-other");
+        let mut other = SledStorage::new(other_path.clone(), None, Network::Dev).unwrap();
+        assert!(matches!(migrate_storage(&empty, &mut other).await, Err(BlockchainError::NotEnoughBlocks)));
+
+        drop(source);
+        stop_node(&blockchain, &source_path).await;
+        for path in [destination_path, empty_path, other_path] {
+            remove_temp_dir(&path);
+        }
+    }
+}
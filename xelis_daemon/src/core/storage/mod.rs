@@ -1,12 +1,36 @@
+// Create an optional LRU cache based on the configured cache size
+macro_rules! init_cache {
+    ($cache_size: expr) => {{
+        if let Some(size) = &$cache_size {
+            Some(tokio::sync::Mutex::new(lru::LruCache::new(std::num::NonZeroUsize::new(*size).unwrap())))
+        } else {
+            None
+        }
+    }};
+}
+
 mod providers;
 mod sled;
+mod migration;
+mod batch;
+#[cfg(feature = "rocksdb")]
+mod rocksdb;
 
 pub use self::{
     sled::SledStorage,
     providers::*,
+    migration::{migrate_storage, upgrade_schema, CURRENT_SCHEMA_VERSION},
 };
 
-use std::{collections::HashSet, sync::Arc};
+#[cfg(feature = "rocksdb")]
+pub use self::rocksdb::RocksStorage;
+
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+    str::FromStr,
+    sync::Arc
+};
 use async_trait::async_trait;
 use xelis_common::{
     block::{Block, BlockHeader},
@@ -19,8 +43,70 @@ use crate::core::error::BlockchainError;
 // Represents the tips of the chain or of a block
 pub type Tips = HashSet<Hash>;
 
+// Storage backend used by the daemon
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StorageBackend {
+    // Sled embedded database (default)
+    Sled,
+    // RocksDB, requires the `rocksdb` feature
+    RocksDB,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "sled" => Self::Sled,
+            "rocksdb" => Self::RocksDB,
+            _ => return Err("Invalid storage backend".into())
+        })
+    }
+}
+
+impl Display for StorageBackend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let str = match &self {
+            Self::Sled => "sled",
+            Self::RocksDB => "rocksdb",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+// When the storage changes are flushed to disk
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FsyncPolicy {
+    // Let the backend flush in background (fastest, last blocks may be lost on crash)
+    Background,
+    // Flush after each block applied
+    Block,
+}
+
+impl FromStr for FsyncPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "background" => Self::Background,
+            "block" => Self::Block,
+            _ => return Err("Invalid fsync policy".into())
+        })
+    }
+}
+
+impl Display for FsyncPolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let str = match &self {
+            Self::Background => "background",
+            Self::Block => "block",
+        };
+        write!(f, "{}", str)
+    }
+}
+
 #[async_trait]
-pub trait Storage: DagOrderProvider + PrunedTopoheightProvider + NonceProvider + AccountProvider + ClientProtocolProvider + BlockDagProvider + MerkleHashProvider + Sync + Send + 'static {
+pub trait Storage: DagOrderProvider + PrunedTopoheightProvider + NonceProvider + AccountProvider + AccountTransactionsProvider + ClientProtocolProvider + BlockDagProvider + MerkleHashProvider + Sync + Send + 'static {
     // Is the chain running on mainnet
     fn is_mainnet(&self) -> bool;
 
@@ -74,6 +160,24 @@ pub trait Storage: DagOrderProvider + PrunedTopoheightProvider + NonceProvider +
     // Set the network on which the chain is running
     fn set_network(&mut self, network: &Network) -> Result<(), BlockchainError>;
 
+    // Get the schema version of the storage layout
+    // None if the storage was created before schema versions were introduced
+    fn get_schema_version(&self) -> Result<Option<u64>, BlockchainError>;
+
+    // Set the schema version of the storage layout
+    fn set_schema_version(&mut self, version: u64) -> Result<(), BlockchainError>;
+
+    // Start a commit point: every change made until `end_commit_point` is kept in memory
+    // and written in a single batch, so a block is either fully applied or not at all
+    async fn start_commit_point(&mut self) -> Result<(), BlockchainError>;
+
+    // End the current commit point
+    // If apply is false, all changes made since its start are discarded
+    async fn end_commit_point(&mut self, apply: bool) -> Result<(), BlockchainError>;
+
+    // Check if a commit point is currently started
+    fn has_commit_point(&self) -> Result<bool, BlockchainError>;
+
     // Count is the number of blocks (topoheight) to rewind
     async fn pop_blocks(&mut self, mut height: u64, mut topoheight: u64, count: u64, stable_height: u64) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError>;
 
@@ -107,6 +211,144 @@ pub trait Storage: DagOrderProvider + PrunedTopoheightProvider + NonceProvider +
     // Get the size of the chain on disk in bytes
     async fn get_size_on_disk(&self) -> Result<u64, BlockchainError>;
 
+    // Flush all pending changes to disk
+    async fn flush(&mut self) -> Result<(), BlockchainError>;
+
     // Stop the storage and wait for it to finish
     async fn stop(&mut self) -> Result<(), BlockchainError>;
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{
+        account::VersionedNonce,
+        asset::AssetData,
+        crypto::KeyPair
+    };
+    use crate::core::blockchain::tests::{remove_temp_dir, temp_dir_path};
+    use super::*;
+
+    // Run the same checks against any storage backend
+    async fn test_storage<S: Storage>(mut storage: S) {
+        assert!(storage.has_network().unwrap());
+        assert_eq!(storage.get_network().unwrap(), Network::Dev);
+
+        // Chain pointers
+        storage.set_top_topoheight(10).unwrap();
+        storage.set_top_height(8).unwrap();
+        assert_eq!(storage.get_top_topoheight().unwrap(), 10);
+        assert_eq!(storage.get_top_height().unwrap(), 8);
+
+        let hash = Hash::new([1u8; 32]);
+        let mut tips = Tips::new();
+        tips.insert(hash.clone());
+        storage.store_tips(&tips).unwrap();
+        assert_eq!(storage.get_tips().await.unwrap(), tips);
+
+        // DAG order
+        storage.set_topo_height_for_block(&hash, 10).await.unwrap();
+        assert_eq!(storage.get_topo_height_for_hash(&hash).await.unwrap(), 10);
+        assert_eq!(storage.get_hash_at_topo_height(10).await.unwrap(), hash);
+        assert!(storage.is_block_topological_ordered(&hash).await);
+
+        // Blocks at height
+        let other = Hash::new([2u8; 32]);
+        storage.add_block_hash_at_height(hash.clone(), 8).await.unwrap();
+        storage.add_block_hash_at_height(other.clone(), 8).await.unwrap();
+        assert_eq!(storage.get_blocks_at_height(8).await.unwrap().len(), 2);
+        storage.remove_block_hash_at_height(&other, 8).await.unwrap();
+        storage.remove_block_hash_at_height(&hash, 8).await.unwrap();
+        assert!(!storage.has_blocks_at_height(8).await.unwrap());
+
+        // Rewards and supply
+        storage.set_block_reward_at_topo_height(10, 50).unwrap();
+        storage.set_supply_at_topo_height(10, 500).unwrap();
+        assert_eq!(storage.get_block_reward_at_topo_height(10).unwrap(), 50);
+        assert_eq!(storage.get_supply_at_topo_height(10).await.unwrap(), 500);
+
+        // Assets
+        let asset = Hash::new([3u8; 32]);
+        assert!(!storage.has_asset(&asset).await.unwrap());
+        storage.add_asset(&asset, AssetData::new(0, 8)).await.unwrap();
+        assert!(storage.has_asset(&asset).await.unwrap());
+        assert_eq!(storage.count_assets().await.unwrap(), 1);
+        assert_eq!(storage.get_assets().await.unwrap(), vec![asset]);
+
+        // Nonces
+        let key = KeyPair::new().get_public_key().compress();
+        storage.set_last_nonce_to(&key, 5, &VersionedNonce::new(1, None)).await.unwrap();
+        storage.set_last_nonce_to(&key, 9, &VersionedNonce::new(2, Some(5))).await.unwrap();
+        assert_eq!(storage.count_accounts().await.unwrap(), 1);
+        let (topoheight, version) = storage.get_nonce_at_maximum_topoheight(&key, 7).await.unwrap().unwrap();
+        assert_eq!(topoheight, 5);
+        assert_eq!(version.get_nonce(), 1);
+
+        storage.delete_versioned_nonces_at_topoheight(9).await.unwrap();
+        assert_eq!(storage.get_last_topoheight_for_nonce(&key).await.unwrap(), 5);
+        storage.delete_versioned_nonces_above_topoheight(4).await.unwrap();
+        assert!(!storage.has_nonce_at_exact_topoheight(&key, 5).await.unwrap());
+
+        // Registrations
+        storage.set_account_registration_topoheight(&key, 5).await.unwrap();
+        assert!(storage.is_account_registered(&key).await.unwrap());
+        assert!(storage.is_account_registered_below_topoheight(&key, 6).await.unwrap());
+        storage.delete_registrations_above_topoheight(4).await.unwrap();
+        assert!(!storage.is_account_registered(&key).await.unwrap());
+
+        // Merkle hashes
+        storage.set_balances_merkle_hash_at_topoheight(10, &hash).await.unwrap();
+        assert_eq!(storage.get_balances_merkle_hash_at_topoheight(10).await.unwrap(), hash);
+
+        // Commit point reverted
+        storage.start_commit_point().await.unwrap();
+        assert!(storage.has_commit_point().unwrap());
+        storage.set_top_topoheight(11).unwrap();
+        storage.set_block_reward_at_topo_height(11, 50).unwrap();
+        storage.set_last_nonce_to(&key, 11, &VersionedNonce::new(3, None)).await.unwrap();
+        let other_asset = Hash::new([4u8; 32]);
+        storage.add_asset(&other_asset, AssetData::new(11, 8)).await.unwrap();
+        // Changes not written yet are visible while the commit point is started
+        assert_eq!(storage.get_top_topoheight().unwrap(), 11);
+        assert_eq!(storage.get_block_reward_at_topo_height(11).unwrap(), 50);
+        assert!(storage.has_nonce_at_exact_topoheight(&key, 11).await.unwrap());
+        assert_eq!(storage.get_assets().await.unwrap(), vec![asset.clone(), other_asset.clone()]);
+        storage.delete_versioned_nonces_at_topoheight(11).await.unwrap();
+        assert!(!storage.has_nonce_at_exact_topoheight(&key, 11).await.unwrap());
+        storage.end_commit_point(false).await.unwrap();
+        assert!(!storage.has_commit_point().unwrap());
+        assert_eq!(storage.get_top_topoheight().unwrap(), 10);
+        assert!(storage.get_block_reward_at_topo_height(11).is_err());
+        assert!(!storage.has_nonce_at_exact_topoheight(&key, 11).await.unwrap());
+        assert_eq!(storage.count_assets().await.unwrap(), 1);
+        assert_eq!(storage.get_assets().await.unwrap(), vec![asset.clone()]);
+
+        // Commit point applied
+        storage.start_commit_point().await.unwrap();
+        storage.set_top_topoheight(11).unwrap();
+        storage.add_asset(&other_asset, AssetData::new(11, 8)).await.unwrap();
+        storage.end_commit_point(true).await.unwrap();
+        assert!(!storage.has_commit_point().unwrap());
+        assert_eq!(storage.get_top_topoheight().unwrap(), 11);
+        assert_eq!(storage.count_assets().await.unwrap(), 2);
+        assert_eq!(storage.get_assets().await.unwrap(), vec![asset, other_asset]);
+
+        storage.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sled_storage() {
+        let dir_path = temp_dir_path("storage");
+        let storage = SledStorage::new(dir_path.clone(), Some(16), Network::Dev).unwrap();
+        test_storage(storage).await;
+        remove_temp_dir(&dir_path);
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[tokio::test]
+    async fn test_rocksdb_storage() {
+        let dir_path = temp_dir_path("storage");
+        let storage = RocksStorage::new(dir_path.clone(), Some(16), Network::Dev).unwrap();
+        test_storage(storage).await;
+        remove_temp_dir(&dir_path);
+    }
+}
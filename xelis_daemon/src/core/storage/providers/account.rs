@@ -39,11 +39,11 @@ impl AccountProvider for SledStorage {
     }
 
     async fn set_account_registration_topoheight(&mut self, key: &PublicKey, topoheight: u64) -> Result<(), BlockchainError> {
-        if let Some(old) = self.registrations.insert(key.as_bytes(), topoheight.to_bytes())? {
-            self.registrations_prefixed.remove(&prefixed_db_key_no_u64(&old, key))?;
+        if let Some(old) = self.insert_into_disk(&self.registrations, key.as_bytes(), topoheight.to_bytes())? {
+            self.remove_from_disk(&self.registrations_prefixed, &prefixed_db_key_no_u64(&old, key))?;
         }
 
-        self.registrations_prefixed.insert(prefixed_db_key(topoheight, key), &[])?;
+        self.insert_into_disk(&self.registrations_prefixed, prefixed_db_key(topoheight, key), &[])?;
 
         Ok(())
     }
@@ -51,7 +51,7 @@ impl AccountProvider for SledStorage {
     async fn is_account_registered(&self, key: &PublicKey) -> Result<bool, BlockchainError> {
         let value = self.load_optional_from_disk::<u64>(&self.registrations, key.as_bytes())?;
         if let Some(topo) = value {
-            return Ok(self.registrations_prefixed.contains_key(prefixed_db_key(topo, key))?)
+            return self.contains_key(&self.registrations_prefixed, prefixed_db_key(topo, key))
         }
 
         Ok(false)
@@ -67,11 +67,11 @@ impl AccountProvider for SledStorage {
     }
 
     async fn delete_registrations_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
-        for el in self.registrations_prefixed.scan_prefix(topoheight.to_bytes()).keys() {
+        for el in self.scan_prefix_keys(&self.registrations_prefixed, &topoheight.to_bytes()) {
             let k = el?;
-            self.registrations_prefixed.remove(&k)?;
+            self.remove_from_disk(&self.registrations_prefixed, &k)?;
             let key = &k[8..40];
-            self.registrations.remove(key)?;
+            self.remove_from_disk(&self.registrations, key)?;
         }
 
         Ok(())
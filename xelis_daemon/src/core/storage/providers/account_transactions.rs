@@ -0,0 +1,199 @@
+use async_trait::async_trait;
+use log::trace;
+use xelis_common::{
+    api::daemon::AccountTransactionRole,
+    crypto::{Hash, PublicKey},
+    serializer::{ReaderError, Serializer}
+};
+use crate::core::{error::BlockchainError, storage::SledStorage};
+
+// Size of a key: public key, topoheight, tx hash and role
+pub(in crate::core::storage) const ACCOUNT_TRANSACTION_KEY_SIZE: usize = 32 + 8 + 32 + 1;
+
+#[async_trait]
+pub trait AccountTransactionsProvider {
+    // Link a transaction to an account at the topoheight where it was executed
+    async fn add_account_transaction(&mut self, key: &PublicKey, topoheight: u64, tx: &Hash, role: AccountTransactionRole) -> Result<(), BlockchainError>;
+
+    // Get the transactions linked to an account in the inclusive topoheight range, ordered by topoheight
+    async fn get_account_transactions(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64, skip: usize, maximum: usize) -> Result<Vec<(u64, Hash, AccountTransactionRole)>, BlockchainError>;
+
+    // Delete all links made at a certain topoheight
+    async fn delete_account_transactions_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+
+    // Delete all links made above topoheight
+    async fn delete_account_transactions_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError>;
+}
+
+pub(in crate::core::storage) fn role_to_byte(role: AccountTransactionRole) -> u8 {
+    match role {
+        AccountTransactionRole::Source => 0,
+        AccountTransactionRole::Destination => 1
+    }
+}
+
+fn role_from_byte(value: u8) -> Result<AccountTransactionRole, BlockchainError> {
+    Ok(match value {
+        0 => AccountTransactionRole::Source,
+        1 => AccountTransactionRole::Destination,
+        _ => return Err(ReaderError::InvalidValue.into())
+    })
+}
+
+// Key used to search the transactions of an account: key | topoheight | tx | role
+pub(in crate::core::storage) fn account_transaction_key(key: &PublicKey, topoheight: u64, tx: &Hash, role: AccountTransactionRole) -> [u8; ACCOUNT_TRANSACTION_KEY_SIZE] {
+    let mut buf = [0u8; ACCOUNT_TRANSACTION_KEY_SIZE];
+    buf[0..32].copy_from_slice(key.as_bytes());
+    buf[32..40].copy_from_slice(&topoheight.to_be_bytes());
+    buf[40..72].copy_from_slice(tx.as_bytes());
+    buf[72] = role_to_byte(role);
+    buf
+}
+
+// Same key but prefixed by the topoheight for easier deletion: topoheight | key | tx | role
+pub(in crate::core::storage) fn prefixed_account_transaction_key(topoheight: u64, key: &PublicKey, tx: &Hash, role: AccountTransactionRole) -> [u8; ACCOUNT_TRANSACTION_KEY_SIZE] {
+    let mut buf = [0u8; ACCOUNT_TRANSACTION_KEY_SIZE];
+    buf[0..8].copy_from_slice(&topoheight.to_be_bytes());
+    buf[8..40].copy_from_slice(key.as_bytes());
+    buf[40..72].copy_from_slice(tx.as_bytes());
+    buf[72] = role_to_byte(role);
+    buf
+}
+
+// Convert a key prefixed by the topoheight into the key used for search
+pub(in crate::core::storage) fn unprefix_account_transaction_key(prefixed: &[u8]) -> Result<[u8; ACCOUNT_TRANSACTION_KEY_SIZE], BlockchainError> {
+    if prefixed.len() != ACCOUNT_TRANSACTION_KEY_SIZE {
+        return Err(ReaderError::InvalidSize.into())
+    }
+
+    let mut buf = [0u8; ACCOUNT_TRANSACTION_KEY_SIZE];
+    buf[0..32].copy_from_slice(&prefixed[8..40]);
+    buf[32..40].copy_from_slice(&prefixed[0..8]);
+    buf[40..72].copy_from_slice(&prefixed[40..72]);
+    buf[72] = prefixed[72];
+    Ok(buf)
+}
+
+// Read the topoheight, tx hash and role from a key used for search
+pub(in crate::core::storage) fn read_account_transaction_key(key: &[u8]) -> Result<(u64, Hash, AccountTransactionRole), BlockchainError> {
+    if key.len() != ACCOUNT_TRANSACTION_KEY_SIZE {
+        return Err(ReaderError::InvalidSize.into())
+    }
+
+    let topoheight = u64::from_bytes(&key[32..40])?;
+    let hash = Hash::from_bytes(&key[40..72])?;
+    let role = role_from_byte(key[72])?;
+    Ok((topoheight, hash, role))
+}
+
+// Start key to search the transactions of an account from a topoheight
+pub(in crate::core::storage) fn account_transactions_start_key(key: &PublicKey, minimum_topoheight: u64) -> [u8; 40] {
+    let mut buf = [0u8; 40];
+    buf[0..32].copy_from_slice(key.as_bytes());
+    buf[32..40].copy_from_slice(&minimum_topoheight.to_be_bytes());
+    buf
+}
+
+#[async_trait]
+impl AccountTransactionsProvider for SledStorage {
+    async fn add_account_transaction(&mut self, key: &PublicKey, topoheight: u64, tx: &Hash, role: AccountTransactionRole) -> Result<(), BlockchainError> {
+        trace!("add account transaction {} for {} at topoheight {}", tx, key.as_address(self.is_mainnet()), topoheight);
+        self.insert_into_disk(&self.account_transactions, account_transaction_key(key, topoheight, tx, role), &[])?;
+        self.insert_into_disk(&self.account_transactions_prefixed, prefixed_account_transaction_key(topoheight, key, tx, role), &[])?;
+        Ok(())
+    }
+
+    async fn get_account_transactions(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64, skip: usize, maximum: usize) -> Result<Vec<(u64, Hash, AccountTransactionRole)>, BlockchainError> {
+        trace!("get account transactions for {} from {} to {}", key.as_address(self.is_mainnet()), minimum_topoheight, maximum_topoheight);
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        for el in self.range_keys(&self.account_transactions, &account_transactions_start_key(key, minimum_topoheight)) {
+            let k = el?;
+            if !k.starts_with(key.as_bytes()) {
+                break;
+            }
+
+            let entry = read_account_transaction_key(&k)?;
+            if entry.0 > maximum_topoheight || entries.len() >= maximum {
+                break;
+            }
+
+            if skipped < skip {
+                skipped += 1;
+                continue;
+            }
+
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    async fn delete_account_transactions_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete account transactions at topoheight {}", topoheight);
+        for el in self.scan_prefix_keys(&self.account_transactions_prefixed, &topoheight.to_be_bytes()) {
+            let k = el?;
+            self.remove_from_disk(&self.account_transactions_prefixed, &k)?;
+            self.remove_from_disk(&self.account_transactions, unprefix_account_transaction_key(&k)?)?;
+        }
+
+        Ok(())
+    }
+
+    async fn delete_account_transactions_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete account transactions above topoheight {}", topoheight);
+        // Keys are prefixed by the topoheight in big endian, so they are sorted by topoheight
+        for el in self.range_keys(&self.account_transactions_prefixed, &(topoheight + 1).to_be_bytes()) {
+            let k = el?;
+            self.remove_from_disk(&self.account_transactions_prefixed, &k)?;
+            self.remove_from_disk(&self.account_transactions, unprefix_account_transaction_key(&k)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{crypto::KeyPair, network::Network};
+    use crate::core::blockchain::tests::{remove_temp_dir, temp_dir_path};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_account_transactions_index() {
+        let dir_path = temp_dir_path("account-txs");
+        let mut storage = SledStorage::new(dir_path.clone(), None, Network::Dev).unwrap();
+        let key = KeyPair::new().get_public_key().compress();
+        let other = KeyPair::new().get_public_key().compress();
+
+        for topoheight in 1..=5u64 {
+            let tx = Hash::new([topoheight as u8; 32]);
+            storage.add_account_transaction(&key, topoheight, &tx, AccountTransactionRole::Source).await.unwrap();
+            storage.add_account_transaction(&other, topoheight, &tx, AccountTransactionRole::Destination).await.unwrap();
+        }
+
+        // Ordered by topoheight and only for the requested account
+        let entries = storage.get_account_transactions(&key, 0, u64::MAX, 0, usize::MAX).await.unwrap();
+        assert_eq!(entries.iter().map(|(topoheight, _, _)| *topoheight).collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+        assert!(entries.iter().all(|(topoheight, tx, role)| *tx == Hash::new([*topoheight as u8; 32]) && *role == AccountTransactionRole::Source));
+
+        // Inclusive topoheight range
+        let entries = storage.get_account_transactions(&key, 2, 4, 0, usize::MAX).await.unwrap();
+        assert_eq!(entries.iter().map(|(topoheight, _, _)| *topoheight).collect::<Vec<_>>(), vec![2, 3, 4]);
+
+        // Pages
+        let entries = storage.get_account_transactions(&key, 0, u64::MAX, 1, 2).await.unwrap();
+        assert_eq!(entries.iter().map(|(topoheight, _, _)| *topoheight).collect::<Vec<_>>(), vec![2, 3]);
+        assert!(storage.get_account_transactions(&key, 0, u64::MAX, 5, 2).await.unwrap().is_empty());
+
+        storage.delete_account_transactions_at_topoheight(1).await.unwrap();
+        storage.delete_account_transactions_above_topoheight(3).await.unwrap();
+        for key in [&key, &other] {
+            let entries = storage.get_account_transactions(key, 0, u64::MAX, 0, usize::MAX).await.unwrap();
+            assert_eq!(entries.iter().map(|(topoheight, _, _)| *topoheight).collect::<Vec<_>>(), vec![2, 3]);
+        }
+
+        drop(storage);
+        remove_temp_dir(&dir_path);
+    }
+}
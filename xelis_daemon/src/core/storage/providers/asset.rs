@@ -59,7 +59,7 @@ impl AssetProvider for SledStorage {
     async fn get_assets(&self) -> Result<Vec<Hash>, BlockchainError> {
         trace!("get assets");
 
-        self.assets.iter().keys().map(|res| {
+        self.iter_keys(&self.assets).map(|res| {
             let key = res?;
             Ok(Hash::new(key[0..HASH_SIZE].try_into()?))
         }).collect()
@@ -68,7 +68,7 @@ impl AssetProvider for SledStorage {
     async fn get_partial_assets(&self, maximum: usize, skip: usize, minimum_topoheight: u64, maximum_topoheight: u64) -> Result<IndexSet<AssetWithData>, BlockchainError> {
         let mut assets = IndexSet::new();
         let mut skip_count = 0;
-        for el in self.assets.iter() {
+        for el in self.iter(&self.assets) {
             let (key, value) = el?;
             let data = AssetData::from_bytes(&value)?;
             // check that we have a registered asset before the maximum topoheight
@@ -90,7 +90,7 @@ impl AssetProvider for SledStorage {
 
     async fn get_chunked_assets(&self, maximum: usize, skip: usize) -> Result<IndexSet<Hash>, BlockchainError> {
         let mut assets = IndexSet::with_capacity(maximum);
-        for el in self.assets.iter().keys().skip(skip).take(maximum) {
+        for el in self.iter_keys(&self.assets).skip(skip).take(maximum) {
             let key = el?;
             let asset = Hash::from_bytes(&key)?;
             assets.insert(asset);
@@ -100,7 +100,7 @@ impl AssetProvider for SledStorage {
 
     // Returns all assets that the key has
     async fn get_assets_for(&self, key: &PublicKey) -> Result<Vec<Hash>, BlockchainError> {
-        self.balances.scan_prefix(key.as_bytes()).keys().map(|res| {
+        self.scan_prefix_keys(&self.balances, key.as_bytes()).map(|res| {
             let key = res?;
             // Keys are stored like this: [public key (32 bytes)][asset hash (32 bytes)]
             // See Self::get_balance_key_for
@@ -116,7 +116,7 @@ impl AssetProvider for SledStorage {
 
     async fn add_asset(&mut self, asset: &Hash, data: AssetData) -> Result<(), BlockchainError> {
         trace!("add asset {} at topoheight {}", asset, data.get_topoheight());
-        self.assets.insert(asset.as_bytes(), data.to_bytes())?;
+        self.insert_into_disk(&self.assets, asset.as_bytes(), data.to_bytes())?;
 
         // Update counter
         self.store_assets_count(self.count_assets().await? + 1)?;
@@ -88,7 +88,7 @@ impl SledStorage {
 
     async fn has_balance_internal(&self, key: &[u8; 64]) -> Result<bool, BlockchainError> {
         trace!("has balance internal");
-        Ok(self.balances.contains_key(key)?)
+        self.contains_key(&self.balances, key)
     }
 
 }
@@ -120,7 +120,7 @@ impl BalanceProvider for SledStorage {
     fn set_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("set last topoheight to {} for balance {} for {}", topoheight, asset, key.as_address(self.is_mainnet()));
         let key = self.get_balance_key_for(key, asset);
-        self.balances.insert(&key, &topoheight.to_be_bytes())?;
+        self.insert_into_disk(&self.balances, &key, &topoheight.to_be_bytes())?;
         Ok(())
     }
 
@@ -157,7 +157,7 @@ impl BalanceProvider for SledStorage {
     fn delete_last_topoheight_for_balance(&mut self, key: &PublicKey, asset: &Hash) -> Result<(), BlockchainError> {
         trace!("delete last topoheight balance {} for {}", asset, key.as_address(self.is_mainnet()));
         let key = self.get_balance_key_for(key, asset);
-        self.balances.remove(&key)?;
+        self.remove_from_disk(&self.balances, &key)?;
         Ok(())
     }
 
@@ -297,7 +297,7 @@ impl BalanceProvider for SledStorage {
     async fn set_balance_at_topoheight(&mut self, asset: &Hash, topoheight: u64, key: &PublicKey, balance: &VersionedBalance) -> Result<(), BlockchainError> {
         trace!("set balance {} at topoheight {} for {}", asset, topoheight, key.as_address(self.is_mainnet()));
         let key = self.get_versioned_balance_key(key, asset, topoheight);
-        self.versioned_balances.insert(key, balance.to_bytes())?;
+        self.insert_into_disk(&self.versioned_balances, key, balance.to_bytes())?;
         Ok(())
     }
 }
\ No newline at end of file
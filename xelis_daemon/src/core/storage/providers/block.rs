@@ -36,7 +36,7 @@ impl SledStorage {
     // Update the blocks count and store it on disk
     fn store_blocks_count(&self, count: u64) -> Result<(), BlockchainError> {
         self.blocks_count.store(count, Ordering::SeqCst);
-        self.extra.insert(BLOCKS_COUNT, &count.to_be_bytes())?;
+        self.insert_into_disk(&self.extra, BLOCKS_COUNT, &count.to_be_bytes())?;
         Ok(())
     }
 }
@@ -45,7 +45,7 @@ impl SledStorage {
 impl BlockProvider for SledStorage {
     async fn has_blocks(&self) -> bool {
         trace!("has blocks");
-        !self.blocks.is_empty()
+        self.is_empty(&self.blocks).map_or(false, |empty| !empty)
     }
 
     async fn count_blocks(&self) -> Result<u64, BlockchainError> {
@@ -65,7 +65,7 @@ impl BlockProvider for SledStorage {
         let mut txs_count = 0;
         for (hash, tx) in block.get_transactions().iter().zip(txs) { // first save all txs, then save block
             if !self.has_transaction(hash).await? {
-                self.transactions.insert(hash.as_bytes(), tx.to_bytes())?;
+                self.insert_into_disk(&self.transactions, hash.as_bytes(), tx.to_bytes())?;
                 txs_count += 1;
             }
         }
@@ -76,14 +76,14 @@ impl BlockProvider for SledStorage {
         }
 
         // Store block header and increase blocks count if it's a new block
-        if self.blocks.insert(hash.as_bytes(), block.to_bytes())?.is_none() {
+        if self.insert_into_disk(&self.blocks, hash.as_bytes(), block.to_bytes())?.is_none() {
             self.store_blocks_count(self.count_blocks().await? + 1)?;
         }
 
         // Store difficulty
-        self.difficulty.insert(hash.as_bytes(), difficulty.to_bytes())?;
+        self.insert_into_disk(&self.difficulty, hash.as_bytes(), difficulty.to_bytes())?;
         // Store P
-        self.difficulty_covariance.insert(hash.as_bytes(), p.to_bytes())?;
+        self.insert_into_disk(&self.difficulty_covariance, hash.as_bytes(), p.to_bytes())?;
 
         self.add_block_hash_at_height(hash.clone(), block.get_height()).await?;
 
@@ -31,7 +31,7 @@ pub trait BlocksAtHeightProvider {
 impl BlocksAtHeightProvider for SledStorage {
     async fn has_blocks_at_height(&self, height: u64) -> Result<bool, BlockchainError> {
         trace!("get blocks at height {}", height);
-        Ok(self.blocks_at_height.contains_key(&height.to_be_bytes())?)
+        self.contains_key(&self.blocks_at_height, &height.to_be_bytes())
     }
 
     async fn get_blocks_at_height(&self, height: u64) -> Result<Tips, BlockchainError> {
@@ -41,7 +41,7 @@ impl BlocksAtHeightProvider for SledStorage {
 
     async fn set_blocks_at_height(&self, tips: Tips, height: u64) -> Result<(), BlockchainError> {
         trace!("set {} blocks at height {}", tips.len(), height);
-        self.blocks_at_height.insert(height.to_be_bytes(), tips.to_bytes())?;
+        self.insert_into_disk(&self.blocks_at_height, height.to_be_bytes(), tips.to_bytes())?;
         Ok(())
     }
 
@@ -67,7 +67,7 @@ impl BlocksAtHeightProvider for SledStorage {
 
         // Delete the height if there is no blocks present anymore
         if tips.is_empty() {
-            self.blocks_at_height.remove(&height.to_be_bytes())?;
+            self.remove_from_disk(&self.blocks_at_height, &height.to_be_bytes())?;
         } else {
             self.set_blocks_at_height(tips, height).await?;
         }
@@ -16,14 +16,15 @@ pub trait DagOrderProvider {
     async fn set_topo_height_for_block(&mut self, hash: &Hash, topoheight: u64) -> Result<(), BlockchainError>;
     async fn is_block_topological_ordered(&self, hash: &Hash) -> bool;
     async fn get_hash_at_topo_height(&self, topoheight: u64) -> Result<Hash, BlockchainError>;
+    async fn has_hash_at_topoheight(&self, topoheight: u64) -> Result<bool, BlockchainError>;
 }
 
 #[async_trait]
 impl DagOrderProvider for SledStorage {
     async fn set_topo_height_for_block(&mut self, hash: &Hash, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("set topo height for {} at {}", hash, topoheight);
-        self.topo_by_hash.insert(hash.as_bytes(), topoheight.to_bytes())?;
-        self.hash_at_topo.insert(topoheight.to_be_bytes(), hash.as_bytes())?;
+        self.insert_into_disk(&self.topo_by_hash, hash.as_bytes(), topoheight.to_bytes())?;
+        self.insert_into_disk(&self.hash_at_topo, topoheight.to_be_bytes(), hash.as_bytes())?;
 
         // save in cache
         if let Some(cache) = &self.topo_by_hash_cache {
@@ -80,4 +81,9 @@ impl DagOrderProvider for SledStorage {
 
         Ok(hash)
     }
+
+    async fn has_hash_at_topoheight(&self, topoheight: u64) -> Result<bool, BlockchainError> {
+        trace!("has hash at topoheight: {}", topoheight);
+        self.contains_key(&self.hash_at_topo, topoheight.to_be_bytes())
+    }
 }
\ No newline at end of file
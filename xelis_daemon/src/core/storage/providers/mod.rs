@@ -11,6 +11,7 @@ mod block;
 mod blockdag;
 mod merkle;
 mod account;
+mod account_transactions;
 
 pub use asset::AssetProvider;
 pub use blocks_at_height::BlocksAtHeightProvider;
@@ -24,4 +25,13 @@ pub use transaction::TransactionProvider;
 pub use block::BlockProvider;
 pub use blockdag::BlockDagProvider;
 pub use merkle::MerkleHashProvider;
-pub use account::AccountProvider;
\ No newline at end of file
+pub use account::AccountProvider;
+pub use account_transactions::AccountTransactionsProvider;
+
+pub(super) use account_transactions::{
+    account_transaction_key,
+    account_transactions_start_key,
+    prefixed_account_transaction_key,
+    read_account_transaction_key,
+    unprefix_account_transaction_key
+};
@@ -65,7 +65,7 @@ impl SledStorage {
     // Update the accounts count and store it on disk
     pub fn store_accounts_count(&mut self, count: u64) -> Result<(), BlockchainError> {
         self.accounts_count.store(count, Ordering::SeqCst);
-        self.extra.insert(ACCOUNTS_COUNT, &count.to_be_bytes())?;
+        self.insert_into_disk(&self.extra, ACCOUNTS_COUNT, &count.to_be_bytes())?;
         Ok(())
     }
 
@@ -96,7 +96,7 @@ impl NonceProvider for SledStorage {
 
     async fn delete_last_topoheight_for_nonce(&mut self, key: &PublicKey) -> Result<(), BlockchainError> {
         trace!("delete last topoheight for nonce {}", key.as_address(self.is_mainnet()));
-        if self.nonces.remove(key.as_bytes())?.is_some() {
+        if self.remove_from_disk(&self.nonces, key.as_bytes())?.is_some() {
             self.store_accounts_count(self.count_accounts().await? - 1)?;
         }
         Ok(())
@@ -109,7 +109,7 @@ impl NonceProvider for SledStorage {
 
     async fn has_nonce(&self, key: &PublicKey) -> Result<bool, BlockchainError> {
         trace!("has nonce {}", key.as_address(self.is_mainnet()));
-        let contains = self.nonces.contains_key(key.as_bytes())?;
+        let contains = self.contains_key(&self.nonces, key.as_bytes())?;
         Ok(contains)
     }
 
@@ -125,7 +125,7 @@ impl NonceProvider for SledStorage {
 
         let mut keys: IndexSet<PublicKey> = IndexSet::new();
         let mut skip_count = 0;
-        for el in self.nonces.iter().keys() {
+        for el in self.iter_keys(&self.nonces) {
             let key = el?;
             let pkey = PublicKey::from_bytes(&key)?;
 
@@ -280,13 +280,13 @@ impl NonceProvider for SledStorage {
     async fn set_nonce_at_topoheight(&mut self, key: &PublicKey, topoheight: u64, version: &VersionedNonce) -> Result<(), BlockchainError> {
         trace!("set nonce to {} for {} at topo {}", version.get_nonce(), key.as_address(self.is_mainnet()), topoheight);
         let disk_key = self.get_versioned_nonce_key(key, topoheight);
-        self.versioned_nonces.insert(&disk_key, version.to_bytes())?;
+        self.insert_into_disk(&self.versioned_nonces, &disk_key, version.to_bytes())?;
         Ok(())
     }
 
     async fn set_last_topoheight_for_nonce(&mut self, key: &PublicKey, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("set last topoheight for nonce {} to {}", key.as_address(self.is_mainnet()), topoheight);
-        if self.nonces.insert(&key.as_bytes(), &topoheight.to_be_bytes())?.is_none() {
+        if self.insert_into_disk(&self.nonces, &key.as_bytes(), &topoheight.to_be_bytes())?.is_none() {
             self.store_accounts_count(self.count_accounts().await? + 1)?;
         }
 
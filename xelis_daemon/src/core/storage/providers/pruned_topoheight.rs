@@ -18,7 +18,7 @@ pub trait PrunedTopoheightProvider {
 impl PrunedTopoheightProvider for SledStorage {
     async fn set_pruned_topoheight(&mut self, pruned_topoheight: u64) -> Result<(), BlockchainError> {
         self.pruned_topoheight = Some(pruned_topoheight);
-        self.extra.insert(PRUNED_TOPOHEIGHT, &pruned_topoheight.to_be_bytes())?;
+        self.insert_into_disk(&self.extra, PRUNED_TOPOHEIGHT, &pruned_topoheight.to_be_bytes())?;
         Ok(())
     }
 
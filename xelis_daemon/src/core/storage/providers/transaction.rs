@@ -38,7 +38,7 @@ impl SledStorage {
     // Update the txs count and store it on disk
     pub(super) fn store_transactions_count(&self, count: u64) -> Result<(), BlockchainError> {
         self.transactions_count.store(count, Ordering::SeqCst);
-        self.extra.insert(TXS_COUNT, &count.to_be_bytes())?;
+        self.insert_into_disk(&self.extra, TXS_COUNT, &count.to_be_bytes())?;
         Ok(())
     }    
 }
@@ -52,7 +52,7 @@ impl TransactionProvider for SledStorage {
 
     async fn get_transaction_size(&self, hash: &Hash) -> Result<usize, BlockchainError> {
         trace!("get transaction size for hash {}", hash);
-        let data = self.transactions.get(hash.as_bytes())?;
+        let data = self.get_raw(&self.transactions, hash.as_bytes())?;
         data.map(|data| data.len()).ok_or(BlockchainError::NotFoundOnDisk(DiskContext::LoadData))
     }
 
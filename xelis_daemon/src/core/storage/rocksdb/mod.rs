@@ -0,0 +1,1272 @@
+mod providers;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use indexmap::IndexSet;
+use crate::{
+    config::PRUNE_SAFETY_LIMIT,
+    core::error::{BlockchainError, DiskContext}
+};
+use xelis_common::{
+    account::{VersionedBalance, VersionedNonce},
+    block::{Block, BlockHeader},
+    crypto::{Hash, PublicKey},
+    difficulty::{CumulativeDifficulty, Difficulty},
+    immutable::Immutable,
+    network::Network,
+    serializer::{Reader, Serializer},
+    transaction::Transaction
+};
+use std::{
+    collections::HashSet,
+    hash::Hash as StdHash,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        Mutex as StdMutex,
+        MutexGuard,
+        PoisonError
+    }
+};
+use tokio::sync::Mutex;
+use lru::LruCache;
+use rocksdb::{
+    properties::TOTAL_SST_FILES_SIZE,
+    ColumnFamily,
+    Direction,
+    IteratorMode,
+    Options,
+    WriteBatch,
+    DB
+};
+use log::{debug, trace, warn, info};
+
+use super::{
+    batch::{MergedIter, PendingChanges},
+    BalanceProvider,
+    BlocksAtHeightProvider,
+    DagOrderProvider,
+    DifficultyProvider,
+    NonceProvider,
+    PrunedTopoheightProvider,
+    ClientProtocolProvider,
+    TransactionProvider,
+    BlockProvider,
+    Storage,
+    Tips
+};
+
+// Constant keys used for extra column
+const TIPS: &[u8; 4] = b"TIPS";
+const TOP_TOPO_HEIGHT: &[u8; 4] = b"TOPO";
+const TOP_HEIGHT: &[u8; 4] = b"TOPH";
+const NETWORK: &[u8] = b"NET";
+const SCHEMA_VERSION: &[u8; 4] = b"SCHV";
+const PRUNED_TOPOHEIGHT: &[u8; 4] = b"PRUN";
+// Counters (prevent to perform a O(n))
+const ACCOUNTS_COUNT: &[u8; 4] = b"CACC";
+const TXS_COUNT: &[u8; 4] = b"CTXS";
+const ASSETS_COUNT: &[u8; 4] = b"CAST";
+const BLOCKS_COUNT: &[u8; 4] = b"CBLK";
+
+// All column families used by the storage
+// Names are the same as the sled trees to keep both backends comparable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Column {
+    // all txs stored on disk
+    Transactions,
+    // all txs executed in block
+    TxsExecuted,
+    // all blocks on disk
+    Blocks,
+    // all blocks height at specific height
+    BlocksAtHeight,
+    // all extra data saved on disk
+    Extra,
+    // topo at hash on disk
+    TopoByHash,
+    // hash at topo height on disk
+    HashAtTopo,
+    // cumulative difficulty for each block hash on disk
+    CumulativeDifficulty,
+    // Difficulty estimated covariance (P)
+    DifficultyCovariance,
+    // keep tracks of all available assets on network
+    Assets,
+    // account nonces to prevent TX replay attack
+    Nonces,
+    // block reward for each block topoheight
+    Rewards,
+    // supply for each block topoheight
+    Supply,
+    // difficulty for each block hash
+    Difficulty,
+    // all blocks hashes where a tx was included in
+    TxBlocks,
+    // all versioned nonces using prefixed keys
+    VersionedNonces,
+    // all balances with prefixed keys
+    Balances,
+    // all versioned balances using prefixed keys
+    VersionedBalances,
+    // all merkle hashes for each topoheight
+    MerkleHashes,
+    // Account registrations topoheight
+    Registrations,
+    // Account registrations prefixed by their topoheight for easier deletion
+    RegistrationsPrefixed,
+    // Transactions linked to each account (as source or destination)
+    AccountTransactions,
+    // Same as above but prefixed by their topoheight for easier deletion
+    AccountTransactionsPrefixed,
+}
+
+impl Column {
+    pub const ALL: [Column; 23] = [
+        Column::Transactions,
+        Column::TxsExecuted,
+        Column::Blocks,
+        Column::BlocksAtHeight,
+        Column::Extra,
+        Column::TopoByHash,
+        Column::HashAtTopo,
+        Column::CumulativeDifficulty,
+        Column::DifficultyCovariance,
+        Column::Assets,
+        Column::Nonces,
+        Column::Rewards,
+        Column::Supply,
+        Column::Difficulty,
+        Column::TxBlocks,
+        Column::VersionedNonces,
+        Column::Balances,
+        Column::VersionedBalances,
+        Column::MerkleHashes,
+        Column::Registrations,
+        Column::RegistrationsPrefixed,
+        Column::AccountTransactions,
+        Column::AccountTransactionsPrefixed,
+    ];
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Column::Transactions => "transactions",
+            Column::TxsExecuted => "txs_executed",
+            Column::Blocks => "blocks",
+            Column::BlocksAtHeight => "blocks_at_height",
+            Column::Extra => "extra",
+            Column::TopoByHash => "topo_at_hash",
+            Column::HashAtTopo => "hash_at_topo",
+            Column::CumulativeDifficulty => "cumulative_difficulty",
+            Column::DifficultyCovariance => "difficulty_covariance",
+            Column::Assets => "assets",
+            Column::Nonces => "nonces",
+            Column::Rewards => "rewards",
+            Column::Supply => "supply",
+            Column::Difficulty => "difficulty",
+            Column::TxBlocks => "tx_blocks",
+            Column::VersionedNonces => "versioned_nonces",
+            Column::Balances => "balances",
+            Column::VersionedBalances => "versioned_balances",
+            Column::MerkleHashes => "merkle_hashes",
+            Column::Registrations => "registrations",
+            Column::RegistrationsPrefixed => "registrations_prefixed",
+            Column::AccountTransactions => "account_transactions",
+            Column::AccountTransactionsPrefixed => "account_transactions_prefixed",
+        }
+    }
+}
+
+pub struct RocksStorage {
+    // Network used by the storage
+    mainnet: bool,
+    // opened DB containing all column families
+    db: DB,
+
+    // all available caches
+    // Transaction cache
+    pub(super) transactions_cache: Option<Mutex<LruCache<Hash, Arc<Transaction>>>>,
+    // Block header cache
+    pub(super) blocks_cache: Option<Mutex<LruCache<Hash, Arc<BlockHeader>>>>,
+    // Blocks Tips cache
+    pub(super) past_blocks_cache: Option<Mutex<LruCache<Hash, Arc<IndexSet<Hash>>>>>,
+    // Topoheight by hash cache
+    pub(super) topo_by_hash_cache: Option<Mutex<LruCache<Hash, u64>>>,
+    // Hash by topoheight cache
+    pub(super) hash_at_topo_cache: Option<Mutex<LruCache<u64, Hash>>>,
+    // Cumulative difficulty cache
+    pub(super) cumulative_difficulty_cache: Option<Mutex<LruCache<Hash, CumulativeDifficulty>>>,
+    // Assets cache
+    pub(super) assets_cache: Option<Mutex<LruCache<Hash, ()>>>,
+    // Tips cache: current chain Tips
+    tips_cache: Tips,
+    // Pruned topoheight cache
+    pub(super) pruned_topoheight: Option<u64>,
+    // Is a commit point started
+    commit_point: bool,
+    // Changes of the current commit point, written at its end
+    pending: StdMutex<PendingChanges<Column>>,
+
+    // Atomic counters
+    // Count of assets
+    pub(super) assets_count: AtomicU64,
+    // Count of accounts
+    pub(super) accounts_count: AtomicU64,
+    // Count of transactions
+    pub(super) transactions_count: AtomicU64,
+    // Count of blocks
+    pub(super) blocks_count: AtomicU64
+}
+
+impl RocksStorage {
+    pub fn new(dir_path: String, cache_size: Option<usize>, network: Network) -> Result<Self, BlockchainError> {
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+
+        let path = format!("{}{}", dir_path, network.to_string().to_lowercase());
+        let db = DB::open_cf(&options, path, Column::ALL.iter().map(Column::name))?;
+        let mut storage = Self {
+            mainnet: network.is_mainnet(),
+            db,
+            transactions_cache: init_cache!(cache_size),
+            blocks_cache: init_cache!(cache_size),
+            past_blocks_cache: init_cache!(cache_size),
+            topo_by_hash_cache: init_cache!(cache_size),
+            hash_at_topo_cache: init_cache!(cache_size),
+            cumulative_difficulty_cache: init_cache!(cache_size),
+            assets_cache: init_cache!(cache_size),
+            tips_cache: HashSet::new(),
+            pruned_topoheight: None,
+            commit_point: false,
+            pending: StdMutex::new(PendingChanges::new()),
+            assets_count: AtomicU64::new(0),
+            accounts_count: AtomicU64::new(0),
+            transactions_count: AtomicU64::new(0),
+            blocks_count: AtomicU64::new(0)
+        };
+
+        // Verify that we are opening a DB on same network
+        // This prevent any corruption made by user
+        if storage.has_network()? {
+            let storage_network = storage.get_network()?;
+            if storage_network != network {
+                return Err(BlockchainError::InvalidNetwork);
+            }
+        } else {
+            storage.set_network(&network)?;
+        }
+
+        storage.load_cached_values()?;
+
+        Ok(storage)
+    }
+
+    pub fn is_mainnet(&self) -> bool {
+        self.mainnet
+    }
+
+    // Load all values kept in memory from disk
+    fn load_cached_values(&mut self) -> Result<(), BlockchainError> {
+        // Load tips from disk if available
+        self.tips_cache = self.load_optional_from_disk::<Tips>(Column::Extra, TIPS)?.unwrap_or_default();
+        debug!("Found tips: {}", self.tips_cache.len());
+
+        // Load the pruned topoheight from disk if available
+        self.pruned_topoheight = self.load_optional_from_disk::<u64>(Column::Extra, PRUNED_TOPOHEIGHT)?;
+        debug!("Found pruned topoheight: {:?}", self.pruned_topoheight);
+
+        // Load the assets count from disk if available
+        let assets_count = self.load_optional_from_disk::<u64>(Column::Extra, ASSETS_COUNT)?.unwrap_or(0);
+        debug!("Found assets count: {}", assets_count);
+        self.assets_count.store(assets_count, Ordering::SeqCst);
+
+        // Load the txs count from disk if available
+        let txs_count = self.load_optional_from_disk::<u64>(Column::Extra, TXS_COUNT)?.unwrap_or(0);
+        debug!("Found txs count: {}", txs_count);
+        self.transactions_count.store(txs_count, Ordering::SeqCst);
+
+        // Load the blocks count from disk if available
+        let blocks_count = self.load_optional_from_disk::<u64>(Column::Extra, BLOCKS_COUNT)?.unwrap_or(0);
+        debug!("Found blocks count: {}", blocks_count);
+        self.blocks_count.store(blocks_count, Ordering::SeqCst);
+
+        // Load the accounts count from disk if available
+        let accounts_count = self.load_optional_from_disk::<u64>(Column::Extra, ACCOUNTS_COUNT)?.unwrap_or(0);
+        debug!("Found accounts count: {}", accounts_count);
+        self.accounts_count.store(accounts_count, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    // Retrieve the handle of a column family
+    // All column families are created when opening the DB, so this should never fail
+    fn cf_handle(&self, column: Column) -> Result<&ColumnFamily, BlockchainError> {
+        self.db.cf_handle(column.name()).ok_or(BlockchainError::ColumnFamilyNotFound(column.name()))
+    }
+
+    // Changes of the current commit point
+    fn pending_changes(&self) -> MutexGuard<'_, PendingChanges<Column>> {
+        // Changes are only written once the lock is released, a poisoned lock is still consistent
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Read a value, the pending changes of a commit point are read first
+    pub(super) fn get_raw(&self, column: Column, key: &[u8]) -> Result<Option<Vec<u8>>, BlockchainError> {
+        if self.commit_point {
+            if let Some(value) = self.pending_changes().get(&column, key) {
+                return Ok(value.map(<[u8]>::to_vec))
+            }
+        }
+
+        Ok(self.db.get_cf(self.cf_handle(column)?, key)?)
+    }
+
+    pub(super) fn contains_key(&self, column: Column, key: &[u8]) -> Result<bool, BlockchainError> {
+        if self.commit_point {
+            if let Some(value) = self.pending_changes().get(&column, key) {
+                return Ok(value.is_some())
+            }
+        }
+
+        Ok(self.db.get_pinned_cf(self.cf_handle(column)?, key)?.is_some())
+    }
+
+    // During a commit point, the value is kept in memory until the commit point is applied
+    pub(super) fn insert(&self, column: Column, key: &[u8], value: &[u8]) -> Result<(), BlockchainError> {
+        if self.commit_point {
+            self.pending_changes().insert(column, key, value.to_vec());
+            return Ok(())
+        }
+
+        self.db.put_cf(self.cf_handle(column)?, key, value)?;
+        Ok(())
+    }
+
+    // Insert the value and returns the previous one if any
+    // This has the same behavior as sled insert
+    pub(super) fn replace(&self, column: Column, key: &[u8], value: &[u8]) -> Result<Option<Vec<u8>>, BlockchainError> {
+        let previous = self.get_raw(column, key)?;
+        self.insert(column, key, value)?;
+        Ok(previous)
+    }
+
+    // During a commit point, the deletion is kept in memory until the commit point is applied
+    pub(super) fn remove(&self, column: Column, key: &[u8]) -> Result<(), BlockchainError> {
+        if self.commit_point {
+            self.pending_changes().remove(column, key);
+            return Ok(())
+        }
+
+        self.db.delete_cf(self.cf_handle(column)?, key)?;
+        Ok(())
+    }
+
+    // Write all the pending changes in a single batch
+    fn write_pending_changes(&self, changes: PendingChanges<Column>) -> Result<(), BlockchainError> {
+        let mut batch = WriteBatch::default();
+        for (column, entries) in changes.into_trees() {
+            let handle = self.cf_handle(column)?;
+            for (key, value) in entries {
+                match value {
+                    Some(value) => batch.put_cf(handle, key, value),
+                    None => batch.delete_cf(handle, key)
+                }
+            }
+        }
+
+        self.db.write(batch)?;
+        Ok(())
+    }
+
+    // Remove the value and returns it if any
+    // This has the same behavior as sled remove
+    pub(super) fn take(&self, column: Column, key: &[u8]) -> Result<Option<Vec<u8>>, BlockchainError> {
+        let previous = self.get_raw(column, key)?;
+        if previous.is_some() {
+            self.remove(column, key)?;
+        }
+        Ok(previous)
+    }
+
+    pub(super) fn is_empty(&self, column: Column) -> Result<bool, BlockchainError> {
+        Ok(self.iter(column)?.next().is_none())
+    }
+
+    // Iterate in order over the entries with a key starting with prefix, from the start key
+    // No prefix extractor is configured, so we stop as soon as a key doesn't match anymore
+    // The pending changes of a commit point are merged in, they are copied before iterating
+    // so the column can be updated while iterating
+    fn scan_from<'a>(&'a self, column: Column, start: &[u8], prefix: &[u8]) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), BlockchainError>> + 'a, BlockchainError> {
+        let changes = if self.commit_point {
+            self.pending_changes().scan(&column, start, prefix)
+        } else {
+            Vec::new()
+        };
+
+        let prefix = prefix.to_vec();
+        let iter = self.db.iterator_cf(self.cf_handle(column)?, IteratorMode::From(start, Direction::Forward))
+            .map(|res| res.map_err(BlockchainError::from))
+            .take_while(move |res| match res {
+                Ok((key, _)) => key.starts_with(&prefix),
+                Err(_) => true
+            });
+
+        Ok(MergedIter::new(iter, changes))
+    }
+
+    // Iterate over all entries of a column family
+    pub(super) fn iter<'a>(&'a self, column: Column) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), BlockchainError>> + 'a, BlockchainError> {
+        self.scan_from(column, &[], &[])
+    }
+
+    // Iterate over all keys of a column family
+    pub(super) fn iter_keys<'a>(&'a self, column: Column) -> Result<impl Iterator<Item = Result<Box<[u8]>, BlockchainError>> + 'a, BlockchainError> {
+        Ok(self.iter(column)?.map(|res| res.map(|(key, _)| key)))
+    }
+
+    // Iterate over all entries starting from the key (included)
+    pub(super) fn iter_from<'a>(&'a self, column: Column, start: &[u8]) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), BlockchainError>> + 'a, BlockchainError> {
+        self.scan_from(column, start, &[])
+    }
+
+    // Iterate over all entries starting with the prefix
+    pub(super) fn scan_prefix<'a>(&'a self, column: Column, prefix: &'a [u8]) -> Result<impl Iterator<Item = Result<(Box<[u8]>, Box<[u8]>), BlockchainError>> + 'a, BlockchainError> {
+        self.scan_from(column, prefix, prefix)
+    }
+
+    pub(super) fn load_optional_from_disk<T: Serializer>(&self, column: Column, key: &[u8]) -> Result<Option<T>, BlockchainError> {
+        match self.get_raw(column, key)? {
+            Some(bytes) => {
+                let mut reader = Reader::new(&bytes);
+                let value = T::read(&mut reader)?;
+                Ok(Some(value))
+            },
+            None => Ok(None)
+        }
+    }
+
+    pub(super) fn load_from_disk<T: Serializer>(&self, column: Column, key: &[u8]) -> Result<T, BlockchainError> {
+        match self.load_optional_from_disk(column, key)? {
+            Some(value) => Ok(value),
+            None => Err(BlockchainError::NotFoundOnDisk(DiskContext::LoadData))
+        }
+    }
+
+    pub(super) async fn get_cacheable_arc_data<K: Eq + StdHash + Serializer + Clone, V: Serializer>(&self, column: Column, cache: &Option<Mutex<LruCache<K, Arc<V>>>>, key: &K) -> Result<Arc<V>, BlockchainError> {
+        let value = if let Some(cache) = cache {
+            let mut cache = cache.lock().await;
+            if let Some(value) = cache.get(key) {
+                return Ok(Arc::clone(&value));
+            }
+
+            let value = Arc::new(self.load_from_disk(column, &key.to_bytes())?);
+            cache.put(key.clone(), Arc::clone(&value));
+            value
+        } else {
+            Arc::new(self.load_from_disk(column, &key.to_bytes())?)
+        };
+
+        Ok(value)
+    }
+
+    pub(super) async fn get_cacheable_data<K: Eq + StdHash + Serializer + Clone, V: Serializer + Clone>(&self, column: Column, cache: &Option<Mutex<LruCache<K, V>>>, key: &K) -> Result<V, BlockchainError> {
+        let value = if let Some(cache) = cache {
+            let mut cache = cache.lock().await;
+            if let Some(value) = cache.get(key) {
+                return Ok(value.clone());
+            }
+
+            let value: V = self.load_from_disk(column, &key.to_bytes())?;
+            cache.put(key.clone(), value.clone());
+            value
+        } else {
+            self.load_from_disk(column, &key.to_bytes())?
+        };
+
+        Ok(value)
+    }
+
+    pub(super) async fn delete_cacheable_data<K: Eq + StdHash + Serializer + Clone, V: Serializer>(&self, column: Column, cache: &Option<Mutex<LruCache<K, V>>>, key: &K) -> Result<V, BlockchainError> {
+        let bytes = match self.take(column, &key.to_bytes())? {
+            Some(data) => data,
+            None => return Err(BlockchainError::NotFoundOnDisk(DiskContext::DeleteData))
+        };
+
+        if let Some(cache) = cache {
+            let mut cache = cache.lock().await;
+            if let Some(value) = cache.pop(key) {
+                return Ok(value);
+            }
+        }
+
+        let mut reader = Reader::new(&bytes);
+        let value = V::read(&mut reader)?;
+        Ok(value)
+    }
+
+    pub(super) async fn delete_data<K: Eq + StdHash + Serializer + Clone, V: Serializer>(&self, column: Column, cache: &Option<Mutex<LruCache<K, Arc<V>>>>, key: &K) -> Result<Arc<V>, BlockchainError> {
+        let bytes = match self.take(column, &key.to_bytes())? {
+            Some(data) => data,
+            None => return Err(BlockchainError::NotFoundOnDisk(DiskContext::DeleteData))
+        };
+
+        if let Some(cache) = cache {
+            let mut cache = cache.lock().await;
+            if let Some(value) = cache.pop(key) {
+                return Ok(value);
+            }
+        }
+
+        let mut reader = Reader::new(&bytes);
+        let value = V::read(&mut reader)?;
+        Ok(Arc::new(value))
+    }
+
+    pub(super) async fn contains_data<K: Eq + StdHash + Serializer + Clone, V>(&self, column: Column, cache: &Option<Mutex<LruCache<K, V>>>, key: &K) -> Result<bool, BlockchainError> {
+        if let Some(cache) = cache {
+            let cache = cache.lock().await;
+            return Ok(cache.contains(key) || self.contains_key(column, &key.to_bytes())?)
+        }
+
+        self.contains_key(column, &key.to_bytes())
+    }
+
+    // Update the assets count and store it on disk
+    pub(super) fn store_assets_count(&self, count: u64) -> Result<(), BlockchainError> {
+        self.assets_count.store(count, Ordering::SeqCst);
+        self.insert(Column::Extra, ASSETS_COUNT, &count.to_be_bytes())
+    }
+
+    // Update the accounts count and store it on disk
+    pub(super) fn store_accounts_count(&self, count: u64) -> Result<(), BlockchainError> {
+        self.accounts_count.store(count, Ordering::SeqCst);
+        self.insert(Column::Extra, ACCOUNTS_COUNT, &count.to_be_bytes())
+    }
+
+    // Update the txs count and store it on disk
+    pub(super) fn store_transactions_count(&self, count: u64) -> Result<(), BlockchainError> {
+        self.transactions_count.store(count, Ordering::SeqCst);
+        self.insert(Column::Extra, TXS_COUNT, &count.to_be_bytes())
+    }
+
+    // Update the blocks count and store it on disk
+    pub(super) fn store_blocks_count(&self, count: u64) -> Result<(), BlockchainError> {
+        self.blocks_count.store(count, Ordering::SeqCst);
+        self.insert(Column::Extra, BLOCKS_COUNT, &count.to_be_bytes())
+    }
+
+    fn delete_versioned_column_above_topoheight(&self, column: Column, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned {} above topoheight {}", column.name(), topoheight);
+        for el in self.iter_keys(column)? {
+            let key = el?;
+            let topo = u64::from_bytes(&key[0..8])?;
+            if topo > topoheight {
+                self.remove(column, &key)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_versioned_column_below_topoheight(&self, column: Column, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned {} below topoheight {}", column.name(), topoheight);
+        for el in self.iter_keys(column)? {
+            let key = el?;
+            let topo = u64::from_bytes(&key[0..8])?;
+            if topo < topoheight {
+                self.remove(column, &key)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Storage for RocksStorage {
+    fn is_mainnet(&self) -> bool {
+        self.mainnet
+    }
+
+    async fn clear_caches(&mut self) -> Result<(), BlockchainError> {
+        if let Some(cache) = self.transactions_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.blocks_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.past_blocks_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.topo_by_hash_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.hash_at_topo_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.cumulative_difficulty_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        if let Some(cache) = self.assets_cache.as_ref() {
+            let mut cache = cache.lock().await;
+            cache.clear();
+        }
+
+        Ok(())
+    }
+
+    // Delete the whole block using its topoheight
+    async fn delete_block_at_topoheight(&mut self, topoheight: u64) -> Result<(Hash, Arc<BlockHeader>, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
+        trace!("Delete block at topoheight {topoheight}");
+
+        // delete topoheight<->hash pointers
+        let hash = self.delete_cacheable_data(Column::HashAtTopo, &self.hash_at_topo_cache, &topoheight).await?;
+        trace!("Hash is {hash} at topo {topoheight}");
+
+        self.delete_cacheable_data::<Hash, u64>(Column::TopoByHash, &self.topo_by_hash_cache, &hash).await?;
+
+        trace!("deleting block header {}", hash);
+        let block = self.delete_data(Column::Blocks, &self.blocks_cache, &hash).await?;
+        trace!("block header deleted successfully");
+
+        trace!("Deleting supply and block reward");
+        let supply: u64 = self.delete_cacheable_data(Column::Supply, &None, &topoheight).await?;
+        trace!("Supply was {}", supply);
+
+        let reward: u64 = self.delete_cacheable_data(Column::Rewards, &None, &topoheight).await?;
+        trace!("Reward for block {} was: {}", hash, reward);
+
+        trace!("Deleting difficulty");
+        let _: Difficulty = self.delete_cacheable_data(Column::Difficulty, &None, &hash).await?;
+
+        trace!("Deleting cumulative difficulty");
+        let cumulative_difficulty: CumulativeDifficulty = self.delete_cacheable_data(Column::CumulativeDifficulty, &self.cumulative_difficulty_cache, &hash).await?;
+        trace!("Cumulative difficulty deleted: {}", cumulative_difficulty);
+
+        let mut txs = Vec::new();
+        for tx_hash in block.get_transactions() {
+            if self.has_tx_blocks(tx_hash)? {
+                let mut blocks: Tips = self.delete_cacheable_data(Column::TxBlocks, &None, tx_hash).await?;
+                let blocks_len =  blocks.len();
+                blocks.remove(&hash);
+                self.set_blocks_for_tx(tx_hash, &blocks)?;
+                trace!("Tx was included in {}, blocks left: {}", blocks_len, blocks.into_iter().map(|b| b.to_string()).collect::<Vec<String>>().join(", "));
+            }
+
+            if self.is_tx_executed_in_a_block(tx_hash)? {
+                trace!("Tx {} was executed, deleting", tx_hash);
+                self.remove_tx_executed(&tx_hash)?;
+            }
+
+            // We have to check first as we may have already deleted it because of client protocol
+            // which allow multiple time the same txs in differents blocks
+            if self.contains_data(Column::Transactions, &self.transactions_cache, tx_hash).await? {
+                trace!("Deleting TX {} in block {}", tx_hash, hash);
+                let tx: Arc<Transaction> = self.delete_data(Column::Transactions, &self.transactions_cache, tx_hash).await?;
+                txs.push((tx_hash.clone(), tx));
+            }
+        }
+
+        // remove the block hash from the set, and delete the set if empty
+        if self.has_blocks_at_height(block.get_height()).await? {
+            self.remove_block_hash_at_height(&hash, block.get_height()).await?;
+        }
+
+        // Delete cache of past blocks
+        if let Some(cache) = &self.past_blocks_cache {
+            let mut cache = cache.lock().await;
+            cache.pop(&hash);
+        }
+
+        Ok((hash, block, txs))
+    }
+
+    async fn delete_versioned_balances_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned balances at topoheight {}", topoheight);
+        let prefix = topoheight.to_be_bytes();
+        for el in self.scan_prefix(Column::VersionedBalances, &prefix)? {
+            let (key, value) = el?;
+            // Delete this version from DB
+            self.remove(Column::VersionedBalances, &key)?;
+
+            // Deserialize keys part
+            let asset = Hash::from_bytes(&key[40..72])?;
+            let key = PublicKey::from_bytes(&key[8..40])?;
+
+            let last_topoheight = self.get_last_topoheight_for_balance(&key, &asset).await?;
+            if last_topoheight >= topoheight {
+                // Deserialize value, it is needed to get the previous topoheight
+                let versioned_balance = VersionedBalance::from_bytes(&value)?;
+
+                // Now records changes, for each balances
+                let db_key = self.get_balance_key_for(&key, &asset);
+                if let Some(previous_topoheight) = versioned_balance.get_previous_topoheight() {
+                    self.insert(Column::Balances, &db_key, &previous_topoheight.to_be_bytes())?;
+                } else {
+                    // if there is no previous topoheight, it means that this is the first version
+                    // so we can delete the balance
+                    self.remove(Column::Balances, &db_key)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_versioned_nonces_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned nonces at topoheight {}", topoheight);
+        // Collect the entries first as we need a mutable access to update the pointers
+        let prefix = topoheight.to_be_bytes();
+        let entries = self.scan_prefix(Column::VersionedNonces, &prefix)?.collect::<Result<Vec<_>, _>>()?;
+        for (key, value) in entries {
+            // Delete this version from DB
+            self.remove(Column::VersionedNonces, &key)?;
+
+            // Deserialize keys part
+            let key = PublicKey::from_bytes(&key[8..40])?;
+
+            // Because of chain reorg, it may have been already deleted
+            if let Ok(last_topoheight) = self.get_last_topoheight_for_nonce(&key).await {
+                if last_topoheight >= topoheight {
+                    // Deserialize value, it is needed to get the previous topoheight
+                    let version = VersionedNonce::from_bytes(&value)?;
+                    // Now records changes
+                    if let Some(previous_topoheight) = version.get_previous_topoheight() {
+                        self.set_last_topoheight_for_nonce(&key, previous_topoheight).await?;
+                    } else {
+                        // if there is no previous topoheight, it means that this is the first version
+                        // so we can delete the balance
+                        self.delete_last_topoheight_for_nonce(&key).await?;
+                    }
+                }
+            }
+        }
+
+        trace!("delete versioned nonces at topoheight {} done!", topoheight);
+        Ok(())
+    }
+
+    async fn delete_versioned_balances_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned balances above topoheight {}!", topoheight);
+        self.delete_versioned_column_above_topoheight(Column::VersionedBalances, topoheight)
+    }
+
+    async fn delete_versioned_nonces_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned nonces above topoheight {}", topoheight);
+        self.delete_versioned_column_above_topoheight(Column::VersionedNonces, topoheight)
+    }
+
+    async fn delete_registrations_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete registrations above topoheight {}", topoheight);
+        for el in self.iter_keys(Column::RegistrationsPrefixed)? {
+            let key = el?;
+            let topo = u64::from_bytes(&key[0..8])?;
+            if topo > topoheight {
+                self.remove(Column::RegistrationsPrefixed, &key)?;
+                let pkey = &key[8..40];
+                self.remove(Column::Registrations, pkey)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_registrations_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete registrations below topoheight {}", topoheight);
+        let mut buf = [0u8; 40];
+        for el in self.iter(Column::Registrations)? {
+            let (key, value) = el?;
+            let topo = u64::from_bytes(&value[0..8])?;
+            if topo < topoheight {
+                buf[0..8].copy_from_slice(&value);
+                buf[8..40].copy_from_slice(&key);
+
+                self.remove(Column::RegistrationsPrefixed, &buf)?;
+                self.remove(Column::Registrations, &key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn delete_versioned_balances_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned balances below topoheight {}!", topoheight);
+        self.delete_versioned_column_below_topoheight(Column::VersionedBalances, topoheight)
+    }
+
+    async fn delete_versioned_nonces_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete versioned nonces below topoheight {}", topoheight);
+        self.delete_versioned_column_below_topoheight(Column::VersionedNonces, topoheight)
+    }
+
+    async fn create_snapshot_balances_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        // column where PublicKey are stored with the highest balance topoheight in it
+        for el in self.iter(Column::Balances)? {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes[0..32])?;
+            let asset = Hash::from_bytes(&key_bytes[32..64])?;
+            let highest_balance_topoheight = u64::from_bytes(&value)?;
+
+            // retrieve the highest versioned balance
+            let mut versioned_balance = self.get_balance_at_exact_topoheight(&key, &asset, highest_balance_topoheight).await?;
+
+            // if the highest topoheight for this account is less than the snapshot topoheight
+            // update it to the topoheight
+            // otherwise, delete the previous topoheight in VersionedBalance which is under topoheight
+            if highest_balance_topoheight < topoheight {
+                // save the new highest topoheight
+                self.insert(Column::Balances, &key_bytes, &topoheight.to_be_bytes())?;
+                // remove the previous topoheight
+                versioned_balance.set_previous_topoheight(None);
+
+                // save it
+                let key = self.get_versioned_balance_key(&key, &asset, topoheight);
+                self.insert(Column::VersionedBalances, &key, &versioned_balance.to_bytes())?;
+            } else {
+                // find the first VersionedBalance which is under topoheight
+                while let Some(previous_topoheight) = versioned_balance.get_previous_topoheight() {
+                    if previous_topoheight < topoheight {
+                        versioned_balance.set_previous_topoheight(None);
+                        // save it
+                        let key = self.get_versioned_balance_key(&key, &asset, topoheight);
+                        self.insert(Column::VersionedBalances, &key, &versioned_balance.to_bytes())?;
+                        break;
+                    }
+
+                    // keep searching
+                    versioned_balance = self.get_balance_at_exact_topoheight(&key, &asset, previous_topoheight).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create_snapshot_nonces_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        // column where PublicKey are stored with the highest nonce topoheight in it
+        for el in self.iter(Column::Nonces)? {
+            let (key_bytes, value) = el?;
+            let key = PublicKey::from_bytes(&key_bytes)?;
+            let highest_topoheight = u64::from_bytes(&value)?;
+
+            // retrieve the highest versioned nonce
+            let mut versioned_nonce = self.get_nonce_at_exact_topoheight(&key, highest_topoheight).await?;
+
+            // if the highest topoheight for this account is less than the snapshot topoheight
+            // update it to the topoheight
+            // otherwise, delete the previous topoheight in VersionedNonce which is under topoheight
+            if highest_topoheight < topoheight {
+                // save the new highest topoheight
+                self.insert(Column::Nonces, &key_bytes, &topoheight.to_be_bytes())?;
+                // remove the previous topoheight
+                versioned_nonce.set_previous_topoheight(None);
+
+                // save it
+                let key = self.get_versioned_nonce_key(&key, topoheight);
+                self.insert(Column::VersionedNonces, &key, &versioned_nonce.to_bytes())?;
+            } else {
+                // find the first VersionedNonce which is under topoheight
+                while let Some(previous_topoheight) = versioned_nonce.get_previous_topoheight() {
+                    if previous_topoheight < topoheight {
+                        versioned_nonce.set_previous_topoheight(None);
+                        // save it
+                        let key = self.get_versioned_nonce_key(&key, topoheight);
+                        self.insert(Column::VersionedNonces, &key, &versioned_nonce.to_bytes())?;
+                        break;
+                    }
+
+                    // keep searching
+                    versioned_nonce = self.get_nonce_at_exact_topoheight(&key, previous_topoheight).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn create_snapshot_registrations_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        // column where PublicKey are stored with the registration topoheight in it
+        let mut buf = [0u8; 40];
+        for el in self.iter(Column::Registrations)? {
+            let (key, value) = el?;
+            let registration_topo = u64::from_bytes(&value)?;
+
+            // if the registration topoheight for this account is less than the snapshot topoheight
+            // update it to the topoheight
+            if registration_topo < topoheight {
+                // Delete the prefixed registration
+                buf[0..8].copy_from_slice(&value);
+                buf[8..40].copy_from_slice(&key);
+                self.remove(Column::RegistrationsPrefixed, &buf)?;
+
+                // save the new registration topoheight
+                self.insert(Column::Registrations, &key, &topoheight.to_be_bytes())?;
+
+                // Overwrite with the new topoheight
+                buf[0..8].copy_from_slice(&topoheight.to_be_bytes());
+                self.insert(Column::RegistrationsPrefixed, &buf, &[])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_network(&self) -> Result<Network, BlockchainError> {
+        trace!("get network");
+        self.load_from_disk(Column::Extra, NETWORK)
+    }
+
+    fn set_network(&mut self, network: &Network) -> Result<(), BlockchainError> {
+        trace!("set network to {}", network);
+        self.insert(Column::Extra, NETWORK, &network.to_bytes())
+    }
+
+    fn has_network(&self) -> Result<bool, BlockchainError> {
+        trace!("has network");
+        self.contains_key(Column::Extra, NETWORK)
+    }
+
+    fn get_schema_version(&self) -> Result<Option<u64>, BlockchainError> {
+        trace!("get schema version");
+        self.load_optional_from_disk(Column::Extra, SCHEMA_VERSION)
+    }
+
+    fn set_schema_version(&mut self, version: u64) -> Result<(), BlockchainError> {
+        trace!("set schema version to {}", version);
+        self.insert(Column::Extra, SCHEMA_VERSION, &version.to_be_bytes())
+    }
+
+    async fn start_commit_point(&mut self) -> Result<(), BlockchainError> {
+        trace!("start commit point");
+        if self.commit_point {
+            return Err(BlockchainError::CommitPointAlreadyStarted)
+        }
+
+        self.commit_point = true;
+        Ok(())
+    }
+
+    async fn end_commit_point(&mut self, apply: bool) -> Result<(), BlockchainError> {
+        trace!("end commit point (apply: {})", apply);
+        self.commit_point = false;
+        let changes = std::mem::replace(&mut *self.pending_changes(), PendingChanges::new());
+        let res = if apply && !changes.is_empty() {
+            self.write_pending_changes(changes)
+        } else {
+            Ok(())
+        };
+
+        if !apply || res.is_err() {
+            // Values in memory may be the ones of the changes discarded
+            self.clear_caches().await?;
+            self.load_cached_values()?;
+        }
+
+        res
+    }
+
+    fn has_commit_point(&self) -> Result<bool, BlockchainError> {
+        trace!("has commit point");
+        Ok(self.commit_point)
+    }
+
+    async fn pop_blocks(&mut self, mut height: u64, mut topoheight: u64, count: u64, stable_topo_height: u64) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
+        trace!("pop blocks from height: {}, topoheight: {}, count: {}", height, topoheight, count);
+        if topoheight < count as u64 { // also prevent removing genesis block
+            return Err(BlockchainError::NotEnoughBlocks);
+        }
+
+        // search the lowest topo height available based on count + 1
+        // (last lowest topo height accepted)
+        let mut lowest_topo = topoheight - count;
+        trace!("Lowest topoheight for rewind: {}", lowest_topo);
+
+        let pruned_topoheight = self.get_pruned_topoheight().await?.unwrap_or(0);
+        if pruned_topoheight != 0 {
+            let safety_pruned_topoheight = pruned_topoheight + PRUNE_SAFETY_LIMIT;
+            if lowest_topo <= safety_pruned_topoheight && stable_topo_height != 0 {
+                warn!("Pruned topoheight is {}, lowest topoheight is {}, rewind only until {}", pruned_topoheight, lowest_topo, safety_pruned_topoheight);
+                lowest_topo = safety_pruned_topoheight;
+            }
+        }
+
+        // new TIPS for chain
+        let mut tips = self.get_tips().await?;
+
+        // Delete all orphaned blocks tips
+        for tip in tips.clone() {
+            if !self.is_block_topological_ordered(&tip).await {
+                debug!("Tip {} is not ordered, removing", tip);
+                tips.remove(&tip);
+            }
+        }
+
+        // all txs to be rewinded
+        let mut txs = Vec::new();
+        let mut done = 0;
+        'main: loop {
+            // stop rewinding if its genesis block or if we reached the lowest topo
+            if topoheight <= lowest_topo || topoheight <= stable_topo_height || height == 0 { // prevent removing genesis block
+                trace!("Done: {done}, count: {count}, height: {height}, topoheight: {topoheight}, lowest topo: {lowest_topo}, stable topo: {stable_topo_height}");
+                break 'main;
+            }
+
+            // Delete the hash at topoheight
+            let (hash, block, block_txs) = self.delete_block_at_topoheight(topoheight).await?;
+            trace!("Block {} at topoheight {} deleted", hash, topoheight);
+            txs.extend(block_txs);
+
+            // generate new tips
+            trace!("Removing {} from {} tips", hash, tips.len());
+            tips.remove(&hash);
+
+            for hash in block.get_tips() {
+                trace!("Adding {} to {} tips", hash, tips.len());
+                tips.insert(hash.clone());
+            }
+
+            if topoheight <= pruned_topoheight {
+                warn!("Pruned topoheight is reached, this is not healthy, starting from 0");
+                topoheight = 0;
+                height = 0;
+
+                tips.clear();
+                tips.insert(self.get_hash_at_topo_height(0).await?);
+
+                self.remove(Column::Extra, PRUNED_TOPOHEIGHT)?;
+                self.pruned_topoheight = None;
+
+                break 'main;
+            }
+
+            topoheight -= 1;
+            // height of old block become new height
+            if block.get_height() < height {
+                height = block.get_height();
+            }
+            done += 1;
+        }
+
+        debug!("Blocks processed {}, new topoheight: {}, new height: {}, tips: {}", done, topoheight, height, tips.len());
+
+        trace!("Cleaning assets");
+
+        // All deleted assets
+        let mut deleted_assets = HashSet::new();
+
+        // clean all assets
+        for el in self.iter(Column::Assets)? {
+            let (key, value) = el.context("error on asset iterator")?;
+            let asset = Hash::from_bytes(&key)?;
+            trace!("verifying asset registered: {}", asset);
+
+            let registration_topoheight = u64::from_bytes(&value)?;
+            if registration_topoheight > topoheight {
+                trace!("Asset {} was registered at topoheight {}, deleting", asset, registration_topoheight);
+                // Delete it from registered assets
+                self.remove(Column::Assets, &key).context(format!("Error while deleting asset {asset} from registered assets"))?;
+
+                deleted_assets.insert(asset);
+            }
+        }
+
+        trace!("Cleaning nonces");
+        // now let's process nonces versions
+        // we set the new highest topoheight to the highest found under the new topoheight
+        for el in self.iter(Column::Nonces)? {
+            let (key, value) = el?;
+            let highest_topoheight = u64::from_bytes(&value)?;
+            if highest_topoheight < pruned_topoheight {
+                warn!("wrong nonce topoheight stored, highest topoheight is {}, pruned topoheight is {}", highest_topoheight, pruned_topoheight);
+                self.remove(Column::Nonces, &key)?;
+                continue;
+            }
+
+            if highest_topoheight > topoheight {
+                if self.take(Column::Nonces, &key)?.is_some() {
+                    self.store_accounts_count(self.count_accounts().await? - 1)?;
+                }
+
+                // find the first version which is under topoheight
+                let pkey = PublicKey::from_bytes(&key)?;
+                let mut version = self.get_nonce_at_exact_topoheight(&pkey, highest_topoheight).await
+                    .context(format!("Error while retrieving nonce at exact topoheight {highest_topoheight}"))?;
+
+                while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                    if previous_topoheight < topoheight {
+                        // we find the new highest version which is under new topoheight
+                        trace!("New highest version nonce for {} is at topoheight {}", pkey.as_address(self.is_mainnet()), previous_topoheight);
+                        if self.replace(Column::Nonces, &key, &previous_topoheight.to_be_bytes())?.is_none() {
+                            self.store_accounts_count(self.count_accounts().await? + 1)?;
+                        }
+                        break;
+                    }
+
+                    // keep searching
+                    version = self.get_nonce_at_exact_topoheight(&pkey, previous_topoheight).await
+                        .context(format!("Error while searching nonce at exact topoheight"))?;
+                }
+            } else {
+                // nothing to do as its under the rewinded topoheight
+            }
+        }
+
+        trace!("Cleaning balances");
+        // do balances too
+        for el in self.iter(Column::Balances)? {
+            let (key, value) = el?;
+            let asset = Hash::from_bytes(&key[32..64])?;
+            let mut delete = true;
+
+            // if the asset is not deleted, we can process it
+            if !deleted_assets.contains(&asset) {
+                let highest_topoheight = u64::from_bytes(&value)?;
+                if highest_topoheight > topoheight && highest_topoheight >= pruned_topoheight {
+                    // find the first version which is under topoheight
+                    let pkey = PublicKey::from_bytes(&key[0..32])?;
+
+                    let mut version = self.get_balance_at_exact_topoheight(&pkey, &asset, highest_topoheight).await
+                        .context(format!("Error while retrieving balance at exact topoheight {highest_topoheight}"))?;
+
+                    while let Some(previous_topoheight) = version.get_previous_topoheight() {
+                        if previous_topoheight < topoheight {
+                            // we find the new highest version which is under new topoheight
+                            trace!("New highest version balance for {} is at topoheight {} with asset {}", pkey.as_address(self.is_mainnet()), previous_topoheight, asset);
+                            self.insert(Column::Balances, &key, &previous_topoheight.to_be_bytes())?;
+                            delete = false;
+                            break;
+                        }
+
+                        // keep searching
+                        version = self.get_balance_at_exact_topoheight(&pkey, &asset, previous_topoheight).await?;
+                    }
+                }
+            }
+
+            if delete {
+                self.remove(Column::Balances, &key)?;
+            }
+        }
+
+        warn!("Blocks rewinded: {}, new topoheight: {}, new height: {}", done, topoheight, height);
+
+        trace!("Cleaning versioned balances and nonces");
+
+        // now delete all versioned balances and nonces above the new topoheight
+        self.delete_versioned_balances_above_topoheight(topoheight).await?;
+        self.delete_versioned_nonces_above_topoheight(topoheight).await?;
+        // Delete also registrations
+        self.delete_registrations_above_topoheight(topoheight).await?;
+
+        trace!("Cleaning caches");
+        // Clear all caches to not have old data after rewind
+        self.clear_caches().await?;
+
+        trace!("Storing new pointers");
+        // store the new tips and topo topoheight
+        self.store_tips(&tips)?;
+        self.set_top_topoheight(topoheight)?;
+        self.set_top_height(height)?;
+
+        // Reduce the count of blocks stored
+        let count = self.count_blocks().await? - done;
+        self.store_blocks_count(count)?;
+
+        Ok((height, topoheight, txs))
+    }
+
+    async fn get_top_block_hash(&self) -> Result<Hash, BlockchainError> {
+        trace!("get top block hash");
+        self.get_hash_at_topo_height(self.get_top_topoheight()?).await
+    }
+
+    fn get_top_topoheight(&self) -> Result<u64, BlockchainError> {
+        trace!("get top topoheight");
+        self.load_from_disk(Column::Extra, TOP_TOPO_HEIGHT)
+    }
+
+    fn set_top_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("set new top topoheight at {}", topoheight);
+        self.insert(Column::Extra, TOP_TOPO_HEIGHT, &topoheight.to_be_bytes())
+    }
+
+    fn get_top_height(&self) -> Result<u64, BlockchainError> {
+        trace!("get top height");
+        self.load_from_disk(Column::Extra, TOP_HEIGHT)
+    }
+
+    fn set_top_height(&mut self, height: u64) -> Result<(), BlockchainError> {
+        trace!("set new top height at {}", height);
+        self.insert(Column::Extra, TOP_HEIGHT, &height.to_be_bytes())
+    }
+
+    async fn get_top_block_header(&self) -> Result<(Arc<BlockHeader>, Hash), BlockchainError> {
+        trace!("get top block header");
+        let hash = self.get_top_block_hash().await?;
+        Ok((self.get_block_header_by_hash(&hash).await?, hash))
+    }
+
+    async fn get_top_block(&self) -> Result<Block, BlockchainError> {
+        trace!("get top block");
+        let (block, _) = self.get_top_block_header().await?;
+        let mut transactions = Vec::new();
+        for tx in block.get_transactions() {
+            let transaction = self.get_transaction(tx).await?;
+            transactions.push(Immutable::Arc(transaction));
+        }
+
+        let block = Block::new(Immutable::Arc(block), transactions);
+        Ok(block)
+    }
+
+    async fn get_tips(&self) -> Result<Tips, BlockchainError> {
+        trace!("get tips");
+        Ok(self.tips_cache.clone())
+    }
+
+    fn store_tips(&mut self, tips: &Tips) -> Result<(), BlockchainError> {
+        trace!("Saving {} Tips", tips.len());
+        self.insert(Column::Extra, TIPS, &tips.to_bytes())?;
+        self.tips_cache = tips.clone();
+        Ok(())
+    }
+
+    // Returns the current size on disk in bytes
+    // This is the sum of all SST files of each column family
+    async fn get_size_on_disk(&self) -> Result<u64, BlockchainError> {
+        let mut size = 0;
+        for column in Column::ALL {
+            if let Some(value) = self.db.property_int_value_cf(self.cf_handle(column)?, TOTAL_SST_FILES_SIZE)? {
+                size += value;
+            }
+        }
+        Ok(size)
+    }
+
+    // Sync the write-ahead log, this is enough to not lose any write
+    async fn flush(&mut self) -> Result<(), BlockchainError> {
+        trace!("flush");
+        self.db.flush_wal(true)?;
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<(), BlockchainError> {
+        info!("Flushing RocksDB database");
+        for column in Column::ALL {
+            self.db.flush_cf(self.cf_handle(column)?)?;
+        }
+        info!("RocksDB database flushed");
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+use xelis_common::{crypto::PublicKey, serializer::Serializer};
+use crate::core::{
+    error::BlockchainError,
+    storage::{rocksdb::Column, AccountProvider, RocksStorage}
+};
+
+fn prefixed_db_key(topoheight: u64, key: &PublicKey) -> [u8; 40] {
+    prefixed_db_key_no_u64(&topoheight.to_bytes(), key)
+}
+
+fn prefixed_db_key_no_u64(topoheight: &[u8], key: &PublicKey) -> [u8; 40] {
+    let mut buf = [0u8; 40];
+    buf[0..8].copy_from_slice(&topoheight);
+    buf[8..40].copy_from_slice(key.as_bytes());
+    buf
+}
+
+#[async_trait]
+impl AccountProvider for RocksStorage {
+    async fn get_account_registration_topoheight(&self, key: &PublicKey) -> Result<u64, BlockchainError> {
+        self.load_from_disk(Column::Registrations, key.as_bytes())
+    }
+
+    async fn set_account_registration_topoheight(&mut self, key: &PublicKey, topoheight: u64) -> Result<(), BlockchainError> {
+        if let Some(old) = self.replace(Column::Registrations, key.as_bytes(), &topoheight.to_bytes())? {
+            self.remove(Column::RegistrationsPrefixed, &prefixed_db_key_no_u64(&old, key))?;
+        }
+
+        self.insert(Column::RegistrationsPrefixed, &prefixed_db_key(topoheight, key), &[])?;
+
+        Ok(())
+    }
+
+    async fn is_account_registered(&self, key: &PublicKey) -> Result<bool, BlockchainError> {
+        let value = self.load_optional_from_disk::<u64>(Column::Registrations, key.as_bytes())?;
+        if let Some(topo) = value {
+            return self.contains_key(Column::RegistrationsPrefixed, &prefixed_db_key(topo, key))
+        }
+
+        Ok(false)
+    }
+
+    async fn is_account_registered_below_topoheight(&self, key: &PublicKey, topoheight: u64) -> Result<bool, BlockchainError> {
+        if !self.is_account_registered(key).await? {
+            return Ok(false);
+        }
+
+        let registration_topoheight = self.get_account_registration_topoheight(key).await?;
+        Ok(registration_topoheight < topoheight)
+    }
+
+    async fn delete_registrations_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        let prefix = topoheight.to_bytes();
+        for el in self.scan_prefix(Column::RegistrationsPrefixed, &prefix)? {
+            let (k, _) = el?;
+            self.remove(Column::RegistrationsPrefixed, &k)?;
+            let key = &k[8..40];
+            self.remove(Column::Registrations, key)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,82 @@
+use async_trait::async_trait;
+use log::trace;
+use xelis_common::{
+    api::daemon::AccountTransactionRole,
+    crypto::{Hash, PublicKey}
+};
+use crate::core::{
+    error::BlockchainError,
+    storage::{
+        providers::{
+            account_transaction_key,
+            account_transactions_start_key,
+            prefixed_account_transaction_key,
+            read_account_transaction_key,
+            unprefix_account_transaction_key
+        },
+        rocksdb::Column,
+        AccountTransactionsProvider,
+        RocksStorage
+    }
+};
+
+#[async_trait]
+impl AccountTransactionsProvider for RocksStorage {
+    async fn add_account_transaction(&mut self, key: &PublicKey, topoheight: u64, tx: &Hash, role: AccountTransactionRole) -> Result<(), BlockchainError> {
+        trace!("add account transaction {} for {} at topoheight {}", tx, key.as_address(self.is_mainnet()), topoheight);
+        self.insert(Column::AccountTransactions, &account_transaction_key(key, topoheight, tx, role), &[])?;
+        self.insert(Column::AccountTransactionsPrefixed, &prefixed_account_transaction_key(topoheight, key, tx, role), &[])?;
+        Ok(())
+    }
+
+    async fn get_account_transactions(&self, key: &PublicKey, minimum_topoheight: u64, maximum_topoheight: u64, skip: usize, maximum: usize) -> Result<Vec<(u64, Hash, AccountTransactionRole)>, BlockchainError> {
+        trace!("get account transactions for {} from {} to {}", key.as_address(self.is_mainnet()), minimum_topoheight, maximum_topoheight);
+        let mut entries = Vec::new();
+        let mut skipped = 0;
+        for el in self.iter_from(Column::AccountTransactions, &account_transactions_start_key(key, minimum_topoheight))? {
+            let (k, _) = el?;
+            if !k.starts_with(key.as_bytes()) {
+                break;
+            }
+
+            let entry = read_account_transaction_key(&k)?;
+            if entry.0 > maximum_topoheight || entries.len() >= maximum {
+                break;
+            }
+
+            if skipped < skip {
+                skipped += 1;
+                continue;
+            }
+
+            entries.push(entry);
+        }
+
+        Ok(entries)
+    }
+
+    async fn delete_account_transactions_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete account transactions at topoheight {}", topoheight);
+        let prefix = topoheight.to_be_bytes();
+        for el in self.scan_prefix(Column::AccountTransactionsPrefixed, &prefix)? {
+            let (k, _) = el?;
+            self.remove(Column::AccountTransactionsPrefixed, &k)?;
+            self.remove(Column::AccountTransactions, &unprefix_account_transaction_key(&k)?)?;
+        }
+
+        Ok(())
+    }
+
+    async fn delete_account_transactions_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
+        trace!("delete account transactions above topoheight {}", topoheight);
+        // Keys are prefixed by the topoheight in big endian, so they are sorted by topoheight
+        let start = (topoheight + 1).to_be_bytes();
+        for el in self.iter_from(Column::AccountTransactionsPrefixed, &start)? {
+            let (k, _) = el?;
+            self.remove(Column::AccountTransactionsPrefixed, &k)?;
+            self.remove(Column::AccountTransactions, &unprefix_account_transaction_key(&k)?)?;
+        }
+
+        Ok(())
+    }
+}
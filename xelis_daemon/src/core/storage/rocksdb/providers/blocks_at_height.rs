@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use log::trace;
+use xelis_common::{
+    crypto::Hash,
+    serializer::Serializer
+};
+use crate::core::{
+    error::BlockchainError,
+    storage::{rocksdb::Column, BlocksAtHeightProvider, RocksStorage, Tips},
+};
+
+#[async_trait]
+impl BlocksAtHeightProvider for RocksStorage {
+    async fn has_blocks_at_height(&self, height: u64) -> Result<bool, BlockchainError> {
+        trace!("get blocks at height {}", height);
+        self.contains_key(Column::BlocksAtHeight, &height.to_be_bytes())
+    }
+
+    async fn get_blocks_at_height(&self, height: u64) -> Result<Tips, BlockchainError> {
+        trace!("get blocks at height {}", height);
+        self.load_from_disk(Column::BlocksAtHeight, &height.to_be_bytes())
+    }
+
+    async fn set_blocks_at_height(&self, tips: Tips, height: u64) -> Result<(), BlockchainError> {
+        trace!("set {} blocks at height {}", tips.len(), height);
+        self.insert(Column::BlocksAtHeight, &height.to_be_bytes(), &tips.to_bytes())
+    }
+
+    async fn add_block_hash_at_height(&mut self, hash: Hash, height: u64) -> Result<(), BlockchainError> {
+        trace!("add block {} at height {}", hash, height);
+        let mut tips = if self.has_blocks_at_height(height).await? {
+            let hashes = self.get_blocks_at_height(height).await?;
+            trace!("Found {} blocks at this height", hashes.len());
+            hashes
+        } else {
+            trace!("No blocks found at this height");
+            Tips::new()
+        };
+
+        tips.insert(hash);
+        self.set_blocks_at_height(tips, height).await
+    }
+
+    async fn remove_block_hash_at_height(&self, hash: &Hash, height: u64) -> Result<(), BlockchainError> {
+        trace!("remove block {} at height {}", hash, height);
+        let mut tips = self.get_blocks_at_height(height).await?;
+        tips.remove(hash);
+
+        // Delete the height if there is no blocks present anymore
+        if tips.is_empty() {
+            self.remove(Column::BlocksAtHeight, &height.to_be_bytes())?;
+        } else {
+            self.set_blocks_at_height(tips, height).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet
+};
+use async_trait::async_trait;
+use log::trace;
+use xelis_common::{
+    crypto::Hash,
+    serializer::Serializer
+};
+use crate::core::{
+    error::BlockchainError,
+    storage::{
+        rocksdb::Column,
+        ClientProtocolProvider,
+        RocksStorage,
+        Tips
+    }
+};
+
+#[async_trait]
+impl ClientProtocolProvider for RocksStorage {
+    fn get_block_executor_for_tx(&self, tx: &Hash) -> Result<Hash, BlockchainError> {
+        trace!("get block executer for tx {}", tx);
+        self.load_from_disk(Column::TxsExecuted, tx.as_bytes())
+    }
+
+    fn set_tx_executed_in_block(&mut self, tx: &Hash, block: &Hash) -> Result<(), BlockchainError> {
+        trace!("set tx {} executed in block {}", tx, block);
+        self.insert(Column::TxsExecuted, tx.as_bytes(), block.as_bytes())
+    }
+
+    fn remove_tx_executed(&mut self, tx: &Hash) -> Result<(), BlockchainError> {
+        trace!("remove tx {} executed", tx);
+        self.remove(Column::TxsExecuted, tx.as_bytes())
+    }
+
+    fn is_tx_executed_in_a_block(&self, tx: &Hash) -> Result<bool, BlockchainError> {
+        trace!("is tx {} executed in a block", tx);
+        self.contains_key(Column::TxsExecuted, tx.as_bytes())
+    }
+
+    fn is_tx_executed_in_block(&self, tx: &Hash, block: &Hash) -> Result<bool, BlockchainError> {
+        trace!("is tx {} executed in block {}", tx, block);
+        if let Ok(hash) = self.get_block_executor_for_tx(tx) {
+            if hash == *block {
+                return Ok(true)
+            }
+        }
+        Ok(false)
+    }
+
+    fn has_tx_blocks(&self, hash: &Hash) -> Result<bool, BlockchainError> {
+        trace!("has tx blocks {}", hash);
+        self.contains_key(Column::TxBlocks, hash.as_bytes())
+    }
+
+    fn has_block_linked_to_tx(&self, tx: &Hash, block: &Hash) -> Result<bool, BlockchainError> {
+        trace!("has block {} linked to tx {}", block, tx);
+        Ok(self.has_tx_blocks(tx)? && self.get_blocks_for_tx(tx)?.contains(block))
+    }
+
+    fn add_block_linked_to_tx_if_not_present(&mut self, tx: &Hash, block: &Hash) -> Result<bool, BlockchainError> {
+        trace!("add block {} linked to tx {} if not present", block, tx);
+        let mut hashes: HashSet<Cow<'_, Hash>> = if self.has_tx_blocks(tx)? {
+            self.load_from_disk(Column::TxBlocks, tx.as_bytes())?
+        } else {
+            HashSet::new()
+        };
+
+        let insert = hashes.insert(Cow::Borrowed(block));
+        if insert {
+            self.insert(Column::TxBlocks, tx.as_bytes(), &hashes.to_bytes())?;
+        }
+
+        Ok(insert)
+    }
+
+    fn get_blocks_for_tx(&self, hash: &Hash) -> Result<Tips, BlockchainError> {
+        trace!("get blocks for tx {}", hash);
+        self.load_from_disk(Column::TxBlocks, hash.as_bytes())
+    }
+
+    fn add_block_for_tx(&mut self, tx: &Hash, block: &Hash) -> Result<(), BlockchainError> {
+        trace!("add block {} for tx {}", block, tx);
+        let mut blocks = if self.has_tx_blocks(tx)? {
+            self.get_blocks_for_tx(tx)?
+        } else {
+            Tips::new()
+        };
+
+        if !blocks.contains(&block) {
+            blocks.insert(block.clone());
+            self.set_blocks_for_tx(tx, &blocks)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_blocks_for_tx(&mut self, tx: &Hash, blocks: &Tips) -> Result<(), BlockchainError> {
+        trace!("set blocks ({}) for tx {} ", blocks.len(), tx);
+        self.insert(Column::TxBlocks, tx.as_bytes(), &blocks.to_bytes())
+    }
+}
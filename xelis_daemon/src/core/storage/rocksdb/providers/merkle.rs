@@ -0,0 +1,20 @@
+use async_trait::async_trait;
+use log::trace;
+use xelis_common::{crypto::Hash, serializer::Serializer};
+use crate::core::{
+    error::BlockchainError,
+    storage::{rocksdb::Column, MerkleHashProvider, RocksStorage}
+};
+
+#[async_trait]
+impl MerkleHashProvider for RocksStorage {
+    async fn get_balances_merkle_hash_at_topoheight(&self, topoheight: u64) -> Result<Hash, BlockchainError> {
+        trace!("get merkle hash at topoheight {}", topoheight);
+        self.load_from_disk(Column::MerkleHashes, &topoheight.to_bytes())
+    }
+
+    async fn set_balances_merkle_hash_at_topoheight(&mut self, topoheight: u64, merkle_proof: &Hash) -> Result<(), BlockchainError> {
+        trace!("set merkle hash {} at topoheight {}", merkle_proof, topoheight);
+        self.insert(Column::MerkleHashes, &topoheight.to_bytes(), merkle_proof.as_bytes())
+    }
+}
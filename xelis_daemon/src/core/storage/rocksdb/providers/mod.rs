@@ -0,0 +1,14 @@
+mod asset;
+mod blocks_at_height;
+mod dag_order;
+mod difficulty;
+mod pruned_topoheight;
+mod nonce;
+mod balance;
+mod client_protocol;
+mod transaction;
+mod block;
+mod blockdag;
+mod merkle;
+mod account;
+mod account_transactions;
@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+use crate::core::{
+    error::BlockchainError,
+    storage::{
+        rocksdb::{Column, PRUNED_TOPOHEIGHT},
+        PrunedTopoheightProvider,
+        RocksStorage
+    },
+};
+
+#[async_trait]
+impl PrunedTopoheightProvider for RocksStorage {
+    async fn set_pruned_topoheight(&mut self, pruned_topoheight: u64) -> Result<(), BlockchainError> {
+        self.pruned_topoheight = Some(pruned_topoheight);
+        self.insert(Column::Extra, PRUNED_TOPOHEIGHT, &pruned_topoheight.to_be_bytes())
+    }
+
+    async fn get_pruned_topoheight(&self) -> Result<Option<u64>, BlockchainError> {
+        Ok(self.pruned_topoheight)
+    }
+}
@@ -18,15 +18,27 @@ use xelis_common::{
 use std::{
     collections::HashSet,
     hash::Hash as StdHash,
-    sync::{Arc, atomic::{AtomicU64, Ordering}},
-    num::NonZeroUsize
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        Mutex as StdMutex,
+        MutexGuard,
+        PoisonError
+    }
 };
 use tokio::sync::Mutex;
 use lru::LruCache;
-use sled::Tree;
+use sled::{
+    transaction::{ConflictableTransactionError, TransactionError},
+    Batch,
+    IVec,
+    Transactional,
+    Tree
+};
 use log::{debug, trace, warn, info};
 
 use super::{
+    batch::{MergedIter, PendingChanges},
     BalanceProvider,
     BlocksAtHeightProvider,
     DagOrderProvider,
@@ -45,6 +57,7 @@ const TIPS: &[u8; 4] = b"TIPS";
 const TOP_TOPO_HEIGHT: &[u8; 4] = b"TOPO";
 const TOP_HEIGHT: &[u8; 4] = b"TOPH";
 const NETWORK: &[u8] = b"NET";
+const SCHEMA_VERSION: &[u8; 4] = b"SCHV";
 pub(super) const PRUNED_TOPOHEIGHT: &[u8; 4] = b"PRUN";
 // Counters (prevent to perform a O(n))
 pub(super) const ACCOUNTS_COUNT: &[u8; 4] = b"CACC";
@@ -98,6 +111,10 @@ pub struct SledStorage {
     pub(super) registrations: Tree,
     // Account registrations prefixed by their topoheight for easier deletion
     pub(super) registrations_prefixed: Tree,
+    // Transactions linked to each account (as source or destination)
+    pub(super) account_transactions: Tree,
+    // Same as above but prefixed by their topoheight for easier deletion
+    pub(super) account_transactions_prefixed: Tree,
     // opened DB used for assets to create dynamic assets
     db: sled::Db,
 
@@ -124,6 +141,10 @@ pub struct SledStorage {
     tips_cache: Tips,
     // Pruned topoheight cache
     pub(super) pruned_topoheight: Option<u64>,
+    // Is a commit point started
+    commit_point: bool,
+    // Changes of the current commit point, written at its end
+    pending: StdMutex<PendingChanges<IVec>>,
 
     // Atomic counters
     // Count of assets
@@ -136,16 +157,6 @@ pub struct SledStorage {
     pub(super) blocks_count: AtomicU64
 }
 
-macro_rules! init_cache {
-    ($cache_size: expr) => {{
-        if let Some(size) = &$cache_size {
-            Some(Mutex::new(LruCache::new(NonZeroUsize::new(*size).unwrap())))
-        } else {
-            None
-        }
-    }};
-}
-
 impl SledStorage {
     pub fn new(dir_path: String, cache_size: Option<usize>, network: Network) -> Result<Self, BlockchainError> {
         let sled = sled::open(format!("{}{}", dir_path, network.to_string().to_lowercase()))?;
@@ -172,6 +183,8 @@ impl SledStorage {
             merkle_hashes: sled.open_tree("merkle_hashes")?,
             registrations: sled.open_tree("registrations")?,
             registrations_prefixed: sled.open_tree("registrations_prefixed")?,
+            account_transactions: sled.open_tree("account_transactions")?,
+            account_transactions_prefixed: sled.open_tree("account_transactions_prefixed")?,
             db: sled,
             transactions_cache: init_cache!(cache_size),
             blocks_cache: init_cache!(cache_size),
@@ -184,6 +197,8 @@ impl SledStorage {
             nonces_trees_cache: init_cache!(cache_size),
             tips_cache: HashSet::new(),
             pruned_topoheight: None,
+            commit_point: false,
+            pending: StdMutex::new(PendingChanges::new()),
             assets_count: AtomicU64::new(0),
             accounts_count: AtomicU64::new(0),
             transactions_count: AtomicU64::new(0),
@@ -201,51 +216,176 @@ impl SledStorage {
             storage.set_network(&network)?;
         }
 
+        storage.load_cached_values()?;
+
+        Ok(storage)
+    }
+
+    pub fn is_mainnet(&self) -> bool {
+        self.mainnet
+    }
+
+    // Load all values kept in memory from disk
+    fn load_cached_values(&mut self) -> Result<(), BlockchainError> {
         // Load tips from disk if available
-        if let Ok(tips) = storage.load_from_disk::<Tips>(&storage.extra, TIPS) {
-            debug!("Found tips: {}", tips.len());
-            storage.tips_cache = tips;
-        }
+        self.tips_cache = self.load_optional_from_disk::<Tips>(&self.extra, TIPS)?.unwrap_or_default();
+        debug!("Found tips: {}", self.tips_cache.len());
 
         // Load the pruned topoheight from disk if available
-        if let Ok(pruned_topoheight) = storage.load_from_disk::<u64>(&storage.extra, PRUNED_TOPOHEIGHT) {
-            debug!("Found pruned topoheight: {}", pruned_topoheight);
-            storage.pruned_topoheight = Some(pruned_topoheight);
-        }
+        self.pruned_topoheight = self.load_optional_from_disk::<u64>(&self.extra, PRUNED_TOPOHEIGHT)?;
+        debug!("Found pruned topoheight: {:?}", self.pruned_topoheight);
 
         // Load the assets count from disk if available
-        if let Ok(assets_count) = storage.load_from_disk::<u64>(&storage.extra, ASSETS_COUNT) {
-            debug!("Found assets count: {}", assets_count);
-            storage.assets_count.store(assets_count, Ordering::SeqCst);
-        }
+        let assets_count = self.load_optional_from_disk::<u64>(&self.extra, ASSETS_COUNT)?.unwrap_or(0);
+        debug!("Found assets count: {}", assets_count);
+        self.assets_count.store(assets_count, Ordering::SeqCst);
 
         // Load the txs count from disk if available
-        if let Ok(txs_count) = storage.load_from_disk::<u64>(&storage.extra, TXS_COUNT) {
-            debug!("Found txs count: {}", txs_count);
-            storage.transactions_count.store(txs_count, Ordering::SeqCst);
-        }
+        let txs_count = self.load_optional_from_disk::<u64>(&self.extra, TXS_COUNT)?.unwrap_or(0);
+        debug!("Found txs count: {}", txs_count);
+        self.transactions_count.store(txs_count, Ordering::SeqCst);
 
         // Load the blocks count from disk if available
-        if let Ok(blocks_count) = storage.load_from_disk::<u64>(&storage.extra, BLOCKS_COUNT) {
-            debug!("Found blocks count: {}", blocks_count);
-            storage.blocks_count.store(blocks_count, Ordering::SeqCst);
-        }
+        let blocks_count = self.load_optional_from_disk::<u64>(&self.extra, BLOCKS_COUNT)?.unwrap_or(0);
+        debug!("Found blocks count: {}", blocks_count);
+        self.blocks_count.store(blocks_count, Ordering::SeqCst);
 
         // Load the accounts count from disk if available
-        if let Ok(accounts_count) = storage.load_from_disk::<u64>(&storage.extra, ACCOUNTS_COUNT) {
-            debug!("Found accounts count: {}", accounts_count);
-            storage.accounts_count.store(accounts_count, Ordering::SeqCst);
+        let accounts_count = self.load_optional_from_disk::<u64>(&self.extra, ACCOUNTS_COUNT)?.unwrap_or(0);
+        debug!("Found accounts count: {}", accounts_count);
+        self.accounts_count.store(accounts_count, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    // Changes of the current commit point
+    fn pending_changes(&self) -> MutexGuard<'_, PendingChanges<IVec>> {
+        // Changes are only written once the lock is released, a poisoned lock is still consistent
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Insert a value on disk and returns the previous one if any
+    // During a commit point, the value is kept in memory until the commit point is applied
+    pub(super) fn insert_into_disk<K: AsRef<[u8]>, V: Into<IVec>>(&self, tree: &Tree, key: K, value: V) -> Result<Option<IVec>, BlockchainError> {
+        if !self.commit_point {
+            return Ok(tree.insert(key, value)?)
         }
 
-        Ok(storage)
+        let key = key.as_ref();
+        let previous = self.get_raw(tree, key)?;
+        let value: IVec = value.into();
+        self.pending_changes().insert(tree.name(), key, value.to_vec());
+        Ok(previous)
     }
 
-    pub fn is_mainnet(&self) -> bool {
-        self.mainnet
+    // Remove a value from disk and returns it if any
+    // During a commit point, the deletion is kept in memory until the commit point is applied
+    pub(super) fn remove_from_disk<K: AsRef<[u8]>>(&self, tree: &Tree, key: K) -> Result<Option<IVec>, BlockchainError> {
+        if !self.commit_point {
+            return Ok(tree.remove(key)?)
+        }
+
+        let key = key.as_ref();
+        let previous = self.get_raw(tree, key)?;
+        if previous.is_some() {
+            self.pending_changes().remove(tree.name(), key);
+        }
+        Ok(previous)
+    }
+
+    // Read a value, the pending changes of a commit point are read first
+    pub(super) fn get_raw<K: AsRef<[u8]>>(&self, tree: &Tree, key: K) -> Result<Option<IVec>, BlockchainError> {
+        if self.commit_point {
+            if let Some(value) = self.pending_changes().get(&tree.name(), key.as_ref()) {
+                return Ok(value.map(IVec::from))
+            }
+        }
+
+        Ok(tree.get(key)?)
+    }
+
+    pub(super) fn contains_key<K: AsRef<[u8]>>(&self, tree: &Tree, key: K) -> Result<bool, BlockchainError> {
+        if self.commit_point {
+            if let Some(value) = self.pending_changes().get(&tree.name(), key.as_ref()) {
+                return Ok(value.is_some())
+            }
+        }
+
+        Ok(tree.contains_key(key)?)
+    }
+
+    // Iterate in order over the entries with a key starting with prefix, from the start key
+    // The pending changes of a commit point are merged in, they are copied before iterating
+    // so the tree can be updated while iterating
+    fn scan_from<'a>(&self, tree: &'a Tree, start: &[u8], prefix: &[u8]) -> impl Iterator<Item = Result<(IVec, IVec), BlockchainError>> + 'a {
+        let changes = if self.commit_point {
+            self.pending_changes().scan(&tree.name(), start, prefix)
+        } else {
+            Vec::new()
+        };
+
+        let prefix = prefix.to_vec();
+        let entries = tree.range(start.to_vec()..)
+            .take_while(move |res| res.as_ref().map_or(true, |(key, _)| key.starts_with(&prefix)));
+
+        MergedIter::new(entries, changes).map(|res| res.map_err(BlockchainError::from))
+    }
+
+    pub(super) fn iter<'a>(&self, tree: &'a Tree) -> impl Iterator<Item = Result<(IVec, IVec), BlockchainError>> + 'a {
+        self.scan_from(tree, &[], &[])
+    }
+
+    pub(super) fn iter_keys<'a>(&self, tree: &'a Tree) -> impl Iterator<Item = Result<IVec, BlockchainError>> + 'a {
+        self.iter(tree).map(|res| res.map(|(key, _)| key))
+    }
+
+    pub(super) fn scan_prefix<'a>(&self, tree: &'a Tree, prefix: &[u8]) -> impl Iterator<Item = Result<(IVec, IVec), BlockchainError>> + 'a {
+        self.scan_from(tree, prefix, prefix)
+    }
+
+    pub(super) fn scan_prefix_keys<'a>(&self, tree: &'a Tree, prefix: &[u8]) -> impl Iterator<Item = Result<IVec, BlockchainError>> + 'a {
+        self.scan_prefix(tree, prefix).map(|res| res.map(|(key, _)| key))
+    }
+
+    // Iterate over the keys greater or equal to start
+    pub(super) fn range_keys<'a>(&self, tree: &'a Tree, start: &[u8]) -> impl Iterator<Item = Result<IVec, BlockchainError>> + 'a {
+        self.scan_from(tree, start, &[]).map(|res| res.map(|(key, _)| key))
+    }
+
+    pub(super) fn is_empty(&self, tree: &Tree) -> Result<bool, BlockchainError> {
+        self.iter(tree).next().transpose().map(|entry| entry.is_none())
+    }
+
+    // Write all the pending changes in a single transaction over all the trees changed
+    fn write_pending_changes(&self, changes: PendingChanges<IVec>) -> Result<(), BlockchainError> {
+        let mut trees = Vec::new();
+        let mut batches = Vec::new();
+        for (name, entries) in changes.into_trees() {
+            let mut batch = Batch::default();
+            for (key, value) in entries {
+                match value {
+                    Some(value) => batch.insert(key, value),
+                    None => batch.remove(key)
+                }
+            }
+            trees.push(self.db.open_tree(name)?);
+            batches.push(batch);
+        }
+
+        trees.as_slice().transaction(|trees| {
+            for (tree, batch) in trees.iter().zip(batches.iter()) {
+                tree.apply_batch(batch)?;
+            }
+            Ok::<_, ConflictableTransactionError>(())
+        }).map_err(|e| match e {
+            TransactionError::Abort(e) | TransactionError::Storage(e) => e
+        })?;
+
+        Ok(())
     }
 
     pub(super) fn load_optional_from_disk<T: Serializer>(&self, tree: &Tree, key: &[u8]) -> Result<Option<T>, BlockchainError> {
-        match tree.get(key)? {
+        match self.get_raw(tree, key)? {
             Some(bytes) => {
                 let bytes = bytes.to_vec();
                 let mut reader = Reader::new(&bytes);
@@ -257,7 +397,7 @@ impl SledStorage {
     }
 
     pub(super) fn load_from_disk<T: Serializer>(&self, tree: &Tree, key: &[u8]) -> Result<T, BlockchainError> {
-        match tree.get(key)? {
+        match self.get_raw(tree, key)? {
             Some(bytes) => {
                 let bytes = bytes.to_vec();
                 let mut reader = Reader::new(&bytes);
@@ -303,7 +443,7 @@ impl SledStorage {
     }
 
     pub(super) async fn delete_cacheable_data<K: Eq + StdHash + Serializer + Clone, V: Serializer>(&self, tree: &Tree, cache: &Option<Mutex<LruCache<K, V>>>, key: &K) -> Result<V, BlockchainError> {
-        let bytes = match tree.remove(key.to_bytes())? {
+        let bytes = match self.remove_from_disk(tree, key.to_bytes())? {
             Some(data) => data.to_vec(),
             None => return Err(BlockchainError::NotFoundOnDisk(DiskContext::DeleteData))
         };
@@ -321,7 +461,7 @@ impl SledStorage {
     }
 
     pub(super) async fn delete_data<K: Eq + StdHash + Serializer + Clone, V: Serializer>(&self, tree: &Tree, cache: &Option<Mutex<LruCache<K, Arc<V>>>>, key: &K) -> Result<Arc<V>, BlockchainError> {
-        let bytes = match tree.remove(key.to_bytes())? {
+        let bytes = match self.remove_from_disk(tree, key.to_bytes())? {
             Some(data) => data.to_vec(),
             None => return Err(BlockchainError::NotFoundOnDisk(DiskContext::DeleteData))
         };
@@ -341,26 +481,26 @@ impl SledStorage {
     pub(super) async fn contains_data<K: Eq + StdHash + Serializer + Clone, V>(&self, tree: &Tree, cache: &Option<Mutex<LruCache<K, V>>>, key: &K) -> Result<bool, BlockchainError> {
         if let Some(cache) = cache {
             let cache = cache.lock().await;
-            return Ok(cache.contains(key) || tree.contains_key(&key.to_bytes())?)
+            return Ok(cache.contains(key) || self.contains_key(tree, &key.to_bytes())?)
         }
 
-        Ok(tree.contains_key(&key.to_bytes())?)
+        self.contains_key(tree, &key.to_bytes())
     }
 
     // Update the assets count and store it on disk
     pub(super) fn store_assets_count(&self, count: u64) -> Result<(), BlockchainError> {
         self.assets_count.store(count, Ordering::SeqCst);
-        self.extra.insert(ASSETS_COUNT, &count.to_be_bytes())?;
+        self.insert_into_disk(&self.extra, ASSETS_COUNT, &count.to_be_bytes())?;
         Ok(())
     }
 
     fn delete_versioned_tree_above_topoheight(&self, tree: &Tree, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("delete versioned nonces above or at topoheight {}", topoheight);
-        for el in tree.iter().keys() {
+        for el in self.iter_keys(tree) {
             let key = el?;
             let topo = u64::from_bytes(&key[0..8])?;
             if topo > topoheight {
-                tree.remove(&key)?;
+                self.remove_from_disk(tree, &key)?;
             }
         }
         Ok(())
@@ -368,11 +508,11 @@ impl SledStorage {
 
     fn delete_versioned_tree_below_topoheight(&self, tree: &Tree, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("delete versioned nonces above or at topoheight {}", topoheight);
-        for el in tree.iter().keys() {
+        for el in self.iter_keys(tree) {
             let key = el?;
             let topo = u64::from_bytes(&key[0..8])?;
             if topo < topoheight {
-                tree.remove(&key)?;
+                self.remove_from_disk(tree, &key)?;
             }
         }
         Ok(())
@@ -502,10 +642,10 @@ impl Storage for SledStorage {
 
     async fn delete_versioned_balances_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("delete versioned balances at topoheight {}", topoheight);
-        for el in self.versioned_balances.scan_prefix(&topoheight.to_be_bytes()) {
+        for el in self.scan_prefix(&self.versioned_balances, &topoheight.to_be_bytes()) {
             let (key, value) = el?;
             // Delete this version from DB
-            self.versioned_balances.remove(&key)?;
+            self.remove_from_disk(&self.versioned_balances, &key)?;
 
             // Deserialize keys part
             let asset = Hash::from_bytes(&key[40..72])?;
@@ -519,11 +659,11 @@ impl Storage for SledStorage {
                 // Now records changes, for each balances
                 let db_key = self.get_balance_key_for(&key, &asset);
                 if let Some(previous_topoheight) = versioned_balance.get_previous_topoheight() {
-                    self.balances.insert(&db_key, &previous_topoheight.to_be_bytes())?;
+                    self.insert_into_disk(&self.balances, &db_key, &previous_topoheight.to_be_bytes())?;
                 } else {
                     // if there is no previous topoheight, it means that this is the first version
                     // so we can delete the balance
-                    self.balances.remove(&db_key)?;
+                    self.remove_from_disk(&self.balances, &db_key)?;
                 }
             }
         }
@@ -533,10 +673,10 @@ impl Storage for SledStorage {
 
     async fn delete_versioned_nonces_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("delete versioned nonces at topoheight {}", topoheight);
-        for el in self.versioned_nonces.scan_prefix(&topoheight.to_be_bytes()) {
+        for el in self.scan_prefix(&self.versioned_nonces, &topoheight.to_be_bytes()) {
             let (key, value) = el?;
             // Delete this version from DB
-            self.versioned_nonces.remove(&key)?;
+            self.remove_from_disk(&self.versioned_nonces, &key)?;
 
             // Deserialize keys part
             let key = PublicKey::from_bytes(&key[8..40])?;
@@ -574,13 +714,13 @@ impl Storage for SledStorage {
 
     async fn delete_registrations_above_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("delete registrations above topoheight {}", topoheight);
-        for el in self.registrations_prefixed.iter().keys() {
+        for el in self.iter_keys(&self.registrations_prefixed) {
             let key = el?;
             let topo = u64::from_bytes(&key[0..8])?;
             if topo > topoheight {
-                self.registrations_prefixed.remove(&key)?;
+                self.remove_from_disk(&self.registrations_prefixed, &key)?;
                 let pkey = &key[8..40];
-                self.registrations.remove(&pkey)?;
+                self.remove_from_disk(&self.registrations, &pkey)?;
             }
         }
 
@@ -590,15 +730,15 @@ impl Storage for SledStorage {
     async fn delete_registrations_below_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("delete registrations below topoheight {}", topoheight);
         let mut buf = [0u8; 40];
-        for el in self.registrations.iter() {
+        for el in self.iter(&self.registrations) {
             let (key, value) = el?;
             let topo = u64::from_bytes(&value[0..8])?;
             if topo < topoheight {
                 buf[0..8].copy_from_slice(&value);
                 buf[8..40].copy_from_slice(&key);
 
-                self.registrations_prefixed.remove(&buf)?;
-                self.registrations.remove(&key)?;
+                self.remove_from_disk(&self.registrations_prefixed, &buf)?;
+                self.remove_from_disk(&self.registrations, &key)?;
             }
         }
 
@@ -617,7 +757,7 @@ impl Storage for SledStorage {
 
     async fn create_snapshot_balances_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
         // asset tree where PublicKey are stored with the highest balance topoheight in it
-        for el in self.balances.iter() {
+        for el in self.iter(&self.balances) {
             let (key_bytes, value) = el?;
             let key = PublicKey::from_bytes(&key_bytes[0..32])?;
             let asset = Hash::from_bytes(&key_bytes[32..64])?;
@@ -631,13 +771,13 @@ impl Storage for SledStorage {
             // otherwise, delete the previous topoheight in VersionedBalance which is under topoheight
             if highest_balance_topoheight < topoheight {
                 // save the new highest topoheight
-                self.balances.insert(&key_bytes, &topoheight.to_be_bytes())?;
+                self.insert_into_disk(&self.balances, &key_bytes, &topoheight.to_be_bytes())?;
                 // remove the previous topoheight
                 versioned_balance.set_previous_topoheight(None);
 
                 // save it
                 let key = self.get_versioned_balance_key(&key, &asset, topoheight);
-                self.versioned_balances.insert(key, versioned_balance.to_bytes())?;
+                self.insert_into_disk(&self.versioned_balances, key, versioned_balance.to_bytes())?;
             } else {
                 // find the first VersionedBalance which is under topoheight
                 while let Some(previous_topoheight) = versioned_balance.get_previous_topoheight() {
@@ -645,7 +785,7 @@ impl Storage for SledStorage {
                         versioned_balance.set_previous_topoheight(None);
                         // save it
                         let key = self.get_versioned_balance_key(&key, &asset, topoheight);
-                        self.versioned_balances.insert(key, versioned_balance.to_bytes())?;
+                        self.insert_into_disk(&self.versioned_balances, key, versioned_balance.to_bytes())?;
                         break;
                     }
 
@@ -661,7 +801,7 @@ impl Storage for SledStorage {
     async fn create_snapshot_nonces_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
         // tree where VersionedNonce are stored
         // tree where PublicKey are stored with the highest noce topoheight in it
-        for el in self.nonces.iter() {
+        for el in self.iter(&self.nonces) {
             let (key_bytes, value) = el?;
             let key = PublicKey::from_bytes(&key_bytes)?;
             let highest_topoheight = u64::from_bytes(&value)?;
@@ -674,13 +814,13 @@ impl Storage for SledStorage {
             // otherwise, delete the previous topoheight in VersionedNonce which is under topoheight
             if highest_topoheight < topoheight {
                 // save the new highest topoheight
-                self.nonces.insert(&key_bytes, &topoheight.to_be_bytes())?;
+                self.insert_into_disk(&self.nonces, &key_bytes, &topoheight.to_be_bytes())?;
                 // remove the previous topoheight
                 versioned_nonce.set_previous_topoheight(None);
 
                 // save it
                 let key = self.get_versioned_nonce_key(&key, topoheight);
-                self.versioned_nonces.insert(key, versioned_nonce.to_bytes())?;
+                self.insert_into_disk(&self.versioned_nonces, key, versioned_nonce.to_bytes())?;
             } else {
                 // find the first VersionedBalance which is under topoheight
                 while let Some(previous_topoheight) = versioned_nonce.get_previous_topoheight() {
@@ -688,7 +828,7 @@ impl Storage for SledStorage {
                         versioned_nonce.set_previous_topoheight(None);
                         // save it
                         let key = self.get_versioned_nonce_key(&key, topoheight);
-                        self.versioned_nonces.insert(key, versioned_nonce.to_bytes())?;
+                        self.insert_into_disk(&self.versioned_nonces, key, versioned_nonce.to_bytes())?;
                         break;
                     }
 
@@ -704,7 +844,7 @@ impl Storage for SledStorage {
     async fn create_snapshot_registrations_at_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
         // tree where PublicKey are stored with the registration topoheight in it
         let mut buf = [0u8; 40];
-        for el in self.registrations.iter() {
+        for el in self.iter(&self.registrations) {
             let (key, value) = el?;
             let registration_topo = u64::from_bytes(&value)?;
 
@@ -714,14 +854,14 @@ impl Storage for SledStorage {
                 // Delete the prefixed registration
                 buf[0..8].copy_from_slice(&value);
                 buf[8..40].copy_from_slice(&key);
-                self.registrations_prefixed.remove(&buf)?;
+                self.remove_from_disk(&self.registrations_prefixed, &buf)?;
 
                 // save the new registration topoheight
-                self.registrations.insert(&key, &topoheight.to_be_bytes())?;
+                self.insert_into_disk(&self.registrations, &key, &topoheight.to_be_bytes())?;
 
                 // Overwrite with the new topoheight
                 buf[0..8].copy_from_slice(&topoheight.to_be_bytes());
-                self.registrations_prefixed.insert(&buf, &[])?;
+                self.insert_into_disk(&self.registrations_prefixed, &buf, &[])?;
             }
         }
 
@@ -735,13 +875,58 @@ impl Storage for SledStorage {
 
     fn set_network(&mut self, network: &Network) -> Result<(), BlockchainError> {
         trace!("set network to {}", network);
-        self.extra.insert(NETWORK, network.to_bytes())?;
+        self.insert_into_disk(&self.extra, NETWORK, network.to_bytes())?;
         Ok(())
     }
 
     fn has_network(&self) -> Result<bool, BlockchainError> {
         trace!("has network");
-        Ok(self.extra.contains_key(NETWORK)?)
+        self.contains_key(&self.extra, NETWORK)
+    }
+
+    fn get_schema_version(&self) -> Result<Option<u64>, BlockchainError> {
+        trace!("get schema version");
+        self.load_optional_from_disk(&self.extra, SCHEMA_VERSION)
+    }
+
+    fn set_schema_version(&mut self, version: u64) -> Result<(), BlockchainError> {
+        trace!("set schema version to {}", version);
+        self.insert_into_disk(&self.extra, SCHEMA_VERSION, &version.to_be_bytes())?;
+        Ok(())
+    }
+
+    async fn start_commit_point(&mut self) -> Result<(), BlockchainError> {
+        trace!("start commit point");
+        if self.commit_point {
+            return Err(BlockchainError::CommitPointAlreadyStarted)
+        }
+
+        self.commit_point = true;
+        Ok(())
+    }
+
+    async fn end_commit_point(&mut self, apply: bool) -> Result<(), BlockchainError> {
+        trace!("end commit point (apply: {})", apply);
+        self.commit_point = false;
+        let changes = std::mem::replace(&mut *self.pending_changes(), PendingChanges::new());
+        let res = if apply && !changes.is_empty() {
+            self.write_pending_changes(changes)
+        } else {
+            Ok(())
+        };
+
+        if !apply || res.is_err() {
+            // Values in memory may be the ones of the changes discarded
+            self.clear_caches().await?;
+            self.load_cached_values()?;
+        }
+
+        res
+    }
+
+    fn has_commit_point(&self) -> Result<bool, BlockchainError> {
+        trace!("has commit point");
+        Ok(self.commit_point)
     }
 
     async fn pop_blocks(&mut self, mut height: u64, mut topoheight: u64, count: u64, stable_topo_height: u64) -> Result<(u64, u64, Vec<(Hash, Arc<Transaction>)>), BlockchainError> {
@@ -807,7 +992,7 @@ impl Storage for SledStorage {
                 tips.clear();
                 tips.insert(self.get_hash_at_topo_height(0).await?);
 
-                self.extra.remove(PRUNED_TOPOHEIGHT)?;
+                self.remove_from_disk(&self.extra, PRUNED_TOPOHEIGHT)?;
                 self.pruned_topoheight = None;
 
                 break 'main;
@@ -829,7 +1014,7 @@ impl Storage for SledStorage {
         let mut deleted_assets = HashSet::new();
         
         // clean all assets
-        for el in self.assets.iter() {
+        for el in self.iter(&self.assets) {
             let (key, value) = el.context("error on asset iterator")?;
             let asset = Hash::from_bytes(&key)?;
             trace!("verifying asset registered: {}", asset);
@@ -838,7 +1023,7 @@ impl Storage for SledStorage {
             if registration_topoheight > topoheight {
                 trace!("Asset {} was registered at topoheight {}, deleting", asset, registration_topoheight);
                 // Delete it from registered assets
-                self.assets.remove(&key).context(format!("Error while deleting asset {asset} from registered assets"))?;
+                self.remove_from_disk(&self.assets, &key).context(format!("Error while deleting asset {asset} from registered assets"))?;
 
                 // drop the tree for this asset
                 self.db.drop_tree(key).context(format!("error on dropping asset {asset} tree"))?;
@@ -850,17 +1035,17 @@ impl Storage for SledStorage {
         trace!("Cleaning nonces");
         // now let's process nonces versions
         // we set the new highest topoheight to the highest found under the new topoheight
-        for el in self.nonces.iter() {
+        for el in self.iter(&self.nonces) {
             let (key, value) = el?;
             let highest_topoheight = u64::from_bytes(&value)?;
             if highest_topoheight < pruned_topoheight {
                 warn!("wrong nonce topoheight stored, highest topoheight is {}, pruned topoheight is {}", highest_topoheight, pruned_topoheight);
-                self.nonces.remove(key)?;
+                self.remove_from_disk(&self.nonces, key)?;
                 continue;
             }
 
             if highest_topoheight > topoheight {
-                if self.nonces.remove(&key)?.is_some() {
+                if self.remove_from_disk(&self.nonces, &key)?.is_some() {
                     self.store_accounts_count(self.count_accounts().await? - 1)?;
                 }
 
@@ -873,7 +1058,7 @@ impl Storage for SledStorage {
                     if previous_topoheight < topoheight {
                         // we find the new highest version which is under new topoheight
                         trace!("New highest version nonce for {} is at topoheight {}", pkey.as_address(self.is_mainnet()), previous_topoheight);
-                        if self.nonces.insert(&key, &previous_topoheight.to_be_bytes())?.is_none() {
+                        if self.insert_into_disk(&self.nonces, &key, &previous_topoheight.to_be_bytes())?.is_none() {
                             self.store_accounts_count(self.count_accounts().await? + 1)?;
                         }
                         break;
@@ -890,7 +1075,7 @@ impl Storage for SledStorage {
 
         trace!("Cleaning balances");
         // do balances too
-        for el in self.balances.iter() {
+        for el in self.iter(&self.balances) {
             let (key, value) = el?;
             let asset = Hash::from_bytes(&key[32..64])?;
             let mut delete = true;
@@ -910,7 +1095,7 @@ impl Storage for SledStorage {
                         if previous_topoheight < topoheight {
                             // we find the new highest version which is under new topoheight
                             trace!("New highest version balance for {} is at topoheight {} with asset {}", pkey.as_address(self.is_mainnet()), previous_topoheight, asset);
-                            self.balances.insert(&key, &previous_topoheight.to_be_bytes())?;
+                            self.insert_into_disk(&self.balances, &key, &previous_topoheight.to_be_bytes())?;
                             delete = false;
                             break;
                         }
@@ -922,7 +1107,7 @@ impl Storage for SledStorage {
             }
 
             if delete {
-                self.balances.remove(&key)?;
+                self.remove_from_disk(&self.balances, &key)?;
             }
         }
 
@@ -948,7 +1133,7 @@ impl Storage for SledStorage {
 
         // Reduce the count of blocks stored
         let count = self.count_blocks().await? - done;
-        self.extra.insert(BLOCKS_COUNT, &count.to_be_bytes())?;
+        self.insert_into_disk(&self.extra, BLOCKS_COUNT, &count.to_be_bytes())?;
 
         Ok((height, topoheight, txs))
     }
@@ -965,7 +1150,7 @@ impl Storage for SledStorage {
 
     fn set_top_topoheight(&mut self, topoheight: u64) -> Result<(), BlockchainError> {
         trace!("set new top topoheight at {}", topoheight);
-        self.extra.insert(TOP_TOPO_HEIGHT, &topoheight.to_be_bytes())?;
+        self.insert_into_disk(&self.extra, TOP_TOPO_HEIGHT, &topoheight.to_be_bytes())?;
         Ok(())
     }
 
@@ -976,7 +1161,7 @@ impl Storage for SledStorage {
 
     fn set_top_height(&mut self, height: u64) -> Result<(), BlockchainError> {
         trace!("set new top height at {}", height);
-        self.extra.insert(TOP_HEIGHT, &height.to_be_bytes())?;
+        self.insert_into_disk(&self.extra, TOP_HEIGHT, &height.to_be_bytes())?;
         Ok(())
     }
 
@@ -1006,7 +1191,7 @@ impl Storage for SledStorage {
 
     fn store_tips(&mut self, tips: &Tips) -> Result<(), BlockchainError> {
         trace!("Saving {} Tips", tips.len());
-        self.extra.insert(TIPS, tips.to_bytes())?;
+        self.insert_into_disk(&self.extra, TIPS, tips.to_bytes())?;
         self.tips_cache = tips.clone();
         Ok(())
     }
@@ -1016,6 +1201,12 @@ impl Storage for SledStorage {
         Ok(self.db.size_on_disk()?)
     }
 
+    async fn flush(&mut self) -> Result<(), BlockchainError> {
+        trace!("flush");
+        self.db.flush_async().await?;
+        Ok(())
+    }
+
     async fn stop(&mut self) -> Result<(), BlockchainError> {
         info!("Flushing Sled database");
         self.db.flush_async().await?;
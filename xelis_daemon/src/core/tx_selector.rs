@@ -1,19 +1,21 @@
 use std::{
     collections::{
         VecDeque,
-        BinaryHeap,
-        HashMap,
-        hash_map::Entry
+        BinaryHeap
     },
+    fmt::{Display, Formatter},
+    str::FromStr,
     sync::Arc,
-    cmp::Ordering
+    cmp::{Ordering, Reverse}
 };
 use xelis_common::{
+    api::daemon::SkippedTransactionReason,
     transaction::Transaction,
     crypto::{
         Hash,
-        PublicKey
-    }
+        HASH_SIZE
+    },
+    time::TimestampSeconds
 };
 
 // this struct is used to store transaction with its hash and its size in bytes
@@ -23,7 +25,16 @@ pub struct TxSelectorEntry<'a> {
     // Current transaction
     pub tx: &'a Arc<Transaction>,
     // Size in bytes of the TX
-    pub size: usize
+    pub size: usize,
+    // Timestamp when the TX was added in mempool
+    pub first_seen: TimestampSeconds
+}
+
+impl TxSelectorEntry<'_> {
+    // Size used in a block by this TX: its hash in the header and its content
+    pub fn get_block_size(&self) -> usize {
+        self.size + HASH_SIZE
+    }
 }
 
 impl PartialEq for TxSelectorEntry<'_> {
@@ -34,94 +45,411 @@ impl PartialEq for TxSelectorEntry<'_> {
 
 impl Eq for TxSelectorEntry<'_> {}
 
-// this struct is used to store transactions in a queue
-// and to order them by fees
-// Each Transactions is for a specific sender
+// Result of the transactions selection for a block template
+pub struct TxSelection<'a> {
+    // TXs included in the block, in order
+    pub selected: Vec<TxSelectorEntry<'a>>,
+    // TXs not included with the reason and the verification error if any
+    pub skipped: Vec<(TxSelectorEntry<'a>, SkippedTransactionReason, Option<String>)>
+}
+
+// Strategy used to order the mempool transactions when building a block template
+// Transactions are given grouped by sender, each group being ordered by nonce
+// The returned list must keep the nonce order of each sender
+// Transactions are then verified and included in this order until the block is full
+pub trait TxSelectionStrategy: Send + Sync {
+    // Name of the strategy, used in logs and RPC
+    fn name(&self) -> &'static str;
+
+    // Order the transactions for a block having available_size bytes left for them
+    // Transactions that are not returned are not included in the block
+    fn select<'a>(&self, groups: Vec<Vec<TxSelectorEntry<'a>>>, available_size: usize) -> Vec<TxSelectorEntry<'a>>;
+}
+
+// Built-in strategies that can be configured on the node
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TxSelectionStrategyType {
+    // Highest fee per byte first
+    FeePerByte,
+    // Oldest transaction in mempool first
+    Fifo,
+    // Set of transactions with the highest total fees fitting in the block
+    Knapsack,
+}
+
+impl TxSelectionStrategyType {
+    // Create the strategy implementation
+    pub fn build(&self) -> Box<dyn TxSelectionStrategy> {
+        match self {
+            Self::FeePerByte => Box::new(FeePerByteStrategy),
+            Self::Fifo => Box::new(FifoStrategy),
+            Self::Knapsack => Box::new(KnapsackStrategy)
+        }
+    }
+}
+
+impl FromStr for TxSelectionStrategyType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "fee_per_byte" => Self::FeePerByte,
+            "fifo" => Self::Fifo,
+            "knapsack" => Self::Knapsack,
+            _ => return Err("Invalid tx selection strategy".into())
+        })
+    }
+}
+
+impl Display for TxSelectionStrategyType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let str = match &self {
+            Self::FeePerByte => "fee_per_byte",
+            Self::Fifo => "fifo",
+            Self::Knapsack => "knapsack",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+// Fee per byte of a TX, compared without losing precision
 #[derive(PartialEq, Eq)]
-struct Transactions<'a>(VecDeque<TxSelectorEntry<'a>>);
+struct FeeRate {
+    fee: u64,
+    size: usize
+}
 
-impl PartialOrd for Transactions<'_> {
+impl FeeRate {
+    fn new(entry: &TxSelectorEntry) -> Self {
+        Self {
+            fee: entry.tx.get_fee(),
+            size: entry.size.max(1)
+        }
+    }
+}
+
+impl PartialOrd for FeeRate {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.0.front().map(|e| e.tx.get_fee()).partial_cmp(&other.0.front().map(|e| e.tx.get_fee()))
+        Some(self.cmp(other))
     }
 }
 
-impl Ord for Transactions<'_> {
+impl Ord for FeeRate {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.0.front().map(|e| e.tx.get_fee()).cmp(&other.0.front().map(|e| e.tx.get_fee()))
+        (self.fee as u128 * other.size as u128).cmp(&(other.fee as u128 * self.size as u128))
+    }
+}
+
+// Merge all groups in one list, by always taking the next transaction of the group
+// having the highest priority
+// Because only the first transaction of each group is compared, the nonce order is kept
+fn merge_groups<'a, K: Ord>(groups: Vec<Vec<TxSelectorEntry<'a>>>, priority: impl Fn(&TxSelectorEntry<'a>) -> K) -> Vec<TxSelectorEntry<'a>> {
+    let mut groups: Vec<VecDeque<TxSelectorEntry<'a>>> = groups.into_iter().map(VecDeque::from).collect();
+    let mut entries = Vec::with_capacity(groups.iter().map(|group| group.len()).sum());
+
+    // the index is reversed to have a deterministic order on equal priorities
+    let mut queue = BinaryHeap::with_capacity(groups.len());
+    for (i, group) in groups.iter().enumerate() {
+        if let Some(entry) = group.front() {
+            queue.push((priority(entry), Reverse(i)));
+        }
+    }
+
+    while let Some((_, Reverse(i))) = queue.pop() {
+        let group = &mut groups[i];
+        if let Some(entry) = group.pop_front() {
+            if let Some(next) = group.front() {
+                queue.push((priority(next), Reverse(i)));
+            }
+            entries.push(entry);
+        }
+    }
+
+    entries
+}
+
+// Select the transactions paying the highest fee per byte first
+pub struct FeePerByteStrategy;
+
+impl TxSelectionStrategy for FeePerByteStrategy {
+    fn name(&self) -> &'static str {
+        "fee_per_byte"
+    }
+
+    fn select<'a>(&self, groups: Vec<Vec<TxSelectorEntry<'a>>>, _: usize) -> Vec<TxSelectorEntry<'a>> {
+        merge_groups(groups, FeeRate::new)
     }
 }
 
-// TX selector is used to select transactions from the mempool
-// It create sub groups of transactions by sender and order them by nonces
-// It joins all sub groups in a queue that is ordered by fees
-pub struct TxSelector<'a> {
-    queue: BinaryHeap<Transactions<'a>>
+// Select the transactions that are in the mempool since the longest time first
+pub struct FifoStrategy;
+
+impl TxSelectionStrategy for FifoStrategy {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn select<'a>(&self, groups: Vec<Vec<TxSelectorEntry<'a>>>, _: usize) -> Vec<TxSelectorEntry<'a>> {
+        merge_groups(groups, |entry| Reverse(entry.first_seen))
+    }
 }
 
-impl<'a> TxSelector<'a> {
-    // Create a TxSelector from a list of groups
-    pub fn grouped<I>(groups: I) -> Self
-    where
-        I: Iterator<Item = Vec<TxSelectorEntry<'a>>>
-    {
-        let mut queue = BinaryHeap::new();
+// Maximum capacity units used by the knapsack
+// Sizes are rounded up to units of available_size / KNAPSACK_UNITS bytes to keep it fast
+const KNAPSACK_UNITS: usize = 2048;
 
-        // push every group to the queue
-        for group in groups {
-            queue.push(Transactions(VecDeque::from(group)));
+// Select the set of transactions with the highest total fees that fits in the block
+// Only the first N transactions of a sender can be selected, as they depend on each other
+// The leftover space is filled by the remaining transactions ordered by fee per byte
+pub struct KnapsackStrategy;
+
+impl TxSelectionStrategy for KnapsackStrategy {
+    fn name(&self) -> &'static str {
+        "knapsack"
+    }
+
+    fn select<'a>(&self, groups: Vec<Vec<TxSelectorEntry<'a>>>, available_size: usize) -> Vec<TxSelectorEntry<'a>> {
+        let unit = (available_size / KNAPSACK_UNITS).max(1);
+        let capacity = available_size / unit;
+
+        // (cost in units, total fees) for each prefix of each group
+        let prefixes: Vec<Vec<(usize, u64)>> = groups.iter().map(|group| {
+            let mut size = 0usize;
+            let mut fees = 0u64;
+            group.iter().take(u16::MAX as usize).map(|entry| {
+                size += entry.get_block_size();
+                fees = fees.saturating_add(entry.tx.get_fee());
+                // round up so the selected set always fits
+                (size.div_ceil(unit), fees)
+            }).collect()
+        }).collect();
+
+        // best[c] is the highest fees with a cost of at most c
+        // choices[g][c] is the prefix length selected for group g at cost c
+        let mut best = vec![0u64; capacity + 1];
+        let mut choices: Vec<Vec<u16>> = Vec::with_capacity(groups.len());
+        for group in prefixes.iter() {
+            let mut next = best.clone();
+            let mut choice = vec![0u16; capacity + 1];
+            for (i, (cost, fees)) in group.iter().enumerate() {
+                if *cost > capacity {
+                    break;
+                }
+
+                for c in *cost..=capacity {
+                    let value = best[c - cost].saturating_add(*fees);
+                    if value > next[c] {
+                        next[c] = value;
+                        choice[c] = (i + 1) as u16;
+                    }
+                }
+            }
+            best = next;
+            choices.push(choice);
         }
 
-        Self {
-            queue
+        // Find the selected prefix of each group
+        let mut selected = vec![0usize; groups.len()];
+        let mut c = capacity;
+        for (g, choice) in choices.iter().enumerate().rev() {
+            let len = choice[c] as usize;
+            if len > 0 {
+                c -= prefixes[g][len - 1].0;
+                selected[g] = len;
+            }
+        }
+
+        let mut chosen = Vec::with_capacity(groups.len());
+        let mut remaining = Vec::with_capacity(groups.len());
+        for (mut group, len) in groups.into_iter().zip(selected) {
+            remaining.push(group.split_off(len));
+            chosen.push(group);
         }
+
+        let mut entries = merge_groups(chosen, FeeRate::new);
+        entries.extend(merge_groups(remaining, FeeRate::new));
+        entries
     }
+}
 
-    // Create a TxSelector from a list of transactions with their hash and size
-    pub fn new<I>(iter: I) -> Self
-    where
-        I: Iterator<Item = (usize, &'a Arc<Hash>, &'a Arc<Transaction>)>
-    {
-        let mut groups: HashMap<&PublicKey, Vec<TxSelectorEntry>> = HashMap::new();
-
-        // Create groups of transactions
-        for (size, hash, tx) in iter {
-            let entry = TxSelectorEntry {
-                hash,
-                tx,
-                size
-            };
-
-            match groups.entry(tx.get_source()) {
-                Entry::Occupied(mut e) => {
-                    e.get_mut().push(entry);
-                },
-                Entry::Vacant(e) => {
-                    e.insert(vec![entry]);
-                }
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use xelis_common::{crypto::Hashable, transaction::builder::FeeBuilder};
+    use crate::core::blockchain::tests::TestAccount;
+    use super::*;
+
+    // TX with the size and first seen timestamp used by the selection
+    struct TestTx {
+        hash: Arc<Hash>,
+        tx: Arc<Transaction>,
+        size: usize,
+        first_seen: TimestampSeconds
+    }
+
+    // Build the TXs of a new sender in nonce order from their (fee, size, first seen)
+    fn create_group(txs: &[(u64, usize, TimestampSeconds)]) -> Vec<TestTx> {
+        let mut account = TestAccount::detached(1_000_000);
+        txs.iter().map(|(fee, size, first_seen)| {
+            let tx = account.build_burn_tx_with_fee(FeeBuilder::Value(*fee));
+            TestTx {
+                hash: Arc::new(tx.hash()),
+                tx: Arc::new(tx),
+                size: *size,
+                first_seen: *first_seen
+            }
+        }).collect()
+    }
+
+    fn to_entries(groups: &[Vec<TestTx>]) -> Vec<Vec<TxSelectorEntry>> {
+        groups.iter().map(|group| group.iter().map(|tx| TxSelectorEntry {
+            hash: &tx.hash,
+            tx: &tx.tx,
+            size: tx.size,
+            first_seen: tx.first_seen
+        }).collect()).collect()
+    }
+
+    // Position of each selected TX as (group, index in the group)
+    fn positions(groups: &[Vec<TestTx>], entries: &[TxSelectorEntry]) -> Vec<(usize, usize)> {
+        entries.iter().map(|entry| {
+            groups.iter().enumerate()
+                .find_map(|(g, group)| group.iter().position(|tx| tx.hash == *entry.hash).map(|i| (g, i)))
+                .unwrap()
+        }).collect()
+    }
+
+    fn select(strategy: TxSelectionStrategyType, groups: &[Vec<TestTx>], available_size: usize) -> Vec<(usize, usize)> {
+        let entries = strategy.build().select(to_entries(groups), available_size);
+        positions(groups, &entries)
+    }
+
+    // Fill a block like the block template: once a TX of a sender doesn't fit, its next TXs are skipped
+    // Returns the size used and the fees of the included TXs
+    fn fill_block(entries: &[TxSelectorEntry], available_size: usize) -> (usize, u64) {
+        let mut skipped = HashSet::new();
+        let (mut size, mut fees) = (0, 0);
+        for entry in entries {
+            let source = entry.tx.get_source();
+            if skipped.contains(source) {
+                continue;
+            }
+
+            if size + entry.get_block_size() > available_size {
+                skipped.insert(source);
+                continue;
             }
+
+            size += entry.get_block_size();
+            fees += entry.tx.get_fee();
         }
+        (size, fees)
+    }
+
+    #[test]
+    fn test_merge_groups() {
+        let groups = vec![
+            create_group(&[(1, 100, 0), (100, 100, 0)]),
+            create_group(&[(50, 100, 0)]),
+            Vec::new(),
+            create_group(&[(50, 100, 0), (10, 100, 0)])
+        ];
+
+        // Only the next TX of each sender is compared, equal priorities keep the groups order
+        let entries = merge_groups(to_entries(&groups), |entry| entry.tx.get_fee());
+        assert_eq!(positions(&groups, &entries), vec![(1, 0), (3, 0), (3, 1), (0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn test_fee_per_byte() {
+        let groups = vec![
+            create_group(&[(100, 100, 0), (10000, 100, 0)]),
+            create_group(&[(500, 100, 0)]),
+            // slightly above the first TX of the first group
+            create_group(&[(201, 200, 0)])
+        ];
 
-        // Order each group by nonces and push it to the queue
-        let iter = groups.into_iter().map(|(_, mut v)| {
-            v.sort_by(|a, b| a.tx.get_nonce().cmp(&b.tx.get_nonce()));
-            v
-        });
-        Self::grouped(iter)
+        // The TX paying the most can't be selected before the previous TX of its sender
+        assert_eq!(select(TxSelectionStrategyType::FeePerByte, &groups, 10_000), vec![(1, 0), (2, 0), (0, 0), (0, 1)]);
     }
 
-    // Get the next transaction with the highest fee
-    pub fn next(&mut self) -> Option<TxSelectorEntry<'a>> {
-        // get the group with the highest fee
-        let mut group = self.queue.pop()?;
-        // get the entry with the highest fee from this group
-        let entry = group.0.pop_front()?;
+    #[test]
+    fn test_fifo() {
+        let groups = vec![
+            create_group(&[(100, 100, 10), (100, 100, 1)]),
+            create_group(&[(100, 100, 5)]),
+            create_group(&[(100, 100, 10)])
+        ];
 
-        // if its not empty, push it back to the queue
-        if !group.0.is_empty() {
-            self.queue.push(group);
+        assert_eq!(select(TxSelectionStrategyType::Fifo, &groups, 10_000), vec![(1, 0), (0, 0), (0, 1), (2, 0)]);
+    }
+
+    #[test]
+    fn test_knapsack() {
+        let size = 500 - HASH_SIZE;
+        // Only two TXs fit: the first group pays the most with both its TXs
+        let groups = vec![
+            create_group(&[(10, size, 0), (2000, size, 0)]),
+            create_group(&[(600, size, 0)]),
+            create_group(&[(700, size, 0)])
+        ];
+        // The leftover TXs are ordered by fee per byte
+        assert_eq!(select(TxSelectionStrategyType::Knapsack, &groups, 1000), vec![(0, 0), (0, 1), (2, 0), (1, 0)]);
+
+        // The TX with the best fee per byte doesn't leave enough space for another one
+        let groups = vec![
+            create_group(&[(700, 600 - HASH_SIZE, 0)]),
+            create_group(&[(500, size, 0)]),
+            create_group(&[(500, size, 0)])
+        ];
+        assert_eq!(select(TxSelectionStrategyType::FeePerByte, &groups, 1000), vec![(0, 0), (1, 0), (2, 0)]);
+        assert_eq!(select(TxSelectionStrategyType::Knapsack, &groups, 1000), vec![(1, 0), (2, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn test_nonce_order() {
+        // Second TX of the first sender is missing
+        let mut first = create_group(&[(10, 100, 3), (10, 100, 3), (5000, 100, 2), (10, 100, 0)]);
+        first.remove(1);
+        let groups = vec![
+            first,
+            create_group(&[(1000, 100, 1), (100, 100, 0)])
+        ];
+
+        for strategy in [TxSelectionStrategyType::FeePerByte, TxSelectionStrategyType::Fifo, TxSelectionStrategyType::Knapsack] {
+            let selected = select(strategy, &groups, 10_000);
+            assert_eq!(selected.len(), 5, "{}", strategy);
+            for g in 0..groups.len() {
+                // TXs of a sender are always in nonce order, even with a gap
+                let nonces: Vec<u64> = selected.iter()
+                    .filter(|(group, _)| *group == g)
+                    .map(|(group, i)| groups[*group][*i].tx.get_nonce())
+                    .collect();
+                assert!(nonces.windows(2).all(|w| w[0] < w[1]), "{}", strategy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_size_limit() {
+        let available_size = 1000;
+        let groups = vec![
+            // bigger than the block
+            create_group(&[(100_000, available_size, 0)]),
+            create_group(&[(700, 600 - HASH_SIZE, 0)]),
+            create_group(&[(500, 500 - HASH_SIZE, 0), (500, 500 - HASH_SIZE, 0)]),
+            create_group(&[(500, 500 - HASH_SIZE, 0)])
+        ];
+
+        let mut fees = Vec::new();
+        for strategy in [TxSelectionStrategyType::FeePerByte, TxSelectionStrategyType::Fifo, TxSelectionStrategyType::Knapsack] {
+            let entries = strategy.build().select(to_entries(&groups), available_size);
+            let (size, total_fees) = fill_block(&entries, available_size);
+            assert!(size <= available_size, "{}", strategy);
+            fees.push(total_fees);
         }
 
-        Some(entry)
+        // fee_per_byte and fifo are stuck with the first TX that fits
+        assert_eq!(fees, vec![700, 700, 1000]);
     }
-}
\ No newline at end of file
+}
@@ -2,6 +2,7 @@ pub mod rpc;
 pub mod p2p;
 pub mod core;
 pub mod config;
+pub mod metrics;
 
 use config::{DEV_PUBLIC_KEY, STABLE_LIMIT};
 use fern::colors::Color;
@@ -17,6 +18,7 @@ use xelis_common::{
     config::{VERSION, XELIS_ASSET},
     context::Context,
     crypto::{
+        elgamal::CompressedPublicKey,
         Address,Hashable
     },
     difficulty::Difficulty,
@@ -55,8 +57,11 @@ use crate::{
             Blockchain,
             get_block_reward
         },
+        snapshot,
         storage::{
+            migrate_storage,
             Storage,
+            StorageBackend,
             SledStorage
         }
     },
@@ -66,14 +71,17 @@ use crate::{
     }
 };
 use core::blockdag;
+#[cfg(feature = "rocksdb")]
+use core::storage::RocksStorage;
 use std::{
+    cmp::Reverse,
     fs::File,
     io::Write,
     net::{IpAddr, SocketAddr},
     sync::Arc,
     time::Duration
 };
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 use anyhow::{
     Result,
     Context as AnyContext
@@ -106,7 +114,31 @@ pub struct NodeConfig {
     logs_path: String,
     /// Network selected for chain
     #[clap(long, value_enum, default_value_t = Network::Mainnet)]
-    network: Network
+    network: Network,
+    /// Command to execute instead of starting the node
+    #[clap(subcommand)]
+    command: Option<NodeCommand>
+}
+
+#[derive(Subcommand)]
+pub enum NodeCommand {
+    /// Copy the whole chain into a fresh database and exit.
+    /// 
+    /// Source database is selected using --dir-path, --storage-backend and --network.
+    /// The node must not be running while migrating.
+    Migrate(MigrateConfig)
+}
+
+#[derive(Args)]
+pub struct MigrateConfig {
+    /// Set dir path for the destination database.
+    /// This will be appended by the network name for the database directory.
+    /// It must ends with a slash and be different from the source dir path.
+    #[clap(long)]
+    output_dir_path: String,
+    /// Storage backend to use for the destination database.
+    #[clap(long, default_value_t = StorageBackend::Sled)]
+    output_storage_backend: StorageBackend
 }
 
 const BLOCK_TIME: Difficulty = Difficulty::from_u64(BLOCK_TIME_MILLIS / MILLIS_PER_SECOND);
@@ -131,19 +163,87 @@ async fn main() -> Result<()> {
         }
     }
 
-    let storage = {
-        let use_cache = if blockchain_config.cache_size > 0 {
-            Some(blockchain_config.cache_size)
-        } else {
-            None
-        };
-
-        let dir_path = blockchain_config.dir_path.clone().unwrap_or_default();
-        SledStorage::new(dir_path, use_cache, config.network)?
+    let use_cache = if blockchain_config.cache_size > 0 {
+        Some(blockchain_config.cache_size)
+    } else {
+        None
     };
 
-    let blockchain = Blockchain::new(blockchain_config, config.network, storage).await?;
-    if let Err(e) = run_prompt(prompt, blockchain.clone(), config.network).await {
+    let dir_path = blockchain_config.dir_path.clone().unwrap_or_default();
+    info!("Using {} storage backend", blockchain_config.storage_backend);
+
+    if let Some(NodeCommand::Migrate(migrate_config)) = config.command {
+        let output_dir_path = &migrate_config.output_dir_path;
+        if !(output_dir_path.ends_with("/") || output_dir_path.ends_with("\\")) {
+            return Err(anyhow::anyhow!("Output path must end with / or \\"));
+        }
+
+        if *output_dir_path == dir_path {
+            return Err(anyhow::anyhow!("Output path must be different from the source path"));
+        }
+
+        return match blockchain_config.storage_backend {
+            StorageBackend::Sled => {
+                let storage = SledStorage::new(dir_path, use_cache, config.network)?;
+                migrate_chain(storage, migrate_config, config.network).await
+            },
+            #[cfg(feature = "rocksdb")]
+            StorageBackend::RocksDB => {
+                let storage = RocksStorage::new(dir_path, use_cache, config.network)?;
+                migrate_chain(storage, migrate_config, config.network).await
+            },
+            #[cfg(not(feature = "rocksdb"))]
+            StorageBackend::RocksDB => Err(rocksdb_unavailable())
+        }
+    }
+
+    match blockchain_config.storage_backend {
+        StorageBackend::Sled => {
+            let storage = SledStorage::new(dir_path, use_cache, config.network)?;
+            start_chain(prompt, blockchain_config, config.network, storage).await
+        },
+        #[cfg(feature = "rocksdb")]
+        StorageBackend::RocksDB => {
+            let storage = RocksStorage::new(dir_path, use_cache, config.network)?;
+            start_chain(prompt, blockchain_config, config.network, storage).await
+        },
+        #[cfg(not(feature = "rocksdb"))]
+        StorageBackend::RocksDB => Err(rocksdb_unavailable())
+    }
+}
+
+#[cfg(not(feature = "rocksdb"))]
+fn rocksdb_unavailable() -> anyhow::Error {
+    anyhow::anyhow!("RocksDB storage backend is not available, daemon must be compiled with the rocksdb feature")
+}
+
+// Copy the chain from the source storage into the destination configured
+async fn migrate_chain<S: Storage>(mut source: S, config: MigrateConfig, network: Network) -> Result<()> {
+    info!("Migrating chain to {} storage backend in {}", config.output_storage_backend, config.output_dir_path);
+    match config.output_storage_backend {
+        StorageBackend::Sled => {
+            let mut destination = SledStorage::new(config.output_dir_path, None, network)?;
+            migrate_storage(&source, &mut destination).await?;
+            destination.stop().await?;
+        },
+        #[cfg(feature = "rocksdb")]
+        StorageBackend::RocksDB => {
+            let mut destination = RocksStorage::new(config.output_dir_path, None, network)?;
+            migrate_storage(&source, &mut destination).await?;
+            destination.stop().await?;
+        },
+        #[cfg(not(feature = "rocksdb"))]
+        StorageBackend::RocksDB => return Err(rocksdb_unavailable())
+    }
+
+    source.stop().await?;
+    Ok(())
+}
+
+// Start the blockchain using the selected storage and run the prompt until exit
+async fn start_chain<S: Storage>(prompt: ShareablePrompt, config: Config, network: Network, storage: S) -> Result<()> {
+    let blockchain = Blockchain::new(config, network, storage).await?;
+    if let Err(e) = run_prompt(prompt, blockchain.clone(), network).await {
         error!("Error while running prompt: {}", e);
     }
 
@@ -172,12 +272,16 @@ async fn run_prompt<S: Storage>(prompt: ShareablePrompt, blockchain: Arc<Blockch
     command_manager.add_command(Command::new("status", "Current daemon status", CommandHandler::Async(async_handler!(status::<S>))))?;
     command_manager.add_command(Command::with_optional_arguments("blacklist", "View blacklist or add a peer address in it", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(blacklist::<S>))))?;
     command_manager.add_command(Command::with_optional_arguments("whitelist", "View whitelist or add a peer address in it", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(whitelist::<S>))))?;
-    command_manager.add_command(Command::with_optional_arguments("verify_chain", "Check chain supply", vec![Arg::new("topoheight", ArgType::Number)], CommandHandler::Async(async_handler!(verify_chain::<S>))))?;
+    command_manager.add_command(Command::with_optional_arguments("allow_identity", "View allowed identities or toggle a peer identity in it", vec![Arg::new("identity", ArgType::String)], CommandHandler::Async(async_handler!(allow_identity::<S>))))?;
+    command_manager.add_command(Command::with_optional_arguments("deny_identity", "View denied identities or toggle a peer identity in it", vec![Arg::new("identity", ArgType::String)], CommandHandler::Async(async_handler!(deny_identity::<S>))))?;
+    command_manager.add_command(Command::new("peer_scores", "Show the score of all connected peers", CommandHandler::Async(async_handler!(peer_scores::<S>))))?;
+    command_manager.add_command(Command::with_optional_arguments("verify_chain", "Check chain supply, or replay the whole chain in full mode", vec![Arg::new("topoheight", ArgType::Number), Arg::new("full", ArgType::Bool)], CommandHandler::Async(async_handler!(verify_chain::<S>))))?;
     command_manager.add_command(Command::with_required_arguments("kick_peer", "Kick a peer using its ip:port", vec![Arg::new("address", ArgType::String)], CommandHandler::Async(async_handler!(kick_peer::<S>))))?;
     command_manager.add_command(Command::new("clear_caches", "Clear storage caches", CommandHandler::Async(async_handler!(clear_caches::<S>))))?;
     command_manager.add_command(Command::new("clear_rpc_connections", "Clear all WS connections from RPC", CommandHandler::Async(async_handler!(clear_rpc_connections::<S>))))?;
     command_manager.add_command(Command::new("clear_p2p_connections", "Clear all P2P connections", CommandHandler::Async(async_handler!(clear_p2p_connections::<S>))))?;
     command_manager.add_command(Command::new("clear_p2p_peerlist", "Clear P2P peerlist", CommandHandler::Async(async_handler!(clear_p2p_peerlist::<S>))))?;
+    command_manager.add_command(Command::with_optional_arguments("export_snapshot", "Export the chain state at stable topoheight in a snapshot file", vec![Arg::new("output", ArgType::String)], CommandHandler::Async(async_handler!(export_snapshot::<S>))))?;
     command_manager.add_command(Command::with_optional_arguments("difficulty_dataset", "Create a dataset for difficulty from chain", vec![Arg::new("output", ArgType::String)], CommandHandler::Async(async_handler!(difficulty_dataset::<S>))))?;
     command_manager.add_command(Command::with_optional_arguments("mine_block", "Mine a block on testnet", vec![Arg::new("count", ArgType::Number)], CommandHandler::Async(async_handler!(mine_block::<S>))))?;
     command_manager.add_command(Command::new("p2p_outgoing_connections", "Accept/refuse to connect to outgoing nodes", CommandHandler::Async(async_handler!(p2p_outgoing_connections::<S>))))?;
@@ -305,6 +409,28 @@ async fn verify_chain<S: Storage>(manager: &CommandManager, mut args: ArgumentMa
     let context = manager.get_context().lock()?;
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
 
+    let topoheight = if args.has_argument("topoheight") {
+        args.get_value("topoheight")?.to_number()?
+    } else {
+        blockchain.get_topo_height()
+    };
+
+    let full = if args.has_argument("full") {
+        args.get_value("full")?.to_bool()?
+    } else {
+        false
+    };
+
+    // Replay every block through the same validation as when it was added
+    if full {
+        manager.message(format!("Replaying chain until topoheight {}, this may take a while...", topoheight));
+        match blockchain.verify_chain_replay(topoheight).await {
+            Ok(count) => manager.message(format!("Chain is valid, {} blocks replayed", count)),
+            Err(e) => manager.error(format!("{}", e))
+        };
+        return Ok(())
+    }
+
     let storage = blockchain.get_storage().read().await;
     let mut pruned_topoheight = storage.get_pruned_topoheight().await.context("Error on pruned topoheight")?.unwrap_or(0);
     let mut expected_supply = if pruned_topoheight > 0 {
@@ -315,12 +441,6 @@ async fn verify_chain<S: Storage>(manager: &CommandManager, mut args: ArgumentMa
         0
     };
 
-    let topoheight = if args.has_argument("topoheight") {
-        args.get_value("topoheight")?.to_number()?
-    } else {
-        blockchain.get_topo_height()
-    };
-
     for topo in pruned_topoheight..=topoheight {
         let hash_at_topo = storage.get_hash_at_topo_height(topo).await.context("Error while retrieving hash at topo")?;
         let block_reward = if pruned_topoheight == 0 || topo - pruned_topoheight > STABLE_LIMIT {
@@ -748,6 +868,91 @@ async fn whitelist<S: Storage>(manager: &CommandManager, mut arguments: Argument
     Ok(())
 }
 
+async fn allow_identity<S: Storage>(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let context = manager.get_context().lock()?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    match blockchain.get_p2p().read().await.as_ref() {
+        Some(p2p) => {
+            if arguments.has_argument("identity") {
+                let identity = CompressedPublicKey::from_hex(arguments.get_value("identity")?.to_string_value()?).context("Error while parsing identity")?;
+                let mut peer_list = p2p.get_peer_list().write().await;
+                if peer_list.toggle_allowed_identity(identity.clone()) {
+                    manager.message(format!("Identity {} has been allowed", identity.to_hex()));
+                } else {
+                    manager.message(format!("Identity {} is not allowed anymore", identity.to_hex()));
+                }
+            } else {
+                let peer_list = p2p.get_peer_list().read().await;
+                let allowed = peer_list.get_identities().get_allow_list();
+                manager.message(format!("Allowed identities ({}):", allowed.len()));
+                for identity in allowed {
+                    manager.message(format!("- {}", identity.to_hex()));
+                }
+            }
+        },
+        None => {
+            manager.error("P2P is not enabled");
+        }
+    };
+
+    Ok(())
+}
+
+async fn deny_identity<S: Storage>(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let context = manager.get_context().lock()?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    match blockchain.get_p2p().read().await.as_ref() {
+        Some(p2p) => {
+            if arguments.has_argument("identity") {
+                let identity = CompressedPublicKey::from_hex(arguments.get_value("identity")?.to_string_value()?).context("Error while parsing identity")?;
+                let mut peer_list = p2p.get_peer_list().write().await;
+                if peer_list.toggle_denied_identity(identity.clone()).await {
+                    manager.message(format!("Identity {} has been denied", identity.to_hex()));
+                } else {
+                    manager.message(format!("Identity {} is not denied anymore", identity.to_hex()));
+                }
+            } else {
+                let peer_list = p2p.get_peer_list().read().await;
+                let denied = peer_list.get_identities().get_deny_list();
+                manager.message(format!("Denied identities ({}):", denied.len()));
+                for identity in denied {
+                    manager.message(format!("- {}", identity.to_hex()));
+                }
+            }
+        },
+        None => {
+            manager.error("P2P is not enabled");
+        }
+    };
+
+    Ok(())
+}
+
+async fn peer_scores<S: Storage>(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let context = manager.get_context().lock()?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    match blockchain.get_p2p().read().await.as_ref() {
+        Some(p2p) => {
+            let peer_list = p2p.get_peer_list().read().await;
+            let mut scores: Vec<_> = peer_list.get_peers().values()
+                .map(|peer| (peer.get_outgoing_address(), peer.get_score()))
+                .collect();
+            // best peers first
+            scores.sort_by_key(|(_, score)| Reverse(score.get_value()));
+
+            manager.message(format!("Peer scores ({}):", scores.len()));
+            for (addr, score) in scores {
+                manager.message(format!("- {}: {}", addr, score));
+            }
+        },
+        None => {
+            manager.error("P2P is not enabled");
+        }
+    };
+
+    Ok(())
+}
+
 // Create a dataset from chain with solve time and difficulty at each block
 async fn difficulty_dataset<S: Storage>(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
     let output_path = if arguments.has_argument("output") {
@@ -794,6 +999,28 @@ async fn difficulty_dataset<S: Storage>(manager: &CommandManager, mut arguments:
     Ok(())
 }
 
+async fn export_snapshot<S: Storage>(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let output_path = if arguments.has_argument("output") {
+        arguments.get_value("output")?.to_string_value()?
+    } else {
+        "snapshot.bin".to_string()
+    };
+
+    let context = manager.get_context().lock()?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let storage = blockchain.get_storage().read().await;
+
+    let topoheight = blockchain.get_stable_topoheight();
+    manager.message(format!("Exporting snapshot at stable topoheight {}...", topoheight));
+    if let Err(e) = snapshot::export_snapshot(&*storage, blockchain.get_network(), topoheight, blockchain.get_stable_height(), &output_path).await {
+        manager.error(format!("Error while exporting snapshot: {}", e));
+        return Ok(())
+    }
+    manager.message(format!("Snapshot written to {}", output_path));
+
+    Ok(())
+}
+
 // Mine a block
 async fn mine_block<S: Storage>(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
     let count = if arguments.has_argument("count") {
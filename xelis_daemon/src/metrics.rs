@@ -0,0 +1,93 @@
+use std::time::Duration;
+use lazy_static::lazy_static;
+use prometheus::{
+    register_gauge,
+    register_histogram_vec,
+    register_int_counter,
+    register_int_counter_vec,
+    register_int_gauge,
+    Encoder,
+    Gauge,
+    HistogramVec,
+    IntCounter,
+    IntCounterVec,
+    IntGauge,
+    TextEncoder
+};
+use crate::{
+    core::{blockchain::Blockchain, storage::Storage},
+    p2p::error::P2pError
+};
+
+// All the metrics exposed on the /metrics route of the RPC server
+// Gauges about the chain state are updated at each scrape,
+// while counters are incremented when the event happens
+lazy_static! {
+    // Chain state
+    pub static ref HEIGHT: IntGauge = register_int_gauge!("xelis_height", "Current height of the chain").unwrap();
+    pub static ref TOPOHEIGHT: IntGauge = register_int_gauge!("xelis_topoheight", "Current topoheight of the chain").unwrap();
+    pub static ref STABLE_HEIGHT: IntGauge = register_int_gauge!("xelis_stable_height", "Current stable height of the chain").unwrap();
+    pub static ref DIFFICULTY: Gauge = register_gauge!("xelis_difficulty", "Current difficulty at tips").unwrap();
+    pub static ref BLOCKS_ACCEPTED: IntCounter = register_int_counter!("xelis_blocks_accepted_total", "Blocks accepted by the chain").unwrap();
+    pub static ref BLOCKS_ORPHANED: IntCounter = register_int_counter!("xelis_blocks_orphaned_total", "Blocks orphaned by a DAG reorg").unwrap();
+
+    // Mempool
+    pub static ref MEMPOOL_TXS: IntGauge = register_int_gauge!("xelis_mempool_transactions", "Transactions count in mempool").unwrap();
+    pub static ref MEMPOOL_BYTES: IntGauge = register_int_gauge!("xelis_mempool_bytes", "Size in bytes of all transactions in mempool").unwrap();
+
+    // P2p
+    pub static ref PEERS: IntGauge = register_int_gauge!("xelis_p2p_peers", "Peers connected").unwrap();
+    pub static ref P2P_PACKETS: IntCounterVec = register_int_counter_vec!("xelis_p2p_packets_total", "P2p packets by type and direction", &["packet", "direction"]).unwrap();
+    pub static ref P2P_BYTES: IntCounterVec = register_int_counter_vec!("xelis_p2p_bytes_total", "P2p packets bytes by type and direction", &["packet", "direction"]).unwrap();
+    pub static ref P2P_ERRORS: IntCounterVec = register_int_counter_vec!("xelis_p2p_errors_total", "P2p errors by kind", &["error"]).unwrap();
+
+    // RPC
+    pub static ref RPC_CALLS: IntCounterVec = register_int_counter_vec!("xelis_rpc_calls_total", "RPC calls by method and status", &["method", "status"]).unwrap();
+    pub static ref RPC_LATENCY: HistogramVec = register_histogram_vec!("xelis_rpc_latency_seconds", "RPC calls latency by method", &["method"]).unwrap();
+    pub static ref GETWORK_MINERS: IntGauge = register_int_gauge!("xelis_getwork_miners", "Miners connected to the GetWork server").unwrap();
+}
+
+// Count a P2p packet sent or received
+pub fn record_p2p_packet(packet: &'static str, incoming: bool, size: usize) {
+    let direction = if incoming { "in" } else { "out" };
+    P2P_PACKETS.with_label_values(&[packet, direction]).inc();
+    P2P_BYTES.with_label_values(&[packet, direction]).inc_by(size as u64);
+}
+
+// Count a P2p error using its variant name
+pub fn record_p2p_error(error: &P2pError) {
+    let debug = format!("{:?}", error);
+    let kind = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap_or_default();
+    P2P_ERRORS.with_label_values(&[kind]).inc();
+}
+
+// Count a RPC call with its latency
+pub fn record_rpc_call(method: &str, duration: Duration, success: bool) {
+    let status = if success { "ok" } else { "error" };
+    RPC_CALLS.with_label_values(&[method, status]).inc();
+    RPC_LATENCY.with_label_values(&[method]).observe(duration.as_secs_f64());
+}
+
+// Update the gauges from the current chain state and encode all metrics in Prometheus text format
+pub async fn gather<S: Storage>(blockchain: &Blockchain<S>) -> Result<String, prometheus::Error> {
+    HEIGHT.set(blockchain.get_height() as i64);
+    TOPOHEIGHT.set(blockchain.get_topo_height() as i64);
+    STABLE_HEIGHT.set(blockchain.get_stable_height() as i64);
+    DIFFICULTY.set(f64::from(blockchain.get_difficulty().await));
+
+    {
+        let mempool = blockchain.get_mempool().read().await;
+        MEMPOOL_TXS.set(mempool.size() as i64);
+        MEMPOOL_BYTES.set(mempool.get_total_size() as i64);
+    }
+
+    let peers = match blockchain.get_p2p().read().await.as_ref() {
+        Some(p2p) => p2p.get_peer_count().await,
+        None => 0
+    };
+    PEERS.set(peers as i64);
+
+    let mut buffer = Vec::new();
+    TextEncoder::new().encode(&prometheus::gather(), &mut buffer)?;
+    String::from_utf8(buffer).map_err(|e| prometheus::Error::Msg(e.to_string()))
+}
@@ -1,8 +1,16 @@
-use crate::config::PEER_TIMEOUT_INIT_CONNECTION;
+use crate::{
+    config::{PEER_TIMEOUT_INIT_CONNECTION, P2P_PROTOCOL_VERSION},
+    metrics
+};
 use super::{
     encryption::Encryption,
     error::P2pError,
-    packet::Packet,
+    identity::{derive_encryption_keys, KeyExchangeTranscript},
+    packet::{
+        key_exchange::EphemeralKeyExchange,
+        Packet,
+        get_packet_name
+    },
     EncryptionKey
 };
 use std::{
@@ -29,6 +37,7 @@ use tokio::{
     time::timeout
 };
 use xelis_common::{
+    crypto::{elgamal::CompressedPublicKey, KeyPair},
     time::{TimestampSeconds, get_current_time_in_seconds},
     serializer::{Reader, Serializer},
 };
@@ -83,7 +92,12 @@ pub struct Connection {
     // How many key rotation we sent
     rotate_key_out: AtomicUsize,
     // Encryption state used for packets
-    encryption: Encryption
+    encryption: Encryption,
+    // Ephemeral keys exchanged with the peer
+    // None if the legacy key exchange was used
+    key_exchange: Option<KeyExchangeTranscript>,
+    // Protocol version negotiated during the key exchange
+    protocol_version: u8
 }
 
 // We are rotating every 1GB sent
@@ -108,61 +122,119 @@ impl Connection {
             closed: AtomicBool::new(false),
             rotate_key_in: AtomicUsize::new(0),
             rotate_key_out: AtomicUsize::new(0),
-            encryption: Encryption::new()
+            encryption: Encryption::new(),
+            key_exchange: None,
+            protocol_version: 0
         }
     }
 
-    // Do a key exchange with the peer
-    // If we are the client, we send our key first in plaintext
-    // We wait for the peer to send its key
-    // If we are the server, we send back our key
-    // NOTE: This doesn't prevent any MITM at this point
-    // Because a MITM could intercept the key and send its own key to the peer
-    // and play the role as a proxy.
-    // Afaik, there is no way to have a decentralized way to prevent MITM without trusting a third party
-    // (That's what TLS/SSL does with the CA, but it's not decentralized and it's not trustless)
-    // A potential idea would be to hardcode seed nodes keys,
-    // and each nodes share the key of other along the socket address
-    pub async fn exchange_keys(&mut self, buffer: &mut [u8]) -> P2pResult<()> {
-        trace!("Exchanging keys with {}", self.addr);
+    // Do a key exchange with the peer, the client sends its key first
+    // The protocol version is negotiated by the ephemeral key exchange:
+    // each side sends an ephemeral public key in plaintext with its highest version,
+    // and both encryption keys are derived from the Diffie-Hellman shared secret so they are never sent over the network.
+    // NOTE: This alone doesn't prevent any MITM, as it could do a key exchange with each side.
+    // The ephemeral keys are signed by the node identity of each side in the handshake,
+    // which lets a peer knowing the expected identity detect it.
+    // If legacy is set, the client uses the key exchange of the older nodes (protocol version 0)
+    // where each side sends its encryption key in plaintext. A server accepts both.
+    pub async fn exchange_keys(&mut self, buffer: &mut [u8], legacy: bool) -> P2pResult<()> {
+        trace!("Exchanging keys with {} (legacy: {})", self.addr, legacy);
 
         // Update our state
         self.set_state(State::KeyHandshake);
 
+        let ephemeral = KeyPair::new();
+        let our_key: EncryptionKey = *ephemeral.get_public_key().compress().as_bytes();
+        let our_packet = Packet::EphemeralKeyExchange(Cow::Owned(EphemeralKeyExchange::new(P2P_PROTOCOL_VERSION, our_key)));
+
         // Send our key if we initiated the connection
         if self.is_out() {
             trace!("Sending our key to {}", self.addr);
-            let packet = self.rotate_key_packet().await?;
-            self.send_bytes(&packet).await?;
-            self.encryption.mark_as_ready();
+            if legacy {
+                let packet = self.rotate_key_packet().await?;
+                self.send_bytes(&packet).await?;
+                self.encryption.mark_as_ready();
+            } else {
+                self.send_bytes(&our_packet.to_bytes()).await?;
+            }
         }
 
         trace!("Waiting for key from {}", self.addr);
         // Wait for the peer to receive its key
-        let Packet::KeyExchange(peer_key) = timeout(
+        let packet = timeout(
             Duration::from_millis(PEER_TIMEOUT_INIT_CONNECTION),
             self.read_packet(buffer, 256)
-        ).await?? else {
-            error!("Expected KeyExchange packet");
-            return Err(P2pError::InvalidPacket);
-        };
-
-        // Now that we got the peer key, update our encryption state
-        self.rotate_peer_key(peer_key.into_owned()).await?;
-
-        // Send back our key if we are the server
-        if !self.is_out() {
-            trace!("Replying with our key to {}", self.addr);
-            let packet = self.rotate_key_packet().await?;
-            self.send_bytes(&packet).await?;
-            self.encryption.mark_as_ready();
+        ).await??;
+
+        match packet {
+            Packet::KeyExchange(peer_key) if legacy || !self.is_out() => {
+                // Now that we got the peer key, update our encryption state
+                self.rotate_peer_key(peer_key.into_owned()).await?;
+
+                // Send back our key if we are the server
+                if !self.is_out() {
+                    trace!("Replying with our legacy key to {}", self.addr);
+                    let packet = self.rotate_key_packet().await?;
+                    self.send_bytes(&packet).await?;
+                    self.encryption.mark_as_ready();
+                }
+                self.protocol_version = 0;
+            },
+            Packet::EphemeralKeyExchange(peer_key_exchange) if !legacy => {
+                // Send back our key if we are the server
+                if !self.is_out() {
+                    trace!("Replying with our key to {}", self.addr);
+                    self.send_bytes(&our_packet.to_bytes()).await?;
+                }
+
+                let peer_key = *peer_key_exchange.get_key();
+                let peer_public_key = CompressedPublicKey::from_bytes(&peer_key)
+                    .ok()
+                    .and_then(|key| key.decompress().ok())
+                    .ok_or(P2pError::InvalidPacket)?;
+
+                let transcript = if self.is_out() {
+                    KeyExchangeTranscript::new(our_key, peer_key)
+                } else {
+                    KeyExchangeTranscript::new(peer_key, our_key)
+                };
+
+                // Now that we got the peer key, update our encryption state
+                let (client_key, server_key) = derive_encryption_keys(ephemeral.get_private_key(), &peer_public_key, &transcript);
+                let (our_key, peer_key) = if self.is_out() {
+                    (client_key, server_key)
+                } else {
+                    (server_key, client_key)
+                };
+                self.encryption.rotate_key(our_key, true).await?;
+                self.rotate_key_out.fetch_add(1, Ordering::Relaxed);
+                self.rotate_peer_key(peer_key).await?;
+                self.encryption.mark_as_ready();
+                self.key_exchange = Some(transcript);
+                self.protocol_version = P2P_PROTOCOL_VERSION.min(peer_key_exchange.get_version());
+            },
+            _ => {
+                error!("Expected KeyExchange packet");
+                return Err(P2pError::InvalidPacket);
+            }
         }
 
-        trace!("Key exchange with {} successful", self.addr);
+        trace!("Key exchange with {} successful (protocol version: {})", self.addr, self.protocol_version);
 
         Ok(())
     }
 
+    // Get the protocol version negotiated during the key exchange
+    pub fn get_protocol_version(&self) -> u8 {
+        self.protocol_version
+    }
+
+    // Get the ephemeral keys exchanged with the peer
+    // They must be signed in the handshake by each side
+    pub fn get_key_exchange(&self) -> Option<&KeyExchangeTranscript> {
+        self.key_exchange.as_ref()
+    }
+
     // Verify if its a outgoing connection
     pub fn is_out(&self) -> bool {
         self.out
@@ -241,6 +313,9 @@ impl Connection {
 
         // Count the bytes sent
         self.bytes_out.fetch_add(packet.len(), Ordering::Relaxed);
+        if let Some(id) = packet.first() {
+            metrics::record_p2p_packet(get_packet_name(*id), false, packet.len());
+        }
 
         if self.encryption.is_write_ready().await {
             let buffer = self.encryption.encrypt_packet(packet).await?;
@@ -284,6 +359,10 @@ impl Connection {
 
     // Deserialize a packet from bytes and verify its integrity
     pub async fn read_packet_from_bytes(&self, bytes: &[u8]) -> P2pResult<Packet<'static>> {
+        if let Some(id) = bytes.first() {
+            metrics::record_p2p_packet(get_packet_name(*id), true, bytes.len());
+        }
+
         let mut reader = Reader::new(&bytes);
         let packet = Packet::read(&mut reader)?;
         if reader.total_read() != bytes.len() {
@@ -0,0 +1,281 @@
+use std::{collections::HashMap, sync::Arc};
+use rand::{seq::SliceRandom, Rng};
+use xelis_common::{
+    crypto::{Hash, PublicKey},
+    time::TimestampMillis,
+    transaction::Transaction
+};
+use crate::config::{MILLIS_PER_SECOND, P2P_DANDELION_EPOCH, P2P_DANDELION_MAX_STEM_TXS};
+
+// TX relayed through the stem and not fluffed yet
+struct StemTx {
+    tx: Arc<Transaction>,
+    // when we must fluff it ourself if it was not diffused by the network
+    embargo: TimestampMillis
+}
+
+// Dandelion++ relay state
+// A TX is first forwarded from peer to peer (stem phase) and is broadcasted (fluff phase)
+// only when a node of the stem decides it, or when its embargo timer has expired
+pub struct Dandelion {
+    // chance in percent to fluff a TX received through the stem
+    fluff_probability: u8,
+    // minimum delay in seconds before fluffing ourself a TX stemmed
+    embargo: u64,
+    // peer to which the TXs are stemmed during the current epoch
+    stem_peer: Option<u64>,
+    // when the stem peer must be selected again
+    epoch_end: TimestampMillis,
+    // TXs stemmed but not fluffed yet
+    stempool: HashMap<Hash, StemTx>
+}
+
+impl Dandelion {
+    pub fn new(fluff_probability: u8, embargo: u64) -> Self {
+        debug_assert!(fluff_probability <= 100);
+        Self {
+            fluff_probability,
+            embargo,
+            stem_peer: None,
+            epoch_end: 0,
+            stempool: HashMap::new()
+        }
+    }
+
+    // Select the peer to which the TXs are stemmed
+    // It stays the same during the epoch, unless it's not in the candidates anymore
+    pub fn get_stem_peer(&mut self, candidates: &[u64], now: TimestampMillis) -> Option<u64> {
+        if let Some(peer_id) = self.stem_peer.filter(|id| now < self.epoch_end && candidates.contains(id)) {
+            return Some(peer_id)
+        }
+
+        self.stem_peer = candidates.choose(&mut rand::thread_rng()).copied();
+        self.epoch_end = now + P2P_DANDELION_EPOCH * MILLIS_PER_SECOND;
+        self.stem_peer
+    }
+
+    // Randomly decide if a TX received through the stem should be fluffed
+    pub fn should_fluff(&self) -> bool {
+        rand::thread_rng().gen_range(0..100) < self.fluff_probability
+    }
+
+    pub fn has_tx(&self, hash: &Hash) -> bool {
+        self.stempool.contains_key(hash)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.stempool.len() >= P2P_DANDELION_MAX_STEM_TXS
+    }
+
+    // Add a TX in the stem, a random delay is added to its embargo
+    // so the first node fluffing it is not always the same
+    pub fn add_tx(&mut self, hash: Hash, tx: Arc<Transaction>, now: TimestampMillis) {
+        let embargo = self.embargo * MILLIS_PER_SECOND;
+        let jitter = rand::thread_rng().gen_range(0..=embargo / 2);
+        self.stempool.insert(hash, StemTx {
+            tx,
+            embargo: now + embargo + jitter
+        });
+    }
+
+    // Get the TXs in the stem from this source, in nonce order
+    // They must be applied before verifying a new TX from the same source
+    pub fn get_txs_for(&self, source: &PublicKey) -> Vec<Arc<Transaction>> {
+        let mut txs: Vec<Arc<Transaction>> = self.stempool.values()
+            .filter(|stem| stem.tx.get_source() == source)
+            .map(|stem| Arc::clone(&stem.tx))
+            .collect();
+
+        txs.sort_by_key(|tx| tx.get_nonce());
+        txs
+    }
+
+    // Remove from the stem the TXs from this source up to this nonce, in nonce order
+    // They are fluffed together as each one depends on the previous ones
+    pub fn take_txs_until(&mut self, source: &PublicKey, nonce: u64) -> Vec<(Hash, Arc<Transaction>)> {
+        let hashes: Vec<Hash> = self.stempool.iter()
+            .filter(|(_, stem)| stem.tx.get_source() == source && stem.tx.get_nonce() <= nonce)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+
+        let mut txs: Vec<(Hash, Arc<Transaction>)> = hashes.into_iter()
+            .filter_map(|hash| self.stempool.remove(&hash).map(|stem| (hash, stem.tx)))
+            .collect();
+
+        txs.sort_by_key(|(_, tx)| tx.get_nonce());
+        txs
+    }
+
+    // Remove from the stem the TXs whose embargo has expired
+    // with the previous TXs of their source
+    pub fn take_expired_txs(&mut self, now: TimestampMillis) -> Vec<(Hash, Arc<Transaction>)> {
+        let mut expired: Vec<Arc<Transaction>> = self.stempool.values()
+            .filter(|stem| stem.embargo <= now)
+            .map(|stem| Arc::clone(&stem.tx))
+            .collect();
+
+        // Start from the highest nonce so its source is taken only once
+        expired.sort_by(|a, b| b.get_nonce().cmp(&a.get_nonce()));
+
+        let mut txs = Vec::new();
+        for tx in expired {
+            txs.extend(self.take_txs_until(tx.get_source(), tx.get_nonce()));
+        }
+        txs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, time::Duration};
+    use clap::Parser;
+    use tokio::time::sleep;
+    use xelis_common::{
+        config::COIN_VALUE,
+        crypto::Hashable,
+        network::Network,
+        time::get_current_time_in_millis
+    };
+    use crate::core::{
+        blockchain::{tests::{create_account, stop_node, temp_dir_path}, Blockchain, Config},
+        storage::SledStorage
+    };
+    use super::*;
+
+    #[derive(Parser)]
+    struct TestConfig {
+        #[clap(flatten)]
+        config: Config
+    }
+
+    // Start an in-process node on testnet, its P2p server listens on a free local port
+    // Outgoing connections are disabled so it never reaches the seed nodes
+    async fn start_node(dir_path: &str, fluff_probability: u8, embargo: u64) -> Arc<Blockchain<SledStorage>> {
+        let args = vec![
+            "xelis_daemon".to_owned(),
+            "--dir-path".to_owned(), dir_path.to_owned(),
+            "--p2p-bind-address".to_owned(), "127.0.0.1:0".to_owned(),
+            "--rpc-bind-address".to_owned(), "127.0.0.1:0".to_owned(),
+            "--disable-getwork-server".to_owned(),
+            "--disable-ip-sharing".to_owned(),
+            "--disable-outgoing-connections".to_owned(),
+            "--p2p-enable-dandelion".to_owned(),
+            "--p2p-dandelion-fluff-probability".to_owned(), fluff_probability.to_string(),
+            "--p2p-dandelion-embargo".to_owned(), embargo.to_string()
+        ];
+
+        let config = TestConfig::parse_from(args).config;
+        let storage = SledStorage::new(dir_path.to_owned(), None, Network::Testnet).unwrap();
+        Blockchain::new(config, Network::Testnet, storage).await.unwrap()
+    }
+
+    // Address on which the P2p server of the node is listening
+    async fn get_address(node: &Blockchain<SledStorage>) -> SocketAddr {
+        *node.get_p2p().read().await.as_ref().unwrap().get_bind_address()
+    }
+
+    // Connect directly the node to another one
+    async fn connect(node: &Blockchain<SledStorage>, address: SocketAddr) {
+        let p2p = node.get_p2p().read().await.clone().unwrap();
+        let connection = p2p.connect_to_peer(address).await.unwrap();
+        p2p.handle_new_connection(&mut [0; 512], connection, false).await.unwrap();
+    }
+
+    async fn count_peers(node: &Blockchain<SledStorage>) -> usize {
+        let p2p = node.get_p2p().read().await;
+        let p2p = p2p.as_ref().unwrap();
+        let peer_list = p2p.get_peer_list().read().await;
+        peer_list.size()
+    }
+
+    // Wait until the condition is true, or fail after a few seconds
+    async fn wait_for<F: std::future::Future<Output = bool>>(mut condition: impl FnMut() -> F) {
+        for _ in 0..100 {
+            if condition().await {
+                return
+            }
+            sleep(Duration::from_millis(100)).await;
+        }
+        panic!("Condition not reached in time");
+    }
+
+    #[test]
+    fn test_stem_peer_epoch() {
+        let mut dandelion = Dandelion::new(0, 30);
+        let stem_peer = dandelion.get_stem_peer(&[1, 2, 3], 0).unwrap();
+        // same peer during the epoch
+        assert_eq!(dandelion.get_stem_peer(&[1, 2, 3], 1000), Some(stem_peer));
+
+        // selected again when it's gone
+        let candidates: Vec<u64> = [1, 2, 3].into_iter().filter(|id| *id != stem_peer).collect();
+        let new_stem_peer = dandelion.get_stem_peer(&candidates, 2000).unwrap();
+        assert_ne!(new_stem_peer, stem_peer);
+
+        assert_eq!(dandelion.get_stem_peer(&[], 3000), None);
+        assert!(!dandelion.should_fluff());
+        assert!(Dandelion::new(100, 30).should_fluff());
+    }
+
+    #[tokio::test]
+    async fn test_stem_relay() {
+        let dirs = [temp_dir_path("dandelion-a"), temp_dir_path("dandelion-b"), temp_dir_path("dandelion-c")];
+        // a <-> b <-> c, the TX is always stemmed by b and fluffed by the last node of the stem
+        let node_a = start_node(&dirs[0], 0, 60).await;
+        let node_b = start_node(&dirs[1], 0, 60).await;
+        let node_c = start_node(&dirs[2], 0, 60).await;
+        let b = get_address(&node_b).await;
+        connect(&node_a, b).await;
+        connect(&node_c, b).await;
+        wait_for(|| async { count_peers(&node_a).await == 1 && count_peers(&node_b).await == 2 && count_peers(&node_c).await == 1 }).await;
+
+        let mut account = create_account(&[&node_a, &node_b, &node_c], COIN_VALUE).await;
+        let tx = account.build_burn_tx();
+        let hash = tx.hash();
+
+        let p2p = node_a.get_p2p().read().await.clone().unwrap();
+        p2p.relay_stem_tx(hash.clone(), Arc::new(tx), None).await.unwrap();
+        // The origin doesn't broadcast it, it waits in its stem until fluffed by another node
+        assert!(p2p.dandelion.as_ref().unwrap().lock().await.has_tx(&hash));
+
+        for node in [&node_a, &node_b, &node_c] {
+            wait_for(|| async { node.has_tx(&hash).await.unwrap() }).await;
+        }
+
+        for (node, dir_path) in [node_a, node_b, node_c].iter().zip(dirs.iter()) {
+            stop_node(node, dir_path).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_embargo_fallback() {
+        let dirs = [temp_dir_path("dandelion-a"), temp_dir_path("dandelion-b")];
+        let node_a = start_node(&dirs[0], 0, 1).await;
+        let node_b = start_node(&dirs[1], 0, 1).await;
+        connect(&node_a, get_address(&node_b).await).await;
+        wait_for(|| async { count_peers(&node_a).await == 1 && count_peers(&node_b).await == 1 }).await;
+
+        let mut account = create_account(&[&node_a, &node_b], COIN_VALUE).await;
+        let first = account.build_burn_tx();
+        let second = account.build_burn_tx();
+        let (first_hash, second_hash) = (first.hash(), second.hash());
+
+        // Simulate a stem that never fluffed the TXs
+        {
+            let p2p = node_a.get_p2p().read().await.clone().unwrap();
+            let mut dandelion = p2p.dandelion.as_ref().unwrap().lock().await;
+            let now = get_current_time_in_millis();
+            dandelion.add_tx(first_hash.clone(), Arc::new(first), now);
+            dandelion.add_tx(second_hash.clone(), Arc::new(second), now);
+            assert_eq!(dandelion.get_txs_for(&account.keypair.get_public_key().compress()).len(), 2);
+        }
+
+        // Both are fluffed in nonce order once their embargo expired
+        for node in [&node_a, &node_b] {
+            wait_for(|| async { node.has_tx(&first_hash).await.unwrap() && node.has_tx(&second_hash).await.unwrap() }).await;
+        }
+
+        for (node, dir_path) in [node_a, node_b].iter().zip(dirs.iter()) {
+            stop_node(node, dir_path).await;
+        }
+    }
+}
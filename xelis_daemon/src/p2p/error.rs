@@ -8,6 +8,7 @@ use xelis_common::serializer::ReaderError;
 use std::array::TryFromSliceError;
 use std::net::{AddrParseError, SocketAddr};
 use tokio::time::error::Elapsed;
+use tokio::task::JoinError;
 use std::sync::mpsc::SendError;
 use std::io::Error as IOError;
 use std::sync::PoisonError;
@@ -59,6 +60,24 @@ pub enum P2pError {
     UnrequestedChainResponse,
     #[error("Invalid chain response size, got {} blocks while maximum set was {}", _0, _1)]
     InvaliChainResponseSize(usize, usize),
+    #[error("Invalid P2p identity file {}", _0)]
+    InvalidIdentityFile(String),
+    #[error("Key exchange was not done before the handshake")]
+    MissingKeyExchange,
+    #[error("{} closed the connection during the ephemeral key exchange", _0)]
+    EphemeralKeyExchangeRejected(SocketAddr),
+    #[error("Invalid key exchange signature")]
+    InvalidKeyExchangeSignature,
+    #[error("Peer identity {} is not allowed", _0)]
+    IdentityNotAllowed(String),
+    #[error("Peer has no identity while the allow list is not empty")]
+    MissingIdentity,
+    #[error("Peer has no identity while it is required")]
+    IdentityDowngrade,
+    #[error("Received a unrequested block transactions response")]
+    UnrequestedBlockTransactionsResponse,
+    #[error("Invalid block transactions response for block {}", _0)]
+    InvalidBlockTransactionsResponse(Hash),
     #[error("Received a unrequested bootstrap chain response")]
     UnrequestedBootstrapChainResponse,
     #[error("Invalid common point at topoheight {}", _0)]
@@ -79,6 +98,8 @@ pub enum P2pError {
     PeerIdAlreadyUsed(u64),
     #[error("Peer already connected: {}", _0)]
     PeerAlreadyConnected(String),
+    #[error("Max peers reached, no peer can be evicted")]
+    MaxPeersReached,
     #[error(transparent)]
     ErrorStd(#[from] IOError),
     #[error("Poison Error: {}", _0)]
@@ -125,6 +146,10 @@ pub enum P2pError {
     BoostSyncModeBlockerError,
     #[error("Boost sync mode failed")]
     BoostSyncModeFailed,
+    #[error("No peer left to download window #{} in headers-first sync", _0)]
+    HeadersSyncNoPeer(usize),
+    #[error("Error while waiting on a window in headers-first sync: {}", _0)]
+    HeadersSyncTaskError(#[from] JoinError),
     #[error("Expected a block type")]
     ExpectedBlock,
     #[error("Expected a transaction type")]
@@ -0,0 +1,477 @@
+use std::{
+    cmp::Reverse,
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Display,
+    future::Future,
+    sync::Arc
+};
+use indexmap::IndexSet;
+use log::{debug, trace, warn};
+use tokio::task::JoinSet;
+use xelis_common::{
+    block::{Block, BlockHeader},
+    crypto::Hash,
+    immutable::Immutable,
+    transaction::Transaction
+};
+use crate::{
+    config::{CHAIN_SYNC_BODIES_WINDOW, CHAIN_SYNC_HEADERS_WINDOW, CHAIN_SYNC_MAX_PEERS},
+    core::{error::BlockchainError, storage::Storage}
+};
+use super::{
+    chain_validator::ChainValidator,
+    error::P2pError,
+    packet::object::{ObjectRequest, OwnedObjectResponse},
+    peer::Peer,
+    P2pServer
+};
+
+// Objects downloaded in a window with their hashes
+type WindowResult<T> = Result<Vec<(Hash, T)>, P2pError>;
+
+// Peer downloading the windows
+trait WindowPeer: Display + Send + Sync + 'static {
+    // Called when the peer failed to send a window it should have
+    fn penalize(&self);
+}
+
+impl WindowPeer for Peer {
+    fn penalize(&self) {
+        self.increment_fail_count();
+    }
+}
+
+// Windows of objects downloaded in parallel from several peers
+// Each window is requested to a single peer, and a peer only downloads one window at a time
+// A window that failed is given to another peer, so a slow or lying peer only stalls its own window
+struct WindowScheduler<P, T, F> {
+    peers: Vec<Arc<P>>,
+    // indexes of the peers waiting for a window
+    idle: VecDeque<usize>,
+    // objects hashes of each window
+    windows: Vec<Vec<Hash>>,
+    // windows not downloaded yet, failed ones are retried first
+    pending: VecDeque<usize>,
+    // peers that don't have all the objects of a window
+    missing: HashMap<usize, HashSet<usize>>,
+    // windows downloaded but not consumed yet
+    completed: HashMap<usize, Vec<(Hash, T)>>,
+    // next window to consume
+    next: usize,
+    // how many windows can be downloaded ahead of the next one to consume
+    lookahead: usize,
+    tasks: JoinSet<(usize, usize, WindowResult<T>)>,
+    fetch: F
+}
+
+impl<P, T, F, Fut> WindowScheduler<P, T, F>
+where
+    P: WindowPeer,
+    T: Send + 'static,
+    F: Fn(Arc<P>, Vec<Hash>) -> Fut,
+    Fut: Future<Output = WindowResult<T>> + Send + 'static
+{
+    fn new(peers: Vec<Arc<P>>, windows: Vec<Vec<Hash>>, fetch: F) -> Self {
+        Self {
+            lookahead: peers.len() * 2,
+            idle: (0..peers.len()).collect(),
+            peers,
+            pending: (0..windows.len()).collect(),
+            windows,
+            missing: HashMap::new(),
+            completed: HashMap::new(),
+            next: 0,
+            tasks: JoinSet::new(),
+            fetch
+        }
+    }
+
+    // Give the pending windows to the idle peers
+    // A window is not given again to a peer that doesn't have it
+    fn dispatch(&mut self) {
+        let mut i = 0;
+        while i < self.pending.len() && !self.idle.is_empty() {
+            let index = self.pending[i];
+            if index >= self.next + self.lookahead {
+                i += 1;
+                continue;
+            }
+
+            let missing = self.missing.get(&index);
+            let Some(position) = self.idle.iter().position(|peer| missing.map_or(true, |peers| !peers.contains(peer))) else {
+                i += 1;
+                continue;
+            };
+
+            let (Some(peer_index), Some(index)) = (self.idle.remove(position), self.pending.remove(i)) else {
+                break;
+            };
+
+            let peer = Arc::clone(&self.peers[peer_index]);
+            trace!("Requesting window #{} of {} objects to {}", index, self.windows[index].len(), peer);
+            let future = (self.fetch)(peer, self.windows[index].clone());
+            self.tasks.spawn(async move {
+                let result = future.await;
+                (peer_index, index, result)
+            });
+        }
+    }
+
+    // Wait until the next window in order is downloaded
+    // Returns None once all windows have been consumed
+    async fn next_window(&mut self) -> Result<Option<Vec<(Hash, T)>>, P2pError> {
+        if self.next >= self.windows.len() {
+            return Ok(None)
+        }
+
+        loop {
+            if let Some(objects) = self.completed.remove(&self.next) {
+                self.next += 1;
+                return Ok(Some(objects))
+            }
+
+            self.dispatch();
+            // No task running means no peer left can download the next window
+            let Some(res) = self.tasks.join_next().await else {
+                return Err(P2pError::HeadersSyncNoPeer(self.next))
+            };
+
+            let (peer_index, index, result) = res?;
+            let peer = &self.peers[peer_index];
+            match result {
+                Ok(objects) => {
+                    self.completed.insert(index, objects);
+                    self.idle.push_back(peer_index);
+                },
+                // The peer is still used for the other windows
+                Err(P2pError::ObjectNotFound(request)) => {
+                    debug!("{} doesn't have {} of window #{} during headers-first sync", peer, request, index);
+                    self.missing.entry(index).or_default().insert(peer_index);
+                    self.idle.push_back(peer_index);
+                    self.pending.push_front(index);
+                },
+                Err(e) => {
+                    warn!("{} failed to send window #{} during headers-first sync: {}", peer, index, e);
+                    peer.penalize();
+                    self.pending.push_front(index);
+                }
+            }
+        }
+    }
+}
+
+// Request all the objects of a window at once instead of waiting each response before the next request
+// All requests are awaited even if one failed, so none is left pending in the peer
+async fn request_objects(peer: &Arc<Peer>, requests: Vec<ObjectRequest>) -> Result<Vec<OwnedObjectResponse>, P2pError> {
+    let mut tasks = JoinSet::new();
+    for (i, request) in requests.into_iter().enumerate() {
+        let peer = Arc::clone(peer);
+        tasks.spawn(async move {
+            (i, peer.request_blocking_object(request).await)
+        });
+    }
+
+    let mut responses = Vec::with_capacity(tasks.len());
+    let mut error = None;
+    while let Some(res) = tasks.join_next().await {
+        match res? {
+            (i, Ok(response)) => responses.push((i, response)),
+            (_, Err(e)) => {
+                // Keep the missing object error so the peer isn't penalized for it
+                if error.is_none() || matches!(e, P2pError::ObjectNotFound(_)) {
+                    error = Some(e);
+                }
+            }
+        }
+    }
+
+    if let Some(e) = error {
+        return Err(e)
+    }
+
+    responses.sort_unstable_by_key(|(i, _)| *i);
+    Ok(responses.into_iter().map(|(_, response)| response).collect())
+}
+
+// Download the headers of a window
+async fn fetch_headers(peer: Arc<Peer>, hashes: Vec<Hash>) -> WindowResult<BlockHeader> {
+    let requests = hashes.into_iter().map(ObjectRequest::BlockHeader).collect();
+    request_objects(&peer, requests).await?
+        .into_iter()
+        .map(|response| match response {
+            OwnedObjectResponse::BlockHeader(header, hash) => Ok((hash, header)),
+            _ => Err(P2pError::ExpectedBlock)
+        })
+        .collect()
+}
+
+// Download the TXs of a window
+async fn fetch_transactions(peer: Arc<Peer>, hashes: Vec<Hash>) -> WindowResult<Transaction> {
+    let requests = hashes.into_iter().map(ObjectRequest::Transaction).collect();
+    request_objects(&peer, requests).await?
+        .into_iter()
+        .map(|response| match response {
+            OwnedObjectResponse::Transaction(tx, hash) => Ok((hash, tx)),
+            _ => Err(P2pError::ExpectedTransaction)
+        })
+        .collect()
+}
+
+impl<S: Storage> P2pServer<S> {
+    // Select the peers used to download the blocks of a chain response
+    // The peer we sync from is always first, others must be at least at its topoheight
+    // and not pruned above the common point
+    async fn get_headers_sync_peers(&self, peer: &Arc<Peer>, common_topoheight: u64) -> Vec<Arc<Peer>> {
+        let peer_list = self.peer_list.read().await;
+        let peer_topoheight = peer.get_topoheight();
+        let mut peers: Vec<Arc<Peer>> = peer_list.get_peers().values()
+            .filter(|p| p.get_id() != peer.get_id()
+                && p.get_topoheight() >= peer_topoheight
+                && p.get_pruned_topoheight().map_or(true, |pruned| pruned <= common_topoheight)
+            )
+            .cloned()
+            .collect();
+
+        // best peers first
+        peers.sort_by_cached_key(|p| Reverse(p.get_score().get_value()));
+        peers.truncate(CHAIN_SYNC_MAX_PEERS - 1);
+        peers.insert(0, Arc::clone(peer));
+        peers
+    }
+
+    // Sync the blocks of a chain response using headers-first
+    // All the headers are downloaded from several peers and verified in topological order (DAG structure and PoW)
+    // Then the TXs are downloaded by windows in parallel and the blocks are added in topological order
+    // Returns the count of blocks added
+    pub(super) async fn headers_first_sync(&self, peer: &Arc<Peer>, blocks: IndexSet<Hash>, common_topoheight: u64) -> Result<usize, BlockchainError> {
+        let mut hashes = Vec::with_capacity(blocks.len());
+        for hash in blocks {
+            if !self.blockchain.has_block(&hash).await? {
+                hashes.push(hash);
+            } else {
+                trace!("Block {} is already in chain, skipping it", hash);
+            }
+        }
+
+        if hashes.is_empty() {
+            return Ok(0)
+        }
+
+        let peers = self.get_headers_sync_peers(peer, common_topoheight).await;
+        debug!("Headers-first sync of {} blocks from {} peers", hashes.len(), peers.len());
+
+        // Download and verify all the headers
+        let windows = hashes.chunks(CHAIN_SYNC_HEADERS_WINDOW).map(|hashes| hashes.to_vec()).collect();
+        let mut scheduler = WindowScheduler::new(peers.clone(), windows, fetch_headers);
+        let mut chain_validator = ChainValidator::new(&self.blockchain, common_topoheight + 1);
+        while let Some(headers) = scheduler.next_window().await? {
+            for (hash, header) in headers {
+                chain_validator.insert_block(hash, header).await?;
+            }
+        }
+
+        // Search the TXs we don't have yet
+        // A TX can be included in several blocks, it is requested only once
+        // Downloaded TXs are kept in memory only until the last block including them is added
+        let blocks: Vec<(Hash, Arc<BlockHeader>)> = chain_validator.get_blocks().collect();
+        // count of blocks including each TX requested
+        let mut requested: HashMap<Hash, usize> = HashMap::new();
+        let mut windows = Vec::with_capacity(blocks.len() / CHAIN_SYNC_BODIES_WINDOW + 1);
+        for chunk in blocks.chunks(CHAIN_SYNC_BODIES_WINDOW) {
+            let mut missing = Vec::new();
+            for (_, header) in chunk {
+                for hash in header.get_txs_hashes() {
+                    if let Some(count) = requested.get_mut(hash) {
+                        *count += 1;
+                        continue;
+                    }
+
+                    if self.blockchain.has_tx(hash).await? {
+                        trace!("Found the transaction {} locally", hash);
+                        continue;
+                    }
+
+                    requested.insert(hash.clone(), 1);
+                    missing.push(hash.clone());
+                }
+            }
+            windows.push(missing);
+        }
+        debug!("Requesting {} TXs for {} blocks in headers-first sync", requested.len(), blocks.len());
+
+        // Download the TXs and add the blocks once their window is complete
+        let mut scheduler = WindowScheduler::new(peers, windows, fetch_transactions);
+        let mut chunks = blocks.chunks(CHAIN_SYNC_BODIES_WINDOW);
+        let mut txs: HashMap<Hash, Arc<Transaction>> = HashMap::new();
+        let mut total = 0;
+        while let Some(fetched) = scheduler.next_window().await? {
+            for (hash, tx) in fetched {
+                txs.insert(hash, Arc::new(tx));
+            }
+
+            let Some(chunk) = chunks.next() else {
+                break;
+            };
+
+            for (hash, header) in chunk {
+                let mut transactions = Vec::with_capacity(header.get_txs_count());
+                for tx_hash in header.get_txs_hashes() {
+                    let tx = match requested.get_mut(tx_hash) {
+                        Some(count) if *count > 1 => {
+                            *count -= 1;
+                            txs.get(tx_hash).cloned()
+                        },
+                        // Last block including it
+                        Some(_) => {
+                            requested.remove(tx_hash);
+                            txs.remove(tx_hash)
+                        },
+                        None => Some(self.blockchain.get_tx(tx_hash).await?)
+                    };
+
+                    let tx = tx.ok_or_else(|| BlockchainError::TxNotFound(tx_hash.clone()))?;
+                    transactions.push(Immutable::Arc(tx));
+                }
+
+                trace!("Adding block {} from headers-first sync", hash);
+                let block = Block::new(Immutable::Arc(Arc::clone(header)), transactions);
+                self.blockchain.add_new_block(block, false, false).await?; // don't broadcast block because it's syncing
+                total += 1;
+            }
+        }
+
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fmt::{self, Formatter},
+        sync::{atomic::{AtomicUsize, Ordering}, Mutex}
+    };
+    use super::*;
+
+    struct TestPeer {
+        id: usize,
+        // windows it doesn't have
+        missing: Vec<usize>,
+        // windows it fails to send
+        failing: Vec<usize>,
+        penalties: AtomicUsize
+    }
+
+    impl Display for TestPeer {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "peer #{}", self.id)
+        }
+    }
+
+    impl WindowPeer for TestPeer {
+        fn penalize(&self) {
+            self.penalties.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn create_peer(id: usize, missing: Vec<usize>, failing: Vec<usize>) -> Arc<TestPeer> {
+        Arc::new(TestPeer {
+            id,
+            missing,
+            failing,
+            penalties: AtomicUsize::new(0)
+        })
+    }
+
+    // Each window contains a single hash made of its index
+    fn create_windows(count: usize) -> Vec<Vec<Hash>> {
+        (0..count).map(|i| vec![Hash::new([i as u8; 32])]).collect()
+    }
+
+    // Each object is the id of the peer that sent it
+    // All windows requested are recorded in order
+    fn fetch(requests: Arc<Mutex<Vec<usize>>>) -> impl Fn(Arc<TestPeer>, Vec<Hash>) -> std::future::Ready<WindowResult<usize>> {
+        move |peer, hashes| {
+            let index = hashes[0].as_bytes()[0] as usize;
+            requests.lock().unwrap().push(index);
+            let result = if peer.missing.contains(&index) {
+                Err(P2pError::ObjectNotFound(ObjectRequest::BlockHeader(hashes[0].clone())))
+            } else if peer.failing.contains(&index) {
+                Err(P2pError::ExpectedBlock)
+            } else {
+                Ok(hashes.into_iter().map(|hash| (hash, peer.id)).collect())
+            };
+            std::future::ready(result)
+        }
+    }
+
+    async fn collect_windows<F, Fut>(scheduler: &mut WindowScheduler<TestPeer, usize, F>) -> Result<Vec<usize>, P2pError>
+    where
+        F: Fn(Arc<TestPeer>, Vec<Hash>) -> Fut,
+        Fut: Future<Output = WindowResult<usize>> + Send + 'static
+    {
+        let mut senders = Vec::new();
+        while let Some(objects) = scheduler.next_window().await? {
+            senders.extend(objects.into_iter().map(|(_, id)| id));
+        }
+        Ok(senders)
+    }
+
+    #[tokio::test]
+    async fn test_windows_assignment() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let peers = vec![create_peer(0, vec![], vec![]), create_peer(1, vec![], vec![])];
+        let mut scheduler = WindowScheduler::new(peers, create_windows(6), fetch(Arc::clone(&requests)));
+
+        let senders = collect_windows(&mut scheduler).await.unwrap();
+        assert_eq!(senders.len(), 6);
+        // First windows are downloaded in parallel by each peer
+        assert_eq!(&senders[..2], &[0, 1]);
+        let mut requested = requests.lock().unwrap().clone();
+        requested.sort_unstable();
+        assert_eq!(requested, (0..6).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_windows_retry() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        // Peer 0 doesn't have window 0 but is still used for the others
+        // Peer 2 fails to send window 2 and is not used anymore
+        let peers = vec![create_peer(0, vec![0], vec![]), create_peer(1, vec![], vec![]), create_peer(2, vec![], vec![2])];
+        let mut scheduler = WindowScheduler::new(peers.clone(), create_windows(6), fetch(Arc::clone(&requests)));
+
+        let senders = collect_windows(&mut scheduler).await.unwrap();
+        assert_eq!(senders.len(), 6);
+        assert_eq!(senders[0], 1);
+        assert!(senders.contains(&0));
+        assert!(!senders.contains(&2));
+        assert_eq!(peers[0].penalties.load(Ordering::SeqCst), 0);
+        assert_eq!(peers[2].penalties.load(Ordering::SeqCst), 1);
+
+        // No peer left having the window
+        let peers = vec![create_peer(0, vec![1], vec![]), create_peer(1, vec![1], vec![])];
+        let mut scheduler = WindowScheduler::new(peers.clone(), create_windows(3), fetch(Arc::clone(&requests)));
+        assert!(matches!(collect_windows(&mut scheduler).await, Err(P2pError::HeadersSyncNoPeer(1))));
+        assert!(peers.iter().all(|peer| peer.penalties.load(Ordering::SeqCst) == 0));
+    }
+
+    #[tokio::test]
+    async fn test_windows_lookahead() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let peers = vec![create_peer(0, vec![], vec![]), create_peer(1, vec![], vec![]), create_peer(2, vec![], vec![])];
+        let mut scheduler = WindowScheduler::new(peers, create_windows(10), fetch(Arc::clone(&requests)));
+        scheduler.lookahead = 2;
+
+        // Only the windows in the lookahead are requested, even with a peer idle
+        assert!(scheduler.next_window().await.unwrap().is_some());
+        let mut requested = requests.lock().unwrap().clone();
+        requested.sort_unstable();
+        assert_eq!(requested, vec![0, 1]);
+
+        // Window 3 can't be requested before window 1 is consumed
+        assert!(scheduler.next_window().await.unwrap().is_some());
+        assert!(requests.lock().unwrap().iter().all(|index| *index < 3));
+
+        assert!(scheduler.next_window().await.unwrap().is_some());
+        assert!(requests.lock().unwrap().iter().all(|index| *index < 4));
+    }
+}
@@ -0,0 +1,247 @@
+use std::{collections::HashSet, fs};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use xelis_common::{
+    crypto::{
+        elgamal::{CompressedPublicKey, PublicKey as DecompressedPublicKey},
+        hash,
+        KeyPair,
+        PrivateKey,
+        Signature
+    },
+    serializer::Serializer
+};
+use super::{error::P2pError, EncryptionKey};
+
+// Domain of the message signed by each side of a connection
+const KEY_EXCHANGE_DOMAIN: &[u8] = b"xelis-p2p-key-exchange";
+
+// Persistent identity of the node
+// Its key signs the key exchange of each connection,
+// so a peer knowing it can detect a man-in-the-middle
+pub struct NodeIdentity {
+    keypair: KeyPair,
+    public_key: CompressedPublicKey
+}
+
+impl NodeIdentity {
+    // Load the identity from its file, or generate and save a new one
+    // A corrupted file is an error: a new identity would break the pinning done by our peers
+    pub fn load_or_create(filename: &str) -> Result<Self, P2pError> {
+        let keypair = if fs::metadata(filename).is_ok() {
+            let content = fs::read_to_string(filename)?;
+            let private_key = PrivateKey::from_hex(content.trim().to_owned())
+                .map_err(|_| P2pError::InvalidIdentityFile(filename.to_owned()))?;
+            KeyPair::from_private_key(private_key)
+        } else {
+            info!("P2p identity file not found, generating a new identity");
+            let keypair = KeyPair::new();
+            fs::write(filename, keypair.get_private_key().to_hex())?;
+            keypair
+        };
+
+        let public_key = keypair.get_public_key().compress();
+        Ok(Self {
+            keypair,
+            public_key
+        })
+    }
+
+    pub fn get_public_key(&self) -> &CompressedPublicKey {
+        &self.public_key
+    }
+
+    // Sign the key exchange of a connection
+    pub fn sign_key_exchange(&self, transcript: &KeyExchangeTranscript, out: bool) -> Signature {
+        self.keypair.sign(&transcript.to_message(out))
+    }
+}
+
+// Ephemeral keys exchanged at the start of a connection
+// The client key is always the one of the side that initiated the connection
+#[derive(Clone, Debug)]
+pub struct KeyExchangeTranscript {
+    client_key: EncryptionKey,
+    server_key: EncryptionKey
+}
+
+impl KeyExchangeTranscript {
+    pub fn new(client_key: EncryptionKey, server_key: EncryptionKey) -> Self {
+        Self {
+            client_key,
+            server_key
+        }
+    }
+
+    // Message signed by a side, its role is included
+    // so a signature can't be sent back to the node that created it
+    fn to_message(&self, out: bool) -> Vec<u8> {
+        let mut message = Vec::with_capacity(KEY_EXCHANGE_DOMAIN.len() + 1 + self.client_key.len() + self.server_key.len());
+        message.extend_from_slice(KEY_EXCHANGE_DOMAIN);
+        message.push(out as u8);
+        message.extend_from_slice(&self.client_key);
+        message.extend_from_slice(&self.server_key);
+        hash(&message).to_bytes().to_vec()
+    }
+
+    // Verify the signature of the key exchange by the peer identity
+    pub fn verify(&self, identity: &CompressedPublicKey, signature: &Signature, out: bool) -> bool {
+        let Ok(key) = identity.decompress() else {
+            return false
+        };
+
+        signature.verify(&self.to_message(out), &key)
+    }
+}
+
+// Derive the encryption keys of both sides from the ephemeral keys exchanged
+// Returns the key used by the client and the key used by the server
+pub fn derive_encryption_keys(our_key: &PrivateKey, peer_key: &DecompressedPublicKey, transcript: &KeyExchangeTranscript) -> (EncryptionKey, EncryptionKey) {
+    let shared = (peer_key.as_point() * our_key.as_scalar()).compress();
+    let derive = |side: &[u8]| {
+        let mut bytes = Vec::with_capacity(32 * 3 + side.len());
+        bytes.extend_from_slice(shared.as_bytes());
+        bytes.extend_from_slice(&transcript.client_key);
+        bytes.extend_from_slice(&transcript.server_key);
+        bytes.extend_from_slice(side);
+        hash(&bytes).to_bytes()
+    };
+
+    (derive(b"client"), derive(b"server"))
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct StoredIdentities {
+    allowed: HashSet<String>,
+    denied: HashSet<String>
+}
+
+// Allow and deny lists of peer identities
+// If the allow list is not empty, only the identities in it can connect
+pub struct IdentityList {
+    allowed: HashSet<CompressedPublicKey>,
+    denied: HashSet<CompressedPublicKey>,
+    filename: String
+}
+
+impl IdentityList {
+    pub fn new(filename: String) -> Self {
+        let stored: StoredIdentities = match fs::read_to_string(&filename) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                error!("Error while deserializing identities file: {}", e);
+                StoredIdentities::default()
+            }),
+            Err(_) => StoredIdentities::default()
+        };
+
+        let parse = |keys: HashSet<String>| -> HashSet<CompressedPublicKey> {
+            keys.into_iter()
+                .filter_map(|key| match CompressedPublicKey::from_hex(key.clone()) {
+                    Ok(key) => Some(key),
+                    Err(e) => {
+                        warn!("Invalid identity {} in identities file: {}", key, e);
+                        None
+                    }
+                })
+                .collect()
+        };
+
+        Self {
+            allowed: parse(stored.allowed),
+            denied: parse(stored.denied),
+            filename
+        }
+    }
+
+    // Verify that a peer identity can connect to us
+    pub fn is_allowed(&self, identity: &CompressedPublicKey) -> bool {
+        !self.denied.contains(identity) && (self.allowed.is_empty() || self.allowed.contains(identity))
+    }
+
+    pub fn is_in_allow_list(&self, identity: &CompressedPublicKey) -> bool {
+        self.allowed.contains(identity)
+    }
+
+    pub fn is_in_deny_list(&self, identity: &CompressedPublicKey) -> bool {
+        self.denied.contains(identity)
+    }
+
+    pub fn get_allow_list(&self) -> &HashSet<CompressedPublicKey> {
+        &self.allowed
+    }
+
+    pub fn get_deny_list(&self) -> &HashSet<CompressedPublicKey> {
+        &self.denied
+    }
+
+    // Add or remove an identity from the allow list
+    // Returns true if it was added
+    pub fn toggle_allowed(&mut self, identity: CompressedPublicKey) -> bool {
+        let added = Self::toggle(&mut self.allowed, identity);
+        self.save();
+        added
+    }
+
+    // Add or remove an identity from the deny list
+    // Returns true if it was added
+    pub fn toggle_denied(&mut self, identity: CompressedPublicKey) -> bool {
+        let added = Self::toggle(&mut self.denied, identity);
+        self.save();
+        added
+    }
+
+    fn toggle(list: &mut HashSet<CompressedPublicKey>, identity: CompressedPublicKey) -> bool {
+        if list.remove(&identity) {
+            false
+        } else {
+            list.insert(identity);
+            true
+        }
+    }
+
+    fn save(&self) {
+        let stored = StoredIdentities {
+            allowed: self.allowed.iter().map(|key| key.to_hex()).collect(),
+            denied: self.denied.iter().map(|key| key.to_hex()).collect()
+        };
+
+        let res = serde_json::to_string_pretty(&stored)
+            .map_err(P2pError::from)
+            .and_then(|content| fs::write(&self.filename, content).map_err(P2pError::from));
+        if let Err(e) = res {
+            error!("Error while saving identities file: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_exchange() {
+        let client = KeyPair::new();
+        let server = KeyPair::new();
+        let transcript = KeyExchangeTranscript::new(*client.get_public_key().compress().as_bytes(), *server.get_public_key().compress().as_bytes());
+
+        // Both sides derive the same keys
+        let client_keys = derive_encryption_keys(client.get_private_key(), server.get_public_key(), &transcript);
+        let server_keys = derive_encryption_keys(server.get_private_key(), client.get_public_key(), &transcript);
+        assert_eq!(client_keys, server_keys);
+        assert_ne!(client_keys.0, client_keys.1);
+
+        let identity = KeyPair::new();
+        let node = NodeIdentity {
+            public_key: identity.get_public_key().compress(),
+            keypair: identity
+        };
+        let signature = node.sign_key_exchange(&transcript, true);
+        assert!(transcript.verify(node.get_public_key(), &signature, true));
+        // Signature can't be used for the other side
+        assert!(!transcript.verify(node.get_public_key(), &signature, false));
+
+        // A relayed connection has a different transcript
+        let other = KeyExchangeTranscript::new(*KeyPair::new().get_public_key().compress().as_bytes(), *server.get_public_key().compress().as_bytes());
+        assert!(!other.verify(node.get_public_key(), &signature, true));
+    }
+}
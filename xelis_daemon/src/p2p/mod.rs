@@ -6,8 +6,15 @@ pub mod peer_list;
 pub mod chain_validator;
 mod tracker;
 mod encryption;
+mod dandelion;
+mod identity;
+mod score;
+mod headers_sync;
 
 pub use encryption::EncryptionKey;
+pub use dandelion::Dandelion;
+pub use identity::NodeIdentity;
+pub use score::PeerScore;
 
 use indexmap::IndexSet;
 use lru::LruCache;
@@ -24,10 +31,12 @@ use xelis_common::{
     difficulty::CumulativeDifficulty,
     immutable::Immutable,
     serializer::Serializer,
+    transaction::Transaction,
     time::{
         get_current_time_in_millis,
         get_current_time_in_seconds,
-        TimestampMillis
+        TimestampMillis,
+        TimestampSeconds
     }
 };
 use crate::{
@@ -36,13 +45,15 @@ use crate::{
         CHAIN_SYNC_DEFAULT_RESPONSE_BLOCKS, CHAIN_SYNC_DELAY, CHAIN_SYNC_REQUEST_EXPONENTIAL_INDEX_START,
         CHAIN_SYNC_REQUEST_MAX_BLOCKS, CHAIN_SYNC_RESPONSE_MIN_BLOCKS, CHAIN_SYNC_TOP_BLOCKS, PEER_MAX_PACKET_SIZE,
         MILLIS_PER_SECOND, NETWORK_ID, P2P_EXTEND_PEERLIST_DELAY, P2P_PING_DELAY, P2P_PING_PEER_LIST_DELAY, P2P_PING_PEER_LIST_LIMIT,
-        PEER_FAIL_LIMIT, PEER_TIMEOUT_INIT_CONNECTION, PRUNE_SAFETY_LIMIT, STABLE_LIMIT, P2P_PEER_WAIT_ON_ERROR
+        PEER_FAIL_LIMIT, PEER_TIMEOUT_INIT_CONNECTION, PRUNE_SAFETY_LIMIT, STABLE_LIMIT, P2P_PEER_WAIT_ON_ERROR,
+        P2P_DANDELION_CHECK_DELAY, P2P_LEGACY_PEER_RETRY_DELAY
     },
     core::{
         blockchain::Blockchain,
         error::BlockchainError,
         storage::Storage
     },
+    metrics,
     p2p::{
         chain_validator::ChainValidator,
         connection::ConnectionMessage,
@@ -55,6 +66,12 @@ use crate::{
                 MAX_ITEMS_PER_PAGE
             },
             chain::CommonPoint,
+            compact_block::{
+                compute_short_id,
+                BlockTransactionsRequest,
+                BlockTransactionsResponse,
+                CompactBlock
+            },
             inventory::{
                 NotifyInventoryRequest,
                 NotifyInventoryResponse,
@@ -69,7 +86,7 @@ use self::{
     packet::{
         chain::{BlockId, ChainRequest, ChainResponse},
         object::{ObjectRequest, ObjectResponse, OwnedObjectResponse},
-        handshake::Handshake,
+        handshake::{Capabilities, Handshake, HandshakeExtension},
         ping::Ping,
         {Packet, PacketWrapper}
     },
@@ -77,6 +94,7 @@ use self::{
     tracker::{ObjectTracker, SharedObjectTracker},
     peer_list::{SharedPeerList, PeerList},
     connection::{State, Connection},
+    score::is_invalid_object_error,
     error::P2pError
 };
 use tokio::{
@@ -97,13 +115,14 @@ use log::{info, warn, error, debug, trace};
 use std::{
     num::NonZeroUsize,
     borrow::Cow,
+    cmp::Reverse,
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering}
     },
-    collections::{hash_map::Entry, HashSet},
+    collections::{hash_map::Entry, HashMap, HashSet},
     convert::TryInto,
-    net::{IpAddr, SocketAddr},
+    net::{IpAddr, SocketAddr, TcpListener as StdTcpListener},
     time::Duration,
 };
 use bytes::Bytes;
@@ -115,6 +134,22 @@ enum MessageChannel {
     Connect((SocketAddr, bool))
 }
 
+// Block propagated by a peer, queued in the blocks processing task
+enum PropagatedBlock {
+    Header(BlockHeader),
+    // TXs hashes must be rebuilt from our mempool
+    Compact(CompactBlock)
+}
+
+impl PropagatedBlock {
+    fn get_height(&self) -> u64 {
+        match self {
+            Self::Header(header) => header.get_height(),
+            Self::Compact(compact) => compact.get_header().get_height()
+        }
+    }
+}
+
 // P2pServer is a fully async TCP server
 // Each connection will block on a data to send or to receive
 // useful for low end hardware
@@ -140,7 +175,7 @@ pub struct P2pServer<S: Storage> {
     // Synced cache to prevent concurrent tasks adding the block
     blocks_propagation_queue: Mutex<LruCache<Hash, ()>>,
     // Sender for the blocks processing task to have a ordered queue
-    blocks_processor: Sender<(Arc<Peer>, BlockHeader, Hash)>,
+    blocks_processor: Sender<(Arc<Peer>, PropagatedBlock, Hash)>,
     // allow fast syncing (only balances / assets / Smart Contracts changes)
     // without syncing the history
     allow_fast_sync_mode: bool,
@@ -163,10 +198,23 @@ pub struct P2pServer<S: Storage> {
     outgoing_connections_disabled: AtomicBool,
     // Are we syncing the chain with another peer
     is_syncing: AtomicBool,
+    // Dandelion relay state, None if our TXs are broadcasted directly
+    dandelion: Option<Mutex<Dandelion>>,
+    // Do we send and accept the compact blocks
+    compact_blocks: bool,
+    // Persistent identity signing the key exchange of each connection
+    identity: NodeIdentity,
+    // Addresses that closed the connection on our ephemeral key exchange
+    // They are older nodes, we use the legacy key exchange with them until the retry delay
+    legacy_peers: Mutex<HashMap<SocketAddr, TimestampSeconds>>,
+    // Reject the peers without identity instead of falling back to the legacy protocol
+    require_identity: bool,
+    // Download and verify the headers before the blocks TXs during chain sync
+    headers_first_sync: bool,
 }
 
 impl<S: Storage> P2pServer<S> {
-    pub fn new(dir_path: Option<String>, tag: Option<String>, max_peers: usize, bind_address: String, blockchain: Arc<Blockchain<S>>, use_peerlist: bool, exclusive_nodes: Vec<SocketAddr>, allow_fast_sync_mode: bool, allow_boost_sync_mode: bool, max_chain_response_size: Option<usize>, sharable: bool, disable_outgoing_connections: bool) -> Result<Arc<Self>, P2pError> {
+    pub fn new(dir_path: Option<String>, tag: Option<String>, max_peers: usize, bind_address: String, blockchain: Arc<Blockchain<S>>, use_peerlist: bool, exclusive_nodes: Vec<SocketAddr>, allow_fast_sync_mode: bool, allow_boost_sync_mode: bool, max_chain_response_size: Option<usize>, sharable: bool, disable_outgoing_connections: bool, dandelion: Option<Dandelion>, compact_blocks: bool, headers_first_sync: bool, require_identity: bool) -> Result<Arc<Self>, P2pError> {
         if let Some(tag) = &tag {
             debug_assert!(tag.len() > 0 && tag.len() <= 16);
         }
@@ -179,13 +227,22 @@ impl<S: Storage> P2pServer<S> {
         let mut rng = rand::thread_rng();
         let peer_id: u64 = rng.gen(); // generate a random peer id for network
         let addr: SocketAddr = bind_address.parse()?; // parse the bind address
+        // bind it now to know the port really used when 0 is requested
+        let listener = StdTcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
         // create mspc channel for connections to peers
         let (connections_sender, connections_receiver) = mpsc::unbounded_channel();
         let (blocks_processor, blocks_processor_receiver) = mpsc::channel(TIPS_LIMIT * STABLE_LIMIT as usize);
         let object_tracker = ObjectTracker::new(blockchain.clone());
 
         let (sender, event_receiver) = unbounded_channel::<Arc<Peer>>(); 
-        let peer_list = PeerList::new(max_peers, format!("{}peerlist-{}.json", dir_path.unwrap_or_default(), blockchain.get_network().to_string().to_lowercase()), Some(sender));
+        // identity and its allow/deny lists are stored next to the peerlist
+        let dir_path = dir_path.unwrap_or_default();
+        let network = blockchain.get_network().to_string().to_lowercase();
+        let identity = NodeIdentity::load_or_create(&format!("{}identity-{}.key", dir_path, network))?;
+        info!("P2p identity: {}", identity.get_public_key().to_hex());
+        let peer_list = PeerList::new(max_peers, format!("{}peerlist-{}.json", dir_path, network), format!("{}identities-{}.json", dir_path, network), Some(sender));
 
         let server = Self {
             peer_id,
@@ -206,13 +263,19 @@ impl<S: Storage> P2pServer<S> {
             sharable,
             is_syncing: AtomicBool::new(false),
             outgoing_connections_disabled: AtomicBool::new(disable_outgoing_connections),
+            dandelion: dandelion.map(Mutex::new),
+            compact_blocks,
+            identity,
+            legacy_peers: Mutex::new(HashMap::new()),
+            require_identity,
+            headers_first_sync,
         };
 
         let arc = Arc::new(server);
         {
             let zelf = Arc::clone(&arc);
             tokio::spawn(async move {
-                if let Err(e) = zelf.start(connections_receiver, blocks_processor_receiver, event_receiver, use_peerlist).await {
+                if let Err(e) = zelf.start(listener, connections_receiver, blocks_processor_receiver, event_receiver, use_peerlist).await {
                     error!("Unexpected error on P2p module: {}", e);
                 }
             });
@@ -287,8 +350,8 @@ impl<S: Storage> P2pServer<S> {
 
     // connect to seed nodes, start p2p server
     // and wait on all new connections
-    async fn start(self: &Arc<Self>, mut receiver: UnboundedReceiver<MessageChannel>, blocks_processor_receiver: Receiver<(Arc<Peer>, BlockHeader, Hash)>, event_receiver: UnboundedReceiver<Arc<Peer>>, use_peerlist: bool) -> Result<(), P2pError> {
-        let listener = TcpListener::bind(self.get_bind_address()).await?;
+    async fn start(self: &Arc<Self>, listener: StdTcpListener, mut receiver: UnboundedReceiver<MessageChannel>, blocks_processor_receiver: Receiver<(Arc<Peer>, PropagatedBlock, Hash)>, event_receiver: UnboundedReceiver<Arc<Peer>>, use_peerlist: bool) -> Result<(), P2pError> {
+        let listener = TcpListener::from_std(listener)?;
         info!("P2p Server will listen on: {}", self.get_bind_address());
 
         let mut exclusive_nodes = self.exclusive_nodes.clone();
@@ -320,6 +383,10 @@ impl<S: Storage> P2pServer<S> {
         // start the event loop task to handle peer disconnect events
         tokio::spawn(Arc::clone(&self).event_loop(event_receiver));
 
+        // start the task fluffing the stem TXs whose embargo has expired
+        if self.is_dandelion_enabled() {
+            tokio::spawn(Arc::clone(&self).dandelion_loop());
+        }
 
         // start another task for peerlist loop
         if use_peerlist {
@@ -343,21 +410,17 @@ impl<S: Storage> P2pServer<S> {
                     };
 
                     // Verify if we can accept new connections
-                    let reject = if !self.accept_new_connections().await { // if we have already reached the limit, we ignore this new connection
-                        debug!("Max peers reached, rejecting connection");
-                        true
-                    } else if !self.is_compatible_with_exclusive_nodes(&addr) {
+                    let reject = if !self.is_compatible_with_exclusive_nodes(&addr) {
                         debug!("{} is not an exclusive node, reject connection", addr);
                         true
+                    } else if !self.peer_list.read().await.is_allowed(&addr.ip()) { // check that this incoming peer isn't blacklisted
+                        debug!("{} is blacklisted, rejecting connection", addr);
+                        true
+                    } else if !self.accept_new_connections().await { // if we have already reached the limit, we ignore this new connection
+                        // a peer is evicted for it only once its handshake is verified
+                        !self.can_evict_peer_for(&addr).await
                     } else {
-                        // check that this incoming peer isn't blacklisted
-                        let peer_list = self.peer_list.read().await;
-                        if !peer_list.is_allowed(&addr.ip()) {
-                            debug!("{} is blacklisted, rejecting connection", addr);
-                            true
-                        } else {
-                            false
-                        }
+                        false
                     };
 
                     // Reject connection
@@ -412,7 +475,9 @@ impl<S: Storage> P2pServer<S> {
             if let Err(e) = self.handle_new_connection(&mut handshake_buffer, connection, priority).await {
                 debug!("Error occured on handled connection {} (out: {}, priority: {}): {}", addr, is_out, priority, e);
                 match e {
-                    P2pError::Disconnected => (),
+                    P2pError::Disconnected | P2pError::MaxPeersReached => (),
+                    // Connect again to it using the legacy key exchange
+                    P2pError::EphemeralKeyExchangeRejected(addr) => self.try_to_connect_to_peer(addr, priority).await,
                     _ => {
                         // if its a outgoing connection, increase its fail count
                         let mut peer_list = self.peer_list.write().await;
@@ -426,6 +491,32 @@ impl<S: Storage> P2pServer<S> {
         Ok(())
     }
 
+    // Verify if a connected peer could be evicted for this incoming address when we are full
+    // Only a peer already known can take a slot
+    async fn can_evict_peer_for(&self, addr: &SocketAddr) -> bool {
+        let peer_list = self.peer_list.read().await;
+        let can_evict = peer_list.get_stored_score(&addr.ip())
+            .map_or(false, |score| peer_list.find_peer_to_evict(score).is_some());
+        if !can_evict {
+            debug!("Max peers reached, rejecting connection");
+        }
+        can_evict
+    }
+
+    // Disconnect the peer with the lowest score if the incoming one has a better score
+    // Called once its handshake is verified, under the same lock used to add it
+    async fn evict_peer_for(&self, peer_list: &mut PeerList, peer: &Peer) -> Result<(), P2pError> {
+        let evicted = peer_list.get_stored_score(&peer.get_outgoing_address().ip())
+            .and_then(|score| peer_list.find_peer_to_evict(score))
+            .ok_or(P2pError::MaxPeersReached)?;
+
+        info!("Max peers reached, evicting {} for {} which has a better score", evicted, peer);
+        if let Err(e) = evicted.close_with_peerlist(peer_list).await {
+            debug!("Error while evicting {}: {}", evicted, e);
+        }
+        Ok(())
+    }
+
     // Verify handshake send by a new connection
     // based on data size, network ID, peers address validity
     // block height and block top hash of this peer (to know if we are on the same chain)
@@ -460,6 +551,45 @@ impl<S: Storage> P2pServer<S> {
             }
         }
 
+        // The extension is sent only if the negotiated protocol supports it
+        match (connection.get_protocol_version(), handshake.get_extension()) {
+            (0, None) => {
+                // A peer that already used an identity can't go back to the legacy protocol
+                // otherwise anyone in the middle could force the downgrade to skip the identity checks
+                if !self.can_use_legacy_protocol(&connection.get_address().ip()).await {
+                    debug!("{} tried to connect without identity while it is required", connection);
+                    connection.close().await?;
+                    return Err(P2pError::IdentityDowngrade)
+                }
+
+                // Legacy peers have no identity, we can't verify them against our allow list
+                if !self.peer_list.read().await.is_identity_allowed(None) {
+                    trace!("{} has no identity while our allow list is not empty", connection);
+                    connection.close().await?;
+                    return Err(P2pError::MissingIdentity)
+                }
+            },
+            (version, Some(extension)) if version > 0 => {
+                // The peer must have signed the keys exchanged on this connection with its identity
+                // The signature is done in its role, which is the opposite of ours
+                let key_exchange = connection.get_key_exchange().ok_or(P2pError::MissingKeyExchange)?;
+                if !key_exchange.verify(extension.get_identity(), extension.get_signature(), !connection.is_out()) {
+                    debug!("{} has an invalid key exchange signature for identity {}", connection, extension.get_identity().to_hex());
+                    return Err(P2pError::InvalidKeyExchangeSignature)
+                }
+
+                if !self.peer_list.read().await.is_identity_allowed(Some(extension.get_identity())) {
+                    trace!("{} has a not allowed identity {}", connection, extension.get_identity().to_hex());
+                    connection.close().await?;
+                    return Err(P2pError::IdentityNotAllowed(extension.get_identity().to_hex()))
+                }
+            },
+            (version, extension) => {
+                debug!("{} sent a handshake (extension: {}) not matching the protocol version {}", connection, extension.is_some(), version);
+                return Err(P2pError::InvalidHandshake)
+            }
+        }
+
         connection.set_state(State::Success);
         let peer = handshake.create_peer(connection, priority, Arc::clone(&self.peer_list));
         Ok(peer)
@@ -467,14 +597,24 @@ impl<S: Storage> P2pServer<S> {
 
     // Build a handshake packet
     // We feed the packet with all chain data
-    async fn build_handshake(&self) -> Result<Vec<u8>, P2pError> {
+    // and the signature of the key exchange done on this connection
+    // The extension is only sent if the negotiated protocol supports it
+    async fn build_handshake(&self, connection: &Connection) -> Result<Vec<u8>, P2pError> {
+        let extension = if connection.get_protocol_version() > 0 {
+            let key_exchange = connection.get_key_exchange().ok_or(P2pError::MissingKeyExchange)?;
+            let signature = self.identity.sign_key_exchange(key_exchange, connection.is_out());
+            Some(HandshakeExtension::new(self.get_capabilities(), Cow::Borrowed(self.identity.get_public_key()), Cow::Owned(signature)))
+        } else {
+            None
+        };
+
         let storage = self.blockchain.get_storage().read().await;
         let (block, top_hash) = storage.get_top_block_header().await?;
         let topoheight = self.blockchain.get_topo_height();
         let pruned_topoheight = storage.get_pruned_topoheight().await?;
         let cumulative_difficulty = storage.get_cumulative_difficulty_for_block_hash(&top_hash).await.unwrap_or_else(|_| CumulativeDifficulty::zero());
         let genesis_block = get_genesis_block_hash(self.blockchain.get_network());
-        let handshake = Handshake::new(Cow::Owned(VERSION.to_owned()), *self.blockchain.get_network(), Cow::Borrowed(self.get_tag()), Cow::Borrowed(&NETWORK_ID), self.get_peer_id(), self.bind_address.port(), get_current_time_in_seconds(), topoheight, block.get_height(), pruned_topoheight, Cow::Borrowed(&top_hash), Cow::Borrowed(genesis_block), Cow::Borrowed(&cumulative_difficulty), self.sharable);
+        let handshake = Handshake::new(Cow::Owned(VERSION.to_owned()), *self.blockchain.get_network(), Cow::Borrowed(self.get_tag()), Cow::Borrowed(&NETWORK_ID), self.get_peer_id(), self.bind_address.port(), get_current_time_in_seconds(), topoheight, block.get_height(), pruned_topoheight, Cow::Borrowed(&top_hash), Cow::Borrowed(genesis_block), Cow::Borrowed(&cumulative_difficulty), self.sharable, extension);
         Ok(Packet::Handshake(Cow::Owned(handshake)).to_bytes())
     }
 
@@ -483,7 +623,18 @@ impl<S: Storage> P2pServer<S> {
     // if the handshake is valid, we accept it & register it on server
     async fn handle_new_connection(self: &Arc<Self>, buf: &mut [u8], mut connection: Connection, priority: bool) -> Result<(), P2pError> {
         trace!("New connection: {}", connection);
-        connection.exchange_keys(buf).await?;
+        let legacy = connection.is_out() && self.is_legacy_peer(connection.get_address()).await;
+        match connection.exchange_keys(buf, legacy).await {
+            Ok(()) => {},
+            // Older nodes close the connection on the packet they don't know
+            Err(P2pError::Disconnected) if connection.is_out() && !legacy && self.can_use_legacy_protocol(&connection.get_address().ip()).await => {
+                let addr = *connection.get_address();
+                debug!("{} closed the connection during the ephemeral key exchange, marking it as legacy peer", addr);
+                self.legacy_peers.lock().await.insert(addr, get_current_time_in_seconds() + P2P_LEGACY_PEER_RETRY_DELAY);
+                return Err(P2pError::EphemeralKeyExchangeRejected(addr))
+            },
+            Err(e) => return Err(e)
+        };
         if connection.is_out() {
             self.send_handshake(&connection).await?;
         }
@@ -510,6 +661,10 @@ impl<S: Storage> P2pServer<S> {
             trace!("Locking peer list write mode (add peer)");
             let mut peer_list = self.peer_list.write().await;
             trace!("End locking peer list write mode (add peer)");
+            // the limit may have been reached while it was connecting
+            if !peer.is_out() && !peer.is_priority() && peer_list.size() >= self.get_max_peers() {
+                self.evict_peer_for(&mut peer_list, &peer).await?;
+            }
             peer_list.add_peer(peer_id, peer)
         };
 
@@ -527,6 +682,26 @@ impl<S: Storage> P2pServer<S> {
         self.handle_connection(peer).await
     }
 
+    // Verify if a peer is allowed to connect using the legacy protocol
+    // This is refused if we require an identity or if the peer already used one
+    async fn can_use_legacy_protocol(&self, ip: &IpAddr) -> bool {
+        !self.require_identity && !self.peer_list.read().await.has_pinned_identity(ip)
+    }
+
+    // Verify if we must use the legacy key exchange with this address
+    // Expired entries are removed so the ephemeral key exchange is tried again
+    async fn is_legacy_peer(&self, addr: &SocketAddr) -> bool {
+        let mut legacy_peers = self.legacy_peers.lock().await;
+        match legacy_peers.get(addr) {
+            Some(until) if *until > get_current_time_in_seconds() => true,
+            Some(_) => {
+                legacy_peers.remove(addr);
+                false
+            },
+            None => false
+        }
+    }
+
     // Verify that we don't have any exclusive nodes configured OR that we are part of this list
     pub fn is_compatible_with_exclusive_nodes(&self, addr: &SocketAddr) -> bool {
         self.exclusive_nodes.is_empty() || self.exclusive_nodes.contains(addr)
@@ -571,7 +746,7 @@ impl<S: Storage> P2pServer<S> {
     // Handsake is sent only once, when we connect to a new peer, and we get it back from connection to make it a peer
     async fn send_handshake(&self, connection: &Connection) -> Result<(), P2pError> {
         trace!("Sending handshake to {}", connection);
-        let handshake = self.build_handshake().await?;
+        let handshake = self.build_handshake(connection).await?;
         connection.send_bytes(&handshake).await
     }
 
@@ -683,6 +858,10 @@ impl<S: Storage> P2pServer<S> {
             }
         }
 
+        // Only keep the best half of the peers based on their score
+        peers.sort_by_cached_key(|p| Reverse(p.get_score().get_value()));
+        peers.truncate((peers.len() + 1) / 2);
+
         let count = peers.len();
         trace!("peers available for random selection: {}", count);
         if count == 0 {
@@ -706,6 +885,12 @@ impl<S: Storage> P2pServer<S> {
         self.allow_boost_sync_mode
     }
 
+    // Check if the headers-first sync is used
+    // Boost sync mode is explicitly enabled by the user, so it takes precedence
+    pub fn is_headers_first_sync(&self) -> bool {
+        self.headers_first_sync && !self.allow_boost_sync()
+    }
+
     // Set the chain syncing state
     fn set_chain_syncing(&self, syncing: bool) {
         self.is_syncing.store(syncing, Ordering::Release);
@@ -943,10 +1128,60 @@ impl<S: Storage> P2pServer<S> {
         debug!("Event loop task is stopped!");
     }
 
+    // Task fluffing the stem TXs whose embargo has expired
+    // This is the fallback in case a node of the stem never fluffed them
+    async fn dandelion_loop(self: Arc<Self>) {
+        debug!("Starting dandelion loop task...");
+        let Some(dandelion) = self.dandelion.as_ref() else {
+            return
+        };
+
+        let mut interval = interval(Duration::from_millis(P2P_DANDELION_CHECK_DELAY));
+        loop {
+            interval.tick().await;
+            if !self.is_running() {
+                break;
+            }
+
+            let expired = {
+                let mut dandelion = dandelion.lock().await;
+                dandelion.take_expired_txs(get_current_time_in_millis())
+            };
+
+            // TXs already diffused by the network don't need to be fluffed
+            let mut txs = Vec::with_capacity(expired.len());
+            for (hash, tx) in expired {
+                match self.blockchain.has_tx(&hash).await {
+                    Ok(true) => trace!("Stem TX {} was already fluffed", hash),
+                    Ok(false) => txs.push((hash, tx)),
+                    Err(e) => debug!("Error while checking if stem TX {} was fluffed: {}", hash, e)
+                }
+            }
+
+            if !txs.is_empty() {
+                debug!("Embargo expired for {} stem TXs, fluffing them", txs.len());
+                self.fluff_stem_txs(txs).await;
+            }
+        }
+        debug!("Dandelion loop task is stopped!");
+    }
+
     // Task for all blocks propagation
-    async fn blocks_processing_task(self: Arc<Self>, mut receiver: Receiver<(Arc<Peer>, BlockHeader, Hash)>) {
+    async fn blocks_processing_task(self: Arc<Self>, mut receiver: Receiver<(Arc<Peer>, PropagatedBlock, Hash)>) {
         debug!("Starting blocks processing task");
-        while let Some((peer, header, block_hash)) = receiver.recv().await {
+        while let Some((peer, block, block_hash)) = receiver.recv().await {
+            let header = match block {
+                PropagatedBlock::Header(header) => header,
+                PropagatedBlock::Compact(compact) => match self.reconstruct_compact_block(&peer, compact).await {
+                    Ok(header) => header,
+                    Err(e) => {
+                        error!("Error while rebuilding compact block {} from {}: {}", block_hash, peer, e);
+                        peer.increment_fail_count();
+                        continue;
+                    }
+                }
+            };
+
             let mut response_blockers: Vec<ResponseBlocker> = Vec::new();
             for hash in header.get_txs_hashes() {
                 let contains = { // we don't lock one time because we may wait on p2p response
@@ -995,6 +1230,9 @@ impl<S: Storage> P2pServer<S> {
             debug!("Adding received block {} from {} to chain", block_hash, peer);
             if let Err(e) = self.blockchain.add_new_block(block, true, false).await {
                 error!("Error while adding new block from {}: {}", peer, e);
+                if is_invalid_object_error(&e) {
+                    peer.add_invalid_object();
+                }
                 peer.increment_fail_count();
             }
         }
@@ -1147,6 +1385,11 @@ impl<S: Storage> P2pServer<S> {
                 peer.get_connection().close().await?;
                 return Err(P2pError::InvalidPacket)
             },
+            Packet::EphemeralKeyExchange(_) => {
+                error!("{} sent us an ephemeral key exchange packet (not valid!)", peer);
+                peer.get_connection().close().await?;
+                return Err(P2pError::InvalidPacket)
+            },
             Packet::KeyExchange(key) => {
                 trace!("{}: Rotate key packet", peer);
                 let key = key.into_owned();
@@ -1192,6 +1435,13 @@ impl<S: Storage> P2pServer<S> {
                     txs_cache.put(hash.clone(), Direction::Out);
                 }
             },
+            Packet::TransactionStem(tx) => {
+                trace!("{}: Transaction Stem packet", peer);
+                let tx = tx.into_owned();
+                let hash = tx.hash();
+                debug!("Received stem tx {} from {}", hash, peer.get_outgoing_address());
+                self.relay_stem_tx(hash, Arc::new(tx), Some(peer)).await?;
+            },
             Packet::BlockPropagation(packet_wrapper) => {
                 trace!("Received a block propagation packet from {}", peer);
                 let (header, ping) = packet_wrapper.consume();
@@ -1200,57 +1450,61 @@ impl<S: Storage> P2pServer<S> {
                 // check that the block height is valid
                 let header = header.into_owned();
                 let block_hash = header.hash();
+                self.handle_block_propagation(peer, PropagatedBlock::Header(header), block_hash).await?;
+            },
+            Packet::CompactBlockPropagation(packet_wrapper) => {
+                trace!("Received a compact block propagation packet from {}", peer);
+                let (compact, ping) = packet_wrapper.consume();
+                ping.into_owned().update_peer(peer, &self.blockchain).await?;
 
-                // verify that this block wasn't already sent by him
-                {
-                    let mut blocks_propagation = peer.get_blocks_propagation().lock().await;
-                    if let Some(direction) = blocks_propagation.get_mut(&block_hash) {
-                        if !direction.update(Direction::In) {
-                            debug!("{} send us a block ({}) already tracked by him ({:?})", peer, block_hash, direction);
-                            // return Err(P2pError::AlreadyTrackedBlock(block_hash, *direction))
-                        }
-                    } else {
-                        debug!("Saving {} in blocks propagation cache for {}", block_hash, peer);
-                        blocks_propagation.put(block_hash.clone(),  Direction::In);
-                    }
-                }
-
-                // Avoid sending the same block to a common peer that may have already got it
-                // because we track peerlist of each peers, we can try to determinate it
-                for common_peer in self.get_common_peers_for(&peer).await {
-                    debug!("{} is a common peer with {}, adding block {} to its propagation cache", common_peer, peer, block_hash);
-                    let mut blocks_propagation = common_peer.get_blocks_propagation().lock().await;
-                    // Out allow to get "In" again, because it's a prediction, don't block it completely
-                    if !blocks_propagation.contains(&block_hash) {
-                        blocks_propagation.put(block_hash.clone(), Direction::Out);
-                    }
+                // We only accept them if both sides advertised the capability
+                if !self.compact_blocks || !peer.supports_compact_blocks() {
+                    debug!("{} sent us a compact block without the compact blocks capability negotiated", peer);
+                    return Err(P2pError::InvalidPacket)
                 }
 
-                // check that we don't have this block in our chain
-                {
+                // the hash is verified once the block is rebuilt
+                let compact = compact.into_owned();
+                let block_hash = compact.get_hash().clone();
+                self.handle_block_propagation(peer, PropagatedBlock::Compact(compact), block_hash).await?;
+            },
+            Packet::BlockTransactionsRequest(request) => {
+                trace!("Received a block transactions request from {}", peer);
+                let request = request.into_owned();
+                let block = {
                     let storage = self.blockchain.get_storage().read().await;
-                    if storage.has_block_with_hash(&block_hash).await? {
-                        debug!("{}: {} with hash {} is already in our chain. Skipping", peer, header, block_hash);
-                        return Ok(())
-                    }
-                }
+                    storage.get_block_by_hash(request.get_hash()).await
+                };
 
-                // Check that we are not already waiting on it
-                {
-                    let mut blocks_propagation_queue = self.blocks_propagation_queue.lock().await;
-                    if blocks_propagation_queue.contains(&block_hash) {
-                        debug!("Block {} propagated is already in processing from another peer", block_hash);
-                        return Ok(())
+                // An empty response is sent if we don't have the block
+                let txs = match block {
+                    Ok(block) => {
+                        let transactions = block.get_transactions();
+                        let mut txs = Vec::with_capacity(request.get_indexes().len());
+                        for index in request.get_indexes() {
+                            let tx = transactions.get(*index as usize).ok_or(P2pError::InvalidPacket)?;
+                            txs.push(tx.clone());
+                        }
+                        txs
+                    },
+                    Err(e) => {
+                        debug!("{} asked TXs of block '{}' but not present in our chain: {}", peer, request.get_hash(), e);
+                        Vec::new()
                     }
-                    blocks_propagation_queue.put(block_hash.clone(), ());
-                }
+                };
 
-                let block_height = header.get_height();
-                debug!("Received block at height {} from {}", block_height, peer);
-                let peer = Arc::clone(peer);
-                // This will block the task if the bounded channel is full
-                if let Err(e) = self.blocks_processor.send((peer, header, block_hash)).await {
-                    error!("Error while sending block propagated to blocks processor task: {}", e);
+                let response = BlockTransactionsResponse::new(request.get_hash().clone(), txs);
+                peer.send_packet(Packet::BlockTransactionsResponse(Cow::Owned(response))).await?;
+            },
+            Packet::BlockTransactionsResponse(response) => {
+                trace!("Received a block transactions response from {}", peer);
+                let sender = peer.get_block_txs_channel()
+                    .lock().await
+                    .take()
+                    .ok_or(P2pError::UnrequestedBlockTransactionsResponse)?;
+
+                if sender.send(response.into_owned()).is_err() {
+                    error!("Error while sending block transactions response to channel of {}", peer);
                 }
             },
             Packet::ChainRequest(packet_wrapper) => {
@@ -1536,16 +1790,125 @@ impl<S: Storage> P2pServer<S> {
         Ok(())
     }
 
+    // Track a block propagated by a peer and queue it in the blocks processing task
+    async fn handle_block_propagation(self: &Arc<Self>, peer: &Arc<Peer>, block: PropagatedBlock, block_hash: Hash) -> Result<(), P2pError> {
+        // verify that this block wasn't already sent by him
+        {
+            let mut blocks_propagation = peer.get_blocks_propagation().lock().await;
+            if let Some(direction) = blocks_propagation.get_mut(&block_hash) {
+                if !direction.update(Direction::In) {
+                    debug!("{} send us a block ({}) already tracked by him ({:?})", peer, block_hash, direction);
+                    // return Err(P2pError::AlreadyTrackedBlock(block_hash, *direction))
+                }
+            } else {
+                debug!("Saving {} in blocks propagation cache for {}", block_hash, peer);
+                blocks_propagation.put(block_hash.clone(),  Direction::In);
+            }
+        }
+
+        // Avoid sending the same block to a common peer that may have already got it
+        // because we track peerlist of each peers, we can try to determinate it
+        for common_peer in self.get_common_peers_for(&peer).await {
+            debug!("{} is a common peer with {}, adding block {} to its propagation cache", common_peer, peer, block_hash);
+            let mut blocks_propagation = common_peer.get_blocks_propagation().lock().await;
+            // Out allow to get "In" again, because it's a prediction, don't block it completely
+            if !blocks_propagation.contains(&block_hash) {
+                blocks_propagation.put(block_hash.clone(), Direction::Out);
+            }
+        }
+
+        let block_height = block.get_height();
+        // check that we don't have this block in our chain
+        {
+            let storage = self.blockchain.get_storage().read().await;
+            if storage.has_block_with_hash(&block_hash).await? {
+                debug!("{}: block at height {} with hash {} is already in our chain. Skipping", peer, block_height, block_hash);
+                return Ok(())
+            }
+        }
+
+        // Check that we are not already waiting on it
+        {
+            let mut blocks_propagation_queue = self.blocks_propagation_queue.lock().await;
+            if blocks_propagation_queue.contains(&block_hash) {
+                debug!("Block {} propagated is already in processing from another peer", block_hash);
+                return Ok(())
+            }
+            blocks_propagation_queue.put(block_hash.clone(), ());
+        }
+
+        debug!("Received block at height {} from {}", block_height, peer);
+        let peer = Arc::clone(peer);
+        // This will block the task if the bounded channel is full
+        if let Err(e) = self.blocks_processor.send((peer, block, block_hash)).await {
+            error!("Error while sending block propagated to blocks processor task: {}", e);
+        }
+
+        Ok(())
+    }
+
+    // Rebuild the header of a compact block from our mempool
+    // Missing TXs are requested in a single batch to the peer
+    // If the block still can't be rebuilt, its full header is requested
+    async fn reconstruct_compact_block(&self, peer: &Arc<Peer>, compact: CompactBlock) -> Result<BlockHeader, P2pError> {
+        let block_hash = compact.get_hash().clone();
+        let (mut txs, missing) = {
+            let mempool = self.blockchain.get_mempool().read().await;
+            compact.reconstruct(mempool.get_txs().keys().map(|hash| hash.as_ref()))
+        };
+
+        if !missing.is_empty() {
+            debug!("Requesting {} missing TXs of compact block {} to {}", missing.len(), block_hash, peer);
+            let response = peer.request_block_transactions(BlockTransactionsRequest::new(block_hash.clone(), missing.clone())).await?;
+            let fetched = response.consume();
+            if fetched.len() != missing.len() {
+                return Err(P2pError::InvalidBlockTransactionsResponse(block_hash))
+            }
+
+            for (index, tx) in missing.into_iter().zip(fetched) {
+                let tx = tx.into_owned();
+                let hash = tx.hash();
+                if compute_short_id(&block_hash, &hash) != compact.get_short_ids()[index as usize] {
+                    return Err(P2pError::InvalidBlockTransactionsResponse(block_hash))
+                }
+
+                // the block is built from the TXs in mempool
+                if let Err(e) = self.blockchain.add_tx_to_mempool_with_hash(tx, hash.clone(), false).await {
+                    debug!("Error while adding TX {} of compact block {} to mempool: {}", hash, block_hash, e);
+                }
+                txs[index as usize] = Some(hash);
+            }
+        }
+
+        let txs: Option<Vec<Hash>> = txs.into_iter().collect();
+        if let Some(header) = txs.and_then(|txs| compact.into_header(txs)) {
+            return Ok(header)
+        }
+
+        debug!("Compact block {} from {} can't be rebuilt, requesting its header", block_hash, peer);
+        match peer.request_blocking_object(ObjectRequest::BlockHeader(block_hash)).await? {
+            OwnedObjectResponse::BlockHeader(header, _) => Ok(header),
+            _ => Err(P2pError::InvalidObjectResponseType)
+        }
+    }
+
     // Listen to incoming packets from a connection
     // Packet is read from the same task always, while its handling is delegated to a unique task
     async fn listen_connection(self: &Arc<Self>, buf: &mut [u8], peer: &Arc<Peer>) -> Result<(), P2pError> {
         // Read & parse the packet
         // 16 additional bytes are for AEAD
-        let packet = peer.get_connection().read_packet(buf, PEER_MAX_PACKET_SIZE).await?;
+        let packet = match peer.get_connection().read_packet(buf, PEER_MAX_PACKET_SIZE).await {
+            Ok(packet) => packet,
+            Err(e) => {
+                metrics::record_p2p_error(&e);
+                return Err(e)
+            }
+        };
         let packet_id = packet.get_id();
         // Handle the packet
         if let Err(e) = self.handle_incoming_packet(&peer, packet).await {
             error!("Error occured while handling incoming packet #{} from {}: {}", packet_id, peer, e);
+            metrics::record_p2p_error(&e);
             peer.increment_fail_count();
         }
 
@@ -1825,6 +2188,10 @@ impl<S: Storage> P2pServer<S> {
                     }
                 }
             }
+        } else if self.is_headers_first_sync() {
+            // no rewind are needed, download all headers first, then the TXs in parallel
+            let total_requested = self.headers_first_sync(peer, blocks, common_topoheight).await?;
+            info!("we've synced {} on {} blocks and {} top blocks from {} using headers-first sync", total_requested, blocks_len, top_len, peer);
         } else {
             // no rewind are needed, process normally
             // it will first add blocks to sync, and then all alt-tips blocks if any (top blocks)
@@ -1952,6 +2319,11 @@ impl<S: Storage> P2pServer<S> {
         self.peer_id
     }
 
+    // Get our persistent identity
+    pub fn get_identity(&self) -> &NodeIdentity {
+        &self.identity
+    }
+
     // Check if we are accepting new connections by verifying if we have free slots available
     pub async fn accept_new_connections(&self) -> bool {
         self.get_peer_count().await < self.get_max_peers()
@@ -2040,6 +2412,115 @@ impl<S: Storage> P2pServer<S> {
         }
     }
 
+    pub fn is_dandelion_enabled(&self) -> bool {
+        self.dandelion.is_some()
+    }
+
+    // Optional features advertised to our peers
+    pub fn get_capabilities(&self) -> Capabilities {
+        let mut capabilities = Capabilities::empty();
+        capabilities.set(Capabilities::STEM_RELAY, self.is_dandelion_enabled());
+        capabilities.set(Capabilities::COMPACT_BLOCKS, self.compact_blocks);
+        capabilities
+    }
+
+    // Relay a TX through the Dandelion stem
+    // The peer is the one from which we received it, None if it's a TX submitted to us
+    // It is fluffed directly if Dandelion is disabled or if no peer can continue the stem
+    pub async fn relay_stem_tx(&self, hash: Hash, tx: Arc<Transaction>, peer: Option<&Arc<Peer>>) -> Result<(), P2pError> {
+        let Some(dandelion) = self.dandelion.as_ref() else {
+            self.fluff_stem_txs(vec![(hash, tx)]).await;
+            return Ok(())
+        };
+
+        if self.blockchain.has_tx(&hash).await? {
+            debug!("Stem TX {} was already fluffed, ignoring", hash);
+            return Ok(())
+        }
+
+        let previous = {
+            let dandelion = dandelion.lock().await;
+            if dandelion.has_tx(&hash) {
+                debug!("Stem TX {} is already in our stem, ignoring", hash);
+                return Ok(())
+            }
+            dandelion.get_txs_for(tx.get_source())
+        };
+
+        // Never relay an invalid TX, this would make the stem a spam vector
+        self.blockchain.verify_stem_tx(&hash, &tx, &previous).await?;
+
+        let mut dandelion = dandelion.lock().await;
+        // Our own TXs are always stemmed to not reveal that we are the origin
+        let stem_peer = if dandelion.is_full() || (peer.is_some() && dandelion.should_fluff()) {
+            None
+        } else {
+            self.get_stem_peer(&mut dandelion, peer).await
+        };
+
+        match stem_peer {
+            Some(stem_peer) => {
+                if dandelion.has_tx(&hash) {
+                    return Ok(())
+                }
+
+                debug!("Stemming TX {} to {}", hash, stem_peer);
+                let packet = Packet::TransactionStem(Cow::Borrowed(tx.as_ref()));
+                if let Err(e) = stem_peer.send_packet(packet).await {
+                    debug!("Error while stemming TX {} to {}: {}", hash, stem_peer, e);
+                }
+                // Even if the peer didn't receive it, it will be fluffed at the end of its embargo
+                dandelion.add_tx(hash, tx, get_current_time_in_millis());
+            },
+            None => {
+                // The previous TXs of the same source in our stem are needed to accept it
+                let mut txs = dandelion.take_txs_until(tx.get_source(), tx.get_nonce());
+                drop(dandelion);
+
+                debug!("Fluffing stem TX {}", hash);
+                txs.push((hash, tx));
+                self.fluff_stem_txs(txs).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Select the peer to which we stem the TXs
+    // Outgoing peers are preferred as it is harder for an attacker to be one of them
+    // Returns None if the stem must end here
+    async fn get_stem_peer(&self, dandelion: &mut Dandelion, from: Option<&Arc<Peer>>) -> Option<Arc<Peer>> {
+        let peer_list = self.peer_list.read().await;
+        let peers: Vec<&Arc<Peer>> = peer_list.get_peers().values()
+            .filter(|peer| peer.supports_stem_relay())
+            .collect();
+
+        let candidates: Vec<u64> = if peers.iter().any(|peer| peer.is_out()) {
+            peers.iter().filter(|peer| peer.is_out()).map(|peer| peer.get_id()).collect()
+        } else {
+            peers.iter().map(|peer| peer.get_id()).collect()
+        };
+
+        let stem_peer = dandelion.get_stem_peer(&candidates, get_current_time_in_millis())?;
+        // Don't send it back to the peer from which we received it
+        if from.map_or(false, |peer| peer.get_id() == stem_peer) {
+            return None
+        }
+
+        peer_list.get_peers().get(&stem_peer).cloned()
+    }
+
+    // Add the TXs to our mempool and broadcast them to all our peers
+    // They must be in nonce order for each source
+    async fn fluff_stem_txs(&self, txs: Vec<(Hash, Arc<Transaction>)>) {
+        let storage = self.blockchain.get_storage().read().await;
+        for (hash, tx) in txs {
+            if let Err(e) = self.blockchain.add_tx_to_mempool_with_storage_and_hash(&*storage, tx, hash.clone(), true).await {
+                debug!("Error while fluffing stem TX {}: {}", hash, e);
+            }
+        }
+    }
+
     // broadcast block to all peers that can accept directly this new block
     pub async fn broadcast_block(&self, block: &BlockHeader, cumulative_difficulty: CumulativeDifficulty, our_topoheight: u64, our_height: u64, pruned_topoheight: Option<u64>, hash: &Hash, lock: bool) {
         debug!("Broadcasting block {} at height {}", hash, block.get_height());
@@ -2048,6 +2529,16 @@ impl<S: Storage> P2pServer<S> {
         let ping = Ping::new(Cow::Borrowed(hash), our_topoheight, our_height, pruned_topoheight, cumulative_difficulty, Vec::new());
         let block_packet = Packet::BlockPropagation(PacketWrapper::new(Cow::Borrowed(block), Cow::Borrowed(&ping)));
         let packet_block_bytes = Bytes::from(block_packet.to_bytes());
+        // Compact version for the peers supporting it
+        // None if two TXs of the block have the same short ID
+        let packet_compact_block_bytes = if self.compact_blocks {
+            CompactBlock::new(block, hash.clone()).map(|compact| {
+                let packet = Packet::CompactBlockPropagation(PacketWrapper::new(Cow::Owned(compact), Cow::Borrowed(&ping)));
+                Bytes::from(packet.to_bytes())
+            })
+        } else {
+            None
+        };
         let packet_ping_bytes = Bytes::from(Packet::Ping(Cow::Owned(ping)).to_bytes());
 
         trace!("Locking peer list for broadcasting block {}", hash);
@@ -2070,8 +2561,14 @@ impl<S: Storage> P2pServer<S> {
                     // he should not send it back to us if it's a block found by us
                     blocks_propagation.put(hash.clone(), if lock { Direction::Both } else { Direction::Out });
 
+                    // Fall back to the full block if the peer didn't advertise the compact blocks capability
+                    let bytes = match &packet_compact_block_bytes {
+                        Some(bytes) if peer.supports_compact_blocks() => bytes,
+                        _ => &packet_block_bytes
+                    };
+
                     debug!("Broadcast {} to {} (lock: {})", hash, peer, lock);
-                    if let Err(e) = peer.send_bytes(packet_block_bytes.clone()).await {
+                    if let Err(e) = peer.send_bytes(bytes.clone()).await {
                         debug!("Error on broadcast block {} to {}: {}", hash, peer, e);
                     }
                 } else {
@@ -0,0 +1,301 @@
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use indexmap::IndexSet;
+use log::debug;
+use xelis_common::{
+    block::BlockHeader,
+    crypto::{hash, Hash, Hashable, HASH_SIZE},
+    immutable::Immutable,
+    serializer::{Reader, ReaderError, Serializer, Writer},
+    transaction::Transaction
+};
+
+// Short ID of a TX in a compact block
+pub type ShortTxId = u64;
+
+// Compute the short ID of a TX for a block
+// The block hash is used as salt so the short IDs change for each block
+pub fn compute_short_id(block_hash: &Hash, tx_hash: &Hash) -> ShortTxId {
+    let mut bytes = [0u8; HASH_SIZE * 2];
+    bytes[..HASH_SIZE].copy_from_slice(block_hash.as_bytes());
+    bytes[HASH_SIZE..].copy_from_slice(tx_hash.as_bytes());
+
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&hash(&bytes).as_bytes()[..8]);
+    ShortTxId::from_le_bytes(id)
+}
+
+// Block propagated with the short IDs of its TXs instead of their hashes
+// The receiver rebuilds the TXs list from its mempool
+#[derive(Clone, Debug)]
+pub struct CompactBlock {
+    // hash of the full block
+    hash: Hash,
+    // block header without its TXs hashes
+    header: BlockHeader,
+    // short IDs of the TXs in the block order
+    short_ids: Vec<ShortTxId>
+}
+
+impl CompactBlock {
+    // Build the compact block from a full header
+    // Returns None if two TXs of the block have the same short ID
+    pub fn new(header: &BlockHeader, hash: Hash) -> Option<Self> {
+        let mut short_ids = Vec::with_capacity(header.get_txs_count());
+        let mut unique = HashSet::with_capacity(header.get_txs_count());
+        for tx in header.get_txs_hashes() {
+            let id = compute_short_id(&hash, tx);
+            if !unique.insert(id) {
+                debug!("Short ID collision for TX {} in block {}", tx, hash);
+                return None
+            }
+            short_ids.push(id);
+        }
+
+        let mut header = header.clone();
+        header.txs_hashes = IndexSet::new();
+
+        Some(Self {
+            hash,
+            header,
+            short_ids
+        })
+    }
+
+    pub fn get_hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    pub fn get_header(&self) -> &BlockHeader {
+        &self.header
+    }
+
+    pub fn get_short_ids(&self) -> &Vec<ShortTxId> {
+        &self.short_ids
+    }
+
+    // Match the short IDs against the known TXs hashes
+    // Returns the TXs found in the block order and the indexes of the missing ones
+    // A short ID shared by several known TXs is considered as missing
+    pub fn reconstruct<'a, I: Iterator<Item = &'a Hash>>(&self, known: I) -> (Vec<Option<Hash>>, IndexSet<u16>) {
+        let mut candidates: HashMap<ShortTxId, Option<&Hash>> = HashMap::new();
+        for tx in known {
+            match candidates.entry(compute_short_id(&self.hash, tx)) {
+                Entry::Occupied(mut entry) => {
+                    entry.insert(None);
+                },
+                Entry::Vacant(entry) => {
+                    entry.insert(Some(tx));
+                }
+            };
+        }
+
+        let mut txs = Vec::with_capacity(self.short_ids.len());
+        let mut missing = IndexSet::new();
+        for (i, id) in self.short_ids.iter().enumerate() {
+            match candidates.get(id) {
+                Some(Some(tx)) => txs.push(Some((*tx).clone())),
+                _ => {
+                    missing.insert(i as u16);
+                    txs.push(None);
+                }
+            }
+        }
+
+        (txs, missing)
+    }
+
+    // Build the full header from all its TXs hashes
+    // Returns None if they don't match the expected block hash
+    pub fn into_header(self, txs: Vec<Hash>) -> Option<BlockHeader> {
+        if txs.len() != self.short_ids.len() {
+            return None
+        }
+
+        let mut header = self.header;
+        header.txs_hashes = IndexSet::from_iter(txs);
+        if header.get_txs_count() != self.short_ids.len() || header.hash() != self.hash {
+            return None
+        }
+
+        Some(header)
+    }
+}
+
+impl Serializer for CompactBlock {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_hash(&self.hash);
+        self.header.write(writer);
+        writer.write_u16(self.short_ids.len() as u16);
+        for id in &self.short_ids {
+            writer.write_u64(id);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let hash = reader.read_hash()?;
+        let header = BlockHeader::read(reader)?;
+        if header.get_txs_count() != 0 {
+            debug!("Compact block header contains TXs hashes");
+            return Err(ReaderError::InvalidValue)
+        }
+
+        let count = reader.read_u16()?;
+        let mut short_ids = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            short_ids.push(reader.read_u64()?);
+        }
+
+        Ok(Self {
+            hash,
+            header,
+            short_ids
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.hash.size() + self.header.size() + 2 + self.short_ids.len() * 8
+    }
+}
+
+// Request the TXs of a compact block that we couldn't find in our mempool
+#[derive(Clone, Debug)]
+pub struct BlockTransactionsRequest {
+    // hash of the block
+    hash: Hash,
+    // indexes of the TXs in the block
+    indexes: IndexSet<u16>
+}
+
+impl BlockTransactionsRequest {
+    pub fn new(hash: Hash, indexes: IndexSet<u16>) -> Self {
+        Self {
+            hash,
+            indexes
+        }
+    }
+
+    pub fn get_hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    pub fn get_indexes(&self) -> &IndexSet<u16> {
+        &self.indexes
+    }
+}
+
+impl Serializer for BlockTransactionsRequest {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_hash(&self.hash);
+        writer.write_u16(self.indexes.len() as u16);
+        for index in &self.indexes {
+            writer.write_u16(*index);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let hash = reader.read_hash()?;
+        let count = reader.read_u16()?;
+        let mut indexes = IndexSet::with_capacity(count as usize);
+        for _ in 0..count {
+            if !indexes.insert(reader.read_u16()?) {
+                debug!("Duplicated index in block transactions request");
+                return Err(ReaderError::InvalidValue)
+            }
+        }
+
+        Ok(Self::new(hash, indexes))
+    }
+
+    fn size(&self) -> usize {
+        self.hash.size() + 2 + self.indexes.len() * 2
+    }
+}
+
+// TXs requested, in the same order as the indexes of the request
+#[derive(Clone, Debug)]
+pub struct BlockTransactionsResponse {
+    // hash of the block
+    hash: Hash,
+    // TXs requested, empty if the block is not found
+    txs: Vec<Immutable<Transaction>>
+}
+
+impl BlockTransactionsResponse {
+    pub fn new(hash: Hash, txs: Vec<Immutable<Transaction>>) -> Self {
+        Self {
+            hash,
+            txs
+        }
+    }
+
+    pub fn get_hash(&self) -> &Hash {
+        &self.hash
+    }
+
+    pub fn consume(self) -> Vec<Immutable<Transaction>> {
+        self.txs
+    }
+}
+
+impl Serializer for BlockTransactionsResponse {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_hash(&self.hash);
+        writer.write_u16(self.txs.len() as u16);
+        for tx in &self.txs {
+            tx.write(writer);
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let hash = reader.read_hash()?;
+        let count = reader.read_u16()?;
+        let mut txs = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            txs.push(Immutable::Owned(Transaction::read(reader)?));
+        }
+
+        Ok(Self::new(hash, txs))
+    }
+
+    fn size(&self) -> usize {
+        self.hash.size() + 2 + self.txs.iter().map(|tx| tx.size()).sum::<usize>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use super::*;
+
+    fn create_header(txs: usize) -> BlockHeader {
+        let txs_hashes = (0..txs).map(|i| hash(&[i as u8])).collect();
+        BlockHeader::new(0, 1, 0, IndexSet::new(), [0u8; 32], KeyPair::new().get_public_key().compress(), txs_hashes)
+    }
+
+    #[test]
+    fn test_compact_block_reconstruction() {
+        let header = create_header(4);
+        let block_hash = header.hash();
+        let compact = CompactBlock::from_bytes(&CompactBlock::new(&header, block_hash).unwrap().to_bytes()).unwrap();
+        assert_eq!(compact.get_short_ids().len(), 4);
+
+        // Only the second TX is unknown
+        let txs: Vec<&Hash> = header.get_txs_hashes().iter().collect();
+        let unknown = hash(&[42]);
+        let (found, missing) = compact.reconstruct([txs[0], txs[2], txs[3], &unknown].into_iter());
+        assert_eq!(missing, IndexSet::from([1]));
+        assert!(found[1].is_none());
+
+        let txs: Vec<Hash> = found.into_iter()
+            .enumerate()
+            .map(|(i, tx)| tx.unwrap_or_else(|| header.get_txs_hashes()[i].clone()))
+            .collect();
+        let rebuilt = compact.clone().into_header(txs).unwrap();
+        assert_eq!(rebuilt.hash(), header.hash());
+
+        // Wrong order is detected by the block hash
+        let mut txs: Vec<Hash> = header.get_txs_hashes().iter().cloned().collect();
+        txs.swap(0, 1);
+        assert!(compact.into_header(txs).is_none());
+    }
+}
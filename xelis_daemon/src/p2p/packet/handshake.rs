@@ -1,6 +1,6 @@
 use log::debug;
 use xelis_common::{
-    crypto::Hash,
+    crypto::{elgamal::CompressedPublicKey, Hash, Signature},
     difficulty::CumulativeDifficulty,
     network::Network,
     serializer::{Reader, ReaderError, Serializer, Writer},
@@ -52,13 +52,113 @@ pub struct Handshake<'a> {
     cumulative_difficulty: Cow<'a, CumulativeDifficulty>,
     // By default it's true, and peer allow to be shared to others and/or through API
     // If false, we must not share it
-    can_be_shared: bool
+    can_be_shared: bool,
+    // Fields that are not part of the first version of the handshake
+    // Older nodes don't send it
+    extension: Option<HandshakeExtension<'a>>
 } // Server reply with his own list of peers, but we remove all already known by requester for the response.
 
+// Optional features supported by a node
+// Unknown bits are ignored so new ones can be added without breaking older nodes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities(u16);
+
+impl Capabilities {
+    // Accept the TXs relayed through the Dandelion stem
+    pub const STEM_RELAY: Self = Self(1 << 0);
+    // Accept the compact blocks propagation
+    pub const COMPACT_BLOCKS: Self = Self(1 << 1);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    // Enable or disable a capability
+    pub fn set(&mut self, capability: Self, enabled: bool) {
+        if enabled {
+            self.0 |= capability.0;
+        } else {
+            self.0 &= !capability.0;
+        }
+    }
+
+    pub fn contains(&self, capability: Self) -> bool {
+        self.0 & capability.0 == capability.0
+    }
+}
+
+impl Serializer for Capabilities {
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u16(self.0);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        Ok(Self(reader.read_u16()?))
+    }
+
+    fn size(&self) -> usize {
+        self.0.size()
+    }
+}
+
+// Extension of the handshake sent after its base fields
+// It is only read if some bytes remain, so the handshake of older nodes is still valid
+#[derive(Clone, Debug)]
+pub struct HandshakeExtension<'a> {
+    // Features supported by the node
+    capabilities: Capabilities,
+    // Persistent identity key of the node
+    identity: Cow<'a, CompressedPublicKey>,
+    // Signature of the key exchange of this connection by the identity key
+    signature: Cow<'a, Signature>
+}
+
+impl<'a> HandshakeExtension<'a> {
+    pub fn new(capabilities: Capabilities, identity: Cow<'a, CompressedPublicKey>, signature: Cow<'a, Signature>) -> Self {
+        Self {
+            capabilities,
+            identity,
+            signature
+        }
+    }
+
+    pub fn get_capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    pub fn get_identity(&self) -> &CompressedPublicKey {
+        &self.identity
+    }
+
+    pub fn get_signature(&self) -> &Signature {
+        &self.signature
+    }
+}
+
+impl Serializer for HandshakeExtension<'_> {
+    fn write(&self, writer: &mut Writer) {
+        self.capabilities.write(writer);
+        self.identity.write(writer);
+        self.signature.write(writer);
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let capabilities = Capabilities::read(reader)?;
+        let identity = CompressedPublicKey::read(reader)?;
+        let signature = Signature::read(reader)?;
+
+        Ok(Self::new(capabilities, Cow::Owned(identity), Cow::Owned(signature)))
+    }
+
+    fn size(&self) -> usize {
+        self.capabilities.size() + self.identity.size() + self.signature.size()
+    }
+}
+
 impl<'a> Handshake<'a> {
     pub const MAX_LEN: usize = 16;
 
-    pub fn new(version: Cow<'a, String>, network: Network, node_tag: Cow<'a, Option<String>>, network_id: Cow<'a, [u8; 16]>, peer_id: u64, local_port: u16, utc_time: TimestampSeconds, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, top_hash: Cow<'a, Hash>, genesis_hash: Cow<'a, Hash>, cumulative_difficulty: Cow<'a, CumulativeDifficulty>, can_be_shared: bool) -> Self {
+    pub fn new(version: Cow<'a, String>, network: Network, node_tag: Cow<'a, Option<String>>, network_id: Cow<'a, [u8; 16]>, peer_id: u64, local_port: u16, utc_time: TimestampSeconds, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, top_hash: Cow<'a, Hash>, genesis_hash: Cow<'a, Hash>, cumulative_difficulty: Cow<'a, CumulativeDifficulty>, can_be_shared: bool, extension: Option<HandshakeExtension<'a>>) -> Self {
         debug_assert!(version.len() > 0 && version.len() <= Handshake::MAX_LEN);
         // version cannot be greater than 16 chars
         if let Some(node_tag) = node_tag.as_ref() {
@@ -80,14 +180,19 @@ impl<'a> Handshake<'a> {
             top_hash,
             genesis_hash,
             cumulative_difficulty,
-            can_be_shared
+            can_be_shared,
+            extension
         }
     }
 
     // Create a new peer using its connection and this handshake packet
     pub fn create_peer(self, connection: Connection, priority: bool, peer_list: SharedPeerList) -> Peer {
         let peers = HashSet::new();
-        Peer::new(connection, self.get_peer_id(), self.node_tag.into_owned(), self.local_port, self.version.into_owned(), self.top_hash.into_owned(), self.topoheight, self.height, self.pruned_topoheight, priority, self.cumulative_difficulty.into_owned(), peer_list, peers, self.can_be_shared)
+        let (capabilities, identity) = match self.extension {
+            Some(extension) => (extension.capabilities, Some(extension.identity.into_owned())),
+            None => (Capabilities::empty(), None)
+        };
+        Peer::new(connection, self.get_peer_id(), self.node_tag.into_owned(), self.local_port, self.version.into_owned(), self.top_hash.into_owned(), self.topoheight, self.height, self.pruned_topoheight, priority, self.cumulative_difficulty.into_owned(), peer_list, peers, self.can_be_shared, capabilities, identity)
     }
 
     pub fn get_version(&self) -> &String {
@@ -133,6 +238,10 @@ impl<'a> Handshake<'a> {
     pub fn get_pruned_topoheight(&self) -> &Option<u64> {
         &self.pruned_topoheight
     }
+
+    pub fn get_extension(&self) -> Option<&HandshakeExtension<'a>> {
+        self.extension.as_ref()
+    }
 }
 
 impl Serializer for Handshake<'_> {
@@ -158,6 +267,9 @@ impl Serializer for Handshake<'_> {
         writer.write_hash(&self.genesis_hash); // Genesis Hash
         self.cumulative_difficulty.write(writer); // Cumulative Difficulty
         writer.write_bool(self.can_be_shared); // Can be shared
+        if let Some(extension) = &self.extension {
+            extension.write(writer); // Extension
+        }
     }
 
     fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
@@ -199,8 +311,14 @@ impl Serializer for Handshake<'_> {
         let genesis_hash = reader.read_hash()?;
         let cumulative_difficulty = CumulativeDifficulty::read(reader)?;
         let can_be_shared = reader.read_bool()?;
+        // Handshake of older nodes ends here
+        let extension = if reader.size() > 0 {
+            Some(HandshakeExtension::read(reader)?)
+        } else {
+            None
+        };
 
-        Ok(Handshake::new(Cow::Owned(version), network, Cow::Owned(node_tag), Cow::Owned(network_id), peer_id, local_port, utc_time, topoheight, height, pruned_topoheight, Cow::Owned(top_hash), Cow::Owned(genesis_hash), Cow::Owned(cumulative_difficulty), can_be_shared))
+        Ok(Handshake::new(Cow::Owned(version), network, Cow::Owned(node_tag), Cow::Owned(network_id), peer_id, local_port, utc_time, topoheight, height, pruned_topoheight, Cow::Owned(top_hash), Cow::Owned(genesis_hash), Cow::Owned(cumulative_difficulty), can_be_shared, extension))
     }
 
     fn size(&self) -> usize {
@@ -231,7 +349,9 @@ impl Serializer for Handshake<'_> {
         // Cumulative Difficulty
         self.cumulative_difficulty.size() +
         // Can be shared
-        self.can_be_shared.size()
+        self.can_be_shared.size() +
+        // Extension
+        self.extension.as_ref().map_or(0, |extension| extension.size())
     }
 }
 
@@ -246,4 +366,54 @@ impl Display for Handshake<'_> {
         };
         write!(f, "Handshake[version: {}, node tag: {}, network_id: {}, peer_id: {}, utc_time: {}, block_height: {}, block_top_hash: {}]", self.get_version(), node_tag, hex::encode(self.get_network_id()), self.get_peer_id(), self.get_utc_time(), self.get_block_height(), self.get_block_top_hash())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use crate::config::NETWORK_ID;
+    use super::*;
+
+    fn create_handshake(extension: Option<HandshakeExtension<'static>>) -> Handshake<'static> {
+        Handshake::new(Cow::Owned("1.0.0".to_owned()), Network::Mainnet, Cow::Owned(Some("node".to_owned())), Cow::Owned(NETWORK_ID), 42, 2125, 1000, 10, 9, None, Cow::Owned(Hash::zero()), Cow::Owned(Hash::zero()), Cow::Owned(CumulativeDifficulty::zero()), true, extension)
+    }
+
+    #[test]
+    fn test_legacy_handshake() {
+        // Handshake sent by older nodes ends with the can be shared flag
+        let bytes = create_handshake(None).to_bytes();
+        assert_eq!(*bytes.last().unwrap(), 1);
+
+        let mut reader = Reader::new(&bytes);
+        let handshake = Handshake::read(&mut reader).unwrap();
+        // Everything must be read, or the packet is rejected
+        assert_eq!(reader.total_read(), bytes.len());
+        assert!(handshake.get_extension().is_none());
+        assert_eq!(handshake.get_peer_id(), 42);
+        assert_eq!(handshake.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_handshake_extension() {
+        let identity = KeyPair::new();
+        let signature = identity.sign(b"key exchange");
+        let mut capabilities = Capabilities::empty();
+        capabilities.set(Capabilities::COMPACT_BLOCKS, true);
+        let extension = HandshakeExtension::new(capabilities, Cow::Owned(identity.get_public_key().compress()), Cow::Owned(signature));
+        let handshake = create_handshake(Some(extension));
+        let bytes = handshake.to_bytes();
+        assert_eq!(bytes.len(), handshake.size());
+
+        let mut reader = Reader::new(&bytes);
+        let handshake = Handshake::read(&mut reader).unwrap();
+        assert_eq!(reader.total_read(), bytes.len());
+        let extension = handshake.get_extension().unwrap();
+        assert!(extension.get_capabilities().contains(Capabilities::COMPACT_BLOCKS));
+        assert!(!extension.get_capabilities().contains(Capabilities::STEM_RELAY));
+        assert_eq!(*extension.get_identity(), identity.get_public_key().compress());
+
+        // Unknown capabilities sent by newer nodes are ignored
+        let capabilities = Capabilities::from_bytes(&[0xff, 0xff]).unwrap();
+        assert!(capabilities.contains(Capabilities::STEM_RELAY));
+    }
+}
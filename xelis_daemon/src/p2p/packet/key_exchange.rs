@@ -0,0 +1,52 @@
+use xelis_common::serializer::{Serializer, Reader, ReaderError, Writer};
+use crate::p2p::EncryptionKey;
+
+// Key exchange of the nodes supporting the protocol version 1 or above
+// Each side sends an ephemeral public key, and the encryption keys are derived from their shared secret
+// Older nodes don't know this packet and close the connection,
+// the client then connects again using the legacy key exchange
+#[derive(Clone, Debug)]
+pub struct EphemeralKeyExchange {
+    // Highest protocol version supported by the sender
+    version: u8,
+    // Compressed ephemeral public key
+    key: EncryptionKey
+}
+
+impl EphemeralKeyExchange {
+    pub fn new(version: u8, key: EncryptionKey) -> Self {
+        Self {
+            version,
+            key
+        }
+    }
+
+    pub fn get_version(&self) -> u8 {
+        self.version
+    }
+
+    pub fn get_key(&self) -> &EncryptionKey {
+        &self.key
+    }
+}
+
+impl Serializer for EphemeralKeyExchange {
+    fn read(reader: &mut Reader) -> Result<Self, ReaderError> {
+        let version = reader.read_u8()?;
+        // version 0 is the legacy key exchange
+        if version == 0 {
+            return Err(ReaderError::InvalidValue)
+        }
+        let key = EncryptionKey::read(reader)?;
+        Ok(Self::new(version, key))
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        writer.write_u8(self.version);
+        self.key.write(writer);
+    }
+
+    fn size(&self) -> usize {
+        self.version.size() + self.key.size()
+    }
+}
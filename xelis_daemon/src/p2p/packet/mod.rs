@@ -5,6 +5,8 @@ pub mod object;
 pub mod inventory;
 pub mod bootstrap_chain;
 pub mod peer_disconnected;
+pub mod compact_block;
+pub mod key_exchange;
 
 use self::bootstrap_chain::{BootstrapChainRequest, BootstrapChainResponse};
 use self::inventory::{NotifyInventoryResponse, NotifyInventoryRequest};
@@ -12,13 +14,16 @@ use self::object::{ObjectRequest, ObjectResponse};
 use self::chain::{ChainRequest, ChainResponse};
 use self::handshake::Handshake;
 use self::peer_disconnected::PacketPeerDisconnected;
+use self::compact_block::{CompactBlock, BlockTransactionsRequest, BlockTransactionsResponse};
+use self::key_exchange::EphemeralKeyExchange;
 use self::ping::Ping;
 use std::borrow::Cow;
 use log::{debug, trace};
 use xelis_common::{
     serializer::{Serializer, Reader, ReaderError, Writer},
     block::BlockHeader,
-    crypto::Hash
+    crypto::Hash,
+    transaction::Transaction
 };
 
 use super::EncryptionKey;
@@ -38,6 +43,37 @@ const NOTIFY_INV_RESPONSE_ID: u8 = 10;
 const BOOTSTRAP_CHAIN_REQUEST_ID: u8 = 11;
 const BOOTSTRAP_CHAIN_RESPONSE_ID: u8 = 12;
 const PEER_DISCONNECTED_ID: u8 = 13;
+const TX_STEM_ID: u8 = 14;
+const COMPACT_BLOCK_PROPAGATION_ID: u8 = 15;
+const BLOCK_TXS_REQUEST_ID: u8 = 16;
+const BLOCK_TXS_RESPONSE_ID: u8 = 17;
+const EPHEMERAL_KEY_EXCHANGE_ID: u8 = 18;
+
+// Name of a packet from its id, used in metrics
+pub fn get_packet_name(id: u8) -> &'static str {
+    match id {
+        KEY_EXCHANGE_ID => "key_exchange",
+        HANDSHAKE_ID => "handshake",
+        TX_PROPAGATION_ID => "transaction_propagation",
+        BLOCK_PROPAGATION_ID => "block_propagation",
+        CHAIN_REQUEST_ID => "chain_request",
+        CHAIN_RESPONSE_ID => "chain_response",
+        PING_ID => "ping",
+        OBJECT_REQUEST_ID => "object_request",
+        OBJECT_RESPONSE_ID => "object_response",
+        NOTIFY_INV_REQUEST_ID => "notify_inventory_request",
+        NOTIFY_INV_RESPONSE_ID => "notify_inventory_response",
+        BOOTSTRAP_CHAIN_REQUEST_ID => "bootstrap_chain_request",
+        BOOTSTRAP_CHAIN_RESPONSE_ID => "bootstrap_chain_response",
+        PEER_DISCONNECTED_ID => "peer_disconnected",
+        TX_STEM_ID => "transaction_stem",
+        COMPACT_BLOCK_PROPAGATION_ID => "compact_block_propagation",
+        BLOCK_TXS_REQUEST_ID => "block_transactions_request",
+        BLOCK_TXS_RESPONSE_ID => "block_transactions_response",
+        EPHEMERAL_KEY_EXCHANGE_ID => "ephemeral_key_exchange",
+        _ => "unknown"
+    }
+}
 
 // PacketWrapper allows us to link any Packet to a Ping
 #[derive(Debug)]
@@ -99,6 +135,14 @@ pub enum Packet<'a> {
     PeerDisconnected(PacketPeerDisconnected),
     // Encryption
     KeyExchange(Cow<'a, EncryptionKey>),
+    // Dandelion stem phase, the whole TX is sent to a single peer
+    TransactionStem(Cow<'a, Transaction>),
+    // Compact blocks, only sent to the peers supporting them
+    CompactBlockPropagation(PacketWrapper<'a, CompactBlock>),
+    BlockTransactionsRequest(Cow<'a, BlockTransactionsRequest>),
+    BlockTransactionsResponse(Cow<'a, BlockTransactionsResponse>),
+    // Key exchange negotiating the protocol version, only sent at the start of a connection
+    EphemeralKeyExchange(Cow<'a, EphemeralKeyExchange>),
 }
 
 impl Packet<'_> {
@@ -118,6 +162,11 @@ impl Packet<'_> {
             Packet::BootstrapChainResponse(_) => BOOTSTRAP_CHAIN_RESPONSE_ID,
             Packet::PeerDisconnected(_) => PEER_DISCONNECTED_ID,
             Packet::KeyExchange(_) => KEY_EXCHANGE_ID,
+            Packet::TransactionStem(_) => TX_STEM_ID,
+            Packet::CompactBlockPropagation(_) => COMPACT_BLOCK_PROPAGATION_ID,
+            Packet::BlockTransactionsRequest(_) => BLOCK_TXS_REQUEST_ID,
+            Packet::BlockTransactionsResponse(_) => BLOCK_TXS_RESPONSE_ID,
+            Packet::EphemeralKeyExchange(_) => EPHEMERAL_KEY_EXCHANGE_ID,
         }
     }
 }
@@ -141,6 +190,11 @@ impl<'a> Serializer for Packet<'a> {
             BOOTSTRAP_CHAIN_REQUEST_ID => Packet::BootstrapChainRequest(BootstrapChainRequest::read(reader)?),
             BOOTSTRAP_CHAIN_RESPONSE_ID => Packet::BootstrapChainResponse(BootstrapChainResponse::read(reader)?),
             PEER_DISCONNECTED_ID => Packet::PeerDisconnected(PacketPeerDisconnected::read(reader)?),
+            TX_STEM_ID => Packet::TransactionStem(Cow::Owned(Transaction::read(reader)?)),
+            COMPACT_BLOCK_PROPAGATION_ID => Packet::CompactBlockPropagation(PacketWrapper::read(reader)?),
+            BLOCK_TXS_REQUEST_ID => Packet::BlockTransactionsRequest(Cow::Owned(BlockTransactionsRequest::read(reader)?)),
+            BLOCK_TXS_RESPONSE_ID => Packet::BlockTransactionsResponse(Cow::Owned(BlockTransactionsResponse::read(reader)?)),
+            EPHEMERAL_KEY_EXCHANGE_ID => Packet::EphemeralKeyExchange(Cow::Owned(EphemeralKeyExchange::read(reader)?)),
             id => {
                 debug!("invalid packet id received: {}", id);
                 return Err(ReaderError::InvalidValue)
@@ -170,6 +224,11 @@ impl<'a> Serializer for Packet<'a> {
             Packet::BootstrapChainRequest(request) => (BOOTSTRAP_CHAIN_REQUEST_ID, request),
             Packet::BootstrapChainResponse(response) => (BOOTSTRAP_CHAIN_RESPONSE_ID, response),
             Packet::PeerDisconnected(disconnected) => (PEER_DISCONNECTED_ID, disconnected),
+            Packet::TransactionStem(tx) => (TX_STEM_ID, tx.as_ref()),
+            Packet::CompactBlockPropagation(block) => (COMPACT_BLOCK_PROPAGATION_ID, block),
+            Packet::BlockTransactionsRequest(request) => (BLOCK_TXS_REQUEST_ID, request.as_ref()),
+            Packet::BlockTransactionsResponse(response) => (BLOCK_TXS_RESPONSE_ID, response.as_ref()),
+            Packet::EphemeralKeyExchange(key_exchange) => (EPHEMERAL_KEY_EXCHANGE_ID, key_exchange.as_ref()),
         };
 
         let packet = serializer.to_bytes();
@@ -8,7 +8,7 @@ use crate::{
 };
 use xelis_common::{
     api::daemon::Direction,
-    crypto::Hash,
+    crypto::{elgamal::CompressedPublicKey, Hash},
     difficulty::CumulativeDifficulty,
     serializer::Serializer,
     time::{
@@ -27,6 +27,11 @@ use super::{
             ChainRequest,
             ChainResponse
         },
+        compact_block::{
+            BlockTransactionsRequest,
+            BlockTransactionsResponse
+        },
+        handshake::Capabilities,
         object::{
             ObjectRequest,
             OwnedObjectResponse
@@ -34,6 +39,7 @@ use super::{
         Packet
     },
     peer_list::{PeerList, SharedPeerList},
+    score::PeerScore,
     connection::Connection,
     error::P2pError
 };
@@ -45,7 +51,7 @@ use std::{
     hash::{Hash as StdHash, Hasher},
     net::{IpAddr, SocketAddr},
     sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering},
-    time::Duration
+    time::{Duration, Instant}
 };
 use tokio::{
     sync::{oneshot::Sender, Mutex},
@@ -124,14 +130,31 @@ pub struct Peer {
     bootstrap_chain: Mutex<Option<Sender<StepResponse>>>,
     // used to wait on chain response when syncing chain
     sync_chain: Mutex<Option<Sender<ChainResponse>>>,
+    // used to wait on the missing TXs of a compact block
+    block_txs: Mutex<Option<Sender<BlockTransactionsResponse>>>,
     // IP address with local port
     outgoing_address: SocketAddr,
     // Determine if this peer allows to be shared to others and/or through API
-    sharable: bool
+    sharable: bool,
+    // Optional features advertised by this peer in its handshake
+    capabilities: Capabilities,
+    // Persistent identity key of the peer, verified during the handshake
+    // None if the peer didn't send the handshake extension
+    identity: Option<CompressedPublicKey>,
+    // average round trip time of our requests in milliseconds, 0 if unknown
+    latency: AtomicU64,
+    // objects requested and received from this peer
+    objects_served: AtomicU64,
+    // invalid blocks or TXs relayed by this peer
+    invalid_objects: AtomicU64,
+    // chain sync and bootstrap requests timed out
+    sync_timeouts: AtomicU64,
+    // seconds connected during its previous connections
+    previous_uptime: AtomicU64
 }
 
 impl Peer {
-    pub fn new(connection: Connection, id: u64, node_tag: Option<String>, local_port: u16, version: String, top_hash: Hash, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, priority: bool, cumulative_difficulty: CumulativeDifficulty, peer_list: SharedPeerList, peers_received: HashSet<SocketAddr>, sharable: bool) -> Self {
+    pub fn new(connection: Connection, id: u64, node_tag: Option<String>, local_port: u16, version: String, top_hash: Hash, topoheight: u64, height: u64, pruned_topoheight: Option<u64>, priority: bool, cumulative_difficulty: CumulativeDifficulty, peer_list: SharedPeerList, peers_received: HashSet<SocketAddr>, sharable: bool, capabilities: Capabilities, identity: Option<CompressedPublicKey>) -> Self {
         let mut outgoing_address = *connection.get_address();
         outgoing_address.set_port(local_port);
 
@@ -168,8 +191,16 @@ impl Peer {
             is_pruned: AtomicBool::new(pruned_topoheight.is_some()),
             bootstrap_chain: Mutex::new(None),
             sync_chain: Mutex::new(None),
+            block_txs: Mutex::new(None),
             outgoing_address,
-            sharable
+            sharable,
+            capabilities,
+            identity,
+            latency: AtomicU64::new(0),
+            objects_served: AtomicU64::new(0),
+            invalid_objects: AtomicU64::new(0),
+            sync_timeouts: AtomicU64::new(0),
+            previous_uptime: AtomicU64::new(0)
         }
     }
 
@@ -294,6 +325,71 @@ impl Peer {
         self.sharable
     }
 
+    // Get the stem relay flag of the peer
+    pub fn supports_stem_relay(&self) -> bool {
+        self.capabilities.contains(Capabilities::STEM_RELAY)
+    }
+
+    // Get the compact blocks flag of the peer
+    pub fn supports_compact_blocks(&self) -> bool {
+        self.capabilities.contains(Capabilities::COMPACT_BLOCKS)
+    }
+
+    // Get the identity key of the peer
+    pub fn get_identity(&self) -> Option<&CompressedPublicKey> {
+        self.identity.as_ref()
+    }
+
+    // Restore the score of the peer from its previous connections
+    pub fn set_previous_score(&self, score: &PeerScore) {
+        self.latency.store(score.get_latency().unwrap_or(0), Ordering::Release);
+        self.objects_served.store(score.get_objects_served(), Ordering::Release);
+        self.invalid_objects.store(score.get_invalid_objects(), Ordering::Release);
+        self.sync_timeouts.store(score.get_sync_timeouts(), Ordering::Release);
+        self.previous_uptime.store(score.get_uptime(), Ordering::Release);
+    }
+
+    // Get the score of the peer, including its previous connections
+    pub fn get_score(&self) -> PeerScore {
+        let latency = self.latency.load(Ordering::Acquire);
+        let uptime = self.previous_uptime.load(Ordering::Acquire) + get_current_time_in_seconds().saturating_sub(self.connection.connected_on());
+        PeerScore::new(
+            if latency == 0 { None } else { Some(latency) },
+            self.objects_served.load(Ordering::Acquire),
+            self.invalid_objects.load(Ordering::Acquire),
+            self.sync_timeouts.load(Ordering::Acquire),
+            uptime
+        )
+    }
+
+    // Track the round trip time of a request answered by the peer
+    // Recent requests weight more in the average
+    pub fn record_latency(&self, elapsed: Duration) {
+        let millis = (elapsed.as_millis() as u64).max(1);
+        let previous = self.latency.load(Ordering::Acquire);
+        let latency = if previous == 0 {
+            millis
+        } else {
+            (previous * 7 + millis) / 8
+        };
+        self.latency.store(latency, Ordering::Release);
+    }
+
+    // Track the objects served by the peer
+    pub fn add_objects_served(&self, count: u64) {
+        self.objects_served.fetch_add(count, Ordering::AcqRel);
+    }
+
+    // Track an invalid block or TX relayed by the peer
+    pub fn add_invalid_object(&self) {
+        self.invalid_objects.fetch_add(1, Ordering::AcqRel);
+    }
+
+    // Track a chain sync or bootstrap request timed out
+    pub fn add_sync_timeout(&self) {
+        self.sync_timeouts.fetch_add(1, Ordering::AcqRel);
+    }
+
     // Get the last time we got a fail from the peer
     pub fn get_last_fail_count(&self) -> u64 {
         self.last_fail_count.load(Ordering::Acquire)
@@ -381,8 +477,13 @@ impl Peer {
             objects.insert(request.clone(), sender); // clone is necessary in case timeout has occured
             receiver
         };
+        let start = Instant::now();
         let object = match timeout(Duration::from_millis(PEER_TIMEOUT_REQUEST_OBJECT), receiver).await {
-            Ok(res) => res?,
+            Ok(res) => {
+                let object = res?;
+                self.record_latency(start.elapsed());
+                object
+            },
             Err(e) => {
                 trace!("Requested data has timed out");
                 let mut objects = self.objects_requested.lock().await;
@@ -402,6 +503,7 @@ impl Peer {
             return Err(P2pError::ObjectNotFound(request.clone()));
         }
 
+        self.add_objects_served(1);
         Ok(object)
     }
 
@@ -423,6 +525,7 @@ impl Peer {
             Ok(res) => res?,
             Err(e) => {
                 debug!("Requested bootstrap chain step {:?} has timed out", step_kind);
+                self.add_sync_timeout();
                 return Err(P2pError::AsyncTimeOut(e));
             }
         };
@@ -433,6 +536,7 @@ impl Peer {
             return Err(P2pError::InvalidBootstrapStep(step_kind, response_kind))
         }
 
+        self.add_objects_served(1);
         Ok(response)
     }
 
@@ -453,10 +557,45 @@ impl Peer {
             Ok(res) => res?,
             Err(e) => {
                 debug!("Requested sync chain step timed out");
+                self.add_sync_timeout();
+                return Err(P2pError::AsyncTimeOut(e));
+            }
+        };
+
+        self.add_objects_served(1);
+        Ok(response)
+    }
+
+    // Request the missing TXs of a compact block and wait on them until we receive them or until timeout
+    pub async fn request_block_transactions(&self, request: BlockTransactionsRequest) -> Result<BlockTransactionsResponse, P2pError> {
+        debug!("Requesting {} TXs of block {}", request.get_indexes().len(), request.get_hash());
+        let block_hash = request.get_hash().clone();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        {
+            let mut sender_lock = self.block_txs.lock().await;
+            *sender_lock = Some(sender);
+        }
+
+        self.send_packet(Packet::BlockTransactionsRequest(Cow::Owned(request))).await?;
+
+        let start = Instant::now();
+        let response: BlockTransactionsResponse = match timeout(Duration::from_millis(PEER_TIMEOUT_REQUEST_OBJECT), receiver).await {
+            Ok(res) => {
+                let response = res?;
+                self.record_latency(start.elapsed());
+                response
+            },
+            Err(e) => {
+                debug!("Requested TXs of block {} have timed out", block_hash);
                 return Err(P2pError::AsyncTimeOut(e));
             }
         };
 
+        if *response.get_hash() != block_hash {
+            return Err(P2pError::InvalidBlockTransactionsResponse(block_hash))
+        }
+
+        self.add_objects_served(1);
         Ok(response)
     }
 
@@ -472,6 +611,12 @@ impl Peer {
         &self.sync_chain
     }
 
+    // Get the block TXs channel
+    // This is used to wait on the missing TXs of a compact block
+    pub fn get_block_txs_channel(&self) -> &Mutex<Option<Sender<BlockTransactionsResponse>>> {
+        &self.block_txs
+    }
+
     // Get all shared peers between this peer and us
     pub fn get_peers(&self) -> &Mutex<HashMap<SocketAddr, Direction>> {
         &self.peers
@@ -601,7 +746,7 @@ impl Display for Peer {
             "No".to_string()
         };
 
-        write!(f, "Peer[connection: {}, id: {}, topoheight: {}, top hash: {}, height: {}, pruned: {}, priority: {}, tag: {}, version: {}, fail count: {}, out: {}, peers: {}]",
+        write!(f, "Peer[connection: {}, id: {}, topoheight: {}, top hash: {}, height: {}, pruned: {}, priority: {}, tag: {}, version: {}, fail count: {}, out: {}, peers: {}, identity: {}, score: {}]",
             self.get_connection(),
             self.get_id(),
             self.get_topoheight(),
@@ -613,7 +758,9 @@ impl Display for Peer {
             self.get_version(),
             self.get_fail_count(),
             self.is_out(),
-            peers
+            peers,
+            self.get_identity().map_or("None".to_owned(), |identity| identity.to_hex()),
+            self.get_score().get_value()
         )
     }
 }
@@ -7,7 +7,7 @@ use crate::{
     },
     p2p::packet::peer_disconnected::PacketPeerDisconnected
 };
-use super::{peer::Peer, packet::Packet, error::P2pError};
+use super::{peer::Peer, packet::Packet, error::P2pError, identity::IdentityList, score::PeerScore};
 use std::{
     collections::{hash_map::Entry, HashMap},
     fmt::{self, Display, Formatter},
@@ -18,6 +18,7 @@ use humantime::format_duration;
 use serde::{Serialize, Deserialize};
 use tokio::sync::{RwLock, mpsc::UnboundedSender};
 use xelis_common::{
+    crypto::elgamal::CompressedPublicKey,
     serializer::Serializer,
     time::{TimestampSeconds, get_current_time_in_seconds},
     api::daemon::Direction
@@ -37,6 +38,8 @@ pub struct PeerList {
     // times its local port
     stored_peers: HashMap<IpAddr, StoredPeer>,
     filename: String,
+    // allow and deny lists of peer identities
+    identities: IdentityList,
     // used to notify the server that a peer disconnected
     // this is done through a channel to not have to handle generic types
     // and to be flexible in the future
@@ -59,7 +62,14 @@ pub struct StoredPeer {
     local_port: u16,
     // Until when the peer is banned
     temp_ban_until: Option<u64>,
-    state: StoredPeerState
+    state: StoredPeerState,
+    // Reputation of the peer across its connections
+    #[serde(default)]
+    score: PeerScore,
+    // Identity (hex) the peer used on its last authenticated connection
+    // Once known, the peer is no longer accepted without an identity
+    #[serde(default)]
+    identity: Option<String>
 }
 
 impl PeerList {
@@ -114,7 +124,7 @@ impl PeerList {
         Ok(peers)
     }
 
-    pub fn new(capacity: usize, filename: String, peer_disconnect_channel: Option<UnboundedSender<Arc<Peer>>>) -> SharedPeerList {
+    pub fn new(capacity: usize, filename: String, identities_filename: String, peer_disconnect_channel: Option<UnboundedSender<Arc<Peer>>>) -> SharedPeerList {
         let stored_peers = match Self::load_stored_peers(&filename) {
             Ok(peers) => peers,
             Err(e) => {
@@ -130,6 +140,7 @@ impl PeerList {
                     peers: HashMap::with_capacity(capacity),
                     stored_peers,
                     filename,
+                    identities: IdentityList::new(identities_filename),
                     peer_disconnect_channel
                 }
             )
@@ -150,6 +161,11 @@ impl PeerList {
     // We will notify all peers that have this peer in common
     pub async fn remove_peer(&mut self, peer_id: u64) -> Result<(), P2pError> {
         let peer = self.peers.remove(&peer_id).ok_or(P2pError::PeerNotFoundById(peer_id))?;
+
+        // Keep its score for its next connections
+        if let Some(stored_peer) = self.stored_peers.get_mut(&peer.get_outgoing_address().ip()) {
+            stored_peer.set_score(peer.get_score());
+        }
     
         // If peer allows us to share it, we have to notify all peers that have this peer in common
         if peer.sharable() {
@@ -188,6 +204,10 @@ impl PeerList {
     }
 
     pub fn add_peer(&mut self, id: u64, peer: Peer) -> Arc<Peer> {
+        if let Some(stored_peer) = self.stored_peers.get(&peer.get_outgoing_address().ip()) {
+            peer.set_previous_score(stored_peer.get_score());
+        }
+
         let peer = Arc::new(peer);
         self.peers.insert(id, peer.clone());
         info!("New peer connected: {}", peer);
@@ -210,6 +230,39 @@ impl PeerList {
             debug!("Saving {} in stored peerlist", peer);
             self.stored_peers.insert(ip, StoredPeer::new(peer.get_local_port(), StoredPeerState::Graylist));
         }
+
+        if let Some(identity) = peer.get_identity() {
+            self.pin_identity(&ip, identity);
+        }
+    }
+
+    // Pin the identity of a stored peer
+    fn pin_identity(&mut self, ip: &IpAddr, identity: &CompressedPublicKey) {
+        if let Some(stored_peer) = self.stored_peers.get_mut(ip) {
+            stored_peer.set_identity(Some(identity.to_hex()));
+        }
+    }
+
+    // Check if a peer already connected to us with an identity
+    // Such a peer can't be downgraded to the legacy protocol
+    pub fn has_pinned_identity(&self, ip: &IpAddr) -> bool {
+        self.stored_peers.get(ip).map_or(false, |stored_peer| stored_peer.get_identity().is_some())
+    }
+
+    // Score of a peer from its previous connections
+    pub fn get_stored_score(&self, ip: &IpAddr) -> Option<i64> {
+        self.stored_peers.get(ip).map(|stored_peer| stored_peer.get_score().get_value())
+    }
+
+    // Find the connected peer with the lowest score to let an incoming one connect when we are full
+    // Only a peer with a lower score than the incoming one can be evicted, priority peers are never evicted
+    pub fn find_peer_to_evict(&self, score: i64) -> Option<Arc<Peer>> {
+        self.peers.values()
+            .filter(|peer| !peer.is_priority())
+            .map(|peer| (peer.get_score().get_value(), peer))
+            .min_by_key(|(value, _)| *value)
+            .filter(|(value, _)| *value < score)
+            .map(|(_, peer)| Arc::clone(peer))
     }
 
     pub fn has_peer(&self, peer_id: &u64) -> bool {
@@ -423,6 +476,47 @@ impl PeerList {
         self.set_state_to_address(ip, StoredPeerState::Whitelist);
     }
 
+    // Verify that the peer identity is not denied
+    // and is in the allow list if it's not empty
+    // Legacy peers without identity are only allowed if the allow list is empty
+    pub fn is_identity_allowed(&self, identity: Option<&CompressedPublicKey>) -> bool {
+        match identity {
+            Some(identity) => self.identities.is_allowed(identity),
+            None => self.identities.get_allow_list().is_empty()
+        }
+    }
+
+    pub fn get_identities(&self) -> &IdentityList {
+        &self.identities
+    }
+
+    // Add or remove an identity from the allow list
+    // Returns true if it was added
+    pub fn toggle_allowed_identity(&mut self, identity: CompressedPublicKey) -> bool {
+        self.identities.toggle_allowed(identity)
+    }
+
+    // Add or remove an identity from the deny list
+    // if it was added, disconnect the peers using it
+    // Returns true if it was added
+    pub async fn toggle_denied_identity(&mut self, identity: CompressedPublicKey) -> bool {
+        if !self.identities.toggle_denied(identity.clone()) {
+            return false
+        }
+
+        let peers: Vec<Arc<Peer>> = self.peers.values()
+            .filter(|peer| peer.get_identity() == Some(&identity))
+            .cloned()
+            .collect();
+        for peer in peers {
+            if let Err(e) = peer.close_with_peerlist(self).await {
+                error!("Error while trying to close peer {} for being denied: {}", peer.get_connection().get_address(), e);
+            }
+        }
+
+        true
+    }
+
     pub fn find_peer_to_connect(&mut self) -> Option<SocketAddr> {
         // remove all peers that have a high fail count
         self.stored_peers.retain(|_, stored_peer| *stored_peer.get_state() == StoredPeerState::Whitelist || stored_peer.get_fail_count() < PEER_FAIL_LIMIT);
@@ -443,16 +537,20 @@ impl PeerList {
 
     // find among stored peers a peer to connect to with the requested StoredPeerState
     // we check that we're not already connected to this peer and that we didn't tried to connect to it recently
+    // the peer with the best score is selected first
     fn find_peer_to_connect_to_with_state(&mut self, current_time: TimestampSeconds, state: StoredPeerState) -> Option<SocketAddr> {
-        for (ip, stored_peer) in &mut self.stored_peers {
-            let addr = SocketAddr::new(*ip, stored_peer.get_local_port());
-            if *stored_peer.get_state() == state && stored_peer.get_last_connection_try() + (stored_peer.get_fail_count() as u64 * P2P_EXTEND_PEERLIST_DELAY) <= current_time && Self::internal_get_peer_by_addr(&self.peers, &addr).is_none() {
-                stored_peer.set_last_connection_try(current_time);
-                return Some(addr);
-            }
+        let addr = self.stored_peers.iter()
+            .filter(|(_, stored_peer)| *stored_peer.get_state() == state && stored_peer.get_last_connection_try() + (stored_peer.get_fail_count() as u64 * P2P_EXTEND_PEERLIST_DELAY) <= current_time)
+            .map(|(ip, stored_peer)| (SocketAddr::new(*ip, stored_peer.get_local_port()), stored_peer.get_score().get_value()))
+            .filter(|(addr, _)| Self::internal_get_peer_by_addr(&self.peers, addr).is_none())
+            .max_by_key(|(_, value)| *value)
+            .map(|(addr, _)| addr)?;
+
+        if let Some(stored_peer) = self.stored_peers.get_mut(&addr.ip()) {
+            stored_peer.set_last_connection_try(current_time);
         }
 
-        None
+        Some(addr)
     }
 
     // increase the fail count of a peer
@@ -496,7 +594,9 @@ impl StoredPeer {
             fail_count: 0,
             local_port,
             temp_ban_until: None,
-            state
+            state,
+            score: PeerScore::default(),
+            identity: None
         }
     }
 
@@ -543,11 +643,63 @@ impl StoredPeer {
     fn get_local_port(&self) -> u16 {
         self.local_port
     }
+
+    pub fn get_score(&self) -> &PeerScore {
+        &self.score
+    }
+
+    fn set_score(&mut self, score: PeerScore) {
+        self.score = score;
+    }
+
+    fn get_identity(&self) -> Option<&String> {
+        self.identity.as_ref()
+    }
+
+    fn set_identity(&mut self, identity: Option<String>) {
+        self.identity = identity;
+    }
 }
 
 impl Display for StoredPeer {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let current_time = get_current_time_in_seconds();
-        write!(f, "StoredPeer[first seen: {} ago, last seen: {} ago]", format_duration(Duration::from_secs(current_time - self.first_seen)), format_duration(Duration::from_secs(current_time - self.last_seen)))
+        write!(f, "StoredPeer[first seen: {} ago, last seen: {} ago, score: {}]", format_duration(Duration::from_secs(current_time - self.first_seen)), format_duration(Duration::from_secs(current_time - self.last_seen)), self.score.get_value())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use crate::core::blockchain::tests::{remove_temp_dir, temp_dir_path};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_pinned_identity() {
+        let dir_path = temp_dir_path("peerlist");
+        fs::create_dir_all(&dir_path).unwrap();
+        let filename = format!("{}peerlist.json", dir_path);
+        let identities = format!("{}identities.json", dir_path);
+
+        // peer stored before the identities were pinned
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        fs::write(&filename, format!(r#"{{"{}": {{"first_seen": 0, "last_seen": 0, "last_connection_try": 0, "fail_count": 0, "local_port": 2125, "temp_ban_until": null, "state": "Graylist"}}}}"#, ip)).unwrap();
+
+        let peer_list = PeerList::new(8, filename.clone(), identities.clone(), None);
+        {
+            let mut peer_list = peer_list.write().await;
+            assert!(!peer_list.has_pinned_identity(&ip));
+
+            peer_list.pin_identity(&ip, &KeyPair::new().get_public_key().compress());
+            assert!(peer_list.has_pinned_identity(&ip));
+            peer_list.save_peers_to_file().unwrap();
+        }
+
+        // the pin is kept across restarts
+        let peer_list = PeerList::new(8, filename, identities, None);
+        assert!(peer_list.read().await.has_pinned_identity(&ip));
+        assert!(!peer_list.read().await.has_pinned_identity(&"127.0.0.2".parse().unwrap()));
+
+        remove_temp_dir(&dir_path);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,130 @@
+use std::{fmt::{self, Display, Formatter}, time::Duration};
+use humantime::format_duration;
+use serde::{Deserialize, Serialize};
+use crate::core::error::BlockchainError;
+
+// Points for each hour connected
+const UPTIME_POINTS_PER_HOUR: i64 = 1;
+// Maximum hours counted for the uptime (one week)
+const UPTIME_MAX_HOURS: u64 = 24 * 7;
+// Objects served needed to earn one point
+const OBJECTS_SERVED_PER_POINT: u64 = 10;
+// Maximum points earned from the objects served
+const OBJECTS_SERVED_MAX_POINTS: i64 = 500;
+// Penalty for each invalid block or TX relayed
+const INVALID_OBJECT_PENALTY: i64 = 100;
+// Penalty for each chain sync request timed out
+const SYNC_TIMEOUT_PENALTY: i64 = 20;
+// Latency in milliseconds costing one point
+const LATENCY_MILLIS_PER_POINT: u64 = 100;
+// Maximum latency in milliseconds counted
+const LATENCY_MAX_MILLIS: u64 = 10_000;
+
+// Reputation of a peer, kept across its connections in the peerlist
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerScore {
+    // average round trip time of our requests in milliseconds
+    latency: Option<u64>,
+    // objects requested and received from it
+    objects_served: u64,
+    // invalid blocks or TXs relayed
+    invalid_objects: u64,
+    // chain sync and bootstrap requests timed out
+    sync_timeouts: u64,
+    // total seconds connected to us
+    uptime: u64
+}
+
+impl PeerScore {
+    pub fn new(latency: Option<u64>, objects_served: u64, invalid_objects: u64, sync_timeouts: u64, uptime: u64) -> Self {
+        Self {
+            latency,
+            objects_served,
+            invalid_objects,
+            sync_timeouts,
+            uptime
+        }
+    }
+
+    pub fn get_latency(&self) -> Option<u64> {
+        self.latency
+    }
+
+    pub fn get_objects_served(&self) -> u64 {
+        self.objects_served
+    }
+
+    pub fn get_invalid_objects(&self) -> u64 {
+        self.invalid_objects
+    }
+
+    pub fn get_sync_timeouts(&self) -> u64 {
+        self.sync_timeouts
+    }
+
+    pub fn get_uptime(&self) -> u64 {
+        self.uptime
+    }
+
+    // Compute the score value, higher is better
+    // A peer never seen has a score of 0
+    pub fn get_value(&self) -> i64 {
+        let uptime = (self.uptime / 3600).min(UPTIME_MAX_HOURS) as i64 * UPTIME_POINTS_PER_HOUR;
+        let served = ((self.objects_served / OBJECTS_SERVED_PER_POINT) as i64).min(OBJECTS_SERVED_MAX_POINTS);
+        let latency = self.latency.map(|latency| (latency.min(LATENCY_MAX_MILLIS) / LATENCY_MILLIS_PER_POINT) as i64).unwrap_or(0);
+
+        uptime + served - latency
+            - (self.invalid_objects as i64).saturating_mul(INVALID_OBJECT_PENALTY)
+            - (self.sync_timeouts as i64).saturating_mul(SYNC_TIMEOUT_PENALTY)
+    }
+}
+
+impl Display for PeerScore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let latency = match self.latency {
+            Some(latency) => format!("{}ms", latency),
+            None => "unknown".to_owned()
+        };
+
+        write!(f, "PeerScore[value: {}, latency: {}, objects served: {}, invalid objects: {}, sync timeouts: {}, uptime: {}]",
+            self.get_value(),
+            latency,
+            self.objects_served,
+            self.invalid_objects,
+            self.sync_timeouts,
+            format_duration(Duration::from_secs(self.uptime))
+        )
+    }
+}
+
+// Verify if an error returned while adding an object relayed by a peer means it was invalid
+// An object already known is not the fault of the peer
+pub fn is_invalid_object_error(error: &BlockchainError) -> bool {
+    !matches!(error,
+        BlockchainError::TxAlreadyInMempool(_)
+        | BlockchainError::TxAlreadyInBlock(_)
+        | BlockchainError::TxAlreadyInBlockchain(_)
+        | BlockchainError::AlreadyInChain
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peer_score_value() {
+        assert_eq!(PeerScore::default().get_value(), 0);
+
+        let good = PeerScore::new(Some(50), 1000, 0, 0, 10 * 3600);
+        assert_eq!(good.get_value(), 10 + 100);
+
+        // invalid objects relayed outweigh the objects served
+        let bad = PeerScore::new(Some(50), 1000, 2, 1, 10 * 3600);
+        assert!(bad.get_value() < 0);
+
+        // a slow peer is behind a fast one
+        let slow = PeerScore::new(Some(2000), 1000, 0, 0, 10 * 3600);
+        assert!(slow.get_value() < good.get_value());
+    }
+}
@@ -40,6 +40,7 @@ use super::{
         Packet
     },
     error::P2pError,
+    score::is_invalid_object_error,
     peer::Peer
 };
 use log::{
@@ -373,7 +374,14 @@ impl ObjectTracker {
                         let (_, request) = queue.pop().unwrap();
                         if let Err(e) = self.handle_object_response_internal(&blockchain, response, request.broadcast()).await {
                             debug!("Error while handling object response for {} in ObjectTracker from {}: {}", request.get_hash(), request.get_peer(), e);
+                            if let P2pError::BlockchainError(e) = &e {
+                                if is_invalid_object_error(e) {
+                                    request.get_peer().add_invalid_object();
+                                }
+                            }
                             self.clean_queue(&mut queue, request.get_peer().get_id(), request.get_group_id().map(|v| (v, e))).await;
+                        } else {
+                            request.get_peer().add_objects_served(1);
                         }
                     },
                     None => {
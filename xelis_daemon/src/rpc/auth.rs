@@ -0,0 +1,387 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::IpAddr,
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant}
+};
+use actix_web::http::header::AUTHORIZATION;
+use actix_web_httpauth::headers::authorization::{Basic, Bearer, Scheme};
+use log::{debug, warn};
+use xelis_common::{
+    context::Context,
+    rpc_server::{
+        websocket::HttpRequest,
+        InternalRpcError
+    }
+};
+
+// Methods available in the public tier
+// They are read-only and cheap to serve
+pub const PUBLIC_METHODS: &[&str] = &[
+    "get_version",
+    "get_height",
+    "get_topoheight",
+    "get_stableheight",
+    "get_block_at_topoheight",
+    "get_blocks_at_height",
+    "get_block_by_hash",
+    "get_top_block",
+    "get_balance",
+    "has_balance",
+    "get_balance_at_topoheight",
+    "get_info",
+    "get_nonce",
+    "has_nonce",
+    "get_nonce_at_topoheight",
+    "get_asset",
+    "get_assets",
+    "count_assets",
+    "count_accounts",
+    "count_transactions",
+    "get_transaction",
+    "p2p_status",
+    "get_mempool",
+    "get_tips",
+    "get_dag_order",
+    "get_account_assets",
+    "is_account_registered",
+    "get_account_registration_topoheight",
+    "is_tx_executed_in_block",
+    "get_dev_fee_thresholds",
+    "subscribe",
+    "unsubscribe",
+];
+
+// Above this count of tracked IPs, the rate limiter drops the idle ones
+const RATE_LIMITER_CLEANUP_THRESHOLD: usize = 10_000;
+
+// Compare two byte slices in a time that only depends on their lengths
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false
+    }
+
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Methods a client is allowed to call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcAccess {
+    // All methods
+    Admin,
+    // Only the methods from PUBLIC_METHODS
+    Public,
+    // Custom list of methods
+    Methods(HashSet<String>)
+}
+
+impl RpcAccess {
+    pub fn is_allowed(&self, method: &str) -> bool {
+        match self {
+            Self::Admin => true,
+            Self::Public => PUBLIC_METHODS.contains(&method),
+            Self::Methods(methods) => methods.contains(method)
+        }
+    }
+}
+
+impl FromStr for RpcAccess {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "admin" => Self::Admin,
+            "public" => Self::Public,
+            methods => {
+                let methods: HashSet<String> = methods.split(',')
+                    .map(|method| method.trim())
+                    .filter(|method| !method.is_empty())
+                    .map(String::from)
+                    .collect();
+
+                if methods.is_empty() {
+                    return Err("Invalid RPC access, expected 'admin', 'public' or a list of methods".into())
+                }
+                Self::Methods(methods)
+            }
+        })
+    }
+}
+
+// Bearer token accepted by the RPC server
+// Configured as <access>:<token>
+#[derive(Debug, Clone)]
+pub struct RpcToken {
+    access: RpcAccess,
+    token: String
+}
+
+impl FromStr for RpcToken {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (access, token) = s.split_once(':').ok_or("Invalid RPC token, expected <access>:<token>")?;
+        if token.is_empty() {
+            return Err("RPC token cannot be empty".into())
+        }
+
+        Ok(Self {
+            access: access.parse()?,
+            token: token.to_owned()
+        })
+    }
+}
+
+// Username and password accepted by the RPC server using basic auth
+// Configured as <access>:<username>:<password>
+#[derive(Debug, Clone)]
+pub struct RpcUser {
+    access: RpcAccess,
+    username: String,
+    password: String
+}
+
+impl FromStr for RpcUser {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (access, username, password) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(access), Some(username), Some(password)) if !username.is_empty() => (access, username, password),
+            _ => return Err("Invalid RPC user, expected <access>:<username>:<password>".into())
+        };
+
+        Ok(Self {
+            access: access.parse()?,
+            username: username.to_owned(),
+            password: password.to_owned()
+        })
+    }
+}
+
+// Token bucket per IP, refilled at `rate` requests per second
+// A client can do a burst of `rate` requests at once
+struct RateLimiter {
+    rate: u32,
+    buckets: Mutex<HashMap<IpAddr, (f64, Instant)>>
+}
+
+impl RateLimiter {
+    fn new(rate: u32) -> Self {
+        Self {
+            rate,
+            buckets: Mutex::new(HashMap::new())
+        }
+    }
+
+    // Returns false if the IP has no request left
+    fn check(&self, ip: IpAddr) -> bool {
+        let now = Instant::now();
+        let rate = self.rate as f64;
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(e) => e.into_inner()
+        };
+
+        if buckets.len() >= RATE_LIMITER_CLEANUP_THRESHOLD {
+            // a bucket is full again after one second, no need to keep it
+            buckets.retain(|_, (_, last)| now.duration_since(*last) < Duration::from_secs(1));
+        }
+
+        let (tokens, last) = buckets.entry(ip).or_insert((rate, now));
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(rate);
+        *last = now;
+
+        if *tokens < 1.0 {
+            return false
+        }
+
+        *tokens -= 1.0;
+        true
+    }
+}
+
+// Access control of the RPC server
+// Requests are rate limited per IP, then their credentials are verified
+// and the method must be in the allowlist of the credentials
+pub struct RpcAuth {
+    tokens: HashMap<String, RpcAccess>,
+    users: HashMap<String, (String, RpcAccess)>,
+    // access for requests without credentials, None if they are rejected
+    anonymous: Option<RpcAccess>,
+    rate_limiter: Option<RateLimiter>
+}
+
+impl RpcAuth {
+    // If no credentials are set, requests without credentials have the admin access
+    // Otherwise they have the public access, unless disable_public_access is set
+    // rate_limit is the maximum requests per second per IP, 0 to disable it
+    pub fn new(tokens: Vec<RpcToken>, users: Vec<RpcUser>, disable_public_access: bool, rate_limit: u32) -> Self {
+        let anonymous = if disable_public_access {
+            if tokens.is_empty() && users.is_empty() {
+                warn!("RPC public access is disabled but no credentials are set, all requests will be rejected");
+            }
+            None
+        } else if tokens.is_empty() && users.is_empty() {
+            Some(RpcAccess::Admin)
+        } else {
+            Some(RpcAccess::Public)
+        };
+
+        Self {
+            tokens: tokens.into_iter().map(|t| (t.token, t.access)).collect(),
+            users: users.into_iter().map(|u| (u.username, (u.password, u.access))).collect(),
+            anonymous,
+            rate_limiter: if rate_limit > 0 { Some(RateLimiter::new(rate_limit)) } else { None }
+        }
+    }
+
+    // Returns true if requests may be rejected
+    pub fn is_enabled(&self) -> bool {
+        self.anonymous != Some(RpcAccess::Admin) || self.rate_limiter.is_some()
+    }
+
+    // Find the access of the request based on its Authorization header
+    fn get_access(&self, request: Option<&HttpRequest>) -> Result<&RpcAccess, InternalRpcError> {
+        let header = match request.and_then(|r| r.headers().get(AUTHORIZATION)) {
+            Some(header) => header,
+            None => return self.anonymous.as_ref().ok_or(InternalRpcError::Unauthorized)
+        };
+
+        // Credentials are compared in constant time and against all the configured ones,
+        // so the response time doesn't leak them
+        if let Ok(bearer) = Bearer::parse(header) {
+            let mut found = None;
+            for (token, access) in self.tokens.iter() {
+                if constant_time_eq(token.as_bytes(), bearer.token().as_bytes()) {
+                    found = Some(access);
+                }
+            }
+
+            return found.ok_or(InternalRpcError::Unauthorized)
+        }
+
+        if let Ok(basic) = Basic::parse(header) {
+            let password = basic.password().ok_or(InternalRpcError::Unauthorized)?;
+            let mut found = None;
+            for (username, (expected, access)) in self.users.iter() {
+                let valid = constant_time_eq(username.as_bytes(), basic.user_id().as_bytes())
+                    & constant_time_eq(expected.as_bytes(), password.as_bytes());
+                if valid {
+                    found = Some(access);
+                }
+            }
+
+            return found.ok_or(InternalRpcError::Unauthorized)
+        }
+
+        Err(InternalRpcError::Unauthorized)
+    }
+
+    // Verify that the request can call this method
+    pub fn authorize(&self, context: &Context, method: &str) -> Result<(), InternalRpcError> {
+        let request: Option<&HttpRequest> = context.get_optional();
+        if let Some(rate_limiter) = &self.rate_limiter {
+            if let Some(addr) = request.and_then(HttpRequest::peer_addr) {
+                if !rate_limiter.check(addr.ip()) {
+                    debug!("RPC rate limit reached for {}", addr.ip());
+                    return Err(InternalRpcError::RateLimited)
+                }
+            }
+        }
+
+        let access = self.get_access(request)?;
+        if !access.is_allowed(method) {
+            return Err(InternalRpcError::MethodNotAllowed(method.to_owned()))
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use actix_web::test::TestRequest;
+    use super::*;
+
+    #[test]
+    fn test_parse_access() {
+        assert_eq!("admin".parse::<RpcAccess>().unwrap(), RpcAccess::Admin);
+        assert_eq!("public".parse::<RpcAccess>().unwrap(), RpcAccess::Public);
+
+        let access: RpcAccess = "get_info, submit_transaction".parse().unwrap();
+        assert!(access.is_allowed("submit_transaction"));
+        assert!(!access.is_allowed("submit_block"));
+        assert!(",".parse::<RpcAccess>().is_err());
+
+        assert!(RpcAccess::Public.is_allowed("get_info"));
+        assert!(!RpcAccess::Public.is_allowed("submit_block"));
+        assert!(!RpcAccess::Public.is_allowed("get_blocks_range_by_topoheight"));
+    }
+
+    #[test]
+    fn test_parse_credentials() {
+        let token: RpcToken = "admin:abc:def".parse().unwrap();
+        assert_eq!(token.access, RpcAccess::Admin);
+        assert_eq!(token.token, "abc:def");
+        assert!("admin:".parse::<RpcToken>().is_err());
+
+        let user: RpcUser = "public:user:pass:word".parse().unwrap();
+        assert_eq!(user.access, RpcAccess::Public);
+        assert_eq!(user.username, "user");
+        assert_eq!(user.password, "pass:word");
+        assert!("public:user".parse::<RpcUser>().is_err());
+    }
+
+    #[test]
+    fn test_anonymous_access() {
+        let context = Context::default();
+        let auth = RpcAuth::new(Vec::new(), Vec::new(), false, 0);
+        assert!(!auth.is_enabled());
+        assert!(auth.authorize(&context, "submit_block").is_ok());
+
+        let auth = RpcAuth::new(vec!["admin:secret".parse().unwrap()], Vec::new(), false, 0);
+        assert!(auth.authorize(&context, "get_info").is_ok());
+        assert!(matches!(auth.authorize(&context, "submit_block"), Err(InternalRpcError::MethodNotAllowed(_))));
+
+        let auth = RpcAuth::new(vec!["admin:secret".parse().unwrap()], Vec::new(), true, 0);
+        assert!(matches!(auth.authorize(&context, "get_info"), Err(InternalRpcError::Unauthorized)));
+    }
+
+    #[test]
+    fn test_credentials() {
+        let auth = RpcAuth::new(vec!["admin:secret".parse().unwrap()], vec!["get_info:user:pass".parse().unwrap()], true, 0);
+        let authorize = |header: &str, method: &str| {
+            let request = TestRequest::default().insert_header((AUTHORIZATION, header)).to_http_request();
+            let mut context = Context::default();
+            context.store(HttpRequest::from(request));
+            auth.authorize(&context, method)
+        };
+
+        assert!(authorize("Bearer secret", "submit_block").is_ok());
+        assert!(matches!(authorize("Bearer secreT", "get_info"), Err(InternalRpcError::Unauthorized)));
+        assert!(matches!(authorize("Bearer secret2", "get_info"), Err(InternalRpcError::Unauthorized)));
+
+        // user:pass
+        assert!(authorize("Basic dXNlcjpwYXNz", "get_info").is_ok());
+        assert!(matches!(authorize("Basic dXNlcjpwYXNz", "metrics"), Err(InternalRpcError::MethodNotAllowed(_))));
+        // user:pasS
+        assert!(matches!(authorize("Basic dXNlcjpwYXNT", "get_info"), Err(InternalRpcError::Unauthorized)));
+
+        assert!(constant_time_eq(b"abc", b"abc"));
+        assert!(!constant_time_eq(b"abc", b"abd"));
+        assert!(!constant_time_eq(b"abc", b"ab"));
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(2);
+        let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        assert!(limiter.check(ip));
+        assert!(limiter.check(ip));
+        assert!(!limiter.check(ip));
+        assert!(limiter.check(IpAddr::V4(Ipv4Addr::UNSPECIFIED)));
+    }
+}
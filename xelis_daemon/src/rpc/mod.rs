@@ -1,5 +1,6 @@
 pub mod rpc;
 pub mod getwork_server;
+pub mod auth;
 
 use crate::{
     core::{
@@ -8,6 +9,7 @@ use crate::{
         blockchain::Blockchain
     },
     rpc::getwork_server::GetWorkServer,
+    metrics
 };
 use actix_web::{
     get,
@@ -31,9 +33,11 @@ use tokio::sync::Mutex;
 use xelis_common::{
     api::daemon::NotifyEvent,
     config,
+    context::Context,
     crypto::Address,
     rpc_server::{
         websocket::{
+            HttpRequest as RpcHttpRequest,
             EventWebSocketHandler,
             WebSocketServerShared,
             WebSocketServer
@@ -41,6 +45,7 @@ use xelis_common::{
         InternalRpcError,
         RPCHandler,
         RPCServerHandler,
+        tls::load_server_config,
         json_rpc,
         websocket,
         WebSocketServerHandler,
@@ -57,6 +62,7 @@ use log::{
     warn,
     error,
 };
+use self::auth::RpcAuth;
 use self::getwork_server::{
     GetWorkWebSocketHandler,
     SharedGetWorkServer
@@ -83,7 +89,7 @@ pub enum ApiError {
 }
 
 impl<S: Storage> DaemonRpcServer<S> {
-    pub async fn new(bind_address: String, blockchain: Arc<Blockchain<S>>, disable_getwork_server: bool) -> Result<SharedDaemonRpcServer<S>, BlockchainError> {
+    pub async fn new(bind_address: String, blockchain: Arc<Blockchain<S>>, disable_getwork_server: bool, max_batch_size: usize, event_history_size: usize, enable_metrics: bool, auth: RpcAuth, tls: Option<(String, String)>) -> Result<SharedDaemonRpcServer<S>, BlockchainError> {
         let getwork: Option<SharedGetWorkServer<S>> = if !disable_getwork_server {
             info!("Creating GetWork server...");
             Some(Arc::new(GetWorkServer::new(blockchain.clone())))
@@ -94,9 +100,26 @@ impl<S: Storage> DaemonRpcServer<S> {
         // create the RPC Handler which will register and contains all available methods
         let mut rpc_handler = RPCHandler::new(blockchain);
         rpc::register_methods(&mut rpc_handler);
+        rpc_handler.set_max_batch_size(max_batch_size);
+        if enable_metrics {
+            rpc_handler.set_method_observer(Box::new(metrics::record_rpc_call));
+        }
+        if auth.is_enabled() {
+            info!("RPC access control is enabled");
+            rpc_handler.set_method_authorizer(Box::new(move |context, method| auth.authorize(context, method)));
+        }
 
         // create the default websocket server (support event & rpc methods)
-        let ws = WebSocketServer::new(EventWebSocketHandler::new(rpc_handler));
+        let ws = WebSocketServer::new(EventWebSocketHandler::with_history(rpc_handler, event_history_size));
+
+        // load the certificate before starting the server
+        let tls_config = match tls {
+            Some((cert_path, key_path)) => {
+                info!("RPC Server will use TLS with certificate '{}'", cert_path);
+                Some(load_server_config(cert_path, key_path)?)
+            },
+            None => None
+        };
 
         let server = Arc::new(Self {
             handle: Mutex::new(None),
@@ -108,17 +131,28 @@ impl<S: Storage> DaemonRpcServer<S> {
             let clone = Arc::clone(&server);
             let http_server = HttpServer::new(move || {
                 let server = Arc::clone(&clone);
-                App::new().app_data(web::Data::from(server))
+                let app = App::new().app_data(web::Data::from(server))
                     // Traditional HTTP
                     .route("/json_rpc", web::post().to(json_rpc::<Arc<Blockchain<S>>, DaemonRpcServer<S>>))
                     // WebSocket support
                     .route("/json_rpc", web::get().to(websocket::<EventWebSocketHandler<Arc<Blockchain<S>>, NotifyEvent>, DaemonRpcServer<S>>))
-                    .route("/getwork/{address}/{worker}", web::get().to(getwork_endpoint::<S>))
-                    .service(index)
+                    .route("/getwork/{address}/{worker}", web::get().to(getwork_endpoint::<S>));
+
+                // Prometheus metrics
+                let app = if enable_metrics {
+                    app.route("/metrics", web::get().to(metrics_endpoint::<S>))
+                } else {
+                    app
+                };
+
+                app.service(index)
             })
-            .disable_signals()
-            .bind(&bind_address)?
-            .run();
+            .disable_signals();
+
+            let http_server = match tls_config {
+                Some(config) => http_server.bind_rustls_0_22(&bind_address, config)?,
+                None => http_server.bind(&bind_address)?
+            }.run();
 
             { // save the server handle to be able to stop it later
                 let handle = http_server.handle();
@@ -184,7 +218,45 @@ async fn index() -> impl Responder {
     HttpResponse::Ok().body(format!("Hello, world!\nRunning on: {}", config::VERSION))
 }
 
+// Verify with the RPC access control that the request can use this endpoint
+// An endpoint is allowed like a RPC method of the same name
+fn authorize_endpoint<S: Storage>(server: &DaemonRpcServer<S>, request: &HttpRequest, endpoint: &str) -> Result<(), HttpResponse> {
+    let mut context = Context::default();
+    context.store(RpcHttpRequest::from(request.clone()));
+    server.get_rpc_handler().authorize(&context, endpoint).map_err(|e| {
+        debug!("Request to {} endpoint rejected: {}", endpoint, e);
+        match e {
+            InternalRpcError::RateLimited => HttpResponse::TooManyRequests().body(e.to_string()),
+            InternalRpcError::MethodNotAllowed(_) => HttpResponse::Forbidden().body(e.to_string()),
+            _ => HttpResponse::Unauthorized().body(e.to_string())
+        }
+    })
+}
+
+async fn metrics_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>, request: HttpRequest) -> HttpResponse {
+    if let Err(response) = authorize_endpoint(&server, &request, "metrics") {
+        return response
+    }
+
+    if let Some(getwork) = &server.getwork {
+        metrics::GETWORK_MINERS.set(getwork.count_miners().await as i64);
+    }
+
+    let blockchain = server.get_rpc_handler().get_data();
+    match metrics::gather(blockchain).await {
+        Ok(body) => HttpResponse::Ok().content_type("text/plain; version=0.0.4").body(body),
+        Err(e) => {
+            error!("Error while gathering metrics: {}", e);
+            HttpResponse::InternalServerError().body("Error while gathering metrics")
+        }
+    }
+}
+
 async fn getwork_endpoint<S: Storage>(server: Data<DaemonRpcServer<S>>, request: HttpRequest, stream: Payload, path: Path<(String, String)>) -> Result<HttpResponse, Error> {
+    if let Err(response) = authorize_endpoint(&server, &request, "getwork") {
+        return Ok(response)
+    }
+
     match &server.getwork {
         Some(getwork) => {
             let (addr, worker) = path.into_inner();
@@ -12,9 +12,10 @@ use crate::{
         },
         error::BlockchainError,
         mempool::Mempool,
-        storage::Storage
+        storage::Storage,
+        tx_selector::{TxSelectionStrategyType, TxSelectorEntry}
     },
-    p2p::peer::Peer
+    p2p::{peer::Peer, PeerScore}
 };
 use super::{InternalRpcError, ApiError};
 use xelis_common::{
@@ -22,10 +23,16 @@ use xelis_common::{
         daemon::{
             AccountHistoryEntry,
             AccountHistoryType,
+            AccountTransactionEntry,
+            BlockTemplateTransaction,
+            DryRunBlockTemplateParams,
+            DryRunBlockTemplateResult,
+            SkippedBlockTemplateTransaction,
             RPCBlockResponse,
             BlockType,
             GetAccountAssetsParams,
             GetAccountHistoryParams,
+            GetAccountTransactionsParams,
             GetAccountsParams,
             GetAssetParams,
             GetAssetsParams,
@@ -54,9 +61,11 @@ use xelis_common::{
             P2pStatusResult,
             GetPeersResponse,
             PeerEntry,
+            PeerScoreEntry,
             SizeOnDiskResult,
             SubmitBlockParams,
             SubmitTransactionParams,
+            SimulateTransactionParams,
             TransactionResponse,
             GetMempoolCacheParams,
             IsAccountRegisteredParams,
@@ -77,7 +86,7 @@ use xelis_common::{
         XELIS_ASSET
     },
     context::Context,
-    crypto::Hash,
+    crypto::{Hash, Hashable},
     difficulty::{
         CumulativeDifficulty,
         Difficulty
@@ -260,6 +269,17 @@ pub async fn get_transaction_response_for_hash<S: Storage>(storage: &S, mempool:
     }
 }
 
+pub fn get_peer_score_entry(score: &PeerScore) -> PeerScoreEntry {
+    PeerScoreEntry {
+        value: score.get_value(),
+        latency: score.get_latency(),
+        objects_served: score.get_objects_served(),
+        invalid_objects: score.get_invalid_objects(),
+        sync_timeouts: score.get_sync_timeouts(),
+        uptime: score.get_uptime()
+    }
+}
+
 // Get a Peer Entry based on peer data
 pub async fn get_peer_entry(peer: &Peer) -> PeerEntry {
     let top_block_hash = peer.get_top_block_hash().lock().await.clone();
@@ -278,7 +298,9 @@ pub async fn get_peer_entry(peer: &Peer) -> PeerEntry {
         peers: Cow::Owned(peers),
         pruned_topoheight: peer.get_pruned_topoheight(),
         cumulative_difficulty: Cow::Owned(*cumulative_difficulty),
-        connected_on: peer.get_connection().connected_on()
+        connected_on: peer.get_connection().connected_on(),
+        identity: peer.get_identity().map(|identity| Cow::Owned(identity.to_hex())),
+        score: get_peer_score_entry(&peer.get_score())
     }
 }
 
@@ -290,6 +312,7 @@ pub fn register_methods<S: Storage>(handler: &mut RPCHandler<Arc<Blockchain<S>>>
     handler.register_method("get_topoheight", async_handler!(get_topoheight::<S>));
     handler.register_method("get_stableheight", async_handler!(get_stableheight::<S>));
     handler.register_method("get_block_template", async_handler!(get_block_template::<S>));
+    handler.register_method("dry_run_block_template", async_handler!(dry_run_block_template::<S>));
     handler.register_method("get_block_at_topoheight", async_handler!(get_block_at_topoheight::<S>));
     handler.register_method("get_blocks_at_height", async_handler!(get_blocks_at_height::<S>));
     handler.register_method("get_block_by_hash", async_handler!(get_block_by_hash::<S>));
@@ -308,6 +331,7 @@ pub fn register_methods<S: Storage>(handler: &mut RPCHandler<Arc<Blockchain<S>>>
     handler.register_method("count_accounts", async_handler!(count_accounts::<S>));
     handler.register_method("count_transactions", async_handler!(count_transactions::<S>));
     handler.register_method("submit_transaction", async_handler!(submit_transaction::<S>));
+    handler.register_method("simulate_transaction", async_handler!(simulate_transaction::<S>));
     handler.register_method("get_transaction", async_handler!(get_transaction::<S>));
     handler.register_method("p2p_status", async_handler!(p2p_status::<S>));
     handler.register_method("get_peers", async_handler!(get_peers::<S>));
@@ -319,6 +343,7 @@ pub fn register_methods<S: Storage>(handler: &mut RPCHandler<Arc<Blockchain<S>>>
     handler.register_method("get_transactions", async_handler!(get_transactions::<S>));
     handler.register_method("get_account_history", async_handler!(get_account_history::<S>));
     handler.register_method("get_account_assets", async_handler!(get_account_assets::<S>));
+    handler.register_method("get_account_transactions", async_handler!(get_account_transactions::<S>));
     handler.register_method("get_accounts", async_handler!(get_accounts::<S>));
     handler.register_method("is_account_registered", async_handler!(is_account_registered::<S>));
     handler.register_method("get_account_registration_topoheight", async_handler!(get_account_registration_topoheight::<S>));
@@ -400,6 +425,52 @@ async fn get_block_template<S: Storage>(context: Context, body: Value) -> Result
     Ok(json!(GetBlockTemplateResult { template: block.to_hex(), height, difficulty }))
 }
 
+fn to_block_template_transaction<'a>(entry: &TxSelectorEntry<'a>, mainnet: bool) -> BlockTemplateTransaction<'a> {
+    let hash: &'a Arc<Hash> = entry.hash;
+    BlockTemplateTransaction {
+        hash: Cow::Borrowed(hash.as_ref()),
+        source: entry.tx.get_source().as_address(mainnet),
+        nonce: entry.tx.get_nonce(),
+        fee: entry.tx.get_fee(),
+        size: entry.size
+    }
+}
+
+// Show which TXs from mempool would be selected for a block template and why the others are skipped
+async fn dry_run_block_template<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: DryRunBlockTemplateParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let custom_strategy = match params.strategy {
+        Some(strategy) => Some(strategy.parse::<TxSelectionStrategyType>().map_err(InternalRpcError::Custom)?.build()),
+        None => None
+    };
+    let strategy = match custom_strategy.as_ref() {
+        Some(strategy) => strategy.as_ref(),
+        None => blockchain.get_tx_selection_strategy()
+    };
+
+    let storage = blockchain.get_storage().read().await;
+    // miner key has no impact on the header size
+    let header = blockchain.get_block_header_template_for_storage(&storage, DEV_PUBLIC_KEY.clone()).await.context("Error while retrieving block header template")?;
+    let mempool = blockchain.get_mempool().read().await;
+    let selection = blockchain.select_block_template_txs(&storage, &mempool, header.size(), strategy).await.context("Error while selecting transactions")?;
+
+    let mainnet = blockchain.get_network().is_mainnet();
+    let result = DryRunBlockTemplateResult {
+        strategy: Cow::Borrowed(strategy.name()),
+        total_size: selection.selected.iter().map(|entry| entry.size).sum(),
+        total_fees: selection.selected.iter().map(|entry| entry.tx.get_fee()).sum(),
+        selected: selection.selected.iter().map(|entry| to_block_template_transaction(entry, mainnet)).collect(),
+        skipped: selection.skipped.iter().map(|(entry, reason, error)| SkippedBlockTemplateTransaction {
+            transaction: to_block_template_transaction(entry, mainnet),
+            reason: *reason,
+            error: error.clone()
+        }).collect()
+    };
+
+    Ok(json!(result))
+}
+
 async fn submit_block<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: SubmitBlockParams = parse_params(body)?;
     let header = BlockHeader::from_hex(params.block_template)?;
@@ -619,10 +690,32 @@ async fn submit_transaction<S: Storage>(context: Context, body: Value) -> Result
 
     let transaction = Transaction::from_hex(params.data)?;
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    // With Dandelion, the TX is stemmed first and reaches our mempool once fluffed
+    let p2p = blockchain.get_p2p().read().await.as_ref().filter(|p2p| p2p.is_dandelion_enabled()).cloned();
+    if let Some(p2p) = p2p {
+        let hash = transaction.hash();
+        p2p.relay_stem_tx(hash, Arc::new(transaction), None).await.map_err(|e| InternalRpcError::AnyError(e.into()))?;
+        return Ok(json!(true))
+    }
+
     blockchain.add_tx_to_mempool(transaction, true).await.map_err(|e| InternalRpcError::AnyError(e.into()))?;
     Ok(json!(true))
 }
 
+// Verify a TX like submit_transaction does, without adding it to the mempool
+async fn simulate_transaction<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: SimulateTransactionParams = parse_params(body)?;
+    // x2 because of hex encoding
+    if params.data.len() > MAX_TRANSACTION_SIZE * 2 {
+        return Err(InternalRpcError::InvalidRequest).context(format!("Transaction size cannot be greater than {}", human_bytes(MAX_TRANSACTION_SIZE as f64)))?
+    }
+
+    let transaction = Transaction::from_hex(params.data)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    let result = blockchain.simulate_transaction(&transaction).await.context("Error while simulating transaction")?;
+    Ok(json!(result))
+}
+
 async fn get_transaction<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: GetTransactionParams = parse_params(body)?;
     let blockchain: &Arc<Blockchain<S>> = context.get()?;
@@ -656,7 +749,8 @@ async fn p2p_status<S: Storage>(context: Context, body: Value) -> Result<Value,
                 our_topoheight,
                 best_topoheight,
                 median_topoheight,
-                max_peers
+                max_peers,
+                identity: Cow::Owned(p2p.get_identity().get_public_key().to_hex())
             }))
         },
         None => Err(InternalRpcError::AnyError(ApiError::NoP2p.into()))
@@ -1035,6 +1129,51 @@ async fn get_accounts<S: Storage>(context: Context, body: Value) -> Result<Value
     Ok(json!(accounts))
 }
 
+const MAX_ACCOUNT_TRANSACTIONS: usize = 100;
+
+// Retrieve the transactions linked to an account from the account transactions index
+async fn get_account_transactions<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetAccountTransactionsParams = parse_params(body)?;
+    let blockchain: &Arc<Blockchain<S>> = context.get()?;
+    if !blockchain.is_account_transactions_indexed() {
+        return Err(InternalRpcError::CustomStr("Account transactions index is not enabled"))
+    }
+
+    if params.address.is_mainnet() != blockchain.get_network().is_mainnet() {
+        return Err(InternalRpcError::AnyError(BlockchainError::InvalidNetwork.into()))
+    }
+
+    let maximum = if let Some(maximum) = params.maximum {
+        if maximum > MAX_ACCOUNT_TRANSACTIONS {
+            return Err(InternalRpcError::InvalidRequest).context(format!("Maximum transactions requested cannot be greater than {}", MAX_ACCOUNT_TRANSACTIONS))?
+        }
+        maximum
+    } else {
+        MAX_ACCOUNT_TRANSACTIONS
+    };
+    let skip = params.skip.unwrap_or(0);
+    let minimum_topoheight = params.minimum_topoheight.unwrap_or(0);
+    let maximum_topoheight = if let Some(maximum) = params.maximum_topoheight {
+        if maximum < minimum_topoheight {
+            return Err(InternalRpcError::InvalidRequest).context(format!("Maximum topoheight requested must be greater or equal to {}", minimum_topoheight))?
+        }
+        maximum
+    } else {
+        u64::MAX
+    };
+
+    let storage = blockchain.get_storage().read().await;
+    let transactions = storage.get_account_transactions(params.address.get_public_key(), minimum_topoheight, maximum_topoheight, skip, maximum).await
+        .context("Error while retrieving account transactions")?
+        .into_iter().map(|(topoheight, hash, role)| AccountTransactionEntry {
+            topoheight,
+            hash: Cow::Owned(hash),
+            role
+        }).collect::<Vec<_>>();
+
+    Ok(json!(transactions))
+}
+
 // Check if the account is registered on chain or not
 async fn is_account_registered<S: Storage>(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: IsAccountRegisteredParams = parse_params(body)?;
@@ -1112,4 +1251,67 @@ async fn get_mempool_cache<S: Storage>(context: Context, body: Value) -> Result<
         .context("Account not found while retrieving mempool cache")?;
 
     Ok(json!(cache))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::{api::daemon::AccountTransactionRole, config::COIN_VALUE, crypto::Hashable};
+    use crate::core::{
+        blockchain::tests::{create_account, mine_blocks, start_node, start_node_with, stop_node, temp_dir_path},
+        storage::SledStorage
+    };
+    use super::*;
+
+    async fn call_get_account_transactions(blockchain: &Arc<Blockchain<SledStorage>>, params: Value) -> Result<Vec<AccountTransactionEntry<'static>>, InternalRpcError> {
+        let mut context = Context::default();
+        context.store(blockchain.clone());
+        let value = get_account_transactions::<SledStorage>(context, params).await?;
+        Ok(serde_json::from_value(value).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_get_account_transactions() {
+        let dir_path = temp_dir_path("rpc-account-txs");
+        let blockchain = start_node_with::<SledStorage>(&dir_path, &["--index-account-transactions"]).await;
+        let mut account = create_account(&[&blockchain], COIN_VALUE).await;
+        let mut hashes = Vec::new();
+        // Each TX is executed in its own block
+        for _ in 0..3 {
+            let tx = account.build_burn_tx();
+            hashes.push(tx.hash());
+            blockchain.add_tx_to_mempool(tx, false).await.unwrap();
+            mine_blocks(&blockchain, 1).await;
+        }
+
+        let address = account.keypair.get_public_key().to_address(false);
+        let entries = call_get_account_transactions(&blockchain, json!({ "address": address })).await.unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.hash.as_ref().clone()).collect::<Vec<_>>(), hashes);
+        assert!(entries.iter().all(|entry| entry.role == AccountTransactionRole::Source));
+        assert!(entries.windows(2).all(|w| w[0].topoheight < w[1].topoheight));
+
+        // Second page of one entry
+        let page = call_get_account_transactions(&blockchain, json!({ "address": address, "skip": 1, "maximum": 1 })).await.unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].hash.as_ref(), &hashes[1]);
+
+        // Only the TX executed at this topoheight
+        let topoheight = entries[2].topoheight;
+        let range = call_get_account_transactions(&blockchain, json!({ "address": address, "minimum_topoheight": topoheight, "maximum_topoheight": topoheight })).await.unwrap();
+        assert_eq!(range.len(), 1);
+        assert_eq!(range[0].hash.as_ref(), &hashes[2]);
+
+        // Invalid requests
+        assert!(call_get_account_transactions(&blockchain, json!({ "address": address, "maximum": MAX_ACCOUNT_TRANSACTIONS + 1 })).await.is_err());
+        assert!(call_get_account_transactions(&blockchain, json!({ "address": address, "minimum_topoheight": 2, "maximum_topoheight": 1 })).await.is_err());
+        let mainnet_address = account.keypair.get_public_key().to_address(true);
+        assert!(call_get_account_transactions(&blockchain, json!({ "address": mainnet_address })).await.is_err());
+
+        stop_node(&blockchain, &dir_path).await;
+
+        // The index must be enabled
+        let dir_path = temp_dir_path("rpc-no-index");
+        let blockchain = start_node::<SledStorage>(&dir_path).await;
+        assert!(call_get_account_transactions(&blockchain, json!({ "address": address })).await.is_err());
+        stop_node(&blockchain, &dir_path).await;
+    }
+}
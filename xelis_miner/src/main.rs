@@ -25,7 +25,8 @@ use tokio::{
     time::Instant,
 };
 use tokio_tungstenite::{
-    connect_async,
+    connect_async_tls_with_config,
+    Connector,
     tungstenite::{
         Message,
         Error as TungsteniteError
@@ -60,6 +61,7 @@ use xelis_common::{
     },
     serializer::Serializer,
     time::get_current_time_in_millis,
+    tls::{load_client_config, TlsClientConfig},
     utils::{
         format_difficulty,
         format_hashrate, sanitize_daemon_address
@@ -89,6 +91,11 @@ pub struct MinerConfig {
     /// Daemon address to connect to for mining
     #[clap(long, default_value_t = String::from(DEFAULT_DAEMON_ADDRESS))]
     daemon_address: String,
+    /// PEM file of the CA certificates trusted to connect to the daemon over TLS.
+    /// 
+    /// This allows to connect to a daemon using a self-signed certificate.
+    #[clap(long)]
+    daemon_ca_bundle: Option<String>,
     /// Set log level
     #[clap(long, value_enum, default_value_t = LogLevel::Info)]
     log_level: LogLevel,
@@ -199,8 +206,13 @@ async fn main() -> Result<()> {
         }
     }
 
+    let tls_config = match config.daemon_ca_bundle {
+        Some(path) => Some(load_client_config(&path)?),
+        None => None
+    };
+
     // start communication task
-    let task = tokio::spawn(communication_task(config.daemon_address, sender.clone(), block_receiver, address, config.worker));
+    let task = tokio::spawn(communication_task(config.daemon_address, tls_config, sender.clone(), block_receiver, address, config.worker));
 
     if let Err(e) = run_prompt(prompt).await {
         error!("Error on running prompt: {}", e);
@@ -251,12 +263,13 @@ fn benchmark(threads: usize, iterations: usize) {
 // It maintains a WebSocket connection with the daemon and notify all threads when it receive a new job.
 // Its also the task who have the job to send directly the new block found by one of the threads.
 // This allow mining threads to only focus on mining and receiving jobs through memory channels.
-async fn communication_task(daemon_address: String, job_sender: broadcast::Sender<ThreadNotification<'_>>, mut block_receiver: mpsc::Receiver<BlockMiner<'_>>, address: Address, worker: String) {
+async fn communication_task(daemon_address: String, tls_config: Option<TlsClientConfig>, job_sender: broadcast::Sender<ThreadNotification<'_>>, mut block_receiver: mpsc::Receiver<BlockMiner<'_>>, address: Address, worker: String) {
     info!("Starting communication task");
     let daemon_address = sanitize_daemon_address(&daemon_address);
     'main: loop {
         info!("Trying to connect to {}", daemon_address);
-        let client = match connect_async(format!("{}/getwork/{}/{}", daemon_address, address.to_string(), worker)).await {
+        let connector = tls_config.as_ref().map(|config| Connector::Rustls(config.clone()));
+        let client = match connect_async_tls_with_config(format!("{}/getwork/{}/{}", daemon_address, address.to_string(), worker), None, false, connector).await {
             Ok((client, response)) => {
                 let status = response.status();
                 if status.is_server_error() || status.is_client_error() {
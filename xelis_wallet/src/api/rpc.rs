@@ -3,24 +3,34 @@ use anyhow::Context as AnyContext;
 use xelis_common::{
     api::{
         wallet::{
+            BatchPayoutParams,
             BuildTransactionParams,
+            BuildUnsignedTransactionParams,
+            ContactEntry,
+            CreateAccountParams,
             DeleteParams,
             EstimateFeesParams,
             GetAddressParams,
             GetAssetPrecisionParams,
             GetBalanceParams,
+            GetContactParams,
             GetMatchingKeysParams,
+            GetPayoutReportParams,
             GetTransactionParams,
             GetValueFromKeyParams,
             HasKeyParams,
             ListTransactionsParams,
             QueryDBParams,
+            RemoveContactParams,
             RescanParams,
+            SetAccountLabelParams,
             SplitAddressParams,
             SplitAddressResult,
             StoreParams,
+            SubmitTransactionParams,
             TransactionResponse,
             SetOnlineModeParams,
+            UnsignedTransaction,
         },
         DataElement,
         DataHash
@@ -36,12 +46,15 @@ use xelis_common::{
         RPCHandler
     },
     serializer::Serializer,
-    transaction::builder::FeeBuilder
+    transaction::{builder::FeeBuilder, Transaction}
 };
 use serde_json::{Value, json};
-use crate::wallet::{
-    Wallet,
-    WalletError
+use crate::{
+    cold_account::ColdAccount,
+    wallet::{
+        Wallet,
+        WalletError
+    }
 };
 use super::xswd::XSWDWebSocketHandler;
 use log::info;
@@ -62,12 +75,27 @@ pub fn register_methods(handler: &mut RPCHandler<Arc<Wallet>>) {
     handler.register_method("get_asset_precision", async_handler!(get_asset_precision));
     handler.register_method("get_transaction", async_handler!(get_transaction));
     handler.register_method("build_transaction", async_handler!(build_transaction));
+    handler.register_method("build_unsigned_transaction", async_handler!(build_unsigned_transaction));
+    handler.register_method("sign_unsigned_transaction", async_handler!(sign_unsigned_transaction));
+    handler.register_method("submit_transaction", async_handler!(submit_transaction));
     handler.register_method("list_transactions", async_handler!(list_transactions));
     handler.register_method("is_online", async_handler!(is_online));
     handler.register_method("set_online_mode", async_handler!(set_online_mode));
     handler.register_method("set_offline_mode", async_handler!(set_offline_mode));
     handler.register_method("sign_data", async_handler!(sign_data));
     handler.register_method("estimate_fees", async_handler!(estimate_fees));
+    handler.register_method("batch_payout", async_handler!(batch_payout));
+    handler.register_method("get_payout_report", async_handler!(get_payout_report));
+    handler.register_method("list_accounts", async_handler!(list_accounts));
+    handler.register_method("create_account", async_handler!(create_account));
+    handler.register_method("set_account_label", async_handler!(set_account_label));
+
+    // Address book shared by all the accounts
+    // Like any other method, an XSWD application must be allowed by the user to read or edit it
+    handler.register_method("list_contacts", async_handler!(list_contacts));
+    handler.register_method("get_contact", async_handler!(get_contact));
+    handler.register_method("add_contact", async_handler!(add_contact));
+    handler.register_method("remove_contact", async_handler!(remove_contact));
 
     // These functions allow to have an encrypted DB directly in the wallet storage
     // You can retrieve keys, values, have differents trees, and store values
@@ -101,34 +129,30 @@ async fn get_network(context: Context, body: Value) -> Result<Value, InternalRpc
 }
 
 // Retrieve the current nonce of the wallet
-async fn get_nonce(context: Context, body: Value) -> Result<Value, InternalRpcError> {
-    if body != Value::Null {
-        return Err(InternalRpcError::UnexpectedParams)
-    }
+async fn get_nonce(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
+    expect_no_params(&body)?;
 
-    let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
     let nonce = storage.get_nonce()?;
     Ok(json!(nonce))
 }
 
 // Retrieve the current topoheight until which the wallet is synced
-async fn get_topoheight(context: Context, body: Value) -> Result<Value, InternalRpcError> {
-    if body != Value::Null {
-        return Err(InternalRpcError::UnexpectedParams)
-    }
+async fn get_topoheight(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
+    expect_no_params(&body)?;
 
-    let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
     let topoheight = storage.get_synced_topoheight()?;
     Ok(json!(topoheight))
 }
 
 // Retrieve the wallet address
-async fn get_address(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn get_address(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
     let params: GetAddressParams = parse_params(body)?;
 
-    let wallet: &Arc<Wallet> = context.get()?;
     let address = if let Some(data) = params.integrated_data {
         wallet.get_address_with(data)
     } else {
@@ -162,10 +186,10 @@ async fn rescan(context: Context, body: Value) -> Result<Value, InternalRpcError
 
 // Retrieve the balance of the wallet for a specific asset
 // By default, it will returns 0 if no balance is found on disk
-async fn get_balance(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn get_balance(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
     let params: GetBalanceParams = parse_params(body)?;
     let asset = params.asset.unwrap_or(XELIS_ASSET);
-    let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
 
     // If the asset is not found, it will returns 0
@@ -175,10 +199,10 @@ async fn get_balance(context: Context, body: Value) -> Result<Value, InternalRpc
 }
 
 // Check if the wallet has a balance for a specific asset
-async fn has_balance(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn has_balance(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
     let params: GetBalanceParams = parse_params(body)?;
     let asset = params.asset.unwrap_or(XELIS_ASSET);
-    let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
 
     let exist = storage.has_balance_for(&asset).await.context("Error while checking if balance exists")?;
@@ -186,12 +210,10 @@ async fn has_balance(context: Context, body: Value) -> Result<Value, InternalRpc
 }
 
 // Retrieve all tracked assets by wallet
-async fn get_tracked_assets(context: Context, body: Value) -> Result<Value, InternalRpcError> {
-    if body != Value::Null {
-        return Err(InternalRpcError::UnexpectedParams)
-    }
+async fn get_tracked_assets(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
+    expect_no_params(&body)?;
 
-    let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
     let tracked_assets = storage.get_assets().await?;
 
@@ -199,20 +221,20 @@ async fn get_tracked_assets(context: Context, body: Value) -> Result<Value, Inte
 }
 
 // Retrieve decimals used by an asset
-async fn get_asset_precision(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn get_asset_precision(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
     let params: GetAssetPrecisionParams = parse_params(body)?;
 
-    let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
     let precision = storage.get_asset_decimals(&params.asset)?;
     Ok(json!(precision))
 }
 
 // Retrieve a transaction from the wallet storage using its hash
-async fn get_transaction(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn get_transaction(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
     let params: GetTransactionParams = parse_params(body)?;
 
-    let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
     let transaction = storage.get_transaction(&params.hash)?;
 
@@ -220,9 +242,9 @@ async fn get_transaction(context: Context, body: Value) -> Result<Value, Interna
 }
 
 // Build a transaction and broadcast it if requested
-async fn build_transaction(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn build_transaction(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
     let params: BuildTransactionParams = parse_params(body)?;
-    let wallet: &Arc<Wallet> = context.get()?;
     // request ask to broadcast the TX but wallet is not connected to any daemon
     if !wallet.is_online().await && params.broadcast {
         return Err(WalletError::NotOnlineMode).context("Cannot broadcast TX")?
@@ -255,17 +277,69 @@ async fn build_transaction(context: Context, body: Value) -> Result<Value, Inter
     }))
 }
 
+// Build a transaction request to be signed by the offline wallet of the source address
+// Only the daemon state of the source is used, never the keys of this wallet
+async fn build_unsigned_transaction(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: BuildUnsignedTransactionParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let account = ColdAccount::new(params.source, *wallet.get_network())
+        .context("Invalid source address")?;
+
+    let network_handler = wallet.get_network_handler().await.lock().await;
+    let network_handler = network_handler.as_ref().ok_or(WalletError::NotOnlineMode)
+        .context("Cannot build unsigned transaction")?;
+    let unsigned = account.create_unsigned_transaction(network_handler.get_api(), params.tx_type, params.fee.unwrap_or(FeeBuilder::Multiplier(1f64))).await
+        .context("Error while creating unsigned transaction")?;
+
+    Ok(json!(unsigned))
+}
+
+// Build and sign a transaction request from an online wallet
+// The TX is not broadcasted, it is returned in hex format
+async fn sign_unsigned_transaction(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
+    let params: UnsignedTransaction = parse_params(body)?;
+    let tx = wallet.sign_unsigned_transaction(params).await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))?;
+
+    Ok(json!(TransactionResponse {
+        tx_as_hex: Some(hex::encode(tx.to_bytes())),
+        inner: DataHash {
+            hash: Cow::Owned(tx.hash()),
+            data: Cow::Owned(tx)
+        }
+    }))
+}
+
+// Verify a transaction signed by an offline wallet and broadcast it
+async fn submit_transaction(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: SubmitTransactionParams = parse_params(body)?;
+    let tx = Transaction::from_hex(params.tx_as_hex)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let account = ColdAccount::new(tx.get_source().as_address(wallet.get_network().is_mainnet()), *wallet.get_network())
+        .context("Invalid transaction source")?;
+
+    let network_handler = wallet.get_network_handler().await.lock().await;
+    let network_handler = network_handler.as_ref().ok_or(WalletError::NotOnlineMode)
+        .context("Cannot submit transaction")?;
+    account.submit_signed_transaction(network_handler.get_api(), &tx).await
+        .context("Couldn't submit transaction")?;
+
+    Ok(json!(tx.hash()))
+}
+
 // Estimate fees for a transaction
-async fn estimate_fees(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn estimate_fees(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
     let params: EstimateFeesParams = parse_params(body)?;
-    let wallet: &Arc<Wallet> = context.get()?;
     let fees = wallet.estimate_fees(params.tx_type).await.context("Error while estimating fees")?;
 
     Ok(json!(fees))
 }
 
 // List transactions from the wallet storage
-async fn list_transactions(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+async fn list_transactions(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
     let params: ListTransactionsParams = parse_params(body)?;
     if let Some(addr) = &params.address {
         if !addr.is_normal() {
@@ -273,7 +347,6 @@ async fn list_transactions(context: Context, body: Value) -> Result<Value, Inter
         }
     }
 
-    let wallet: &Arc<Wallet> = context.get()?;
     let storage = wallet.get_storage().read().await;
     let opt_key = params.address.map(|addr| addr.to_public_key());
     
@@ -331,11 +404,125 @@ async fn set_offline_mode(context: Context, body: Value) -> Result<Value, Intern
 async fn sign_data(context: Context, body: Value) -> Result<Value, InternalRpcError> {
     let params: DataElement = parse_params(body)?;
 
+    // Params are the data itself, so it is always signed by account 0
     let wallet: &Arc<Wallet> = context.get()?;
     let signature = wallet.sign_data(&params.to_bytes());
     Ok(json!(signature))
 }
 
+// Pay all the entries using as few TXs as possible
+// Calling it again with the same entries resumes the payout
+async fn batch_payout(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
+    let params: BatchPayoutParams = parse_params(body)?;
+    let report = wallet.batch_payout(params.entries, params.fee.unwrap_or(FeeBuilder::Multiplier(1f64))).await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))?;
+
+    Ok(json!(report))
+}
+
+// Get the status of each recipient of a batch payout
+async fn get_payout_report(context: Context, mut body: Value) -> Result<Value, InternalRpcError> {
+    let wallet = get_account(&context, &mut body).await?;
+    let params: GetPayoutReportParams = parse_params(body)?;
+    let storage = wallet.get_storage().read().await;
+    let report = storage.get_payout_report(&params.id)?;
+
+    Ok(json!(report))
+}
+
+// Get the wallet of the account selected by the optional `account` param, account 0 by default
+// The selection is done per request so a client can't change the account used by the others
+// The param is removed before parsing the params of the method
+async fn get_account(context: &Context, body: &mut Value) -> Result<Arc<Wallet>, InternalRpcError> {
+    let index = match body.as_object_mut().and_then(|params| params.remove("account")) {
+        Some(value) => parse_params(value)?,
+        None => 0
+    };
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet.get_account(index).await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))
+}
+
+// Verify that no params were given except the account
+fn expect_no_params(body: &Value) -> Result<(), InternalRpcError> {
+    match body {
+        Value::Null => Ok(()),
+        Value::Object(params) if params.is_empty() => Ok(()),
+        _ => Err(InternalRpcError::UnexpectedParams)
+    }
+}
+
+// List the accounts derived from the wallet seed
+async fn list_accounts(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    let accounts = wallet.get_accounts().await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))?;
+    Ok(json!(accounts))
+}
+
+// Create a new account derived from the wallet seed and returns its index
+async fn create_account(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: CreateAccountParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let index = wallet.create_account(params.label).await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))?;
+    Ok(json!(index))
+}
+
+// Set the label of an account
+async fn set_account_label(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: SetAccountLabelParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet.set_account_label(params.account, params.label).await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))?;
+    Ok(json!(true))
+}
+
+// List the contacts of the address book
+async fn list_contacts(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    if body != Value::Null {
+        return Err(InternalRpcError::UnexpectedParams)
+    }
+
+    let wallet: &Arc<Wallet> = context.get()?;
+    let storage = wallet.get_storage().read().await;
+    let contacts = storage.get_contacts()?;
+    Ok(json!(contacts))
+}
+
+// Get the address of a contact
+async fn get_contact(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: GetContactParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let address = wallet.get_contact(&params.name).await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))?;
+    Ok(json!(address))
+}
+
+// Add an address to the address book
+async fn add_contact(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: ContactEntry = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet.add_contact(params.name, params.address).await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))?;
+    Ok(json!(true))
+}
+
+// Remove a contact from the address book
+async fn remove_contact(context: Context, body: Value) -> Result<Value, InternalRpcError> {
+    let params: RemoveContactParams = parse_params(body)?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet.remove_contact(&params.name).await
+        .map_err(|e| InternalRpcError::AnyError(e.into()))?;
+    Ok(json!(true))
+}
+
 // In EncryptedStorage, custom trees are already prefixed
 async fn get_tree_name(context: &Context, tree: String) -> Result<String, InternalRpcError> {
     // If the API is not used through XSWD, we don't need to prefix the tree name with the app id
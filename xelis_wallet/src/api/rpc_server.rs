@@ -4,7 +4,7 @@ use actix_web_httpauth::{middleware::HttpAuthentication, extractors::basic::Basi
 use anyhow::Result;
 use log::{info, warn};
 use tokio::sync::Mutex;
-use xelis_common::{config, rpc_server::{RPCHandler, RPCServerHandler, json_rpc, websocket, websocket::{EventWebSocketHandler, WebSocketServerShared, WebSocketServer}, WebSocketServerHandler}, api::wallet::NotifyEvent};
+use xelis_common::{config, rpc_server::{tls::load_server_config, RPCHandler, RPCServerHandler, json_rpc, websocket, websocket::{EventWebSocketHandler, WebSocketServerShared, WebSocketServer}, WebSocketServerHandler}, api::wallet::NotifyEvent};
 use actix_web::{get, HttpResponse, Responder, HttpServer, web::{Data, self}, App, dev::{ServerHandle, ServiceRequest}, Error, error::{ErrorUnauthorized, ErrorBadGateway, ErrorBadRequest}};
 
 pub type WalletRpcServerShared<W> = Arc<WalletRpcServer<W>>;
@@ -27,7 +27,13 @@ impl<W> WalletRpcServer<W>
 where
     W: Clone + Send + Sync + 'static
 {
-    pub async fn new(bind_address: String, rpc_handler: RPCHandler<W>, auth_config: Option<AuthConfig>) -> Result<WalletRpcServerShared<W>> {
+    // tls is the PEM certificate and private key files to serve the RPC over HTTPS/WSS
+    pub async fn new(bind_address: String, rpc_handler: RPCHandler<W>, auth_config: Option<AuthConfig>, tls: Option<(String, String)>) -> Result<WalletRpcServerShared<W>> {
+        let tls_config = match tls {
+            Some((cert_path, key_path)) => Some(load_server_config(cert_path, key_path)?),
+            None => None
+        };
+
         let server = Arc::new(Self {
             handle: Mutex::new(None),
             websocket: WebSocketServer::new(EventWebSocketHandler::new(rpc_handler)),
@@ -48,9 +54,12 @@ where
                     .route("/json_rpc", web::post().to(json_rpc::<W, WalletRpcServer<W>>))
                     .service(index)
             })
-            .disable_signals()
-            .bind(&bind_address)?
-            .run();
+            .disable_signals();
+
+            let http_server = match tls_config {
+                Some(config) => http_server.bind_rustls_0_22(&bind_address, config)?,
+                None => http_server.bind(&bind_address)?
+            }.run();
 
             { // save the server handle to be able to stop it later
                 let handle = http_server.handle();
@@ -50,6 +50,7 @@ use xelis_common::{
         SIGNATURE_SIZE
     },
     rpc_server::{
+        tls::load_server_config,
         websocket::{
             WebSocketHandler,
             WebSocketServer,
@@ -265,8 +266,15 @@ impl<W> XSWD<W>
 where
     W: Clone + Send + Sync + XSWDPermissionHandler + XSWDNodeMethodHandler + 'static
 {
-    pub fn new(rpc_handler: RPCHandler<W>) -> Result<Self, anyhow::Error> {
+    // tls is the PEM certificate and private key files to serve XSWD over WSS
+    pub fn new(rpc_handler: RPCHandler<W>, tls: Option<(String, String)>) -> Result<Self, anyhow::Error> {
         info!("Starting XSWD Server...");
+        let tls_config = match tls {
+            Some((cert_path, key_path)) => Some(load_server_config(cert_path, key_path)?),
+            None => None
+        };
+        let scheme = if tls_config.is_some() { "wss" } else { "ws" };
+
         let websocket = WebSocketServer::new(XSWDWebSocketHandler::new(rpc_handler));
         let cloned_websocket = websocket.clone();
         let http_server = HttpServer::new(move || {
@@ -276,14 +284,17 @@ where
                 .service(index)
                 .route("/xswd", web::get().to(endpoint::<W>))
         })
-        .disable_signals()
-        .bind(&XSWD_BIND_ADDRESS)?
-        .run();
+        .disable_signals();
+
+        let http_server = match tls_config {
+            Some(config) => http_server.bind_rustls_0_22(&XSWD_BIND_ADDRESS, config)?,
+            None => http_server.bind(&XSWD_BIND_ADDRESS)?
+        }.run();
 
         let handle = http_server.handle();
         tokio::spawn(http_server);
 
-        info!("XSWD is listening on ws://{}", XSWD_BIND_ADDRESS);
+        info!("XSWD is listening on {}://{}", scheme, XSWD_BIND_ADDRESS);
 
         Ok(Self {
             websocket,
@@ -401,7 +412,7 @@ where
 
     // notify a new event to all connected WebSocket
     pub async fn notify(&self, event: &NotifyEvent, value: Value) {
-        let value = json!(EventResult { event: Cow::Borrowed(event), value });
+        let value = json!(EventResult { event: Cow::Borrowed(event), sequence: None, value });
         let sessions = self.listeners.lock().await;
         for (session, subscriptions) in sessions.iter() {
             if let Some(id) = subscriptions.get(event) {
@@ -601,60 +612,71 @@ where
     }
 
     async fn on_message_internal(&self, session: &WebSocketSessionShared<Self>, message: &[u8]) -> Result<Option<Value>, RpcResponseError> {
-        let (request, is_subscribe, is_unsubscribe) = {
-            let app_state = {
-                let applications = self.applications.read().await;
-                applications.get(session).cloned()
-            };
-
-            // Application is already registered, verify permission and call the method
-            if let Some(app) = app_state {
-                let mut request: RpcRequest = self.handler.parse_request(message)?;
-                // Redirect all node methods to the node method handler
-                if request.method.starts_with("node.") {
-                    // Remove the 5 first chars (node.)
-                    request.method = request.method[5..].into();
-                    return self.handler.get_data().call_node_with(request).await.map(|v| Some(v))
-                }
-
-                // Verify that the method start with "wallet."
-                if !request.method.starts_with("wallet.") {
-                    return Err(RpcResponseError::new(request.id, InternalRpcError::MethodNotFound(request.method)))
-                }
-                request.method = request.method[7..].into();
+        let app_state = {
+            let applications = self.applications.read().await;
+            applications.get(session).cloned()
+        };
 
-                // Verify first if the method exist (and that its not a built-in one)
-                let is_subscribe = request.method == "subscribe";
-                let is_unsubscribe = request.method == "unsubscribe";
-                if !self.handler.has_method(&request.method) && !is_subscribe && !is_unsubscribe {
-                    return Err(RpcResponseError::new(request.id, InternalRpcError::MethodNotFound(request.method)))
-                }
-    
-                // let's check the permission set by user for this method
-                app.set_requesting(true);
-                self.verify_permission_for_request(&app, &request).await?;
-                app.set_requesting(false);
+        // Application is already registered, verify permission and call the method(s)
+        if let Some(app) = app_state {
+            let app = &app;
+            return self.handler.handle_body(message, move |value| self.on_request(session, app, value)).await
+        }
 
-                (request, is_subscribe, is_unsubscribe)
-            } else {
-                // Application is not registered, register it
-                return match self.add_application(session, message).await {
-                    Ok(v) => Ok(Some(v)),
-                    Err(e) => {
-                        if !session.is_closed().await {
-                            // Send error message and then close the session
-                            if let Err(e) = session.send_text(&e.to_json().to_string()).await {
-                                error!("Error while sending error message to session: {}", e);
-                            }
-                        }
-
-                        session.get_server().delete_session(&session, None).await;
-    
-                        Ok(None)
+        // Application is not registered, register it
+        match self.add_application(session, message).await {
+            Ok(v) => Ok(Some(v)),
+            Err(e) => {
+                if !session.is_closed().await {
+                    // Send error message and then close the session
+                    if let Err(e) = session.send_text(&e.to_json().to_string()).await {
+                        error!("Error while sending error message to session: {}", e);
                     }
                 }
+
+                session.get_server().delete_session(&session, None).await;
+
+                Ok(None)
             }
-        };
+        }
+    }
+
+    // Handle a single request of the application, no response is returned for a notification
+    async fn on_request(&self, session: &WebSocketSessionShared<Self>, app: &AppStateShared, value: Value) -> Result<Option<Value>, RpcResponseError> {
+        let request = self.handler.parse_request_from_value(value)?;
+        let is_notification = request.id.is_none();
+        match self.execute_request(session, app, request).await {
+            Ok(response) if !is_notification => Ok(response),
+            Err(e) if !is_notification => Err(e),
+            _ => Ok(None)
+        }
+    }
+
+    async fn execute_request(&self, session: &WebSocketSessionShared<Self>, app: &AppStateShared, mut request: RpcRequest) -> Result<Option<Value>, RpcResponseError> {
+        // Redirect all node methods to the node method handler
+        if request.method.starts_with("node.") {
+            // Remove the 5 first chars (node.)
+            request.method = request.method[5..].into();
+            return self.handler.get_data().call_node_with(request).await.map(|v| Some(v))
+        }
+
+        // Verify that the method start with "wallet."
+        if !request.method.starts_with("wallet.") {
+            return Err(RpcResponseError::new(request.id, InternalRpcError::MethodNotFound(request.method)))
+        }
+        request.method = request.method[7..].into();
+
+        // Verify first if the method exist (and that its not a built-in one)
+        let is_subscribe = request.method == "subscribe";
+        let is_unsubscribe = request.method == "unsubscribe";
+        if !self.handler.has_method(&request.method) && !is_subscribe && !is_unsubscribe {
+            return Err(RpcResponseError::new(request.id, InternalRpcError::MethodNotFound(request.method)))
+        }
+
+        // let's check the permission set by user for this method
+        app.set_requesting(true);
+        self.verify_permission_for_request(app, &request).await?;
+        app.set_requesting(false);
 
         if is_subscribe || is_unsubscribe {
             // retrieve the event variant
@@ -14,6 +14,7 @@ use xelis_common::crypto::{
 use crate::{wallet::WalletError, config::SALT_SIZE};
 
 
+#[derive(Clone)]
 pub struct Cipher {
     cipher: XChaCha20Poly1305,
     // this salt is used for keys and values
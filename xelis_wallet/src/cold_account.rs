@@ -0,0 +1,142 @@
+use indexmap::IndexMap;
+use log::{debug, trace};
+use xelis_common::{
+    api::wallet::UnsignedTransaction,
+    crypto::{
+        elgamal::PublicKey as DecompressedPublicKey,
+        Address,
+        Hashable,
+        SIGNATURE_SIZE
+    },
+    network::Network,
+    serializer::Serializer,
+    transaction::{
+        builder::{FeeBuilder, TransactionTypeBuilder},
+        Reference,
+        Transaction
+    }
+};
+use crate::{
+    daemon_api::DaemonAPI,
+    wallet::WalletError
+};
+
+// Online side of the offline signing workflow
+// It follows an account whose private key is kept on an offline wallet using only its address:
+// everything is read from the daemon, so it can neither decrypt nor sign anything
+pub struct ColdAccount {
+    address: Address,
+    // Decompressed once to verify the signed transactions
+    public_key: DecompressedPublicKey,
+    network: Network
+}
+
+impl ColdAccount {
+    pub fn new(address: Address, network: Network) -> Result<Self, WalletError> {
+        if !address.is_normal() || address.is_mainnet() != network.is_mainnet() {
+            return Err(WalletError::InvalidAddressParams)
+        }
+
+        let public_key = address.get_public_key().decompress()
+            .map_err(|e| WalletError::Any(e.into()))?;
+
+        Ok(Self {
+            address,
+            public_key,
+            network
+        })
+    }
+
+    pub fn get_address(&self) -> &Address {
+        &self.address
+    }
+
+    // Create a transaction request to be built and signed by the offline wallet
+    // The TXs of the account still in mempool are taken into account
+    pub async fn create_unsigned_transaction(&self, api: &DaemonAPI, transaction_type: TransactionTypeBuilder, fee: FeeBuilder) -> Result<UnsignedTransaction, WalletError> {
+        trace!("create unsigned transaction for {}", self.address);
+        // The daemon returns an error if the account has no TX in mempool
+        let mempool = api.get_mempool_cache(&self.address).await.ok();
+
+        let mut balances = IndexMap::new();
+        for asset in transaction_type.used_assets() {
+            trace!("Fetching balance for asset {}", asset);
+            let ciphertext = match mempool.as_ref().and_then(|cache| cache.get_balances().get(&asset)) {
+                Some(balance) => balance.compress().into_owned(),
+                None => api.get_balance(&self.address, &asset).await
+                    .map_err(|_| WalletError::BalanceNotFound(asset.clone()))?
+                    .version
+                    .take_balance()
+                    .compress()
+                    .into_owned()
+            };
+            balances.insert(asset, ciphertext);
+        }
+
+        let nonce = match mempool.as_ref() {
+            Some(cache) => cache.get_max() + 1,
+            None => api.get_nonce(&self.address).await?.version.get_nonce()
+        };
+
+        // The offline wallet can't search the registered keys itself
+        let mut registered_keys = Vec::new();
+        if let FeeBuilder::Multiplier(_) = fee {
+            for key in transaction_type.used_keys() {
+                let addr = key.to_address(self.network.is_mainnet());
+                trace!("Checking if {} is registered in stable height", addr);
+                if api.is_account_registered(&addr, true).await? {
+                    registered_keys.push(addr);
+                }
+            }
+        }
+
+        // Fetched after the balances so the reference is never older than them
+        let info = api.get_info().await?;
+        Ok(UnsignedTransaction {
+            source: self.address.clone(),
+            network: self.network,
+            tx_type: transaction_type,
+            fee,
+            reference: Reference {
+                topoheight: info.topoheight,
+                hash: info.top_block_hash
+            },
+            nonce,
+            balances,
+            registered_keys
+        })
+    }
+
+    // Verify a transaction signed by the offline wallet and submit it to the network
+    // It must use the next nonce of the account
+    pub async fn submit_signed_transaction(&self, api: &DaemonAPI, transaction: &Transaction) -> Result<(), WalletError> {
+        trace!("submit signed transaction");
+        let nonce = match api.get_mempool_cache(&self.address).await {
+            Ok(cache) => cache.get_max() + 1,
+            Err(_) => api.get_nonce(&self.address).await?.version.get_nonce()
+        };
+
+        self.verify_signed_transaction(transaction, nonce)?;
+        debug!("Submitting transaction {} signed by {}", transaction.hash(), self.address);
+        api.submit_transaction(transaction).await?;
+        Ok(())
+    }
+
+    // Verify that a transaction is signed by this account and uses the expected nonce
+    pub fn verify_signed_transaction(&self, transaction: &Transaction, nonce: u64) -> Result<(), WalletError> {
+        if transaction.get_source() != self.address.get_public_key() {
+            return Err(WalletError::InvalidSignedTransactionSource)
+        }
+
+        let bytes = transaction.to_bytes();
+        if !transaction.get_signature().verify(&bytes[..bytes.len() - SIGNATURE_SIZE], &self.public_key) {
+            return Err(WalletError::InvalidSignature)
+        }
+
+        if transaction.get_nonce() != nonce {
+            return Err(WalletError::InvalidSignedTransactionNonce(transaction.get_nonce(), nonce))
+        }
+
+        Ok(())
+    }
+}
@@ -11,6 +11,7 @@ use xelis_common::{
         JsonRPCResult,
         EventReceiver
     },
+    tls::TlsClientConfig,
     api::daemon::{
         GetBalanceResult,
         GetBalanceAtTopoHeightParams,
@@ -20,6 +21,7 @@ use xelis_common::{
         BlockResponse,
         GetBlockAtTopoHeightParams,
         GetTransactionParams,
+        GetTransactionsParams,
         GetNonceParams,
         GetNonceResult,
         GetAssetsParams,
@@ -54,7 +56,12 @@ pub struct DaemonAPI {
 
 impl DaemonAPI {
     pub async fn new(daemon_address: String) -> Result<Self> {
-        let client = WebSocketJsonRPCClientImpl::new(daemon_address).await?;
+        Self::new_with_tls(daemon_address, None).await
+    }
+
+    // Connect to the daemon using a custom TLS configuration, for example to trust a self-signed certificate
+    pub async fn new_with_tls(daemon_address: String, tls_config: Option<TlsClientConfig>) -> Result<Self> {
+        let client = WebSocketJsonRPCClientImpl::new_with_tls(daemon_address, tls_config).await?;
         Ok(Self {
             client
         })
@@ -188,6 +195,14 @@ impl DaemonAPI {
         Ok(tx)
     }
 
+    // Transactions unknown by the daemon are returned as None instead of an error
+    pub async fn get_transactions(&self, hashes: Vec<Hash>) -> Result<Vec<Option<Transaction>>> {
+        let txs = self.client.call_with("get_transactions", &GetTransactionsParams {
+            tx_hashes: hashes
+        }).await.context("Error while fetching transactions")?;
+        Ok(txs)
+    }
+
     pub async fn submit_transaction(&self, transaction: &Transaction) -> Result<()> {
         let _: bool = self.client.call_with("submit_transaction", &SubmitTransactionParams {
             data: transaction.to_hex()
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use xelis_common::{
     api::{
         DataElement,
@@ -319,7 +320,13 @@ impl TransactionEntry {
         }
     }
 
-    pub fn summary(&self, mainnet: bool, storage: &EncryptedStorage) -> Result<String> {
+    // Counterparties found in the contacts are shown with their name
+    pub fn summary(&self, mainnet: bool, storage: &EncryptedStorage, contacts: &HashMap<PublicKey, String>) -> Result<String> {
+        let display = |key: &PublicKey| match contacts.get(key) {
+            Some(name) => name.clone(),
+            None => key.as_address(mainnet).to_string()
+        };
+
         let entry_str = match self.get_entry() {
             EntryData::Coinbase { reward } => format!("Coinbase {} XELIS", format_xelis(*reward)),
             EntryData::Burn { asset, amount } => {
@@ -330,10 +337,10 @@ impl TransactionEntry {
                 let mut str = String::new();
                 for transfer in transfers {
                     if *transfer.get_asset() == XELIS_ASSET {
-                        str.push_str(&format!("Received {} XELIS from {}", format_xelis(transfer.get_amount()), from.as_address(mainnet)));
+                        str.push_str(&format!("Received {} XELIS from {}", format_xelis(transfer.get_amount()), display(from)));
                     } else {
                         let decimals = storage.get_asset_decimals(transfer.get_asset())?;
-                        str.push_str(&format!("Received {} {} from {}", format_coin(transfer.get_amount(), decimals), transfer.get_asset(), from.as_address(mainnet)));
+                        str.push_str(&format!("Received {} {} from {}", format_coin(transfer.get_amount(), decimals), transfer.get_asset(), display(from)));
                     }
                 }
                 str
@@ -342,10 +349,10 @@ impl TransactionEntry {
                 let mut str = format!("Fee: {}, Nonce: {} ", format_xelis(*fee), nonce);
                 for transfer in transfers {
                     if *transfer.get_asset() == XELIS_ASSET {
-                        str.push_str(&format!("Sent {} XELIS to {}", format_xelis(transfer.get_amount()), transfer.get_destination().as_address(mainnet)));
+                        str.push_str(&format!("Sent {} XELIS to {}", format_xelis(transfer.get_amount()), display(transfer.get_destination())));
                     } else {
                         let decimals = storage.get_asset_decimals(transfer.get_asset())?;
-                        str.push_str(&format!("Sent {} {} to {}", format_coin(transfer.get_amount(), decimals), transfer.get_asset(), transfer.get_destination().as_address(mainnet)));
+                        str.push_str(&format!("Sent {} {} to {}", format_coin(transfer.get_amount(), decimals), transfer.get_asset(), display(transfer.get_destination())));
                     }
                 }
                 str
@@ -7,6 +7,8 @@ pub mod network_handler;
 pub mod entry;
 pub mod mnemonics;
 pub mod transaction_builder;
+pub mod payout;
+pub mod cold_account;
 
 #[cfg(feature = "api_server")]
 pub mod api;
\ No newline at end of file
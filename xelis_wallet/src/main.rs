@@ -1,15 +1,19 @@
 use std::{
+    fs,
     ops::ControlFlow,
     path::Path,
     sync::Arc,
     time::Duration
 };
 use anyhow::{Result, Context};
+use indexmap::IndexMap;
 use fern::colors::Color;
 use log::{error, info};
 use clap::Parser;
 use xelis_common::{
+    api::wallet::{PayoutEntry, PayoutStatus, UnsignedTransaction},
     async_handler,
+    context::Context as CommandContext,
     config::{
         COIN_DECIMALS,
         VERSION,
@@ -44,14 +48,19 @@ use xelis_common::{
         BurnPayload,
         Transaction
     },
+    tls::load_client_config,
     utils::{
         format_coin,
-        format_xelis
+        format_xelis,
+        sanitize_daemon_address
     }
 };
 use xelis_wallet::{
     wallet::Wallet,
-    config::{DEFAULT_DAEMON_ADDRESS, DIR_PATH}
+    cold_account::ColdAccount,
+    config::{DEFAULT_DAEMON_ADDRESS, DIR_PATH},
+    daemon_api::DaemonAPI,
+    payout
 };
 
 #[cfg(feature = "api_server")]
@@ -65,7 +74,7 @@ use {
         wallet::XSWDEvent,
     },
     xelis_common::{
-        rpc_server::RpcRequest,
+        rpc_server::{RpcRequest, DEFAULT_MAX_BATCH_SIZE},
         prompt::{
             ShareablePrompt,
             colorize_string,
@@ -90,7 +99,18 @@ pub struct RPCConfig {
     rpc_username: Option<String>,
     /// password for RPC authentication
     #[clap(long)]
-    rpc_password: Option<String>
+    rpc_password: Option<String>,
+    /// Maximum number of requests allowed in a JSON-RPC batch
+    #[clap(long, default_value_t = DEFAULT_MAX_BATCH_SIZE)]
+    rpc_max_batch_size: usize,
+    /// PEM certificate file to serve the RPC Server or XSWD over TLS.
+    /// 
+    /// It is reloaded with the private key when the process receives a SIGHUP.
+    #[clap(long)]
+    rpc_tls_cert: Option<String>,
+    /// PEM private key file of the TLS certificate
+    #[clap(long)]
+    rpc_tls_key: Option<String>
 }
 
 #[derive(Parser)]
@@ -100,6 +120,11 @@ pub struct Config {
     /// Daemon address to use
     #[clap(long, default_value_t = String::from(DEFAULT_DAEMON_ADDRESS))]
     daemon_address: String,
+    /// PEM file of the CA certificates trusted to connect to the daemon over TLS.
+    /// 
+    /// This allows to connect to a daemon using a self-signed certificate.
+    #[clap(long)]
+    daemon_ca_bundle: Option<String>,
     /// Disable online mode
     #[clap(long)]
     offline_mode: bool,
@@ -184,6 +209,12 @@ async fn main() -> Result<()> {
             error!("Invalid parameters configuration: usernamd AND password must be provided");
             return Ok(())
         }
+
+        // check that TLS certificate and private key are set together
+        if config.rpc.rpc_tls_cert.is_some() != config.rpc.rpc_tls_key.is_some() {
+            error!("Invalid parameters configuration: TLS certificate AND private key must be provided");
+            return Ok(())
+        }
     }
 
     let command_manager = CommandManager::new(prompt.clone());
@@ -191,6 +222,10 @@ async fn main() -> Result<()> {
 
     command_manager.register_default_commands()?;
 
+    // Online side of the offline signing, only the address of the offline wallet is used
+    command_manager.add_command(Command::with_required_arguments("unsigned_transfer", "Create a transfer from an address to be signed by its offline wallet", vec![Arg::new("address", ArgType::String), Arg::new("filename", ArgType::String)], CommandHandler::Async(async_handler!(unsigned_transfer))))?;
+    command_manager.add_command(Command::with_required_arguments("submit_transaction", "Submit a transaction signed by an offline wallet", vec![Arg::new("filename", ArgType::String)], CommandHandler::Async(async_handler!(submit_transaction))))?;
+
     if let Some(path) = config.wallet_path {
         // read password from option or ask him
         let password = if let Some(password) = config.password {
@@ -305,6 +340,13 @@ async fn xswd_handle_request_permission(prompt: &ShareablePrompt, app_state: App
 async fn apply_config(wallet: &Arc<Wallet>, #[cfg(feature = "api_server")] prompt: &ShareablePrompt) {
     let config: Config = Config::parse();
 
+    if let Some(path) = config.daemon_ca_bundle.as_ref() {
+        match load_client_config(path) {
+            Ok(tls_config) => wallet.set_daemon_tls_config(Some(tls_config)).await,
+            Err(e) => error!("Error while loading daemon CA bundle: {:#}", e)
+        };
+    }
+
     if !config.offline_mode {
         info!("Trying to connect to daemon at '{}'", config.daemon_address);
         if let Err(e) = wallet.set_online_mode(&config.daemon_address).await {
@@ -322,6 +364,8 @@ async fn apply_config(wallet: &Arc<Wallet>, #[cfg(feature = "api_server")] promp
             return;
         }
 
+        let tls = config.rpc.rpc_tls_cert.zip(config.rpc.rpc_tls_key);
+
         if let Some(address) = config.rpc.rpc_bind_address {
             let auth_config = if let (Some(username), Some(password)) = (config.rpc.rpc_username, config.rpc.rpc_password) {
                 Some(AuthConfig {
@@ -333,11 +377,11 @@ async fn apply_config(wallet: &Arc<Wallet>, #[cfg(feature = "api_server")] promp
             };
 
             info!("Enabling RPC Server on {} {}", address, if auth_config.is_some() { "with authentication" } else { "without authentication" });
-            if let Err(e) = wallet.enable_rpc_server(address, auth_config).await {
+            if let Err(e) = wallet.enable_rpc_server(address, auth_config, config.rpc.rpc_max_batch_size, tls).await {
                 error!("Error while enabling RPC Server: {}", e);
             }
         } else if config.enable_xswd {
-            match wallet.enable_xswd().await {
+            match wallet.enable_xswd(tls).await {
                 Ok(receiver) => {
                     // Only clone when its necessary
                     let prompt = prompt.clone();
@@ -360,6 +404,8 @@ async fn setup_wallet_command_manager(wallet: Arc<Wallet>, command_manager: &Com
     command_manager.add_command(Command::new("change_password", "Set a new password to open your wallet", CommandHandler::Async(async_handler!(change_password))))?;
     command_manager.add_command(Command::with_optional_arguments("transfer", "Send asset to a specified address", vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(transfer))))?;
     command_manager.add_command(Command::with_optional_arguments("transfer_all", "Send all your asset balance to a specified address", vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(transfer_all))))?;
+    command_manager.add_command(Command::with_required_arguments("sign_transaction", "Sign a transaction created by an online wallet", vec![Arg::new("input", ArgType::String), Arg::new("output", ArgType::String)], CommandHandler::Async(async_handler!(sign_transaction))))?;
+    command_manager.add_command(Command::with_required_arguments("batch_payout", "Pay all the recipients of a CSV or JSON file", vec![Arg::new("filename", ArgType::String)], CommandHandler::Async(async_handler!(batch_payout))))?;
     command_manager.add_command(Command::with_required_arguments("burn", "Burn amount of asset", vec![Arg::new("asset", ArgType::Hash), Arg::new("amount", ArgType::Number)], CommandHandler::Async(async_handler!(burn))))?;
     command_manager.add_command(Command::new("display_address", "Show your wallet address", CommandHandler::Async(async_handler!(display_address))))?;
     command_manager.add_command(Command::with_optional_arguments("balance", "List all non-zero balances or show the selected one", vec![Arg::new("asset", ArgType::Hash)], CommandHandler::Async(async_handler!(balance))))?;
@@ -369,6 +415,11 @@ async fn setup_wallet_command_manager(wallet: Arc<Wallet>, command_manager: &Com
     command_manager.add_command(Command::with_optional_arguments("rescan", "Rescan balance and transactions", vec![Arg::new("topoheight", ArgType::Number)], CommandHandler::Async(async_handler!(rescan))))?;
     command_manager.add_command(Command::with_optional_arguments("seed", "Show seed of selected language", vec![Arg::new("language", ArgType::Number)], CommandHandler::Async(async_handler!(seed))))?;
     command_manager.add_command(Command::new("nonce", "Show current nonce", CommandHandler::Async(async_handler!(nonce))))?;
+    command_manager.add_command(Command::new("accounts", "List the accounts derived from your seed", CommandHandler::Async(async_handler!(accounts))))?;
+    command_manager.add_command(Command::new("create_account", "Create a new account derived from your seed", CommandHandler::Async(async_handler!(create_account))))?;
+    command_manager.add_command(Command::with_required_arguments("label_account", "Set the label of an account", vec![Arg::new("account", ArgType::Number)], CommandHandler::Async(async_handler!(label_account))))?;
+    command_manager.add_command(Command::with_required_arguments("switch_account", "Select the account used by the wallet commands", vec![Arg::new("account", ArgType::Number)], CommandHandler::Async(async_handler!(switch_account))))?;
+    command_manager.add_command(Command::with_arguments("contacts", "Manage your address book: add, list or remove a contact", vec![Arg::new("action", ArgType::String)], vec![Arg::new("name", ArgType::String)], CommandHandler::Async(async_handler!(contacts))))?;
     command_manager.add_command(Command::new("set_nonce", "Set new nonce", CommandHandler::Async(async_handler!(set_nonce))))?;
 
     #[cfg(feature = "api_server")]
@@ -388,20 +439,37 @@ async fn setup_wallet_command_manager(wallet: Arc<Wallet>, command_manager: &Com
 
     let mut context = command_manager.get_context().lock()?;
     context.store(wallet);
+    context.store(SelectedAccount(0));
 
     command_manager.display_commands()
 }
 
+// Account selected in the CLI with the switch_account command
+// RPC and XSWD clients select their account in each request
+#[derive(Clone, Copy)]
+struct SelectedAccount(u32);
+
+// Get the wallet of the account selected in the CLI
+async fn get_selected_account(context: &CommandContext) -> Result<Arc<Wallet>, CommandError> {
+    let wallet: &Arc<Wallet> = context.get()?;
+    let index = context.get_optional::<SelectedAccount>().map(|selected| selected.0).unwrap_or(0);
+    let account = wallet.get_account(index).await.context("Error while retrieving selected account")?;
+    Ok(account)
+}
+
 // Function passed as param to prompt to build the prompt message shown
 async fn prompt_message_builder(_: &Prompt, command_manager: Option<&CommandManager>) -> Result<String, PromptError> {
     if let Some(manager) = command_manager {
         let context = manager.get_context().lock()?;
-        if let Ok(wallet) = context.get::<Arc<Wallet>>() {
+        if let Ok(wallet) = get_selected_account(&context).await {
             let network = wallet.get_network();
 
             let addr_str = {
                 let addr = &wallet.get_address().to_string()[..8];
-                prompt::colorize_str(Color::Yellow, addr)
+                match wallet.get_account_index() {
+                    0 => prompt::colorize_str(Color::Yellow, addr),
+                    account => prompt::colorize_string(Color::Yellow, &format!("#{} {}", account, addr))
+                }
             };
     
             let storage = wallet.get_storage().read().await;
@@ -618,14 +686,27 @@ async fn change_password(manager: &CommandManager, _: ArgumentManager) -> Result
 async fn transfer(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let prompt = manager.get_prompt();
     let context = manager.get_context().lock()?;
-    let wallet: &Arc<Wallet> = context.get()?;
+    let wallet = &get_selected_account(&context).await?;
 
-    // read address
-    let str_address = prompt.read_input(
-        prompt::colorize_str(Color::Green, "Address: "),
-        false
-    ).await.context("Error while reading address")?;
-    let address = Address::from_string(&str_address).context("Invalid address")?;
+    let transfer = read_transfer(manager, wallet).await?;
+    if !prompt.ask_confirmation().await.context("Error while confirming action")? {
+        manager.message("Transaction has been aborted");
+        return Ok(())
+    }
+
+    manager.message("Building transaction...");
+
+    let tx = wallet.create_transaction(TransactionTypeBuilder::Transfers(vec![transfer]), FeeBuilder::default()).await
+        .context("Error while creating transaction")?;
+
+    broadcast_tx(wallet, manager, tx).await;
+    Ok(())
+}
+
+// Read the destination, asset and amount of a transfer
+async fn read_transfer(manager: &CommandManager, wallet: &Wallet) -> Result<TransferBuilder, CommandError> {
+    let prompt = manager.get_prompt();
+    let address = read_address(manager, wallet).await?;
 
     let asset = prompt.read_hash(
         prompt::colorize_str(Color::Green, "Asset (default XELIS): ")
@@ -648,39 +729,228 @@ async fn transfer(manager: &CommandManager, _: ArgumentManager) -> Result<(), Co
     let amount = (float_amount * 10u32.pow(decimals as u32) as f64) as u64;
     manager.message(format!("Sending {} of {} to {}", format_coin(amount, decimals), asset, address.to_string()));
 
+    Ok(TransferBuilder {
+        destination: address,
+        amount,
+        asset,
+        extra_data: None
+    })
+}
+
+// Read an address or the name of a contact
+async fn read_address(manager: &CommandManager, wallet: &Wallet) -> Result<Address, CommandError> {
+    let input = manager.get_prompt().read_input(
+        prompt::colorize_str(Color::Green, "Address or contact: "),
+        false
+    ).await.context("Error while reading address")?;
+
+    match Address::from_string(&input) {
+        Ok(address) => Ok(address),
+        Err(_) => {
+            let address = wallet.get_contact(&input).await.context("Invalid address or unknown contact")?;
+            manager.message(format!("Contact {}: {}", input, address));
+            Ok(address)
+        }
+    }
+}
+
+// Connect to the daemon set in the config
+// Used by the commands that don't need a wallet
+async fn connect_to_daemon() -> Result<DaemonAPI, CommandError> {
+    let config: Config = Config::parse();
+    let tls_config = match config.daemon_ca_bundle.as_ref() {
+        Some(path) => Some(load_client_config(path).context("Error while loading daemon CA bundle")?),
+        None => None
+    };
+
+    let api = DaemonAPI::new_with_tls(format!("{}/json_rpc", sanitize_daemon_address(&config.daemon_address)), tls_config).await
+        .context("Couldn't connect to daemon")?;
+    Ok(api)
+}
+
+// Create a transfer request to be signed by the offline wallet of an address
+// Only the address is known here, its balances and nonce are read from the daemon
+async fn unsigned_transfer(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let address = arguments.get_value("address")?.to_string_value()?;
+    let filename = arguments.get_value("filename")?.to_string_value()?;
+    let prompt = manager.get_prompt();
+    let network = {
+        let context = manager.get_context().lock()?;
+        *context.get::<Network>()?
+    };
+
+    let source = Address::from_string(&address).context("Invalid address")?;
+    let account = ColdAccount::new(source, network).context("Invalid address")?;
+    let api = connect_to_daemon().await?;
+
+    let destination = prompt.read_input(prompt::colorize_str(Color::Green, "Destination address: "), false)
+        .await.context("Error while reading address")?;
+    let destination = Address::from_string(&destination).context("Invalid destination address")?;
+
+    let asset = prompt.read_hash(
+        prompt::colorize_str(Color::Green, "Asset (default XELIS): ")
+    ).await.ok();
+
+    let asset = asset.unwrap_or(XELIS_ASSET);
+    let decimals = api.get_asset(&asset).await.context("Error while retrieving asset")?.get_decimals();
+
+    let float_amount: f64 = prompt.read(
+        prompt::colorize_str(Color::Green, "Amount: ")
+    ).await.context("Error while reading amount")?;
+
+    let amount = (float_amount * 10u32.pow(decimals as u32) as f64) as u64;
+    manager.message(format!("Sending {} of {} to {}", format_coin(amount, decimals), asset, destination));
+
     if !prompt.ask_confirmation().await.context("Error while confirming action")? {
         manager.message("Transaction has been aborted");
         return Ok(())
     }
 
-    manager.message("Building transaction...");
-
     let transfer = TransferBuilder {
-        destination: address,
+        destination,
         amount,
         asset,
         extra_data: None
     };
-    let tx = wallet.create_transaction(TransactionTypeBuilder::Transfers(vec![transfer]), FeeBuilder::default()).await
-        .context("Error while creating transaction")?;
+    let unsigned = account.create_unsigned_transaction(&api, TransactionTypeBuilder::Transfers(vec![transfer]), FeeBuilder::default()).await
+        .context("Error while creating unsigned transaction")?;
 
-    broadcast_tx(wallet, manager, tx).await;
+    let content = serde_json::to_string_pretty(&unsigned).context("Error while serializing unsigned transaction")?;
+    fs::write(&filename, content).context("Error while writing unsigned transaction")?;
+    manager.message(format!("Unsigned transaction written to {}, sign it with the command 'sign_transaction' on your offline wallet", filename));
+    Ok(())
+}
+
+// Build and sign a transaction request created on an online machine
+async fn sign_transaction(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let input = arguments.get_value("input")?.to_string_value()?;
+    let output = arguments.get_value("output")?.to_string_value()?;
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet = &get_selected_account(&context).await?;
+
+    let content = fs::read_to_string(&input).context("Error while reading unsigned transaction")?;
+    let unsigned: UnsignedTransaction = serde_json::from_str(&content).context("Invalid unsigned transaction")?;
+
+    // Show what will be signed
+    {
+        let storage = wallet.get_storage().read().await;
+        match &unsigned.tx_type {
+            TransactionTypeBuilder::Transfers(transfers) => for transfer in transfers {
+                let decimals = storage.get_asset_decimals(&transfer.asset).unwrap_or(COIN_DECIMALS);
+                manager.message(format!("Transfer {} of {} to {}", format_coin(transfer.amount, decimals), transfer.asset, transfer.destination));
+            },
+            TransactionTypeBuilder::Burn(payload) => {
+                let decimals = storage.get_asset_decimals(&payload.asset).unwrap_or(COIN_DECIMALS);
+                manager.message(format!("Burn {} of {}", format_coin(payload.amount, decimals), payload.asset));
+            }
+        };
+        manager.message(format!("Nonce: {}, reference topoheight: {}", unsigned.nonce, unsigned.reference.topoheight));
+    }
+
+    if !prompt.ask_confirmation().await.context("Error while confirming action")? {
+        manager.message("Signing has been aborted");
+        return Ok(())
+    }
+
+    let tx = wallet.sign_unsigned_transaction(unsigned).await
+        .context("Error while signing transaction")?;
+
+    fs::write(&output, tx.to_hex()).context("Error while writing signed transaction")?;
+    manager.message(format!("Transaction {} (fees: {}) written to {}, submit it with the command 'submit_transaction' on your online machine", tx.hash(), format_xelis(tx.get_fee()), output));
+    Ok(())
+}
+
+// Verify a transaction signed by an offline wallet and broadcast it
+async fn submit_transaction(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let filename = arguments.get_value("filename")?.to_string_value()?;
+    let network = {
+        let context = manager.get_context().lock()?;
+        *context.get::<Network>()?
+    };
+
+    let content = fs::read_to_string(&filename).context("Error while reading signed transaction")?;
+    let tx = Transaction::from_hex(content.trim().to_owned()).context("Invalid signed transaction")?;
+    let account = ColdAccount::new(tx.get_source().as_address(network.is_mainnet()), network)
+        .context("Invalid transaction source")?;
+
+    let api = connect_to_daemon().await?;
+    account.submit_signed_transaction(&api, &tx).await
+        .context("Couldn't submit transaction")?;
+
+    manager.message(format!("Transaction {} submitted successfully!", tx.hash()));
     Ok(())
 }
 
+// Pay all the recipients of a file using as few TXs as possible
+// A `.json` file contains a list of entries, any other file is read as CSV
+async fn batch_payout(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let filename = arguments.get_value("filename")?.to_string_value()?;
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet = &get_selected_account(&context).await?;
+
+    let content = fs::read_to_string(&filename).context("Error while reading payout file")?;
+    let entries: Vec<PayoutEntry> = if filename.ends_with(".json") {
+        serde_json::from_str(&content).context("Invalid payout file")?
+    } else {
+        payout::parse_payout_csv(&content).context("Invalid payout file")?
+    };
+
+    // Show what will be sent
+    {
+        let storage = wallet.get_storage().read().await;
+        let mut totals: IndexMap<_, u64> = IndexMap::new();
+        for entry in entries.iter() {
+            let total = totals.entry(&entry.asset).or_insert(0);
+            *total = total.saturating_add(entry.amount);
+        }
+
+        for (asset, total) in totals {
+            let decimals = storage.get_asset_decimals(asset).unwrap_or(COIN_DECIMALS);
+            manager.message(format!("Total of {}: {}", asset, format_coin(total, decimals)));
+        }
+
+        let mainnet = wallet.get_network().is_mainnet();
+        let txs = payout::pack_payout_entries(entries.iter()
+            .enumerate()
+            .filter(|(_, entry)| payout::validate_payout_entry(entry, wallet.get_public_key(), mainnet).is_ok())
+        );
+        manager.message(format!("Paying {} recipients in {} transactions", entries.len(), txs.len()));
+    }
+
+    if !prompt.ask_confirmation().await.context("Error while confirming action")? {
+        manager.message("Batch payout has been aborted");
+        return Ok(())
+    }
+
+    manager.message("Sending transactions...");
+    let report = match wallet.batch_payout(entries, FeeBuilder::default()).await {
+        Ok(report) => report,
+        Err(e) => {
+            manager.error(format!("Batch payout stopped: {:#}", e));
+            manager.message("Run this command again with the same file to resume it");
+            return Ok(())
+        }
+    };
+
+    let output = format!("{}.report.json", filename);
+    let content = serde_json::to_string_pretty(&report).context("Error while serializing payout report")?;
+    fs::write(&output, content).context("Error while writing payout report")?;
+
+    let failed = report.recipients.iter()
+        .filter(|recipient| matches!(recipient.status, PayoutStatus::Failed { .. }))
+        .count();
+    manager.message(format!("Batch payout {} done: {} recipients paid, {} failed, report written to {}", report.id, report.recipients.len() - failed, failed, output));
+    Ok(())
+}
 
 // Send the whole balance to a specified address
 async fn transfer_all(manager: &CommandManager, mut args: ArgumentManager) -> Result<(), CommandError> {
     let prompt = manager.get_prompt();
     let context = manager.get_context().lock()?;
-    let wallet: &Arc<Wallet> = context.get()?;
-
-    // read address
-    let str_address = prompt.read_input(
-        prompt::colorize_str(Color::Green, "Address: "),
-        false
-    ).await.context("Error while reading address")?;
-    let address = Address::from_string(&str_address).context("Invalid address")?;
+    let wallet = &get_selected_account(&context).await?;
+    let address = read_address(manager, wallet).await?;
 
     let mut asset = args.get_value("asset").and_then(|v| v.to_hash()).ok();
     if asset.is_none() {
@@ -738,7 +1008,7 @@ async fn burn(manager: &CommandManager, mut arguments: ArgumentManager) -> Resul
     let amount = arguments.get_value("amount")?.to_number()?;
     let asset = arguments.get_value("asset")?.to_hash()?;
     let context = manager.get_context().lock()?;
-    let wallet: &Arc<Wallet> = context.get()?;
+    let wallet = &get_selected_account(&context).await?;
     {
         let storage = wallet.get_storage().read().await;
         let decimals = storage.get_asset_decimals(&asset).unwrap_or(COIN_DECIMALS);
@@ -759,7 +1029,7 @@ async fn burn(manager: &CommandManager, mut arguments: ArgumentManager) -> Resul
 // Show current wallet address
 async fn display_address(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
-    let wallet: &Arc<Wallet> = context.get()?;
+    let wallet = &get_selected_account(&context).await?;
     manager.message(format!("Wallet address: {}", wallet.get_address()));
     Ok(())
 }
@@ -767,7 +1037,7 @@ async fn display_address(manager: &CommandManager, _: ArgumentManager) -> Result
 // Show current balance for specified asset or list all non-zero balances
 async fn balance(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
-    let wallet: &Arc<Wallet> = context.get()?;
+    let wallet = &get_selected_account(&context).await?;
     let storage = wallet.get_storage().read().await;
 
     if arguments.has_argument("asset") {
@@ -801,7 +1071,7 @@ async fn history(manager: &CommandManager, mut arguments: ArgumentManager) -> Re
     }
 
     let context = manager.get_context().lock()?;
-    let wallet: &Arc<Wallet> = context.get()?;
+    let wallet = &get_selected_account(&context).await?;
     let storage = wallet.get_storage().read().await;
     let mut transactions = storage.get_transactions()?;
 
@@ -822,9 +1092,11 @@ async fn history(manager: &CommandManager, mut arguments: ArgumentManager) -> Re
         return Err(CommandError::InvalidArgument(format!("Page must be less than maximum pages ({})", max_pages - 1)));
     }
 
+    let contacts = storage.get_contact_names()?;
+
     manager.message(format!("Transactions (total {}) page {}/{}:", transactions.len(), page, max_pages));
     for tx in transactions.iter().skip((page - 1) * TXS_PER_PAGE).take(TXS_PER_PAGE) {
-        manager.message(format!("- {}", tx.summary(wallet.get_network().is_mainnet(), &*storage)?));
+        manager.message(format!("- {}", tx.summary(wallet.get_network().is_mainnet(), &*storage, &contacts)?));
     }
 
     Ok(())
@@ -903,18 +1175,135 @@ async fn seed(manager: &CommandManager, mut arguments: ArgumentManager) -> Resul
 
 async fn nonce(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
-    let wallet: &Arc<Wallet> = context.get()?;
+    let wallet = &get_selected_account(&context).await?;
     let nonce = wallet.get_nonce().await;
     manager.message(format!("Nonce: {}", nonce));
     Ok(())
 }
 
+// List the accounts derived from the seed
+async fn accounts(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let selected = context.get::<SelectedAccount>()?.0;
+    for account in wallet.get_accounts().await.context("Error while retrieving accounts")? {
+        let line = format!("#{} {} {}", account.index, account.address, account.label);
+        if account.index == selected {
+            manager.message(prompt::colorize_string(Color::Green, &format!("{} (selected)", line)));
+        } else {
+            manager.message(line);
+        }
+    }
+    Ok(())
+}
+
+// Create a new account derived from the seed
+async fn create_account(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
+    let label = manager.get_prompt().read_input(prompt::colorize_str(Color::Green, "Label: "), false)
+        .await.context("Error while reading label")?;
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    let index = wallet.create_account(label).await.context("Error while creating account")?;
+    manager.message(format!("Account #{} has been created, use 'switch_account {}' to select it in the CLI", index, index));
+    Ok(())
+}
+
+// Set the label of an account
+async fn label_account(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let account = arguments.get_value("account")?.to_number()?;
+    let account = u32::try_from(account).map_err(|_| CommandError::InvalidArgument("Invalid account index".to_string()))?;
+    let label = manager.get_prompt().read_input(prompt::colorize_str(Color::Green, "Label: "), false)
+        .await.context("Error while reading label")?;
+
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    wallet.set_account_label(account, label).await.context("Error while setting account label")?;
+    manager.message(format!("Label of account #{} has been updated", account));
+    Ok(())
+}
+
+// Select the account used by the wallet commands
+async fn switch_account(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let account = arguments.get_value("account")?.to_number()?;
+    let account = u32::try_from(account).map_err(|_| CommandError::InvalidArgument("Invalid account index".to_string()))?;
+
+    let mut context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    // Only verify that the account exists, the selection is local to the CLI
+    wallet.get_account(account).await.context("Error while switching account")?;
+    context.store(SelectedAccount(account));
+    manager.message(format!("Account #{} is now selected", account));
+    Ok(())
+}
+
+// Manage the address book shared by all the accounts
+async fn contacts(manager: &CommandManager, mut arguments: ArgumentManager) -> Result<(), CommandError> {
+    let action = arguments.get_value("action")?.to_string_value()?;
+    let name = if arguments.has_argument("name") {
+        Some(arguments.get_value("name")?.to_string_value()?)
+    } else {
+        None
+    };
+
+    let prompt = manager.get_prompt();
+    let context = manager.get_context().lock()?;
+    let wallet: &Arc<Wallet> = context.get()?;
+    match action.as_str() {
+        "list" => {
+            let contacts = {
+                let storage = wallet.get_storage().read().await;
+                storage.get_contacts()?
+            };
+
+            if contacts.is_empty() {
+                manager.message("No contacts available");
+                return Ok(())
+            }
+
+            manager.message(format!("Contacts (total {}):", contacts.len()));
+            for contact in contacts {
+                manager.message(format!("- {}: {}", contact.name, contact.address));
+            }
+        },
+        "add" => {
+            let name = match name {
+                Some(name) => name,
+                None => prompt.read_input(prompt::colorize_str(Color::Green, "Name: "), false)
+                    .await.context("Error while reading contact name")?
+            };
+
+            let str_address = prompt.read_input(
+                prompt::colorize_str(Color::Green, "Address: "),
+                false
+            ).await.context("Error while reading address")?;
+            let address = Address::from_string(&str_address).context("Invalid address")?;
+
+            wallet.add_contact(name.clone(), address).await.context("Error while adding contact")?;
+            manager.message(format!("Contact {} has been added", name));
+        },
+        "remove" => {
+            let name = match name {
+                Some(name) => name,
+                None => prompt.read_input(prompt::colorize_str(Color::Green, "Name: "), false)
+                    .await.context("Error while reading contact name")?
+            };
+
+            wallet.remove_contact(&name).await.context("Error while removing contact")?;
+            manager.message(format!("Contact {} has been removed", name));
+        },
+        _ => return Err(CommandError::InvalidArgument("Action must be add, list or remove".to_string()))
+    };
+
+    Ok(())
+}
+
 async fn set_nonce(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let value = manager.get_prompt().read("New Nonce: ".to_string()).await
         .context("Error while reading new nonce to set")?;
 
     let context = manager.get_context().lock()?;
-    let wallet: &Arc<Wallet> = context.get()?;
+    let wallet = &get_selected_account(&context).await?;
     let mut storage = wallet.get_storage().write().await;
     storage.set_nonce(value)?;
     manager.message(format!("New nonce is: {}", value));
@@ -943,7 +1332,9 @@ async fn start_rpc_server(manager: &CommandManager, mut arguments: ArgumentManag
         password
     });
 
-    wallet.enable_rpc_server(bind_address, auth_config).await.context("Error while enabling RPC Server")?;
+    // Use the batch size limit set in the launch arguments
+    let config: Config = Config::parse();
+    wallet.enable_rpc_server(bind_address, auth_config, config.rpc.rpc_max_batch_size, None).await.context("Error while enabling RPC Server")?;
     manager.message("RPC Server has been enabled");
     Ok(())
 }
@@ -952,7 +1343,7 @@ async fn start_rpc_server(manager: &CommandManager, mut arguments: ArgumentManag
 async fn start_xswd(manager: &CommandManager, _: ArgumentManager) -> Result<(), CommandError> {
     let context = manager.get_context().lock()?;
     let wallet: &Arc<Wallet> = context.get()?;
-    match wallet.enable_xswd().await {
+    match wallet.enable_xswd(None).await {
         Ok(receiver) => {
             let prompt = manager.get_prompt().clone();
             tokio::spawn(xswd_handler(receiver, prompt));
@@ -26,7 +26,9 @@ use xelis_common::{
         Address,
         Hash
     },
+    json_rpc::JsonRPCError,
     serializer::Serializer,
+    tls::TlsClientConfig,
     utils::sanitize_daemon_address
 };
 use crate::{
@@ -68,20 +70,29 @@ pub struct NetworkHandler {
     // api to communicate with daemon
     // It is behind a Arc to be shared across several wallets
     // in case someone make a custom service and don't want to create a new connection
-    api: Arc<DaemonAPI>
+    api: Arc<DaemonAPI>,
+    // Only a connection created by this handler is closed when it stops
+    // A shared connection stays open for the other wallets using it
+    owns_api: bool
 }
 
 impl NetworkHandler {
     // Create a new network handler with a wallet and a daemon address
     // This will create itself a DaemonAPI and verify if connection is possible
-    pub async fn new<S: ToString>(wallet: Arc<Wallet>, daemon_address: S) -> Result<SharedNetworkHandler, Error> {
+    // A custom TLS configuration can be set to trust a self-signed daemon
+    pub async fn new<S: ToString>(wallet: Arc<Wallet>, daemon_address: S, tls_config: Option<TlsClientConfig>) -> Result<SharedNetworkHandler, Error> {
         let s = daemon_address.to_string();
-        let api = DaemonAPI::new(format!("{}/json_rpc", sanitize_daemon_address(s.as_str()))).await?;
-        Self::with_api(wallet, Arc::new(api)).await
+        let api = DaemonAPI::new_with_tls(format!("{}/json_rpc", sanitize_daemon_address(s.as_str())), tls_config).await?;
+        Self::with_api_internal(wallet, Arc::new(api), true).await
     }
 
     // Create a new network handler with an already created daemon API
+    // The connection is not closed when the handler stops
     pub async fn with_api(wallet: Arc<Wallet>, api: Arc<DaemonAPI>) -> Result<SharedNetworkHandler, Error> {
+        Self::with_api_internal(wallet, api, false).await
+    }
+
+    async fn with_api_internal(wallet: Arc<Wallet>, api: Arc<DaemonAPI>, owns_api: bool) -> Result<SharedNetworkHandler, Error> {
         // check that we can correctly get version from daemon
         let version = api.get_version().await?;
         debug!("Connected to daemon running version {}", version);
@@ -89,7 +100,8 @@ impl NetworkHandler {
         Ok(Arc::new(Self {
             task: Mutex::new(None),
             wallet,
-            api
+            api,
+            owns_api
         }))
     }
 
@@ -117,8 +129,10 @@ impl NetworkHandler {
             }
 
             // Turn off the websocket connection
-            if let Err(e) = zelf.api.disconnect().await {
-                debug!("Error while closing websocket connection: {}", e);
+            if zelf.owns_api {
+                if let Err(e) = zelf.api.disconnect().await {
+                    debug!("Error while closing websocket connection: {}", e);
+                }
             }
 
             // Notify that we are offline
@@ -149,8 +163,10 @@ impl NetworkHandler {
             }
 
             // Turn off the websocket connection
-            if let Err(e) = self.api.disconnect().await {
-                debug!("Error while closing websocket connection: {}", e);
+            if self.owns_api {
+                if let Err(e) = self.api.disconnect().await {
+                    debug!("Error while closing websocket connection: {}", e);
+                }
             }
 
             Ok(())
@@ -164,6 +180,11 @@ impl NetworkHandler {
         &self.api
     }
 
+    // Retrieve the daemon API to share its connection with another wallet
+    pub fn get_shared_api(&self) -> Arc<DaemonAPI> {
+        Arc::clone(&self.api)
+    }
+
     // check if the network handler is running (that we have a task and its not finished)
     pub async fn is_running(&self) -> bool {
         let task = self.task.lock().await;
@@ -759,8 +780,15 @@ impl NetworkHandler {
                 // have reorg the chain
                 res = receiver.next() => {
                     trace!("on_new_block_event");
-                    let event = res?;
-                    self.sync(&address, Some(event)).await?;
+                    match res {
+                        Ok(event) => self.sync(&address, Some(event)).await?,
+                        // Blocks were missed during a reconnection, do a full sync
+                        Err(e) if matches!(e.downcast_ref::<JsonRPCError>(), Some(JsonRPCError::EventsMissed(_))) => {
+                            warn!("{}, syncing again", e);
+                            self.sync(&address, None).await?;
+                        },
+                        Err(e) => return Err(e)
+                    }
                 },
                 // Detect network events
                 res = on_connection.recv() => {
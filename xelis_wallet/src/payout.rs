@@ -0,0 +1,203 @@
+use std::fmt::Display;
+use anyhow::{anyhow, Context, Error};
+use xelis_common::{
+    api::{
+        wallet::{PayoutEntry, PayoutStatus},
+        DataElement,
+        DataValue
+    },
+    config::XELIS_ASSET,
+    crypto::{hash, Address, AddressType, Hash, PublicKey},
+    serializer::Serializer,
+    transaction::{EXTRA_DATA_LIMIT_SIZE, MAX_TRANSFER_COUNT}
+};
+
+// Maximum number of TXs fetched in one get_transactions request to the daemon
+pub const MAX_TXS_PER_REQUEST: usize = 20;
+
+// Bytes added to the extra data of a transfer once encrypted: u16 length and AEAD tag
+const ENCRYPTED_EXTRA_DATA_OVERHEAD: usize = 2 + 16;
+
+// Compute the id of a batch payout from its entries
+pub fn compute_payout_id(entries: &[PayoutEntry]) -> Hash {
+    let mut bytes = Vec::new();
+    for entry in entries {
+        bytes.extend(entry.to_bytes());
+    }
+    hash(&bytes)
+}
+
+// Size of the extra data of an entry once encrypted in the TX
+// The data integrated in the address is sent as extra data
+fn encrypted_extra_data_size(entry: &PayoutEntry) -> usize {
+    let data = match (&entry.extra_data, entry.destination.get_type()) {
+        (Some(data), _) | (None, AddressType::Data(data)) => data,
+        (None, AddressType::Normal) => return 0
+    };
+
+    data.size() + ENCRYPTED_EXTRA_DATA_OVERHEAD
+}
+
+// Verify that an entry can be paid by the wallet
+pub fn validate_payout_entry(entry: &PayoutEntry, source: &PublicKey, mainnet: bool) -> Result<(), &'static str> {
+    if entry.destination.is_mainnet() != mainnet {
+        return Err("Destination is on another network")
+    }
+
+    if entry.destination.get_public_key() == source {
+        return Err("Destination is the wallet itself")
+    }
+
+    if entry.extra_data.is_some() && !entry.destination.is_normal() {
+        return Err("Extra data can't be sent to an integrated address")
+    }
+
+    if encrypted_extra_data_size(entry) > EXTRA_DATA_LIMIT_SIZE {
+        return Err("Extra data is too big")
+    }
+
+    Ok(())
+}
+
+// Failed status of an entry, the error is truncated to be stored
+pub fn failed_status<E: Display>(error: E) -> PayoutStatus {
+    let mut error = error.to_string();
+    if error.len() > u8::MAX as usize {
+        let mut end = u8::MAX as usize;
+        while !error.is_char_boundary(end) {
+            end -= 1;
+        }
+        error.truncate(end);
+    }
+
+    PayoutStatus::Failed { error }
+}
+
+// Pack the entries in the fewest TXs possible, returns the indexes of the entries of each TX
+// A TX is limited to MAX_TRANSFER_COUNT transfers and EXTRA_DATA_LIMIT_SIZE bytes of encrypted extra data,
+// with these limits it is always far below MAX_TRANSACTION_SIZE
+// Entries are placed from the biggest extra data in the first TX with enough space (first-fit decreasing)
+pub fn pack_payout_entries<'a, I: IntoIterator<Item = (usize, &'a PayoutEntry)>>(entries: I) -> Vec<Vec<usize>> {
+    let mut sized: Vec<(usize, usize)> = entries.into_iter()
+        .map(|(index, entry)| (index, encrypted_extra_data_size(entry)))
+        .collect();
+    sized.sort_by(|a, b| b.1.cmp(&a.1));
+
+    // indexes and extra data size of each TX
+    let mut txs: Vec<(Vec<usize>, usize)> = Vec::new();
+    for (index, size) in sized {
+        let tx = txs.iter_mut()
+            .find(|(indexes, extra_data)| indexes.len() < MAX_TRANSFER_COUNT && extra_data + size <= EXTRA_DATA_LIMIT_SIZE);
+
+        match tx {
+            Some((indexes, extra_data)) => {
+                indexes.push(index);
+                *extra_data += size;
+            },
+            None => txs.push((vec![index], size))
+        }
+    }
+
+    txs.into_iter()
+        .map(|(mut indexes, _)| {
+            // Keep the order of the entries in each TX
+            indexes.sort_unstable();
+            indexes
+        })
+        .collect()
+}
+
+// Parse the entries of a batch payout from a CSV content
+// Each line is `address,asset,amount,extra_data` with the amount in atomic units
+// The asset (XELIS by default) and the extra data (sent as a string) are optional
+// A first line starting with `address` is a header and is skipped
+pub fn parse_payout_csv(content: &str) -> Result<Vec<PayoutEntry>, Error> {
+    let mut entries = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || (i == 0 && line.starts_with("address")) {
+            continue;
+        }
+
+        // Extra data is the last column and can contain commas
+        let mut columns = line.splitn(4, ',').map(str::trim);
+        let destination = columns.next()
+            .ok_or_else(|| anyhow!("Missing address at line {}", i + 1))
+            .and_then(|address| Address::from_string(&address.to_owned()))
+            .with_context(|| format!("Invalid address at line {}", i + 1))?;
+
+        let asset = match columns.next() {
+            Some(asset) if !asset.is_empty() => Hash::from_hex(asset.to_owned())
+                .with_context(|| format!("Invalid asset at line {}", i + 1))?,
+            _ => XELIS_ASSET
+        };
+
+        let amount = columns.next()
+            .ok_or_else(|| anyhow!("Missing amount at line {}", i + 1))?
+            .parse::<u64>()
+            .with_context(|| format!("Invalid amount at line {}", i + 1))?;
+
+        let extra_data = match columns.next() {
+            Some(data) if data.len() > u8::MAX as usize => return Err(anyhow!("Extra data is too long at line {}", i + 1)),
+            Some(data) if !data.is_empty() => Some(DataElement::Value(DataValue::String(data.to_owned()))),
+            _ => None
+        };
+
+        entries.push(PayoutEntry {
+            destination,
+            asset,
+            amount,
+            extra_data
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use xelis_common::crypto::KeyPair;
+    use super::*;
+
+    fn create_entry(extra_data: Option<usize>) -> PayoutEntry {
+        PayoutEntry {
+            destination: KeyPair::new().get_public_key().to_address(false),
+            asset: XELIS_ASSET,
+            amount: 100,
+            extra_data: extra_data.map(|size| DataElement::Value(DataValue::String("a".repeat(size))))
+        }
+    }
+
+    #[test]
+    fn test_pack_payout_entries() {
+        let entries: Vec<PayoutEntry> = (0..MAX_TRANSFER_COUNT * 2 + 1).map(|_| create_entry(None)).collect();
+        let txs = pack_payout_entries(entries.iter().enumerate());
+        assert_eq!(txs.len(), 3);
+        assert_eq!(txs[0].len(), MAX_TRANSFER_COUNT);
+        assert_eq!(txs.iter().map(Vec::len).sum::<usize>(), entries.len());
+
+        // Only three big extra data fit in a TX, small ones fill the remaining space
+        let entries: Vec<PayoutEntry> = [240, 10, 240, 240, 240, 10].into_iter().map(|size| create_entry(Some(size))).collect();
+        let txs = pack_payout_entries(entries.iter().enumerate());
+        assert_eq!(txs.len(), 2);
+        for tx in &txs {
+            let size: usize = tx.iter().map(|i| encrypted_extra_data_size(&entries[*i])).sum();
+            assert!(size <= EXTRA_DATA_LIMIT_SIZE);
+        }
+    }
+
+    #[test]
+    fn test_parse_payout_csv() {
+        let address = KeyPair::new().get_public_key().to_address(false);
+        let content = format!("address,asset,amount,extra_data\n{address},,100,\n\n{address},{XELIS_ASSET},200,invoice 1, paid\n");
+        let entries = parse_payout_csv(&content).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].asset, XELIS_ASSET);
+        assert_eq!(entries[0].amount, 100);
+        assert!(entries[0].extra_data.is_none());
+        assert_eq!(entries[1].extra_data, Some(DataElement::Value(DataValue::String("invoice 1, paid".to_owned()))));
+
+        assert!(parse_payout_csv(&format!("{address},,abc")).is_err());
+        assert!(compute_payout_id(&entries) != compute_payout_id(&entries[..1]));
+    }
+}
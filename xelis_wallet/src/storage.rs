@@ -17,6 +17,7 @@ use xelis_common::{
             Query,
             QueryResult
         },
+        wallet::{ContactEntry, PayoutReport},
         DataElement,
         DataValue
     },
@@ -102,6 +103,7 @@ impl Serializer for Balance {
 }
 
 // Use this struct to get access to non-encrypted keys (such as salt for KDF and encrypted master key)
+#[derive(Clone)]
 pub struct Storage {
     db: Db
 }
@@ -120,6 +122,12 @@ pub struct EncryptedStorage {
     assets: Tree,
     // This tree is used to store all topoheight where a change in the wallet occured
     changes_topoheight: Tree,
+    // labels of the accounts derived from the seed, shared by all accounts
+    accounts: Tree,
+    // reports of the batch payouts, by their id
+    payouts: Tree,
+    // address book, shared by all accounts
+    contacts: Tree,
     // The inner storage
     inner: Storage,
     // Caches
@@ -137,12 +145,32 @@ pub struct EncryptedStorage {
 impl EncryptedStorage {
     pub fn new(inner: Storage, key: &[u8], salt: [u8; SALT_SIZE], network: Network) -> Result<Self> {
         let cipher = Cipher::new(key, Some(salt))?;
+        Self::with_cipher(inner, cipher, 0, network)
+    }
+
+    // Open the storage of an account derived from the seed
+    // It is in the same database and encrypted with the same keys, but has its own trees
+    pub fn open_account(&self, account: u32, network: Network) -> Result<Self> {
+        Self::with_cipher(self.inner.clone(), self.cipher.clone(), account, network)
+    }
+
+    fn with_cipher(inner: Storage, cipher: Cipher, account: u32, network: Network) -> Result<Self> {
+        // The primary account keeps the original tree names
+        let tree_name = |name: &str| if account == 0 {
+            name.to_owned()
+        } else {
+            format!("account_{}_{}", account, name)
+        };
+
         let mut storage = Self {
-            transactions: inner.db.open_tree(&cipher.hash_key("transactions"))?,
-            balances: inner.db.open_tree(&cipher.hash_key("balances"))?,
-            extra: inner.db.open_tree(&cipher.hash_key("extra"))?,
-            assets: inner.db.open_tree(&cipher.hash_key("assets"))?,
-            changes_topoheight: inner.db.open_tree(&cipher.hash_key("changes_topoheight"))?,
+            transactions: inner.db.open_tree(&cipher.hash_key(tree_name("transactions")))?,
+            balances: inner.db.open_tree(&cipher.hash_key(tree_name("balances")))?,
+            extra: inner.db.open_tree(&cipher.hash_key(tree_name("extra")))?,
+            assets: inner.db.open_tree(&cipher.hash_key(tree_name("assets")))?,
+            changes_topoheight: inner.db.open_tree(&cipher.hash_key(tree_name("changes_topoheight")))?,
+            accounts: inner.db.open_tree(&cipher.hash_key("accounts"))?,
+            payouts: inner.db.open_tree(&cipher.hash_key(tree_name("payouts")))?,
+            contacts: inner.db.open_tree(&cipher.hash_key("contacts"))?,
             cipher,
             inner,
             balances_cache: Mutex::new(LruCache::new(NonZeroUsize::new(DEFAULT_CACHE_SIZE).unwrap())),
@@ -724,6 +752,101 @@ impl EncryptedStorage {
         self.load_from_disk(&self.extra, PRIVATE_KEY)
     }
 
+    // Set the label of an account, the account is registered if it's not already
+    pub fn set_account_label(&mut self, account: u32, label: &String) -> Result<()> {
+        trace!("set account label for {}", account);
+        self.save_to_disk_with_encrypted_key(&self.accounts, &account.to_bytes(), &label.to_bytes())
+    }
+
+    // Check if an account is registered
+    pub fn has_account(&self, account: u32) -> Result<bool> {
+        trace!("has account {}", account);
+        self.contains_encrypted_data(&self.accounts, &account.to_bytes())
+    }
+
+    // Get all the registered accounts with their label, ordered by index
+    pub fn get_accounts(&self) -> Result<IndexMap<u32, String>> {
+        trace!("get accounts");
+        let mut accounts = IndexMap::new();
+        for res in self.accounts.iter() {
+            let (key, value) = res?;
+            let account = u32::from_bytes(&self.cipher.decrypt_value(&key)?)?;
+            let label = String::from_bytes(&self.cipher.decrypt_value(&value)?)?;
+            accounts.insert(account, label);
+        }
+        accounts.sort_keys();
+
+        Ok(accounts)
+    }
+
+    // Save the report of a batch payout
+    pub fn set_payout_report(&mut self, report: &PayoutReport) -> Result<()> {
+        trace!("set payout report {}", report.id);
+        self.save_to_disk(&self.payouts, report.id.as_bytes(), &report.to_bytes())
+    }
+
+    // Get the report of a batch payout using its id
+    pub fn get_payout_report(&self, id: &Hash) -> Result<PayoutReport> {
+        trace!("get payout report {}", id);
+        self.load_from_disk(&self.payouts, id.as_bytes())
+    }
+
+    // Check if a batch payout was already started
+    pub fn has_payout_report(&self, id: &Hash) -> Result<bool> {
+        trace!("has payout report {}", id);
+        self.contains_data(&self.payouts, id.as_bytes())
+    }
+
+    // Save a contact in the address book, it replaces the one with the same name
+    pub fn set_contact(&mut self, contact: &ContactEntry) -> Result<()> {
+        trace!("set contact {}", contact.name);
+        self.save_to_disk_with_encrypted_key(&self.contacts, contact.name.as_bytes(), &contact.to_bytes())
+    }
+
+    // Get a contact using its name
+    pub fn get_contact(&self, name: &str) -> Result<ContactEntry> {
+        trace!("get contact {}", name);
+        self.load_from_disk_with_encrypted_key(&self.contacts, name.as_bytes())
+    }
+
+    // Check if a contact exists with this name
+    pub fn has_contact(&self, name: &str) -> Result<bool> {
+        trace!("has contact {}", name);
+        self.contains_encrypted_data(&self.contacts, name.as_bytes())
+    }
+
+    // Delete a contact using its name
+    pub fn delete_contact(&mut self, name: &str) -> Result<()> {
+        trace!("delete contact {}", name);
+        self.delete_from_disk_with_encrypted_key(&self.contacts, name.as_bytes())
+    }
+
+    // Get all the contacts of the address book, ordered by name
+    pub fn get_contacts(&self) -> Result<Vec<ContactEntry>> {
+        trace!("get contacts");
+        let mut contacts = Vec::new();
+        for res in self.contacts.iter() {
+            let (_, value) = res?;
+            contacts.push(ContactEntry::from_bytes(&self.cipher.decrypt_value(&value)?)?);
+        }
+        contacts.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(contacts)
+    }
+
+    // Get the name of the contact of each key, used to display the history
+    // Contacts sharing the same key (integrated addresses) are shown with the first name
+    pub fn get_contact_names(&self) -> Result<HashMap<PublicKey, String>> {
+        trace!("get contact names");
+        let mut names = HashMap::new();
+        for contact in self.get_contacts()? {
+            let (key, _) = contact.address.split();
+            names.entry(key).or_insert(contact.name);
+        }
+
+        Ok(names)
+    }
+
     // Set the topoheight until which the wallet is synchronized
     pub fn set_synced_topoheight(&mut self, topoheight: u64) -> Result<()> {
         trace!("set synced topoheight to {}", topoheight);
@@ -1,10 +1,12 @@
 use std::{
+    collections::{HashMap, HashSet},
     fs::{create_dir_all, File},
     io::{Read, Write},
     path::Path,
     sync::Arc
 };
 use anyhow::{Error, Context};
+use indexmap::IndexMap;
 use serde::Serialize;
 use tokio::sync::{
     broadcast::{
@@ -15,32 +17,44 @@ use tokio::sync::{
     RwLock
 };
 use xelis_common::{
+    account::CiphertextCache,
     api::{
         wallet::{
+            AccountEntry,
             BalanceChanged,
+            ContactEntry,
             NotifyEvent,
-            TransactionEntry
+            PayoutEntry,
+            PayoutRecipientReport,
+            PayoutReport,
+            PayoutStatus,
+            TransactionEntry,
+            UnsignedTransaction
         },
         DataElement
     },
     asset::AssetWithData,
+    config::MAX_TRANSACTION_SIZE,
     crypto::{
         ecdlp::{self, ECDLPTablesFileView},
         elgamal::{Ciphertext, DecryptHandle, PublicKey as DecompressedPublicKey},
         Address,
         Hash,
+        Hashable,
         KeyPair,
         PublicKey,
         Signature
     },
     network::Network,
     serializer::Serializer,
+    tls::TlsClientConfig,
     transaction::{
         aead::{self, AEADCipher},
         builder::{
             FeeBuilder,
             TransactionBuilder,
-            TransactionTypeBuilder
+            TransactionTypeBuilder,
+            TransferBuilder
         },
         Reference,
         Transaction
@@ -64,7 +78,9 @@ use crate::{
         NetworkHandler,
         SharedNetworkHandler
     },
+    payout,
     storage::{
+        Balance,
         EncryptedStorage,
         Storage
     },
@@ -182,6 +198,30 @@ pub enum WalletError {
     CiphertextDecode,
     #[error(transparent)]
     AEADCipherFormatError(#[from] aead::CipherFormatError),
+    #[error("Unsigned transaction must be signed by {}", _0)]
+    InvalidUnsignedTransactionSource(Address),
+    #[error("Unsigned transaction is for network {}", _0)]
+    InvalidUnsignedTransactionNetwork(Network),
+    #[error("Transaction is not signed by this wallet")]
+    InvalidSignedTransactionSource,
+    #[error("Invalid nonce in signed transaction: {}, expected {}", _0, _1)]
+    InvalidSignedTransactionNonce(u64, u64),
+    #[error("Account {} was not found", _0)]
+    AccountNotFound(u32),
+    #[error("Account label is too long, maximum is {} bytes", u8::MAX)]
+    AccountLabelTooLong,
+    #[error("Batch payout has no entries")]
+    EmptyPayout,
+    #[error("Transaction {} spends more than the balance for asset {}", _0, _1)]
+    InvalidTransactionBalance(Hash, Hash),
+    #[error("Invalid contact name, it must not be empty, be up to {} bytes and not be an address", u8::MAX)]
+    InvalidContactName,
+    #[error("Contact address must be on network {}", _0)]
+    InvalidContactNetwork(Network),
+    #[error("Contact {} already exists", _0)]
+    ContactAlreadyExists(String),
+    #[error("Contact {} was not found", _0)]
+    ContactNotFound(String),
 }
 
 #[derive(Serialize, Clone)]
@@ -278,7 +318,11 @@ impl PrecomputedTables {
     }
 }
 
+#[cfg(not(test))]
 pub const PRECOMPUTED_TABLES_L1: usize = 26;
+// Small tables are generated quickly and are enough to decrypt the amounts used in tests
+#[cfg(test)]
+pub const PRECOMPUTED_TABLES_L1: usize = 13;
 
 pub struct Wallet {
     // Encrypted Wallet Storage
@@ -287,10 +331,16 @@ pub struct Wallet {
     keypair: KeyPair,
     // Compressed public key
     public_key: PublicKey,
+    // Index of the account derived from the seed, 0 for the seed itself
+    account: u32,
+    // Accounts derived from the seed, only loaded in the wallet of account 0
+    accounts: RwLock<IndexMap<u32, Arc<Wallet>>>,
     // network handler for online mode to keep wallet synced
     network_handler: Mutex<Option<SharedNetworkHandler>>,
     // network on which we are connected
     network: Network,
+    // custom TLS configuration used to connect to the daemon
+    daemon_tls_config: RwLock<Option<TlsClientConfig>>,
     // RPC Server
     #[cfg(feature = "api_server")]
     api_server: Mutex<Option<APIServer<Arc<Self>>>>,
@@ -299,6 +349,8 @@ pub struct Wallet {
     xswd_channel: RwLock<Option<UnboundedSender<XSWDEvent>>>,
     // Event broadcaster
     event_broadcaster: Mutex<Option<BroadcastSender<Event>>>,
+    // Held during a batch payout so two payouts can't run at the same time
+    payout_lock: Mutex<()>,
     // Precomputed tables byte array
     precomputed_tables: PrecomputedTablesShared
 }
@@ -339,18 +391,22 @@ impl Wallet {
     }
 
     // Create a new wallet with the specificed storage, keypair and its network
-    fn new(storage: EncryptedStorage, keypair: KeyPair, network: Network, precomputed_tables: PrecomputedTablesShared) -> Arc<Self> {
+    fn new(storage: EncryptedStorage, keypair: KeyPair, account: u32, accounts: IndexMap<u32, Arc<Wallet>>, network: Network, precomputed_tables: PrecomputedTablesShared) -> Arc<Self> {
         let zelf = Self {
             storage: RwLock::new(storage),
             public_key: keypair.get_public_key().compress(),
             keypair,
+            account,
+            accounts: RwLock::new(accounts),
             network_handler: Mutex::new(None),
             network,
+            daemon_tls_config: RwLock::new(None),
             #[cfg(feature = "api_server")]
             api_server: Mutex::new(None),
             #[cfg(feature = "api_server")]
             xswd_channel: RwLock::new(None),
             event_broadcaster: Mutex::new(None),
+            payout_lock: Mutex::new(()),
             precomputed_tables
         };
 
@@ -414,7 +470,7 @@ impl Wallet {
         // Flush the storage to be sure its written on disk
         storage.flush()?;
 
-        Ok(Self::new(storage, keypair, network, precomputed_tables))
+        Ok(Self::new(storage, keypair, 0, IndexMap::new(), network, precomputed_tables))
     }
 
     // Open an existing wallet on disk
@@ -457,7 +513,22 @@ impl Wallet {
         let private_key =  storage.get_private_key()?;
         let keypair = KeyPair::from_private_key(private_key);
 
-        Ok(Self::new(storage, keypair, network, precomputed_tables))
+        // Load the accounts derived from the seed
+        let mut accounts = IndexMap::new();
+        for index in storage.get_accounts()?.into_keys().filter(|index| *index != 0) {
+            debug!("Loading account {}", index);
+            let account = Self::new_account(&storage, &keypair, index, network, &precomputed_tables)?;
+            accounts.insert(index, account);
+        }
+
+        Ok(Self::new(storage, keypair, 0, accounts, network, precomputed_tables))
+    }
+
+    // Open the wallet of an account derived from the seed
+    fn new_account(storage: &EncryptedStorage, keypair: &KeyPair, index: u32, network: Network, precomputed_tables: &PrecomputedTablesShared) -> Result<Arc<Self>, Error> {
+        let storage = storage.open_account(index, network)?;
+        let keypair = KeyPair::from_private_key(keypair.get_private_key().derive_account(index));
+        Ok(Self::new(storage, keypair, index, IndexMap::new(), network, Arc::clone(precomputed_tables)))
     }
 
     // Close the wallet
@@ -485,6 +556,16 @@ impl Wallet {
             }
         }
 
+        // Close the accounts before the connection they share
+        for account in self.accounts.read().await.values() {
+            account.close_sync().await;
+        }
+
+        self.close_sync().await;
+    }
+
+    // Stop the network handler, the storage and the events channel
+    async fn close_sync(&self) {
         // Stop gracefully the network handler
         {
             let mut lock = self.network_handler.lock().await;
@@ -551,24 +632,25 @@ impl Wallet {
         broadcaster.take().is_some()
     }
 
-    // Enable RPC Server with requested authentication and bind address
+    // Enable RPC Server with requested authentication, bind address, batch size limit and TLS certificate
     #[cfg(feature = "api_server")]
-    pub async fn enable_rpc_server(self: &Arc<Self>, bind_address: String, config: Option<AuthConfig>) -> Result<(), Error> {
+    pub async fn enable_rpc_server(self: &Arc<Self>, bind_address: String, config: Option<AuthConfig>, max_batch_size: usize, tls: Option<(String, String)>) -> Result<(), Error> {
         let mut lock = self.api_server.lock().await;
         if lock.is_some() {
             return Err(WalletError::RPCServerAlreadyRunning.into())
         }
         let mut rpc_handler = RPCHandler::new(self.clone());
         register_rpc_methods(&mut rpc_handler);
+        rpc_handler.set_max_batch_size(max_batch_size);
 
-        let rpc_server = WalletRpcServer::new(bind_address, rpc_handler, config).await?;
+        let rpc_server = WalletRpcServer::new(bind_address, rpc_handler, config, tls).await?;
         *lock = Some(APIServer::RPCServer(rpc_server));
         Ok(())
     }
 
     // Enable XSWD Protocol
     #[cfg(feature = "api_server")]
-    pub async fn enable_xswd(self: &Arc<Self>) -> Result<UnboundedReceiver<XSWDEvent>, Error> {
+    pub async fn enable_xswd(self: &Arc<Self>, tls: Option<(String, String)>) -> Result<UnboundedReceiver<XSWDEvent>, Error> {
         let receiver = {
             let (sender, receiver) = unbounded_channel();
             let mut channel = self.xswd_channel.write().await;
@@ -583,7 +665,7 @@ impl Wallet {
         let mut rpc_handler = RPCHandler::new(self.clone());
         register_rpc_methods(&mut rpc_handler);
 
-        *lock = Some(APIServer::XSWD(XSWD::new(rpc_handler)?));
+        *lock = Some(APIServer::XSWD(XSWD::new(rpc_handler, tls)?));
         Ok(receiver)
     }
 
@@ -728,6 +810,35 @@ impl Wallet {
         Ok((state, transaction))
     }
 
+    // Build and sign a transaction requested by an online wallet
+    // Balances are decrypted with our key, the storage is not used so it can be done in offline mode
+    pub async fn sign_unsigned_transaction(self: &Arc<Self>, unsigned: UnsignedTransaction) -> Result<Transaction, WalletError> {
+        trace!("sign unsigned transaction");
+        if *unsigned.source.get_public_key() != self.public_key {
+            return Err(WalletError::InvalidUnsignedTransactionSource(unsigned.source))
+        }
+
+        if unsigned.network != self.network {
+            return Err(WalletError::InvalidUnsignedTransactionNetwork(unsigned.network))
+        }
+
+        let mut state = TransactionBuilderState::new(self.network.is_mainnet(), unsigned.reference, unsigned.nonce);
+        for asset in unsigned.tx_type.used_assets() {
+            let ciphertext = unsigned.balances.get(&asset)
+                .ok_or_else(|| WalletError::BalanceNotFound(asset.clone()))?
+                .decompress()
+                .map_err(|e| WalletError::Any(e.into()))?;
+
+            let amount = Arc::clone(self).decrypt_ciphertext(ciphertext.clone()).await?;
+            state.add_balance(asset, Balance::new(amount, CiphertextCache::Decompressed(ciphertext)));
+        }
+        state.set_registered_keys(unsigned.registered_keys.into_iter().map(Address::to_public_key).collect());
+
+        let builder = TransactionBuilder::new(0, self.public_key.clone(), unsigned.tx_type, unsigned.fee);
+        builder.build(&mut state, &self.keypair)
+            .map_err(|e| WalletError::Any(e.into()))
+    }
+
     // submit a transaction to the network through the connection to daemon
     // It will increase the local nonce by 1 if the TX is accepted by the daemon
     // returns error if the wallet is in offline mode or if the TX is rejected
@@ -742,6 +853,194 @@ impl Wallet {
         }
     }
 
+    // Apply a TX that was not built from the unconfirmed balances of this wallet, or not applied after being built
+    // The amounts spent are computed from the TX itself like the daemon does
+    async fn apply_transaction_changes(self: &Arc<Self>, storage: &mut EncryptedStorage, transaction: &Transaction) -> Result<(), WalletError> {
+        trace!("apply transaction changes");
+        for commitment in transaction.get_source_commitments() {
+            let asset = commitment.get_asset();
+            let output = transaction.get_sender_output_ciphertext(asset)
+                .map_err(|e| WalletError::Any(e.into()))?;
+            let spent = Arc::clone(self).decrypt_ciphertext(output.clone()).await?;
+
+            let balance = storage.get_unconfirmed_balance_for(asset).await?;
+            let amount = balance.amount.checked_sub(spent)
+                .ok_or_else(|| WalletError::InvalidTransactionBalance(transaction.hash(), asset.clone()))?;
+            let ciphertext = balance.ciphertext.take_ciphertext()
+                .map_err(|e| WalletError::Any(e.into()))? - output;
+
+            storage.set_unconfirmed_balance_for(asset.clone(), Balance::new(amount, CiphertextCache::Decompressed(ciphertext))).await?;
+        }
+        storage.set_nonce(transaction.get_nonce() + 1)?;
+
+        Ok(())
+    }
+
+    // Pay all the entries using the fewest TXs possible, submitted in order with consecutive nonces
+    // The report is saved after each TX, calling it again with the same entries resumes the payout
+    // Invalid entries are marked as failed, on any other error the payout stops and can be resumed
+    pub async fn batch_payout(self: &Arc<Self>, entries: Vec<PayoutEntry>, fee: FeeBuilder) -> Result<PayoutReport, WalletError> {
+        trace!("batch payout");
+        if entries.is_empty() {
+            return Err(WalletError::EmptyPayout)
+        }
+
+        let api = {
+            let network_handler = self.network_handler.lock().await;
+            network_handler.as_ref().ok_or(WalletError::NotOnlineMode)?.get_shared_api()
+        };
+
+        // Only one payout runs at a time so the same report can't be paid twice
+        let _payout = self.payout_lock.lock().await;
+
+        let id = payout::compute_payout_id(&entries);
+        let report = {
+            let storage = self.storage.read().await;
+            if storage.has_payout_report(&id)? {
+                Some(storage.get_payout_report(&id)?)
+            } else {
+                None
+            }
+        };
+
+        let mut report = match report {
+            Some(mut report) => {
+                debug!("Resuming batch payout {}", id);
+                // A built TX may have been sent before the payout stopped, ask the daemon if it knows it
+                let hashes: Vec<Hash> = report.recipients.iter()
+                    .filter_map(|recipient| match &recipient.status {
+                        PayoutStatus::Built { tx_hash, .. } => Some(tx_hash.clone()),
+                        _ => None
+                    })
+                    .collect::<HashSet<Hash>>()
+                    .into_iter()
+                    .collect();
+
+                let mut known = HashMap::new();
+                for chunk in hashes.chunks(payout::MAX_TXS_PER_REQUEST) {
+                    // Unlike a TX not found, an error doesn't tell us if it was sent, so the payout can't continue
+                    let transactions = api.get_transactions(chunk.to_vec()).await?;
+                    known.extend(chunk.iter().cloned().zip(transactions));
+                }
+
+                let mut storage = self.storage.write().await;
+                self.resume_payout_report(&mut storage, &mut report, known).await?;
+                storage.set_payout_report(&report)?;
+                report
+            },
+            None => {
+                let mainnet = self.network.is_mainnet();
+                let recipients = entries.into_iter()
+                    .map(|entry| {
+                        let status = match payout::validate_payout_entry(&entry, &self.public_key, mainnet) {
+                            Ok(()) => PayoutStatus::Pending,
+                            Err(e) => payout::failed_status(e)
+                        };
+                        PayoutRecipientReport { entry, status }
+                    })
+                    .collect();
+
+                let report = PayoutReport { id, recipients };
+                self.storage.write().await.set_payout_report(&report)?;
+                report
+            }
+        };
+
+        let groups = payout::pack_payout_entries(report.recipients.iter()
+            .enumerate()
+            .filter(|(_, recipient)| recipient.status == PayoutStatus::Pending)
+            .map(|(i, recipient)| (i, &recipient.entry))
+        );
+        debug!("Sending {} TXs for batch payout {}", groups.len(), report.id);
+
+        for group in groups {
+            let transfers = group.iter()
+                .map(|i| {
+                    let entry = &report.recipients[*i].entry;
+                    TransferBuilder {
+                        destination: entry.destination.clone(),
+                        asset: entry.asset.clone(),
+                        amount: entry.amount,
+                        extra_data: entry.extra_data.clone()
+                    }
+                })
+                .collect();
+
+            // Locked until the TX is applied so no other TX can use its nonce,
+            // other TXs can be sent between the TXs of the payout
+            let mut storage = self.storage.write().await;
+            let (mut state, transaction) = match self.create_transaction_with_storage(&mut storage, TransactionTypeBuilder::Transfers(transfers), fee.clone()).await {
+                Ok(v) => v,
+                Err(e) => {
+                    storage.set_payout_report(&report)?;
+                    storage.flush()?;
+                    return Err(e)
+                }
+            };
+
+            let size = transaction.size();
+            if size > MAX_TRANSACTION_SIZE {
+                let status = payout::failed_status(WalletError::TransactionTooBig(size, MAX_TRANSACTION_SIZE));
+                for i in group.iter() {
+                    report.recipients[*i].status = status.clone();
+                }
+                storage.set_payout_report(&report)?;
+                continue;
+            }
+
+            // Saved before sending it, so we can check if the daemon received it after a crash
+            let tx_hash = transaction.hash();
+            let nonce = transaction.get_nonce();
+            for i in group.iter() {
+                report.recipients[*i].status = PayoutStatus::Built { tx_hash: tx_hash.clone(), nonce };
+            }
+            storage.set_payout_report(&report)?;
+            storage.flush()?;
+
+            debug!("Submitting TX {} with nonce {} for {} recipients of batch payout {}", tx_hash, nonce, group.len(), report.id);
+            self.submit_transaction(&transaction).await?;
+            state.apply_changes(&mut storage).await?;
+
+            for i in group.iter() {
+                report.recipients[*i].status = PayoutStatus::Submitted { tx_hash: tx_hash.clone(), nonce };
+            }
+            storage.set_payout_report(&report)?;
+        }
+
+        let mut storage = self.storage.write().await;
+        storage.set_payout_report(&report)?;
+        storage.flush()?;
+
+        Ok(report)
+    }
+
+    // Update the report of a stopped payout with the built TXs known by the daemon
+    // Known TXs were sent, they are applied to the balances and nonce if it wasn't done before the payout stopped
+    // The entries of the unknown TXs are pending again and will be sent in new TXs
+    async fn resume_payout_report(self: &Arc<Self>, storage: &mut EncryptedStorage, report: &mut PayoutReport, known: HashMap<Hash, Option<Transaction>>) -> Result<(), WalletError> {
+        trace!("resume payout report");
+        for recipient in report.recipients.iter_mut() {
+            if let PayoutStatus::Built { tx_hash, nonce } = &recipient.status {
+                recipient.status = match known.get(tx_hash) {
+                    Some(Some(_)) => PayoutStatus::Submitted { tx_hash: tx_hash.clone(), nonce: *nonce },
+                    _ => PayoutStatus::Pending
+                };
+            }
+        }
+
+        // Applied in the order they were built, balances will be confirmed once these TXs are synced
+        let mut transactions: Vec<Transaction> = known.into_values().flatten().collect();
+        transactions.sort_by_key(Transaction::get_nonce);
+        for transaction in transactions {
+            // The nonce is set along the balances, a higher nonce means the TX was already applied
+            if storage.get_nonce().unwrap_or(0) <= transaction.get_nonce() {
+                self.apply_transaction_changes(storage, &transaction).await?;
+            }
+        }
+
+        Ok(())
+    }
+
     // Search if possible all registered keys for the transaction type
     pub async fn add_registered_keys_for_fees_estimation(&self, state: &mut EstimateFeesState, fee: &FeeBuilder, transaction_type: &TransactionTypeBuilder) -> Result<(), WalletError> {
         trace!("add registered keys for fees estimation");
@@ -783,6 +1082,12 @@ impl Wallet {
         Ok(estimated_fees)
     }
 
+    // set the TLS configuration used to connect to the daemon in online mode
+    // it allows to trust the CA of a daemon using a self-signed certificate
+    pub async fn set_daemon_tls_config(&self, tls_config: Option<TlsClientConfig>) {
+        *self.daemon_tls_config.write().await = tls_config;
+    }
+
     // set wallet in online mode: start a communication task which will keep the wallet synced
     pub async fn set_online_mode(self: &Arc<Self>, daemon_address: &String) -> Result<(), WalletError> {
         trace!("Set online mode");
@@ -792,18 +1097,26 @@ impl Wallet {
         }
 
         // create the network handler
-        let network_handler = NetworkHandler::new(Arc::clone(&self), daemon_address).await?;
+        let tls_config = self.daemon_tls_config.read().await.clone();
+        let network_handler = NetworkHandler::new(Arc::clone(&self), daemon_address, tls_config).await?;
         // start the task
         network_handler.start().await?;
+        let api = network_handler.get_shared_api();
         *self.network_handler.lock().await = Some(network_handler);
 
-        Ok(())
+        self.set_accounts_online_mode(api).await
     }
 
     // set the wallet in online mode using a shared daemon API
     // this allows to share the same connection/Daemon API across several wallets to save resources
     pub async fn set_online_mode_with_api(self: &Arc<Self>, daemon_api: Arc<DaemonAPI>) -> Result<(), WalletError> {
         trace!("Set online mode with API");
+        self.start_network_handler_with_api(Arc::clone(&daemon_api)).await?;
+        self.set_accounts_online_mode(daemon_api).await
+    }
+
+    // Start to sync this wallet only using a shared daemon API
+    async fn start_network_handler_with_api(self: &Arc<Self>, daemon_api: Arc<DaemonAPI>) -> Result<(), WalletError> {
         if self.is_online().await {
             // user have to set in offline mode himself first
             return Err(WalletError::AlreadyOnlineMode)
@@ -818,9 +1131,34 @@ impl Wallet {
         Ok(())
     }
 
+    // Sync the accounts derived from the seed over the same daemon connection
+    async fn set_accounts_online_mode(&self, daemon_api: Arc<DaemonAPI>) -> Result<(), WalletError> {
+        for account in self.accounts.read().await.values() {
+            if !account.is_online().await {
+                account.start_network_handler_with_api(Arc::clone(&daemon_api)).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Stop the sync of the accounts derived from the seed
+    async fn stop_accounts_network_handlers(accounts: &[Arc<Self>]) {
+        for account in accounts {
+            if let Some(handler) = account.network_handler.lock().await.take() {
+                if let Err(e) = handler.stop().await {
+                    debug!("Error while stopping network handler of account {}: {}", account.account, e);
+                }
+            }
+        }
+    }
+
     // set wallet in offline mode: stop communication task if exists
     pub async fn set_offline_mode(&self) -> Result<(), WalletError> {
         trace!("Set offline mode");
+        let accounts: Vec<Arc<Self>> = self.accounts.read().await.values().cloned().collect();
+        Self::stop_accounts_network_handlers(&accounts).await;
+
         let mut handler = self.network_handler.lock().await;
         if let Some(network_handler) = handler.take() {
             network_handler.stop().await?;
@@ -841,41 +1179,35 @@ impl Wallet {
             return Err(WalletError::NotOnlineMode)
         }
 
+        // Retrieved before locking the storage, create_account locks them in the other order
+        let accounts: Vec<Arc<Self>> = self.accounts.read().await.values().cloned().collect();
+
         let mut storage = self.get_storage().write().await;
         if topoheight > storage.get_synced_topoheight()? {
             return Err(WalletError::RescanTopoheightTooHigh)
         }
 
+        // Accounts share our connection, they are stopped while it restarts
+        Self::stop_accounts_network_handlers(&accounts).await;
+
         let handler = self.network_handler.lock().await;
         if let Some(network_handler) = handler.as_ref() {
             debug!("Stopping network handler!");
             network_handler.stop().await?;
-            {
-                debug!("set synced topoheight to {}", topoheight);
-                storage.set_synced_topoheight(topoheight)?;
-                storage.delete_top_block_hash()?;
-                // balances will be re-fetched from daemon
-                storage.delete_balances().await?;
-                storage.delete_assets().await?;
-
-                debug!("Retrieve current wallet nonce");
-                let nonce_result = network_handler.get_api()
-                    .get_nonce(&self.get_address()).await
-                    // User has no transactions/balances yet, set its nonce to 0
-                    .map(|v| v.version.get_nonce()).unwrap_or(0);
-
-                storage.set_nonce(nonce_result)?;
-
-                if topoheight == 0 {
-                    debug!("Deleting all transactions for full rescan");
-                    storage.delete_transactions()?;
-                } else {
-                    debug!("Deleting transactions above {} for partial rescan", topoheight);
-                    storage.delete_transactions_above_topoheight(topoheight)?;
-                }
-            }
+            self.clean_storage_for_rescan(&mut storage, network_handler.get_api(), topoheight).await?;
             debug!("Starting again network handler");
             network_handler.start().await.context("Error while restarting network handler")?;
+
+            // Rescan the accounts from the same topoheight, or from where they are synced if lower
+            let api = network_handler.get_shared_api();
+            for account in accounts {
+                {
+                    let mut storage = account.get_storage().write().await;
+                    let topoheight = topoheight.min(storage.get_synced_topoheight().unwrap_or(0));
+                    account.clean_storage_for_rescan(&mut storage, &api, topoheight).await?;
+                }
+                account.start_network_handler_with_api(Arc::clone(&api)).await?;
+            }
         } else {
             return Err(WalletError::NotOnlineMode)
         }
@@ -883,6 +1215,33 @@ impl Wallet {
         Ok(())
     }
 
+    // Delete everything above the topoheight so the next sync fetches it again
+    async fn clean_storage_for_rescan(&self, storage: &mut EncryptedStorage, api: &DaemonAPI, topoheight: u64) -> Result<(), WalletError> {
+        debug!("set synced topoheight to {}", topoheight);
+        storage.set_synced_topoheight(topoheight)?;
+        storage.delete_top_block_hash()?;
+        // balances will be re-fetched from daemon
+        storage.delete_balances().await?;
+        storage.delete_assets().await?;
+
+        debug!("Retrieve current wallet nonce");
+        let nonce_result = api.get_nonce(&self.get_address()).await
+            // User has no transactions/balances yet, set its nonce to 0
+            .map(|v| v.version.get_nonce()).unwrap_or(0);
+
+        storage.set_nonce(nonce_result)?;
+
+        if topoheight == 0 {
+            debug!("Deleting all transactions for full rescan");
+            storage.delete_transactions()?;
+        } else {
+            debug!("Deleting transactions above {} for partial rescan", topoheight);
+            storage.delete_transactions_above_topoheight(topoheight)?;
+        }
+
+        Ok(())
+    }
+
     // Check if the wallet is in online mode
     pub async fn is_online(&self) -> bool {
         if let Some(network_handler) = self.network_handler.lock().await.as_ref() {
@@ -903,6 +1262,137 @@ impl Wallet {
         self.keypair.sign(data)
     }
 
+    // Index of the account derived from the seed, 0 for the seed itself
+    pub fn get_account_index(&self) -> u32 {
+        self.account
+    }
+
+    // Create a new account derived from the seed, synced over the same daemon connection
+    // Returns the index of the new account
+    pub async fn create_account(self: &Arc<Self>, label: String) -> Result<u32, WalletError> {
+        trace!("create account");
+        if label.len() > u8::MAX as usize {
+            return Err(WalletError::AccountLabelTooLong)
+        }
+
+        // Retrieved before locking the accounts, rescan locks them in the other order
+        let api = self.network_handler.lock().await.as_ref().map(|handler| handler.get_shared_api());
+
+        let mut accounts = self.accounts.write().await;
+        let index = accounts.keys().max().map_or(1, |index| index + 1);
+        let account = {
+            let mut storage = self.storage.write().await;
+            storage.set_account_label(index, &label)?;
+            storage.flush()?;
+            Self::new_account(&storage, &self.keypair, index, self.network, &self.precomputed_tables)?
+        };
+
+        if let Some(api) = api {
+            account.start_network_handler_with_api(api).await?;
+        }
+
+        accounts.insert(index, account);
+        Ok(index)
+    }
+
+    // Set the label of an account
+    pub async fn set_account_label(&self, index: u32, label: String) -> Result<(), WalletError> {
+        trace!("set account label");
+        if label.len() > u8::MAX as usize {
+            return Err(WalletError::AccountLabelTooLong)
+        }
+
+        if index != 0 && !self.accounts.read().await.contains_key(&index) {
+            return Err(WalletError::AccountNotFound(index))
+        }
+
+        let mut storage = self.storage.write().await;
+        storage.set_account_label(index, &label)?;
+        Ok(())
+    }
+
+    // Get the wallet of an account, 0 being this wallet
+    // There is no global selection: each caller (CLI, RPC or XSWD request) chooses its account
+    pub async fn get_account(self: &Arc<Self>, index: u32) -> Result<Arc<Self>, WalletError> {
+        if index == 0 {
+            return Ok(Arc::clone(self))
+        }
+
+        self.accounts.read().await.get(&index)
+            .cloned()
+            .ok_or(WalletError::AccountNotFound(index))
+    }
+
+    // List the accounts derived from the seed with their label
+    pub async fn get_accounts(&self) -> Result<Vec<AccountEntry>, WalletError> {
+        trace!("get accounts");
+        let labels = {
+            let storage = self.storage.read().await;
+            storage.get_accounts()?
+        };
+
+        let mainnet = self.network.is_mainnet();
+        let mut entries = vec![AccountEntry {
+            index: 0,
+            label: labels.get(&0).cloned().unwrap_or_default(),
+            address: self.get_address()
+        }];
+
+        for (index, account) in self.accounts.read().await.iter() {
+            entries.push(AccountEntry {
+                index: *index,
+                label: labels.get(index).cloned().unwrap_or_default(),
+                address: account.get_public_key().clone().to_address(mainnet)
+            });
+        }
+
+        Ok(entries)
+    }
+
+    // Add an address to the address book, it is shared by all the accounts
+    pub async fn add_contact(&self, name: String, address: Address) -> Result<(), WalletError> {
+        trace!("add contact {}", name);
+        // The name is used in place of an address, so it must not be one
+        if name.is_empty() || name.len() > u8::MAX as usize || Address::from_string(&name).is_ok() {
+            return Err(WalletError::InvalidContactName)
+        }
+
+        if address.is_mainnet() != self.network.is_mainnet() {
+            return Err(WalletError::InvalidContactNetwork(self.network))
+        }
+
+        let mut storage = self.storage.write().await;
+        if storage.has_contact(&name)? {
+            return Err(WalletError::ContactAlreadyExists(name))
+        }
+
+        storage.set_contact(&ContactEntry { name, address })?;
+        Ok(())
+    }
+
+    // Remove a contact from the address book
+    pub async fn remove_contact(&self, name: &str) -> Result<(), WalletError> {
+        trace!("remove contact {}", name);
+        let mut storage = self.storage.write().await;
+        if !storage.has_contact(name)? {
+            return Err(WalletError::ContactNotFound(name.to_owned()))
+        }
+
+        storage.delete_contact(name)?;
+        Ok(())
+    }
+
+    // Get the address of a contact using its name
+    pub async fn get_contact(&self, name: &str) -> Result<Address, WalletError> {
+        trace!("get contact {}", name);
+        let storage = self.storage.read().await;
+        if !storage.has_contact(name)? {
+            return Err(WalletError::ContactNotFound(name.to_owned()))
+        }
+
+        Ok(storage.get_contact(name)?.address)
+    }
+
     // Get the public key of the wallet
     pub fn get_public_key(&self) -> &PublicKey {
         &self.public_key
@@ -1015,4 +1505,258 @@ impl XSWDNodeMethodHandler for Arc<Wallet> {
 
         Err(RpcResponseError::new(id, InternalRpcError::CustomStr("Wallet is not in online mode")))
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::ControlFlow;
+    use xelis_common::config::XELIS_ASSET;
+    use xelis_common::{api::DataValue, crypto::AddressType};
+    use crate::{
+        cold_account::ColdAccount,
+        entry::{EntryData, TransactionEntry as StoredTransactionEntry, TransferOut}
+    };
+    use super::*;
+
+    struct NoProgressReport;
+
+    impl ecdlp::ProgressTableGenerationReportFunction for NoProgressReport {
+        fn report(&self, _: f64, _: ecdlp::ReportStep) -> ControlFlow<()> {
+            ControlFlow::Continue(())
+        }
+    }
+
+    fn create_tables() -> PrecomputedTablesShared {
+        let mut tables = PrecomputedTables::new(PRECOMPUTED_TABLES_L1);
+        ecdlp::table_generation::create_table_file_with_progress_report(PRECOMPUTED_TABLES_L1, tables.get_mut(), NoProgressReport).unwrap();
+        Arc::new(tables)
+    }
+
+    fn temp_wallet_path() -> String {
+        std::env::temp_dir()
+            .join(format!("xelis-wallet-test-{}", OsRng.next_u64()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn create_wallet(seed: Option<String>) -> Arc<Wallet> {
+        Wallet::create(temp_wallet_path(), "password".to_owned(), seed, Network::Dev, create_tables()).unwrap()
+    }
+
+    // Set a balance encrypted for the wallet and the reference used to build TXs
+    async fn set_balance(wallet: &Arc<Wallet>, amount: u64) {
+        let ciphertext = wallet.keypair.get_public_key().encrypt(amount);
+        let mut storage = wallet.get_storage().write().await;
+        storage.set_balance_for(&XELIS_ASSET, Balance::new(amount, CiphertextCache::Decompressed(ciphertext))).await.unwrap();
+        storage.set_synced_topoheight(1).unwrap();
+        storage.set_top_block_hash(&Hash::zero()).unwrap();
+    }
+
+    async fn get_addresses(wallet: &Arc<Wallet>) -> Vec<Address> {
+        wallet.get_accounts().await.unwrap()
+            .into_iter()
+            .map(|account| account.address)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_account_derivation_is_deterministic() {
+        let (_, key) = KeyPair::new().split();
+        let seed = mnemonics::key_to_words(&key, 0).unwrap().join(" ");
+
+        let wallet = create_wallet(Some(seed.clone()));
+        assert_eq!(wallet.create_account(String::new()).await.unwrap(), 1);
+        assert_eq!(wallet.create_account("savings".to_owned()).await.unwrap(), 2);
+
+        let recovered = create_wallet(Some(seed));
+        recovered.create_account(String::new()).await.unwrap();
+        recovered.create_account(String::new()).await.unwrap();
+
+        let addresses = get_addresses(&wallet).await;
+        assert_eq!(addresses.len(), 3);
+        assert_eq!(addresses, get_addresses(&recovered).await);
+
+        // Each account has its own key
+        assert!(addresses[0] != addresses[1] && addresses[1] != addresses[2]);
+    }
+
+    #[tokio::test]
+    async fn test_accounts_are_isolated() {
+        let wallet = create_wallet(None);
+        let index = wallet.create_account(String::new()).await.unwrap();
+        let account = wallet.get_account(index).await.unwrap();
+        assert_eq!(account.get_account_index(), index);
+        assert!(matches!(wallet.get_account(index + 1).await, Err(WalletError::AccountNotFound(_))));
+
+        account.get_storage().write().await.set_nonce(5).unwrap();
+        assert_eq!(account.get_nonce().await, 5);
+        assert_eq!(wallet.get_nonce().await, 0);
+
+        wallet.get_storage().write().await.set_nonce(1).unwrap();
+        assert_eq!(account.get_nonce().await, 5);
+        assert_eq!(wallet.get_nonce().await, 1);
+    }
+
+    // Build the TX of a payout and save it as built, like batch_payout does before sending it
+    async fn build_payout(wallet: &Arc<Wallet>, entries: Vec<PayoutEntry>) -> (PayoutReport, Transaction) {
+        let mut storage = wallet.get_storage().write().await;
+        let transfers = entries.iter()
+            .map(|entry| TransferBuilder {
+                destination: entry.destination.clone(),
+                asset: entry.asset.clone(),
+                amount: entry.amount,
+                extra_data: None
+            })
+            .collect();
+        let (_, transaction) = wallet.create_transaction_with_storage(&mut storage, TransactionTypeBuilder::Transfers(transfers), FeeBuilder::Value(100)).await.unwrap();
+
+        let status = PayoutStatus::Built { tx_hash: transaction.hash(), nonce: transaction.get_nonce() };
+        let report = PayoutReport {
+            id: payout::compute_payout_id(&entries),
+            recipients: entries.into_iter()
+                .map(|entry| PayoutRecipientReport { entry, status: status.clone() })
+                .collect()
+        };
+        storage.set_payout_report(&report).unwrap();
+
+        (report, transaction)
+    }
+
+    fn create_payout_entries() -> Vec<PayoutEntry> {
+        (0..2).map(|_| PayoutEntry {
+                destination: KeyPair::new().get_public_key().to_address(false),
+                asset: XELIS_ASSET,
+                amount: 1000,
+                extra_data: None
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_batch_payout_resume() {
+        let wallet = create_wallet(None);
+        set_balance(&wallet, 100_000).await;
+
+        // The payout stopped after building its TX, the daemon received it
+        let (mut report, transaction) = build_payout(&wallet, create_payout_entries()).await;
+        let known = HashMap::from([(transaction.hash(), Some(transaction.clone()))]);
+        {
+            let mut storage = wallet.get_storage().write().await;
+            wallet.resume_payout_report(&mut storage, &mut report, known.clone()).await.unwrap();
+        }
+
+        assert!(report.recipients.iter().all(|recipient| matches!(recipient.status, PayoutStatus::Submitted { .. })));
+        // Same changes as when the TX is applied after being sent: nonce and unconfirmed balance
+        let storage = wallet.get_storage().read().await;
+        assert_eq!(storage.get_nonce().unwrap(), 1);
+        let mut balance = storage.get_unconfirmed_balance_for(&XELIS_ASSET).await.unwrap();
+        assert_eq!(balance.amount, 100_000 - 2 * 1000 - 100);
+        let ciphertext = balance.ciphertext.decompressed().unwrap().clone();
+        assert_eq!(Arc::clone(&wallet).decrypt_ciphertext(ciphertext).await.unwrap(), balance.amount);
+        drop(storage);
+
+        // Resuming again doesn't apply the TX twice
+        {
+            let mut storage = wallet.get_storage().write().await;
+            wallet.resume_payout_report(&mut storage, &mut report, known).await.unwrap();
+            assert_eq!(storage.get_nonce().unwrap(), 1);
+            assert_eq!(storage.get_unconfirmed_balance_for(&XELIS_ASSET).await.unwrap().amount, balance.amount);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_payout_resume_unknown_tx() {
+        let wallet = create_wallet(None);
+        set_balance(&wallet, 100_000).await;
+
+        // The payout stopped before the daemon received its TX, its entries are sent again
+        let (mut report, transaction) = build_payout(&wallet, create_payout_entries()).await;
+        let known = HashMap::from([(transaction.hash(), None)]);
+        let mut storage = wallet.get_storage().write().await;
+        wallet.resume_payout_report(&mut storage, &mut report, known).await.unwrap();
+
+        assert!(report.recipients.iter().all(|recipient| recipient.status == PayoutStatus::Pending));
+        assert_eq!(storage.get_nonce().unwrap_or(0), 0);
+        assert_eq!(storage.get_unconfirmed_balance_for(&XELIS_ASSET).await.unwrap().amount, 100_000);
+    }
+
+    #[tokio::test]
+    async fn test_offline_signing() {
+        let offline = create_wallet(None);
+        // The online side only knows the address of the offline wallet
+        let online = ColdAccount::new(offline.get_address(), Network::Dev).unwrap();
+
+        // Request as created from the daemon state of the account
+        let destination = KeyPair::new().get_public_key().to_address(false);
+        let transfers = vec![TransferBuilder { destination, asset: XELIS_ASSET, amount: 1000, extra_data: None }];
+        let unsigned = UnsignedTransaction {
+            source: online.get_address().clone(),
+            network: Network::Dev,
+            tx_type: TransactionTypeBuilder::Transfers(transfers.clone()),
+            fee: FeeBuilder::Value(100),
+            reference: Reference { topoheight: 1, hash: Hash::zero() },
+            nonce: 0,
+            balances: [(XELIS_ASSET, offline.keypair.get_public_key().encrypt(100_000u64).compress())].into_iter().collect(),
+            registered_keys: Vec::new()
+        };
+
+        // Only the key of the source can sign it
+        let other = create_wallet(None);
+        assert!(matches!(other.sign_unsigned_transaction(unsigned.clone()).await, Err(WalletError::InvalidUnsignedTransactionSource(_))));
+
+        let transaction = offline.sign_unsigned_transaction(unsigned).await.unwrap();
+        online.verify_signed_transaction(&transaction, 0).unwrap();
+        // It can't be submitted twice
+        assert!(matches!(online.verify_signed_transaction(&transaction, 1), Err(WalletError::InvalidSignedTransactionNonce(0, 1))));
+
+        // A TX signed by another key is refused
+        set_balance(&other, 100_000).await;
+        let transaction = other.create_transaction(TransactionTypeBuilder::Transfers(transfers), FeeBuilder::Value(100)).await.unwrap();
+        assert!(matches!(online.verify_signed_transaction(&transaction, 0), Err(WalletError::InvalidSignedTransactionSource)));
+    }
+
+    #[tokio::test]
+    async fn test_contacts() {
+        let wallet = create_wallet(None);
+        let key = KeyPair::new().get_public_key().compress();
+        let address = key.clone().to_address(false);
+
+        // A name parsed as an address would be used in place of the contact
+        assert!(matches!(wallet.add_contact(address.to_string(), address.clone()).await, Err(WalletError::InvalidContactName)));
+        assert!(matches!(wallet.add_contact("alice".to_owned(), key.clone().to_address(true)).await, Err(WalletError::InvalidContactNetwork(Network::Dev))));
+
+        wallet.add_contact("alice".to_owned(), address.clone()).await.unwrap();
+        assert!(matches!(wallet.add_contact("alice".to_owned(), address.clone()).await, Err(WalletError::ContactAlreadyExists(name)) if name == "alice"));
+        assert_eq!(wallet.get_contact("alice").await.unwrap(), address);
+
+        assert!(matches!(wallet.remove_contact("bob").await, Err(WalletError::ContactNotFound(name)) if name == "bob"));
+        wallet.remove_contact("alice").await.unwrap();
+        assert!(matches!(wallet.get_contact("alice").await, Err(WalletError::ContactNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_integrated_address_contact() {
+        let wallet = create_wallet(None);
+        let key = KeyPair::new().get_public_key().compress();
+        let integrated = key.clone().to_address_with(false, DataElement::Value(DataValue::U64(42)));
+
+        // The integrated data is kept in storage
+        wallet.add_contact("exchange".to_owned(), integrated.clone()).await.unwrap();
+        let address = wallet.get_contact("exchange").await.unwrap();
+        assert!(matches!(address.get_type(), AddressType::Data(_)));
+        assert_eq!(address, integrated);
+
+        // The history shows the first name of the key
+        wallet.add_contact("withdrawal".to_owned(), key.clone().to_address(false)).await.unwrap();
+        let storage = wallet.get_storage().read().await;
+        let names = storage.get_contact_names().unwrap();
+        assert_eq!(names.get(&key), Some(&"exchange".to_owned()));
+
+        let entry = StoredTransactionEntry::new(Hash::zero(), 1, EntryData::Outgoing {
+            transfers: vec![TransferOut::new(key, XELIS_ASSET, 1000, None)],
+            fee: 100,
+            nonce: 0
+        });
+        assert!(entry.summary(false, &storage, &names).unwrap().contains("to exchange"));
+    }
+}